@@ -0,0 +1,100 @@
+#!/usr/bin/env python3
+"""Regenerate the tables of src/normalize.rs from the Unicode Character Database of Python
+
+Usage: scripts/normalize.py src/normalize.rs
+
+The tables are written in place, between the doc comment of COMBINING_CLASSES and the end of
+COMPOSITIONS. They follow the version of the database Python is built with, printed on stderr;
+record it in the module doc when it changes. The tables in the repository are of 14.0.0
+(Python 3.11).
+"""
+import re
+import sys
+import unicodedata
+
+# Hangul syllables are decomposed and composed by the code
+HANGUL = range(0xAC00, 0xAC00 + 11172)
+
+
+def char(c):
+    return "'\\u{%04X}'" % ord(c)
+
+
+def string(s):
+    return '"%s"' % "".join("\\u{%04X}" % ord(c) for c in s)
+
+
+def canonical(c):
+    """The canonical decomposition of the character, or None"""
+    decomposition = unicodedata.decomposition(c)
+    if not decomposition or decomposition.startswith("<"):
+        return None
+    return "".join(chr(int(code, 16)) for code in decomposition.split())
+
+
+def combining_classes():
+    ranges = []
+    for code in range(0x110000):
+        c = chr(code)
+        ccc = unicodedata.combining(c)
+        if not ccc:
+            continue
+        if ranges and ranges[-1][2] == ccc and ord(ranges[-1][1]) == code - 1:
+            ranges[-1][1] = c
+        else:
+            ranges.append([c, c, ccc])
+    return ranges
+
+
+def tables():
+    classes = combining_classes()
+    decompositions = []
+    compositions = []
+    for code in range(0x110000):
+        if code in HANGUL:
+            continue
+        c = chr(code)
+        decomposition = canonical(c)
+        if decomposition is None:
+            continue
+        decompositions.append((c, unicodedata.normalize("NFD", c)))
+        # The composition exclusions, singletons and non-starter decompositions do not compose
+        if len(decomposition) == 2 and unicodedata.normalize("NFC", decomposition) == c:
+            compositions.append((decomposition[0], decomposition[1], c))
+    compositions.sort()
+    lines = ["/// Canonical combining classes other than 0, as sorted `(first, last, class)` ranges"]
+    lines.append("const COMBINING_CLASSES: &[(char, char, u8)] = &[")
+    lines += ["    (%s, %s, %d)," % (char(first), char(last), ccc) for first, last, ccc in classes]
+    lines.append("];")
+    lines.append("")
+    lines.append(
+        "/// Full canonical decompositions other than the Hangul syllables, sorted by the character"
+    )
+    lines.append("const DECOMPOSITIONS: &[(char, &str)] = &[")
+    lines += ["    (%s, %s)," % (char(c), string(d)) for c, d in decompositions]
+    lines.append("];")
+    lines.append("")
+    lines.append("/// Primary composites, as `(starter, combining, composite)` sorted by the pair")
+    lines.append("const COMPOSITIONS: &[(char, char, char)] = &[")
+    lines += ["    (%s, %s, %s)," % tuple(map(char, composition)) for composition in compositions]
+    lines.append("];")
+    return "\n".join(lines)
+
+
+def main():
+    path = sys.argv[1] if len(sys.argv) > 1 else "src/normalize.rs"
+    with open(path, encoding="utf-8") as f:
+        source = f.read()
+    pattern = re.compile(
+        r"/// Canonical combining classes.*?const COMPOSITIONS: [^\n]*\n.*?\n\];", re.DOTALL
+    )
+    source, count = pattern.subn(lambda _: tables(), source)
+    if count != 1:
+        sys.exit("the tables are not found in " + path)
+    with open(path, "w", encoding="utf-8") as f:
+        f.write(source)
+    print("Unicode " + unicodedata.unidata_version, file=sys.stderr)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,86 @@
+#!/usr/bin/env python3
+"""Write src/tzdata.txt, the time zones embedded in the binary, from a compiled tz database
+
+Usage: scripts/tzdata.py [/usr/share/zoneinfo] > src/tzdata.txt
+
+The tzdata workflow of the CI fails once a newer release of the tz database is out. Regenerate the
+file from a zoneinfo of the release, such as the one of an updated tzdata package.
+"""
+import os
+import struct
+import sys
+
+# Twitter was launched in 2006; the transitions before 2000 are left out
+SINCE = 946684800
+
+
+def read_tzif(path):
+    with open(path, "rb") as f:
+        data = f.read()
+    if data[:4] != b"TZif" or data[4:5] < b"2":
+        return None
+    # Skip the 32-bit data of version 1
+    counts = struct.unpack(">6l", data[20:44])
+    isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt = counts
+    offset = 44 + timecnt * 5 + typecnt * 6 + charcnt + leapcnt * 8 + isstdcnt + isutcnt
+    data = data[offset:]
+    isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt = struct.unpack(">6l", data[20:44])
+    pos = 44
+    times = struct.unpack(">%dq" % timecnt, data[pos : pos + timecnt * 8])
+    pos += timecnt * 8
+    indices = data[pos : pos + timecnt]
+    pos += timecnt
+    types = [struct.unpack(">lBB", data[pos + i * 6 : pos + i * 6 + 6])[0] for i in range(typecnt)]
+    pos += typecnt * 6 + charcnt + leapcnt * 12 + isstdcnt + isutcnt
+    footer = data[pos:].strip(b"\n").decode()
+    transitions = [(t, types[i]) for t, i in zip(times, indices)]
+    # The offset in effect at SINCE, then the changes after it
+    initial = types[0]
+    for t, utoff in transitions:
+        if t <= SINCE:
+            initial = utoff
+    changes = []
+    last = initial
+    for t, utoff in transitions:
+        if t > SINCE and utoff != last:
+            changes.append((t, utoff))
+            last = utoff
+    return footer, initial, changes
+
+
+def main():
+    root = sys.argv[1] if len(sys.argv) > 1 else "/usr/share/zoneinfo"
+    with open(os.path.join(root, "tzdata.zi")) as f:
+        version = f.readline().split()[-1]
+    zones = {}
+    for dirpath, dirnames, filenames in os.walk(root):
+        dirnames[:] = sorted(d for d in dirnames if d not in ("posix", "right"))
+        for filename in filenames:
+            path = os.path.join(dirpath, filename)
+            name = os.path.relpath(path, root)
+            if name in ("localtime", "posixrules", "Factory") or "." in filename:
+                continue
+            zone = read_tzif(path)
+            if zone is not None:
+                zones[name] = zone
+    print("# Time zones of the tz database %s, generated by scripts/tzdata.py" % version)
+    print("# <name> <POSIX TZ rule> <UTC offset at 2000-01-01> [<time>=<UTC offset> ...]")
+    print("# <alias> = <name>")
+    # The zones of zone1970.tab are written in full, and their aliases refer to them
+    with open(os.path.join(root, "zone1970.tab")) as f:
+        canonical = {line.split("\t")[2].strip() for line in f if not line.startswith("#")}
+    written = {}
+    for name in sorted(zones, key=lambda name: (name not in canonical, name)):
+        footer, initial, changes = zones[name]
+        key = (footer, initial, tuple(changes))
+        if key in written:
+            print("%s = %s" % (name, written[key]))
+            continue
+        written[key] = name
+        fields = [name, footer or "-", str(initial)]
+        fields.extend("%d=%d" % change for change in changes)
+        print(" ".join(fields))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,56 @@
+use crate::error::{Error, Result};
+use crate::wrapper::load_arrays;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// File of the account information in the `data` folder of the Twitter archive
+pub const ACCOUNT_FILE_NAME: &str = "account.js";
+
+/// The account an archive was downloaded from
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    /// Screen name, without the leading "@"
+    pub username: String,
+    #[serde(default)]
+    pub account_id: String,
+    #[serde(default)]
+    pub account_display_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountEntry {
+    account: Account,
+}
+
+impl Account {
+    /// Load the account from an `account.js` file of the archive
+    pub fn load(account_file_path: &Path) -> Result<Self> {
+        let entries: Vec<AccountEntry> = load_arrays(account_file_path)?;
+        entries
+            .into_iter()
+            .next()
+            .map(|entry| entry.account)
+            .ok_or_else(|| Error::Json(serde::de::Error::custom("there is no account in the file")))
+    }
+    /// The `account.js` next to a `tweets.js`, if the file is in an archive
+    pub fn file_for(tweets_file_path: &Path) -> Option<PathBuf> {
+        let path = tweets_file_path.parent()?.join(ACCOUNT_FILE_NAME);
+        path.is_file().then_some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AccountEntry;
+
+    #[test]
+    fn test_parse_account() {
+        let entries: Vec<AccountEntry> = serde_json::from_str(
+            r#"[{"account": {"email": "a@example.com", "createdVia": "web", "username": "hoge", "accountId": "42", "createdAt": "2010-01-01T00:00:00.000Z", "accountDisplayName": "Hoge"}}]"#,
+        )
+        .unwrap();
+        assert_eq!(entries[0].account.username, "hoge");
+        assert_eq!(entries[0].account.account_display_name, "Hoge");
+    }
+}
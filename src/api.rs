@@ -0,0 +1,451 @@
+use crate::config::ApiConfig;
+use crate::error::{Error, Result};
+use crate::parallel;
+use crate::tweet::html_to_text;
+use chrono::DateTime;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Environment variable the bearer token of the X API is read from
+pub const BEARER_TOKEN_ENV: &str = "TWITTER_BEARER_TOKEN";
+
+/// Base URL of the v2 API
+const API_BASE_URL: &str = "https://api.twitter.com/2";
+
+/// Endpoint of the embed codes of tweets, which needs no token
+const OEMBED_URL: &str = "https://publish.twitter.com/oembed";
+
+/// Number of tweets the timeline endpoint returns at most per page
+const MAX_RESULTS_PER_PAGE: usize = 100;
+
+/// Number of tweets the lookup endpoint takes at most per request
+const MAX_IDS_PER_LOOKUP: usize = 100;
+
+/// Fields requested for each tweet, the ones the archive has
+const TWEET_FIELDS: &str =
+    "created_at,entities,public_metrics,in_reply_to_user_id,referenced_tweets,lang,attachments,possibly_sensitive";
+
+/// Expansions of the timeline, for the polls attached to the tweets
+const POLL_EXPANSIONS: &str = "expansions=attachments.poll_ids&poll.fields=options,voting_status";
+
+/// A client of the X API v2, which runs the `curl` command for the requests
+pub struct ApiClient {
+    bearer_token: String,
+    timeout: Duration,
+    max_concurrent_requests: usize,
+}
+impl ApiClient {
+    pub fn new(bearer_token: String) -> Self {
+        let config = ApiConfig::default();
+        Self {
+            bearer_token,
+            timeout: config.timeout(),
+            max_concurrent_requests: config.max_concurrent_requests,
+        }
+    }
+    /// A client with the bearer token from the environment or the configuration
+    pub fn from_config(config: &ApiConfig) -> Result<Self> {
+        std::env::var(BEARER_TOKEN_ENV)
+            .ok()
+            .or_else(|| config.bearer_token.clone())
+            .map(|bearer_token| Self {
+                bearer_token,
+                timeout: config.timeout(),
+                max_concurrent_requests: config.max_concurrent_requests,
+            })
+            .ok_or_else(|| Error::Api {
+                url: API_BASE_URL.to_string(),
+                message: format!(
+                    "set the bearer token to {} or api.bearer_token of the configuration file",
+                    BEARER_TOKEN_ENV
+                ),
+            })
+    }
+    /// GET a JSON resource of the API, e.g. `/users/me`
+    pub fn get(&self, path: &str) -> Result<Value> {
+        get_json(
+            &format!("{}{}", API_BASE_URL, path),
+            Some(&format!("Authorization: Bearer {}", self.bearer_token)),
+            self.timeout,
+        )
+    }
+    /// Fetch the `max_tweets` most recent tweets of the user, or of the authenticated user
+    ///
+    /// The tweets are returned as the elements of `tweets.js`, so that they are converted like
+    /// the archive.
+    pub fn fetch_tweets(&self, username: Option<&str>, max_tweets: usize) -> Result<Vec<Value>> {
+        let user = match username {
+            Some(username) => self.get(&format!("/users/by/username/{}", username))?,
+            None => self.get("/users/me")?,
+        };
+        let user_id = user["data"]["id"]
+            .as_str()
+            .ok_or_else(|| Error::Api {
+                url: API_BASE_URL.to_string(),
+                message: format!("unexpected response for the user: {}", user),
+            })?
+            .to_string();
+        let mut entries = Vec::new();
+        let mut next_token: Option<String> = None;
+        while entries.len() < max_tweets {
+            // The endpoint accepts 5 to 100 tweets per page
+            let max_results = (max_tweets - entries.len()).clamp(5, MAX_RESULTS_PER_PAGE);
+            let mut path = format!(
+                "/users/{}/tweets?max_results={}&tweet.fields={}&{}",
+                user_id, max_results, TWEET_FIELDS, POLL_EXPANSIONS
+            );
+            if let Some(ref token) = next_token {
+                path.push_str(&format!("&pagination_token={}", token));
+            }
+            let page: TimelinePage = serde_json::from_value(self.get(&path)?)?;
+            let polls = page
+                .includes
+                .polls
+                .iter()
+                .filter_map(|poll| Some((poll["id"].as_str()?, poll)))
+                .collect::<HashMap<&str, &Value>>();
+            entries.extend(
+                page.data
+                    .iter()
+                    .map(|tweet| to_archive_entry(tweet, &polls)),
+            );
+            next_token = page.meta.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+        entries.truncate(max_tweets);
+        Ok(entries)
+    }
+    /// Look up the tweets by their IDs, leaving out the deleted and protected ones
+    ///
+    /// The IDs are looked up in chunks, several of which are requested at the same time.
+    pub fn lookup_tweets(&self, ids: &[&str]) -> Result<HashMap<String, LookedUpTweet>> {
+        let chunks = ids.chunks(MAX_IDS_PER_LOOKUP).collect::<Vec<&[&str]>>();
+        let responses = parallel::map_bounded(&chunks, self.max_concurrent_requests, |chunk| {
+            self.get(&format!(
+                "/tweets?ids={}&expansions=author_id&user.fields=username",
+                chunk.join(",")
+            ))
+        });
+        let mut tweets = HashMap::new();
+        for response in responses {
+            tweets.extend(parse_lookup(&response?));
+        }
+        Ok(tweets)
+    }
+}
+
+/// The tweets of a response of the lookup endpoint, with the authors in `includes`
+fn parse_lookup(response: &Value) -> HashMap<String, LookedUpTweet> {
+    let usernames = response["includes"]["users"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|user| Some((user["id"].as_str()?, user["username"].as_str()?)))
+        .collect::<HashMap<&str, &str>>();
+    response["data"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|tweet| {
+            let author = tweet["author_id"]
+                .as_str()
+                .and_then(|id| usernames.get(id))?;
+            Some((
+                tweet["id"].as_str()?.to_string(),
+                LookedUpTweet {
+                    author: author.to_string(),
+                    text: tweet["text"].as_str()?.to_string(),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// GET a JSON resource with the `curl` command, giving up after `timeout`
+fn get_json(url: &str, header: Option<&str>, timeout: Duration) -> Result<Value> {
+    let api_error = |message: String| Error::Api {
+        url: url.to_string(),
+        message,
+    };
+    // The header is passed on stdin to keep the token out of the process list
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail-with-body"])
+        .args(["--max-time", &timeout.as_secs().max(1).to_string()])
+        .args(["--header", "@-", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| api_error(format!("failed to run curl: {}", e)))?;
+    if let (Some(mut stdin), Some(header)) = (child.stdin.take(), header) {
+        writeln!(stdin, "{}", header)?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let body = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(api_error(format!("{} {}", stderr.trim(), body.trim())));
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+/// How the tweets replied to or quoted are looked up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ReferenceLookup {
+    /// The X API, with the bearer token
+    Api,
+    /// The public oEmbed endpoint, which needs no token but takes a request per tweet
+    Oembed,
+}
+
+/// Look up a tweet with the public oEmbed endpoint of Twitter
+pub fn lookup_oembed(id: &str, timeout: Duration) -> Result<LookedUpTweet> {
+    let url = format!(
+        "{}?url=https://twitter.com/i/status/{}&omit_script=true&dnt=true",
+        OEMBED_URL, id
+    );
+    let response = get_json(&url, None, timeout)?;
+    parse_oembed(&response).ok_or_else(|| Error::Api {
+        url,
+        message: format!("unexpected response: {}", response),
+    })
+}
+
+/// Look up the tweets one by one with the oEmbed endpoint, several at the same time
+///
+/// The tweets deleted or made private since are left out.
+pub fn lookup_oembed_all(ids: &[&str], config: &ApiConfig) -> HashMap<String, LookedUpTweet> {
+    let results = parallel::map_bounded(ids, config.max_concurrent_requests, |&id| {
+        (id, lookup_oembed(id, config.timeout()))
+    });
+    results
+        .into_iter()
+        .filter_map(|(id, result)| match result {
+            Ok(tweet) => Some((id.to_string(), tweet)),
+            Err(e) => {
+                debug!("Failed to look up the tweet {}: {}", id, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// The author and the text of the `<blockquote>` in an oEmbed response
+fn parse_oembed(response: &Value) -> Option<LookedUpTweet> {
+    let author = response["author_url"].as_str()?.rsplit('/').next()?;
+    let html = response["html"].as_str()?;
+    let start = html.find("<p")?;
+    let end = html[start..].find("</p>")? + start;
+    let paragraph = &html[start..end];
+    let text = &paragraph[paragraph.find('>')? + 1..];
+    Some(LookedUpTweet {
+        author: author.to_string(),
+        text: html_to_text(text),
+    })
+}
+
+/// A tweet by anyone, as looked up by its ID
+#[derive(Debug, Clone)]
+pub struct LookedUpTweet {
+    /// Screen name of the author
+    pub author: String,
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimelinePage {
+    #[serde(default)]
+    data: Vec<Value>,
+    #[serde(default)]
+    includes: TimelineIncludes,
+    #[serde(default)]
+    meta: TimelineMeta,
+}
+
+/// The objects expanded from the tweets of a page
+#[derive(Debug, Default, Deserialize)]
+struct TimelineIncludes {
+    #[serde(default)]
+    polls: Vec<Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TimelineMeta {
+    next_token: Option<String>,
+}
+
+/// Convert a tweet of the v2 API into an element of `tweets.js`, with the fields the conversion reads
+///
+/// The poll of the tweet is looked up in `polls` by its ID, and turned into a card of the v1.1 API.
+fn to_archive_entry(tweet: &Value, polls: &HashMap<&str, &Value>) -> Value {
+    let created_at = tweet["created_at"]
+        .as_str()
+        .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+        .map(|date| date.format("%a %b %d %H:%M:%S %z %Y").to_string())
+        .unwrap_or_default();
+    let entities = &tweet["entities"];
+    let replied_to = tweet["referenced_tweets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|referenced| referenced["type"] == "replied_to")
+        .and_then(|referenced| referenced["id"].as_str());
+    let list = |key: &str| entities[key].as_array().cloned().unwrap_or_default();
+    let poll = tweet["attachments"]["poll_ids"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find_map(|id| polls.get(id.as_str()?));
+    let card = poll.map(|poll| {
+        let options = poll["options"].as_array().cloned().unwrap_or_default();
+        let mut binding_values = serde_json::Map::new();
+        for option in options.iter() {
+            let position = option["position"].as_u64().unwrap_or_default();
+            binding_values.insert(
+                format!("choice{}_label", position),
+                json!({ "string_value": option["label"] }),
+            );
+            if let Some(votes) = option["votes"].as_u64() {
+                binding_values.insert(
+                    format!("choice{}_count", position),
+                    json!({ "string_value": votes.to_string() }),
+                );
+            }
+        }
+        binding_values.insert(
+            "counts_are_final".to_string(),
+            json!({ "boolean_value": poll["voting_status"] == "closed" }),
+        );
+        json!({
+            "name": format!("poll{}choice_text_only", options.len()),
+            "binding_values": binding_values,
+        })
+    });
+    json!({
+        "tweet": {
+            "id_str": tweet["id"],
+            "created_at": created_at,
+            "full_text": tweet["text"],
+            "in_reply_to_user_id": tweet["in_reply_to_user_id"],
+            "in_reply_to_status_id_str": replied_to,
+            "lang": tweet["lang"],
+            "possibly_sensitive": tweet["possibly_sensitive"],
+            "favorite_count": tweet["public_metrics"]["like_count"].as_u64().unwrap_or(0),
+            "retweet_count": tweet["public_metrics"]["retweet_count"].as_u64().unwrap_or(0),
+            "entities": {
+                "hashtags": list("hashtags")
+                    .iter()
+                    .map(|h| json!({ "text": h["tag"] }))
+                    .collect::<Vec<Value>>(),
+                "user_mentions": list("mentions")
+                    .iter()
+                    .map(|m| json!({ "screen_name": m["username"] }))
+                    .collect::<Vec<Value>>(),
+                "symbols": list("cashtags")
+                    .iter()
+                    .map(|c| json!({ "text": c["tag"] }))
+                    .collect::<Vec<Value>>(),
+                "urls": list("urls")
+                    .iter()
+                    .map(|u| json!({
+                        "url": u["url"],
+                        "expanded_url": u["expanded_url"],
+                        "display_url": u["display_url"],
+                    }))
+                    .collect::<Vec<Value>>(),
+            },
+            "card": card,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_lookup, parse_oembed, to_archive_entry};
+    use crate::tweet::parse_tweets;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_to_archive_entry() {
+        let tweet = serde_json::json!({
+            "id": "1634420338812760065",
+            "text": "@hoge #rust https://t.co/x",
+            "created_at": "2023-03-11T04:12:48.000Z",
+            "in_reply_to_user_id": "42",
+            "referenced_tweets": [{"type": "replied_to", "id": "1634420338812760000"}],
+            "public_metrics": {"like_count": 3, "retweet_count": 1},
+            "entities": {
+                "hashtags": [{"start": 6, "end": 11, "tag": "rust"}],
+                "mentions": [{"start": 0, "end": 5, "username": "hoge"}],
+                "urls": [{"url": "https://t.co/x", "expanded_url": "https://example.com", "display_url": "example.com"}]
+            }
+        });
+        let entries = serde_json::Value::Array(vec![to_archive_entry(&tweet, &HashMap::new())]);
+        let tweets = parse_tweets(&entries.to_string()).unwrap();
+        assert_eq!(tweets[0].id(), "1634420338812760065");
+        assert!(tweets[0].is_reply());
+        assert_eq!(
+            tweets[0].in_reply_to_status_id(),
+            Some("1634420338812760000")
+        );
+        assert!(tweets[0].has_hashtag("rust"));
+        assert_eq!(tweets[0].favorite_count(), 3);
+        assert_eq!(tweets[0].expanded_text(), "@hoge #rust https://example.com");
+        assert_eq!(tweets[0].created_at().timestamp(), 1678507968);
+    }
+    #[test]
+    fn test_to_archive_entry_with_poll() {
+        let tweet = serde_json::json!({
+            "id": "1",
+            "text": "どっち？",
+            "created_at": "2023-03-11T04:12:48.000Z",
+            "attachments": {"poll_ids": ["9"]}
+        });
+        let poll = serde_json::json!({
+            "id": "9",
+            "voting_status": "closed",
+            "options": [
+                {"position": 1, "label": "犬", "votes": 12},
+                {"position": 2, "label": "猫", "votes": 30}
+            ]
+        });
+        let polls = HashMap::from([("9", &poll)]);
+        let entries = serde_json::Value::Array(vec![to_archive_entry(&tweet, &polls)]);
+        let tweets = parse_tweets(&entries.to_string()).unwrap();
+        let poll = tweets[0].poll().unwrap();
+        assert!(poll.closed);
+        assert_eq!(poll.options[1].label, "猫");
+        assert_eq!(poll.options[1].votes, Some(30));
+    }
+    #[test]
+    fn test_parse_lookup() {
+        let response = serde_json::json!({
+            "data": [{"id": "1", "text": "parent", "author_id": "10"}],
+            "includes": {"users": [{"id": "10", "username": "hoge"}]},
+            "errors": [{"resource_id": "2", "title": "Not Found Error"}]
+        });
+        let tweets = parse_lookup(&response);
+        assert_eq!(tweets.len(), 1);
+        assert_eq!(tweets["1"].author, "hoge");
+        assert_eq!(tweets["1"].text, "parent");
+    }
+    #[test]
+    fn test_parse_oembed() {
+        let response = serde_json::json!({
+            "author_name": "Hoge",
+            "author_url": "https://twitter.com/hoge",
+            "html": "<blockquote class=\"twitter-tweet\"><p lang=\"ja\" dir=\"ltr\">寒い &amp; 眠い<br>おやすみ <a href=\"https://t.co/x\">pic.twitter.com/x</a></p>&mdash; Hoge (@hoge) <a href=\"https://twitter.com/hoge/status/1\">March 11, 2023</a></blockquote>\n"
+        });
+        let tweet = parse_oembed(&response).unwrap();
+        assert_eq!(tweet.author, "hoge");
+        assert_eq!(tweet.text, "寒い & 眠い\nおやすみ pic.twitter.com/x");
+    }
+}
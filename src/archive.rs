@@ -0,0 +1,166 @@
+//! The `manifest.js` of the Twitter archive, which lists the data files of each type, split in
+//! parts for the large archives, and the folders of their media
+use crate::error::{Error, Result};
+use crate::tweet::deserialize_count;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// File of the manifest in the `data` folder of the archive
+pub const MANIFEST_FILE_NAME: &str = "manifest.js";
+/// Data type of the tweets
+pub const TWEETS: &str = "tweets";
+/// Data type of the media of the tweets
+pub const TWEETS_MEDIA: &str = "tweetsMedia";
+/// Data type of the tweets posted to the Twitter Circle
+pub const CIRCLE_TWEETS: &str = "twitterCircleTweet";
+/// Data type of the tweets deleted before the archive was exported
+pub const DELETED_TWEETS: &str = "deletedTweets";
+/// Data type of the account information
+pub const ACCOUNT: &str = "account";
+/// Data type of the Spaces
+pub const SPACES: &str = "spacesMetadata";
+/// Data type of the Moments
+pub const MOMENTS: &str = "moment";
+
+/// A data file of the archive
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataFile {
+    /// Path from the root of the archive, such as `data/tweets-part1.js`
+    pub file_name: String,
+    /// Number of entries in the file
+    #[serde(default, deserialize_with = "deserialize_count")]
+    pub count: u64,
+}
+
+/// The files of a type of data
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataType {
+    #[serde(default)]
+    pub files: Vec<DataFile>,
+    /// Folder of the media, from the root of the archive
+    pub media_directory: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestContent {
+    #[serde(default)]
+    data_types: HashMap<String, DataType>,
+}
+
+/// The manifest of an archive, with the root folder of the archive its paths are relative to
+#[derive(Debug, Clone)]
+pub struct ArchiveManifest {
+    root: PathBuf,
+    data_types: HashMap<String, DataType>,
+}
+impl ArchiveManifest {
+    /// The manifest of the archive of a data file, or of the root folder of an archive
+    pub fn find(path: &Path) -> Option<PathBuf> {
+        let data_dir = if path.is_dir() {
+            path.join("data")
+        } else {
+            path.parent()?.to_path_buf()
+        };
+        let manifest_path = data_dir.join(MANIFEST_FILE_NAME);
+        manifest_path.is_file().then_some(manifest_path)
+    }
+    /// Load the `manifest.js` in the `data` folder of an archive
+    ///
+    /// Unlike the other files, it assigns an object, `window.__THAR_CONFIG = {...}`.
+    pub fn load(manifest_path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(manifest_path).map_err(|source| Error::Input {
+            path: manifest_path.display().to_string(),
+            source,
+        })?;
+        let json = content.find('{').map_or("", |start| &content[start..]);
+        let json = json.trim_end().trim_end_matches(';');
+        let content: ManifestContent = serde_json::from_str(json)?;
+        let root = manifest_path
+            .parent()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+        Ok(Self {
+            root,
+            data_types: content.data_types,
+        })
+    }
+    /// The files of the type of data that exist, in the order of their parts
+    pub fn files(&self, data_type: &str) -> Vec<PathBuf> {
+        self.data_types
+            .get(data_type)
+            .map(|data_type| {
+                data_type
+                    .files
+                    .iter()
+                    .map(|file| self.root.join(&file.file_name))
+                    .filter(|path| path.is_file())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+    /// Number of entries of the type of data, as counted when the archive was exported
+    pub fn count(&self, data_type: &str) -> Option<u64> {
+        let data_type = self.data_types.get(data_type)?;
+        Some(data_type.files.iter().map(|file| file.count).sum())
+    }
+    /// The folder of the media of the type of data, if it exists
+    pub fn media_dir(&self, data_type: &str) -> Option<PathBuf> {
+        let dir = self.data_types.get(data_type)?.media_directory.as_ref()?;
+        Some(self.root.join(dir)).filter(|dir| dir.is_dir())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArchiveManifest, TWEETS, TWEETS_MEDIA};
+
+    #[test]
+    fn test_load_manifest() {
+        let root =
+            std::env::temp_dir().join(format!("twitter2obsidian-manifest-{}", std::process::id()));
+        let data = root.join("data");
+        std::fs::create_dir_all(data.join("tweets_media")).unwrap();
+        std::fs::write(data.join("tweets.js"), "[]").unwrap();
+        std::fs::write(data.join("tweets-part1.js"), "[]").unwrap();
+        std::fs::write(
+            data.join("manifest.js"),
+            r#"window.__THAR_CONFIG = {
+  "userInfo": {"accountId": "42", "userName": "hoge"},
+  "dataTypes": {
+    "tweets": {"files": [
+      {"fileName": "data/tweets.js", "globalName": "YTD.tweets.part0", "count": "2"},
+      {"fileName": "data/tweets-part1.js", "globalName": "YTD.tweets.part1", "count": "1"},
+      {"fileName": "data/tweets-part2.js", "globalName": "YTD.tweets.part2", "count": "0"}
+    ]},
+    "tweetsMedia": {"mediaDirectory": "data/tweets_media"},
+    "deletedTweets": {"files": [], "mediaDirectory": "data/deleted_tweets_media"}
+  }
+}"#,
+        )
+        .unwrap();
+        let manifest_path = ArchiveManifest::find(&root).unwrap();
+        assert_eq!(
+            ArchiveManifest::find(&data.join("tweets.js")),
+            Some(manifest_path.clone())
+        );
+        let manifest = ArchiveManifest::load(&manifest_path).unwrap();
+        assert_eq!(
+            manifest.files(TWEETS),
+            vec![data.join("tweets.js"), data.join("tweets-part1.js")]
+        );
+        assert_eq!(manifest.count(TWEETS), Some(3));
+        assert_eq!(
+            manifest.media_dir(TWEETS_MEDIA),
+            Some(data.join("tweets_media"))
+        );
+        // Folders missing from the archive are left out
+        assert_eq!(manifest.media_dir("deletedTweets"), None);
+        assert!(manifest.files("moment").is_empty());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,438 @@
+use crate::error::{Error, Result};
+use crate::tweet::{Entities, Hashtag, Source, Tweet, UrlEntity, UserMention};
+use chrono::DateTime;
+use std::{collections::HashMap, io, path::Path};
+
+/// Extension of the repository export of Bluesky, a CAR (content addressable archive) file
+pub const CAR_EXTENSION: &str = "car";
+
+/// Collection of the posts in a repository
+const POST_COLLECTION: &str = "app.bsky.feed.post";
+
+/// A DAG-CBOR value, as the records and the nodes of a repository are encoded
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Integer(i128),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Value>),
+    Map(Vec<(String, Value)>),
+    /// A link to another block, tag 42
+    Link(Vec<u8>),
+    Bool(bool),
+    Null,
+    Float(f64),
+}
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+    fn as_usize(&self) -> Option<usize> {
+        match self {
+            Value::Integer(n) => usize::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+    fn as_array(&self) -> &[Value] {
+        match self {
+            Value::Array(values) => values,
+            _ => &[],
+        }
+    }
+}
+
+/// How deep the arrays, the maps and the tags of a data item may nest, far deeper than any record
+const MAX_DEPTH: usize = 64;
+
+/// Reader of the bytes of a CAR file
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+    fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| invalid_data("unexpected end of the data"))?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+    /// An unsigned LEB128 integer, as the lengths and the CIDs are encoded
+    fn varint(&mut self) -> io::Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid_data("the varint is too long"))
+    }
+    /// The bytes of a CID, a content identifier of a block
+    fn cid(&mut self) -> io::Result<Vec<u8>> {
+        let start = self.position;
+        // CIDv0 is a bare SHA-256 multihash
+        if self.data[start..].starts_with(&[0x12, 0x20]) {
+            return Ok(self.take(34)?.to_vec());
+        }
+        let _version = self.varint()?;
+        let _codec = self.varint()?;
+        let _hash = self.varint()?;
+        let len = self.varint()? as usize;
+        self.take(len)?;
+        Ok(self.data[start..self.position].to_vec())
+    }
+    /// The argument of a CBOR data item
+    fn argument(&mut self, info: u8) -> io::Result<u64> {
+        Ok(match info {
+            0..=23 => info as u64,
+            24 => self.byte()? as u64,
+            25 => u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as u64,
+            26 => u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as u64,
+            27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+            _ => {
+                return Err(invalid_data(
+                    "indefinite lengths are not allowed in DAG-CBOR",
+                ))
+            }
+        })
+    }
+    fn value(&mut self) -> io::Result<Value> {
+        self.nested_value(0)
+    }
+    /// A data item nested in `depth` arrays, maps or tags
+    fn nested_value(&mut self, depth: usize) -> io::Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(invalid_data("the data item is nested too deeply"));
+        }
+        let initial = self.byte()?;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        if major == 7 {
+            return match info {
+                20 => Ok(Value::Bool(false)),
+                21 => Ok(Value::Bool(true)),
+                22 | 23 => Ok(Value::Null),
+                26 => Ok(Value::Float(
+                    f32::from_be_bytes(self.take(4)?.try_into().unwrap()) as f64,
+                )),
+                27 => Ok(Value::Float(f64::from_be_bytes(
+                    self.take(8)?.try_into().unwrap(),
+                ))),
+                _ => Err(invalid_data("unsupported simple value")),
+            };
+        }
+        let argument = self.argument(info)?;
+        Ok(match major {
+            0 => Value::Integer(argument as i128),
+            1 => Value::Integer(-1 - argument as i128),
+            2 => Value::Bytes(self.take(argument as usize)?.to_vec()),
+            3 => Value::Text(
+                String::from_utf8(self.take(argument as usize)?.to_vec())
+                    .map_err(|_| invalid_data("the text is not UTF-8"))?,
+            ),
+            4 => Value::Array(
+                (0..argument)
+                    .map(|_| self.nested_value(depth + 1))
+                    .collect::<io::Result<Vec<Value>>>()?,
+            ),
+            5 => {
+                let mut entries = Vec::new();
+                for _ in 0..argument {
+                    let key = match self.nested_value(depth + 1)? {
+                        Value::Text(key) => key,
+                        _ => return Err(invalid_data("the keys of a map must be strings")),
+                    };
+                    entries.push((key, self.nested_value(depth + 1)?));
+                }
+                Value::Map(entries)
+            }
+            6 => match (argument, self.nested_value(depth + 1)?) {
+                // The CID is prefixed with the identity multibase
+                (42, Value::Bytes(bytes)) => Value::Link(bytes.get(1..).unwrap_or(&[]).to_vec()),
+                _ => return Err(invalid_data("unsupported tag")),
+            },
+            _ => unreachable!(),
+        })
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Blocks of a CAR file by their CID
+fn read_blocks(data: &[u8]) -> io::Result<HashMap<Vec<u8>, Value>> {
+    let mut reader = Reader::new(data);
+    let header_len = reader.varint()? as usize;
+    reader.take(header_len)?;
+    let mut blocks = HashMap::new();
+    while !reader.is_empty() {
+        let len = reader.varint()? as usize;
+        let end = reader
+            .position
+            .checked_add(len)
+            .ok_or_else(|| invalid_data("the block is too long"))?;
+        let cid = reader.cid()?;
+        let block = reader.take(end.saturating_sub(reader.position))?;
+        // Blobs such as images are not DAG-CBOR and are skipped
+        if let Ok(value) = Reader::new(block).value() {
+            blocks.insert(cid, value);
+        }
+    }
+    Ok(blocks)
+}
+
+/// Keys of the records, `<collection>/<record key>`, by the CID of the record
+///
+/// The keys are stored in the nodes of the Merkle search tree of the repository, each
+/// compressed against the previous key in the node.
+fn record_keys(blocks: &HashMap<Vec<u8>, Value>) -> HashMap<Vec<u8>, String> {
+    let mut keys = HashMap::new();
+    for node in blocks.values() {
+        let Some(Value::Array(entries)) = node.get("e") else {
+            continue;
+        };
+        let mut key = Vec::new();
+        for entry in entries.iter() {
+            let (Some(prefix_len), Some(Value::Bytes(suffix)), Some(Value::Link(cid))) = (
+                entry.get("p").and_then(Value::as_usize),
+                entry.get("k"),
+                entry.get("v"),
+            ) else {
+                continue;
+            };
+            key.truncate(prefix_len);
+            key.extend_from_slice(suffix);
+            keys.insert(cid.clone(), String::from_utf8_lossy(&key).to_string());
+        }
+    }
+    keys
+}
+
+/// Load the posts in the repository export (`.car`) of a Bluesky account as tweets
+///
+/// Reposts and likes are not included, as the repository has only the links to the posts.
+pub fn load_repo<F: FnMut(Tweet)>(car_file_path: &Path, mut on_tweet: F) -> Result<()> {
+    let input_error = |source| Error::Input {
+        path: car_file_path.display().to_string(),
+        source,
+    };
+    let data = std::fs::read(car_file_path).map_err(input_error)?;
+    // A corrupt file is told apart from a missing one by its exit code
+    let blocks = read_blocks(&data).map_err(|e| Error::Parse {
+        path: car_file_path.display().to_string(),
+        message: e.to_string(),
+    })?;
+    let keys = record_keys(&blocks);
+    let mut posts = blocks
+        .iter()
+        .filter(|(_, record)| record.get("$type").and_then(Value::as_str) == Some(POST_COLLECTION))
+        .filter_map(|(cid, record)| {
+            let record_key = keys
+                .get(cid)
+                .and_then(|key| key.strip_prefix(&format!("{}/", POST_COLLECTION)));
+            let id = record_key.map_or_else(
+                || cid.iter().map(|b| format!("{:02x}", b)).collect(),
+                |key| key.to_string(),
+            );
+            record_to_tweet(id, record)
+        })
+        .collect::<Vec<Tweet>>();
+    posts.sort_by_key(|post| post.created_at());
+    posts.into_iter().for_each(&mut on_tweet);
+    Ok(())
+}
+
+fn record_to_tweet(id: String, record: &Value) -> Option<Tweet> {
+    let text = record.get("text").and_then(Value::as_str)?;
+    let created_at =
+        DateTime::parse_from_rfc3339(record.get("createdAt").and_then(Value::as_str)?).ok()?;
+    let mut entities = Entities::default();
+    for facet in record.get("facets").map_or(&[][..], Value::as_array) {
+        let index = facet.get("index");
+        let range = (
+            index
+                .and_then(|i| i.get("byteStart"))
+                .and_then(Value::as_usize),
+            index
+                .and_then(|i| i.get("byteEnd"))
+                .and_then(Value::as_usize),
+        );
+        let facet_text = match range {
+            (Some(start), Some(end)) => text.get(start..end).unwrap_or_default(),
+            _ => "",
+        };
+        for feature in facet.get("features").map_or(&[][..], Value::as_array) {
+            match feature.get("$type").and_then(Value::as_str) {
+                Some("app.bsky.richtext.facet#mention") => {
+                    entities.user_mentions.push(UserMention {
+                        screen_name: facet_text.trim_start_matches('@').to_string(),
+                        name: feature
+                            .get("did")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default()
+                            .to_string(),
+                    })
+                }
+                Some("app.bsky.richtext.facet#tag") => entities.hashtags.push(Hashtag {
+                    text: feature
+                        .get("tag")
+                        .and_then(Value::as_str)
+                        .unwrap_or(facet_text.trim_start_matches('#'))
+                        .to_string(),
+                }),
+                // The text has the links shortened for display
+                Some("app.bsky.richtext.facet#link") => entities.urls.push(UrlEntity {
+                    url: facet_text.to_string(),
+                    expanded_url: feature
+                        .get("uri")
+                        .and_then(Value::as_str)
+                        .unwrap_or(facet_text)
+                        .to_string(),
+                    display_url: facet_text.to_string(),
+                }),
+                _ => {}
+            }
+        }
+    }
+    let in_reply_to = record
+        .get("reply")
+        .and_then(|reply| reply.get("parent"))
+        .and_then(|parent| parent.get("uri"))
+        .and_then(Value::as_str)
+        .and_then(|uri| uri.rsplit('/').next())
+        .map(str::to_string);
+    Some(Tweet::from_post(
+        Source::Bluesky,
+        id,
+        created_at,
+        text,
+        in_reply_to,
+        entities,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_repo, read_blocks, record_keys, record_to_tweet, Reader, Value};
+
+    /// Encode the head of a CBOR data item
+    fn head(major: u8, len: usize) -> Vec<u8> {
+        assert!(len < 256);
+        if len < 24 {
+            vec![major << 5 | len as u8]
+        } else {
+            vec![major << 5 | 24, len as u8]
+        }
+    }
+    fn text(s: &str) -> Vec<u8> {
+        [head(3, s.len()), s.as_bytes().to_vec()].concat()
+    }
+
+    #[test]
+    fn test_read_value() {
+        let data = [
+            head(5, 2),
+            text("text"),
+            text("hi"),
+            text("n"),
+            vec![0x39, 0x01, 0x00],
+        ]
+        .concat();
+        let value = Reader::new(&data).value().unwrap();
+        assert_eq!(value.get("text"), Some(&Value::Text("hi".to_string())));
+        assert_eq!(value.get("n"), Some(&Value::Integer(-257)));
+    }
+    #[test]
+    fn test_read_blocks() {
+        let record = [
+            head(5, 3),
+            text("$type"),
+            text("app.bsky.feed.post"),
+            text("text"),
+            text("hello"),
+            text("createdAt"),
+            text("2023-03-11T04:12:48.000Z"),
+        ]
+        .concat();
+        // CIDv1, dag-cbor, sha2-256 of 32 bytes
+        let cid = [vec![0x01, 0x71, 0x12, 0x20], vec![7; 32]].concat();
+        let link = [vec![0xd8, 42], head(2, cid.len() + 1), vec![0], cid.clone()].concat();
+        let node = [
+            head(5, 2),
+            text("l"),
+            vec![0xf6],
+            text("e"),
+            head(4, 1),
+            head(5, 4),
+            text("p"),
+            vec![0],
+            text("k"),
+            head(2, 22),
+            b"app.bsky.feed.post/3ab".to_vec(),
+            text("v"),
+            link,
+            text("t"),
+            vec![0xf6],
+        ]
+        .concat();
+        let node_cid = [vec![0x01, 0x71, 0x12, 0x20], vec![8; 32]].concat();
+        let header = [head(5, 1), text("version"), vec![1]].concat();
+        let mut car = head(0, header.len());
+        car.extend(header);
+        for (cid, block) in [(&cid, &record), (&node_cid, &node)] {
+            let len = cid.len() + block.len();
+            car.extend([len as u8 | 0x80, (len >> 7) as u8]);
+            car.extend(cid.iter());
+            car.extend(block.iter());
+        }
+        let blocks = read_blocks(&car).unwrap();
+        assert_eq!(blocks.len(), 2);
+        let keys = record_keys(&blocks);
+        assert_eq!(keys[&cid], "app.bsky.feed.post/3ab");
+        let tweet = record_to_tweet("3ab".to_string(), &blocks[&cid]).unwrap();
+        assert_eq!(tweet.full_text(), "hello");
+    }
+    #[test]
+    fn test_read_malformed() {
+        // A block length of 2^64 - 1
+        let car = [vec![0], vec![0xff; 9], vec![0x01]].concat();
+        assert!(read_blocks(&car).is_err());
+        // Arrays nested deeper than any record
+        let data = [vec![0x81; 100_000], vec![0xf6]].concat();
+        assert!(Reader::new(&data).value().is_err());
+        let data = [vec![0x81; 10], vec![0xf6]].concat();
+        assert!(Reader::new(&data).value().is_ok());
+        // A corrupt file fails with another exit code than a missing one
+        let path =
+            std::env::temp_dir().join(format!("twitter2obsidian-{}.car", std::process::id()));
+        std::fs::write(&path, &car).unwrap();
+        let err = load_repo(&path, |_| {}).unwrap_err();
+        assert_eq!(err.exit_code(), crate::exit_code::PARSE_FAILURE);
+        std::fs::remove_file(&path).unwrap();
+        let err = load_repo(&path, |_| {}).unwrap_err();
+        assert_eq!(err.exit_code(), crate::exit_code::INPUT_MISSING);
+    }
+}
@@ -0,0 +1,98 @@
+//! The commands of the `browse` subcommand, previewing the notes before they are written
+use std::fmt;
+use std::str::FromStr;
+
+/// A filter switched on and off while browsing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Toggle {
+    Retweets,
+    Replies,
+    Circle,
+    Sensitive,
+}
+
+/// A command typed at the prompt of the `browse` subcommand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrowseCommand {
+    /// List the notes
+    List,
+    /// Print the note of this number in the list, from 1
+    Show(usize),
+    /// Print the next or the previous note
+    Next,
+    Previous,
+    /// Leave out the tweets of a kind, or bring them back
+    Toggle(Toggle),
+    /// Write the notes as previewed and quit
+    Write,
+    Quit,
+    Help,
+}
+impl FromStr for BrowseCommand {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s.split_whitespace().collect::<Vec<&str>>();
+        let command = match words.as_slice() {
+            [] | ["l" | "ls" | "list"] => BrowseCommand::List,
+            [n] | ["show" | "s", n] if n.parse::<usize>().is_ok() => {
+                BrowseCommand::Show(n.parse().unwrap())
+            }
+            ["n" | "next"] => BrowseCommand::Next,
+            ["p" | "prev" | "previous"] => BrowseCommand::Previous,
+            ["retweets" | "rt"] => BrowseCommand::Toggle(Toggle::Retweets),
+            ["replies"] => BrowseCommand::Toggle(Toggle::Replies),
+            ["circle"] => BrowseCommand::Toggle(Toggle::Circle),
+            ["sensitive"] => BrowseCommand::Toggle(Toggle::Sensitive),
+            ["w" | "write"] => BrowseCommand::Write,
+            ["q" | "quit" | "exit"] => BrowseCommand::Quit,
+            ["h" | "help" | "?"] => BrowseCommand::Help,
+            _ => {
+                return Err(format!(
+                    "unknown command: {}; type help for the commands",
+                    s.trim()
+                ))
+            }
+        };
+        Ok(command)
+    }
+}
+impl fmt::Display for Toggle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Toggle::Retweets => "retweets",
+            Toggle::Replies => "replies",
+            Toggle::Circle => "Circle tweets",
+            Toggle::Sensitive => "sensitive tweets",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The commands, printed by `help`
+pub const HELP: &str = "\
+list (l)          list the notes
+<n>, show <n>     print the note of the number
+next (n)          print the next note
+prev (p)          print the previous note
+retweets (rt)     leave out the retweets, or bring them back
+replies           leave out the replies, then only the replies to others, or bring them back
+circle            leave out the Circle tweets, or bring them back
+sensitive         leave out the sensitive tweets, or bring them back
+write (w)         write the notes as previewed and quit
+quit (q)          quit without writing";
+
+#[cfg(test)]
+mod tests {
+    use super::{BrowseCommand, Toggle};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("".parse(), Ok(BrowseCommand::List));
+        assert_eq!("3".parse(), Ok(BrowseCommand::Show(3)));
+        assert_eq!(" show 12 ".parse(), Ok(BrowseCommand::Show(12)));
+        assert_eq!("rt".parse(), Ok(BrowseCommand::Toggle(Toggle::Retweets)));
+        assert_eq!("w".parse(), Ok(BrowseCommand::Write));
+        assert!("show".parse::<BrowseCommand>().is_err());
+        assert!("delete 3".parse::<BrowseCommand>().is_err());
+    }
+}
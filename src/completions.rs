@@ -0,0 +1,345 @@
+//! Shell completion scripts generated from the definition of the command line
+use clap::{Arg, Command, ValueEnum, ValueHint};
+use std::io::{self, Write};
+
+/// Shells the completion scripts are generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// Write the completion script of `command` for `shell`
+pub fn generate<W: Write>(shell: Shell, command: &mut Command, writer: &mut W) -> io::Result<()> {
+    command.build();
+    let script = match shell {
+        Shell::Bash => bash(command),
+        Shell::Zsh => zsh(command),
+        Shell::Fish => fish(command),
+        Shell::Powershell => powershell(command),
+    };
+    writer.write_all(script.as_bytes())
+}
+
+/// The options of a command, leaving out the positional arguments and the hidden ones
+fn options(command: &Command) -> Vec<&Arg> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+        .collect()
+}
+
+/// The visible subcommands, leaving out the generated `help`
+fn subcommands(command: &Command) -> Vec<&Command> {
+    command
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+        .collect()
+}
+
+/// `-f` and `--tweets-file-path` of an option
+fn flags(arg: &Arg) -> Vec<String> {
+    let mut flags = Vec::new();
+    if let Some(short) = arg.get_short() {
+        flags.push(format!("-{}", short));
+    }
+    if let Some(long) = arg.get_long() {
+        flags.push(format!("--{}", long));
+    }
+    flags
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    arg.get_action().takes_values()
+}
+
+/// Whether the value of an option is a path, completed with the files, or a number, a name, ...
+/// completed with nothing
+fn takes_path(arg: &Arg) -> bool {
+    matches!(
+        arg.get_value_hint(),
+        ValueHint::AnyPath | ValueHint::FilePath | ValueHint::DirPath | ValueHint::ExecutablePath
+    )
+}
+
+fn takes_dir(arg: &Arg) -> bool {
+    arg.get_value_hint() == ValueHint::DirPath
+}
+
+fn possible_values(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+fn help(arg: &Arg) -> String {
+    arg.get_help()
+        .map(|help| help.to_string())
+        .unwrap_or_default()
+}
+
+fn about(command: &Command) -> String {
+    command
+        .get_about()
+        .map(|about| about.to_string())
+        .unwrap_or_default()
+}
+
+fn bash(command: &Command) -> String {
+    let name = command.get_name();
+    let function = format!("_{}", name.replace('-', "_"));
+    let mut script = format!(
+        "{function}() {{\n    local cur prev cmd\n    cur=\"${{COMP_WORDS[COMP_CWORD]}}\"\n    prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"\n    cmd=\"${{COMP_WORDS[1]}}\"\n    case \"$cmd\" in\n"
+    );
+    let mut commands = subcommands(command)
+        .into_iter()
+        .map(|sub| (format!("        {})\n", sub.get_name()), sub))
+        .collect::<Vec<(String, &Command)>>();
+    commands.push(("        *)\n".to_string(), command));
+    for (pattern, sub) in commands {
+        script.push_str(&pattern);
+        script.push_str("            case \"$prev\" in\n");
+        for arg in options(sub).into_iter().filter(|arg| takes_value(arg)) {
+            let values = possible_values(arg);
+            let reply = if !values.is_empty() {
+                format!("compgen -W \"{}\" -- \"$cur\"", values.join(" "))
+            } else if takes_dir(arg) {
+                "compgen -d -- \"$cur\"".to_string()
+            } else if takes_path(arg) {
+                "compgen -f -- \"$cur\"".to_string()
+            } else {
+                // A number, a name, ... which the shell has nothing to offer for
+                String::new()
+            };
+            script.push_str(&format!(
+                "                {})\n                    COMPREPLY=({})\n                    return\n                    ;;\n",
+                flags(arg).join("|"),
+                if reply.is_empty() {
+                    reply
+                } else {
+                    format!("$({})", reply)
+                }
+            ));
+        }
+        script.push_str("            esac\n");
+        let words = options(sub)
+            .into_iter()
+            .flat_map(flags)
+            .collect::<Vec<String>>();
+        let names = if std::ptr::eq(sub, command) {
+            subcommands(command)
+                .iter()
+                .map(|sub| sub.get_name().to_string())
+                .collect::<Vec<String>>()
+        } else {
+            // The values of the positional arguments, e.g. the shells of `completions`
+            sub.get_positionals().flat_map(possible_values).collect()
+        };
+        script.push_str(&format!(
+            "            if [[ \"$cur\" == -* ]]; then\n                COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n            else\n                COMPREPLY=($(compgen -W \"{}\" -- \"$cur\") $(compgen -f -- \"$cur\"))\n            fi\n            ;;\n",
+            words.join(" "),
+            names.join(" ")
+        ));
+    }
+    script.push_str(&format!(
+        "    esac\n}}\ncomplete -o filenames -F {function} {name}\n"
+    ));
+    script
+}
+
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+/// The `_arguments` specifications of the options of a command
+fn zsh_specs(command: &Command) -> Vec<String> {
+    let mut specs = Vec::new();
+    for arg in options(command) {
+        let action = if !takes_value(arg) {
+            String::new()
+        } else if !possible_values(arg).is_empty() {
+            format!(":{}:({})", arg.get_id(), possible_values(arg).join(" "))
+        } else if takes_dir(arg) {
+            format!(":{}:_files -/", arg.get_id())
+        } else if takes_path(arg) {
+            format!(":{}:_files", arg.get_id())
+        } else {
+            format!(":{}: ", arg.get_id())
+        };
+        for flag in flags(arg) {
+            specs.push(format!("'{}[{}]{}'", flag, zsh_escape(&help(arg)), action));
+        }
+    }
+    specs
+}
+
+fn zsh(command: &Command) -> String {
+    let name = command.get_name();
+    let mut script =
+        format!("#compdef {name}\n\n_{name}() {{\n    local -a commands\n    commands=(\n");
+    for sub in subcommands(command) {
+        script.push_str(&format!(
+            "        '{}:{}'\n",
+            sub.get_name(),
+            zsh_escape(&about(sub))
+        ));
+    }
+    script.push_str("    )\n    if (( CURRENT > 2 )); then\n        case $words[2] in\n");
+    for sub in subcommands(command) {
+        script.push_str(&format!(
+            "            {})\n                _arguments {} '*:file:_files'\n                return\n                ;;\n",
+            sub.get_name(),
+            zsh_specs(sub).join(" ")
+        ));
+    }
+    script.push_str("        esac\n    fi\n");
+    script.push_str(&format!(
+        "    _arguments {} '1: :{{_describe command commands; _files}}' '*:file:_files'\n}}\n\n_{name} \"$@\"\n",
+        zsh_specs(command).join(" ")
+    ));
+    script
+}
+
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish(command: &Command) -> String {
+    let name = command.get_name();
+    let mut lines = Vec::new();
+    let mut add_options = |sub: &Command, condition: String| {
+        for arg in options(sub) {
+            let mut line = format!("complete -c {} -n '{}'", name, condition);
+            if let Some(short) = arg.get_short() {
+                line.push_str(&format!(" -s {}", short));
+            }
+            if let Some(long) = arg.get_long() {
+                line.push_str(&format!(" -l {}", long));
+            }
+            let values = possible_values(arg);
+            if !values.is_empty() {
+                line.push_str(&format!(" -x -a '{}'", values.join(" ")));
+            } else if takes_dir(arg) {
+                line.push_str(" -x -a '(__fish_complete_directories)'");
+            } else if takes_path(arg) {
+                line.push_str(" -r -F");
+            } else if takes_value(arg) {
+                line.push_str(" -x");
+            }
+            if !help(arg).is_empty() {
+                line.push_str(&format!(" -d '{}'", fish_escape(&help(arg))));
+            }
+            lines.push(line);
+        }
+    };
+    add_options(command, "__fish_use_subcommand".to_string());
+    for sub in subcommands(command) {
+        add_options(
+            sub,
+            format!("__fish_seen_subcommand_from {}", sub.get_name()),
+        );
+    }
+    for sub in subcommands(command) {
+        lines.push(format!(
+            "complete -c {} -n '__fish_use_subcommand' -f -a {} -d '{}'",
+            name,
+            sub.get_name(),
+            fish_escape(&about(sub))
+        ));
+    }
+    lines.join("\n") + "\n"
+}
+
+fn powershell_escape(text: &str) -> String {
+    text.replace('\'', "''")
+}
+
+fn powershell(command: &Command) -> String {
+    let name = command.get_name();
+    let mut completions = Vec::new();
+    for sub in subcommands(command) {
+        completions.push(format!(
+            "        [CompletionResult]::new('{0}', '{0}', [CompletionResultType]::ParameterValue, '{1}')",
+            sub.get_name(),
+            powershell_escape(&about(sub))
+        ));
+    }
+    for arg in options(command) {
+        for flag in flags(arg) {
+            completions.push(format!(
+                "        [CompletionResult]::new('{0}', '{0}', [CompletionResultType]::ParameterName, '{1}')",
+                flag,
+                powershell_escape(&help(arg))
+            ));
+        }
+    }
+    format!(
+        "using namespace System.Management.Automation\n\nRegister-ArgumentCompleter -Native -CommandName '{}' -ScriptBlock {{\n    param($wordToComplete, $commandAst, $cursorPosition)\n    @(\n{}\n    ) | Where-Object {{ $_.CompletionText -like \"$wordToComplete*\" }}\n}}\n",
+        name,
+        completions.join(",\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, Shell};
+    use clap::{Arg, ArgAction, Command, ValueHint};
+
+    fn command() -> Command {
+        Command::new("t2o")
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .value_parser(["markdown", "csv"])
+                    .help("Format of the notes"),
+            )
+            .arg(
+                Arg::new("output")
+                    .short('o')
+                    .long("output")
+                    .value_hint(ValueHint::DirPath),
+            )
+            .arg(
+                Arg::new("config")
+                    .long("config")
+                    .value_hint(ValueHint::FilePath),
+            )
+            .arg(Arg::new("max-chars").long("max-chars"))
+            .arg(Arg::new("quiet").long("quiet").action(ArgAction::SetTrue))
+            .subcommand(Command::new("search").about("Search the tweets"))
+    }
+
+    #[test]
+    fn test_generate() {
+        let script = |shell| {
+            let mut script = Vec::new();
+            generate(shell, &mut command(), &mut script).unwrap();
+            String::from_utf8(script).unwrap()
+        };
+        let bash = script(Shell::Bash);
+        assert!(bash
+            .contains("--format)\n                    COMPREPLY=($(compgen -W \"markdown csv\""));
+        assert!(bash.contains("-o|--output)\n                    COMPREPLY=($(compgen -d"));
+        assert!(bash.contains("--config)\n                    COMPREPLY=($(compgen -f"));
+        assert!(bash.contains("--max-chars)\n                    COMPREPLY=()\n"));
+        assert!(bash.contains("complete -o filenames -F _t2o t2o"));
+        let fish = script(Shell::Fish);
+        assert!(fish.contains("complete -c t2o -n '__fish_use_subcommand' -l format -x -a 'markdown csv' -d 'Format of the notes'"));
+        assert!(fish.contains("-f -a search -d 'Search the tweets'"));
+        assert!(fish.contains("-l config -r -F"));
+        assert!(fish.contains("-l max-chars -x"));
+        let zsh = script(Shell::Zsh);
+        assert!(zsh.contains("'--format[Format of the notes]:format:(markdown csv)'"));
+        assert!(zsh.contains("'--output[]:output:_files -/'"));
+        assert!(zsh.contains("'--config[]:config:_files'"));
+        assert!(zsh.contains("'--max-chars[]:max-chars: '"));
+        assert!(script(Shell::Powershell).contains("'--quiet'"));
+    }
+}
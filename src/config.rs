@@ -0,0 +1,207 @@
+use crate::error::{Error, Result};
+use crate::templates::emoji::EmojiStyle;
+use crate::templates::frontmatter::Frontmatter;
+use crate::templates::layout::Layout;
+use crate::templates::{CashtagStyle, EmbedStyle, HashtagStyle, MentionStyle, Stage};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::Path;
+use std::time::Duration;
+
+/// Settings read from the JSON configuration file given by `--config`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Rules applied to the tweet text after the built-in formatting, in order
+    pub replacements: Vec<ReplacementRule>,
+    pub formatter: FormatterConfig,
+    /// Layout of the tweets in the monthly notes
+    pub layout: Layout,
+    /// Frontmatter of the generated notes
+    pub frontmatter: Frontmatter,
+    /// Access to the X API for the `fetch` subcommand
+    pub api: ApiConfig,
+    /// Commands run on the files written
+    pub hooks: HooksConfig,
+    /// Where the media are imported to and how the notes embed them
+    pub media: MediaConfig,
+}
+
+/// Settings of the X API, in the `api` section of the configuration file
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    /// Bearer token, used when the `TWITTER_BEARER_TOKEN` environment variable is not set
+    pub bearer_token: Option<String>,
+    /// Seconds a request may take before it is given up
+    pub timeout_secs: u64,
+    /// Number of requests sent at the same time
+    pub max_concurrent_requests: usize,
+}
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            bearer_token: None,
+            timeout_secs: 30,
+            max_concurrent_requests: 4,
+        }
+    }
+}
+impl ApiConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+}
+
+/// Commands run after the conversion writes files, e.g. to commit or sync the vault
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Run after each note or file is written, with its path as the argument
+    pub after_note: Option<String>,
+    /// Run once after the conversion, with the output directory as the argument
+    pub after_run: Option<String>,
+}
+
+/// The `media` settings of the configuration file
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MediaConfig {
+    /// Folder the media are imported to, in place of the attachment folder of the vault:
+    /// a folder of the vault, or `./<folder>` for one in the folder of the notes
+    pub folder: Option<String>,
+    /// How the notes reference the media
+    pub embeds: EmbedStyle,
+    /// Remove the EXIF and XMP metadata of the photos, such as the camera and the location
+    pub strip_metadata: bool,
+    /// Shrink the photos larger than this many pixels on their longer side, with ImageMagick
+    pub max_dimension: Option<u32>,
+}
+
+/// Settings of the Formatter pipeline
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatterConfig {
+    /// Built-in stages to run, in order; all of them in the default order when omitted
+    pub stages: Option<Vec<Stage>>,
+    /// How @mentions are linked
+    pub mentions: MentionStyle,
+    /// How hashtags are rendered
+    pub hashtags: HashtagStyle,
+    /// How cashtags are rendered
+    pub cashtags: CashtagStyle,
+    /// How emoji are rendered
+    pub emoji: EmojiStyle,
+    /// Folder the person notes are written to and the `people` mention style links to
+    pub people_folder: Option<String>,
+    /// Show the display names of the mentioned accounts in place of their handles
+    pub display_names: bool,
+}
+impl Config {
+    /// Load the configuration from a JSON file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|source| Error::Input {
+            path: path.display().to_string(),
+            source,
+        })?;
+        serde_json::from_str(&content).map_err(|e| Error::Config {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })
+    }
+}
+
+/// A user-defined regex replacement of the tweet text
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplacementRule {
+    #[serde(
+        deserialize_with = "deserialize_regex",
+        serialize_with = "serialize_regex"
+    )]
+    pub pattern: Regex,
+    /// The replacement, which can refer to the capture groups as `$1`, `${name}`, ...
+    pub replacement: String,
+}
+impl ReplacementRule {
+    pub fn apply(&self, text: &str) -> String {
+        self.pattern
+            .replace_all(text, self.replacement.as_str())
+            .to_string()
+    }
+}
+
+fn deserialize_regex<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Regex, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(serde::de::Error::custom)
+}
+
+/// Serialize a pattern as its source, for the hash of the settings
+pub(crate) fn serialize_regex<S: Serializer>(
+    regex: &Regex,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(regex.as_str())
+}
+
+pub(crate) fn serialize_optional_regex<S: Serializer>(
+    regex: &Option<Regex>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match regex {
+        Some(regex) => serializer.serialize_some(regex.as_str()),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub(crate) fn serialize_regexes<S: Serializer>(
+    regexes: &[Regex],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_seq(regexes.iter().map(Regex::as_str))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::templates::Stage;
+
+    #[test]
+    fn test_replacements() {
+        let config: Config = serde_json::from_str(
+            r#"{"replacements": [
+                {"pattern": "https?://htn\\.to/\\S+", "replacement": ""},
+                {"pattern": "(\\w+)さん", "replacement": "$1 さん"}
+            ]}"#,
+        )
+        .unwrap();
+        let text = config
+            .replacements
+            .iter()
+            .fold("hogeさん https://htn.to/abc".to_string(), |text, rule| {
+                rule.apply(&text)
+            });
+        assert_eq!(text, "hoge さん ");
+    }
+    #[test]
+    fn test_formatter_stages() {
+        let config: Config = serde_json::from_str(
+            r#"{"formatter": {"stages": ["mention-linking", "indentation"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.formatter.stages,
+            Some(vec![Stage::MentionLinking, Stage::Indentation])
+        );
+        assert!(serde_json::from_str::<Config>(r#"{"formatter": {"stages": ["nope"]}}"#).is_err());
+    }
+    #[test]
+    fn test_invalid_pattern() {
+        let config = serde_json::from_str::<Config>(
+            r#"{"replacements": [{"pattern": "(", "replacement": ""}]}"#,
+        );
+        assert!(config.is_err());
+    }
+}
@@ -0,0 +1,2600 @@
+//! Conversion of the tweet archives to notes, the library form of the command line
+#[cfg(feature = "native")]
+use crate::api::{self, ApiClient, LookedUpTweet, ReferenceLookup};
+use crate::config::Config;
+use crate::dayone;
+use crate::edits;
+use crate::error::{Error, Result};
+use crate::event;
+use crate::export::{self, OutputFormat};
+use crate::filter::TweetFilter;
+use crate::granularity::Granularity;
+#[cfg(feature = "native")]
+use crate::hook;
+use crate::ignore::IgnoreList;
+#[cfg(feature = "native")]
+use crate::image;
+use crate::input::{InputFile, SourceMetadata, TweetSource};
+use crate::joplin;
+use crate::journal::{self, JournalLedger};
+#[cfg(feature = "native")]
+use crate::links::{self, LinkCheck, LinkChecks, LinkTitles};
+#[cfg(feature = "native")]
+use crate::media::{self, MediaManifest};
+use crate::moments;
+use crate::parallel;
+use crate::people::{self, PeopleMap};
+use crate::report::{ConversionReport, NoteStatus, NoteSummary};
+#[cfg(feature = "native")]
+use crate::runs::{self, RunLog, RunManifest};
+use crate::sampling;
+use crate::search::{self, SearchIndex};
+use crate::sink::{DirectorySink, NoteSink, RecordingSink};
+use crate::spaces::{self, Space};
+#[cfg(feature = "native")]
+use crate::spill::Spill;
+use crate::stats::{self, PeriodStats};
+use crate::templates::{
+    full_tweet::{FullTweetTemplate, FullTweetTemplateInput},
+    logseq,
+    monthly_tweets::{MonthlyTweetsTemplate, MonthlyTweetsTemplateInput},
+    single_file, CashtagStyle, Formatter, HashtagStyle, MentionStyle, Redaction, Stage,
+    WaybackMode,
+};
+use crate::timezone::Timezone;
+use crate::tweet::Tweet;
+#[cfg(feature = "native")]
+use crate::tweet::{ReferenceKind, ReferencedTweet};
+use chrono::Datelike;
+use log::{debug, info, warn};
+use serde::Serialize;
+#[cfg(feature = "native")]
+use std::collections::BTreeSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "native")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The tweets grouped by the period of their note, such as 202303
+type TweetsByPeriod = HashMap<i32, Vec<Tweet>>;
+
+/// Folder of the notes the tweets of the hashtags given to `hashtag_notes` are written to
+pub const HASHTAG_NOTES_DIR: &str = "hashtags";
+
+/// A conversion of tweet archives to notes, set up step by step
+///
+/// ```no_run
+/// use twitter2obsidian::converter::Converter;
+/// use twitter2obsidian::granularity::Granularity;
+///
+/// let report = Converter::new()
+///     .input("data/tweets.js")
+///     .output_dir("vault/Twitter")
+///     .granularity(Granularity::Year)
+///     .run()?;
+/// # Ok::<(), twitter2obsidian::error::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Converter {
+    sources: Vec<Box<dyn TweetSource>>,
+    sink: RecordingSink,
+    #[cfg(feature = "native")]
+    /// Failures of the hooks in the current run, counted as warnings at its end
+    hook_failures: AtomicUsize,
+    /// Folder of the sink the notes are written to
+    notes_folder: PathBuf,
+    #[cfg(feature = "native")]
+    /// Folder of the sink the media of the archive are copied to, to embed them in the notes
+    attachments_folder: Option<PathBuf>,
+    format: OutputFormat,
+    granularity: Granularity,
+    filter: TweetFilter,
+    timezone: Timezone,
+    config: Config,
+    ignore_list: IgnoreList,
+    people_map: PeopleMap,
+    redactions: Vec<Redaction>,
+    keep_markdown: bool,
+    limit: Option<usize>,
+    /// The number of tweets to sample and the seed
+    sample: Option<(usize, u64)>,
+    jobs: Option<usize>,
+    /// Split the notes of the periods with more tweets than this
+    max_tweets_per_file: Option<usize>,
+    split_accounts: bool,
+    search_index: bool,
+    #[cfg(feature = "native")]
+    reference_lookup: Option<ReferenceLookup>,
+    deleted_tweets: Option<DeletedTweets>,
+    /// Hashtags to collect the tweets of in monthly notes of their own
+    hashtag_notes: Vec<String>,
+    /// Number of the most interacted accounts to write person notes of
+    person_notes: Option<usize>,
+    single_file: bool,
+    /// Screen name of the account, instead of the one of the `account.js`
+    screen_name: Option<String>,
+    /// File to export the stats of the periods to, as CSV or JSON
+    stats_export: Option<PathBuf>,
+    #[cfg(feature = "native")]
+    /// Download the media missing from the archive to the attachments folder
+    download_media: bool,
+    #[cfg(feature = "native")]
+    /// Fetch the titles of the linked pages to render the links with them
+    link_titles: bool,
+    /// Point the links at their snapshots in the Wayback Machine
+    wayback: Option<WaybackMode>,
+    #[cfg(feature = "native")]
+    /// Convert a period at a time, keeping the tweets in temporary files
+    low_memory: bool,
+}
+
+/// Where the deleted tweets of the archive go, when they are converted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum DeletedTweets {
+    /// Among the other tweets, tagged `#deleted`
+    Inline,
+    /// To a `deleted_tweets_<period>` note per period
+    Separate,
+}
+impl Default for Converter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Converter {
+    /// A conversion to monthly Markdown notes in the current directory, with no sources yet
+    pub fn new() -> Self {
+        Self {
+            sources: Vec::new(),
+            sink: RecordingSink::new(Box::new(DirectorySink::new("."))),
+            #[cfg(feature = "native")]
+            hook_failures: AtomicUsize::new(0),
+            notes_folder: PathBuf::new(),
+            #[cfg(feature = "native")]
+            attachments_folder: None,
+            format: OutputFormat::Markdown,
+            granularity: Granularity::default(),
+            filter: TweetFilter::default(),
+            timezone: Timezone::default(),
+            config: Config::default(),
+            ignore_list: IgnoreList::default(),
+            people_map: PeopleMap::default(),
+            redactions: Vec::new(),
+            keep_markdown: false,
+            limit: None,
+            sample: None,
+            jobs: None,
+            max_tweets_per_file: None,
+            split_accounts: false,
+            search_index: false,
+            #[cfg(feature = "native")]
+            reference_lookup: None,
+            deleted_tweets: None,
+            hashtag_notes: Vec::new(),
+            person_notes: None,
+            single_file: false,
+            screen_name: None,
+            stats_export: None,
+            #[cfg(feature = "native")]
+            download_media: false,
+            #[cfg(feature = "native")]
+            link_titles: false,
+            wayback: None,
+            #[cfg(feature = "native")]
+            low_memory: false,
+        }
+    }
+    /// Add a part of an archive, a Mastodon outbox, a Bluesky repository or a Threads export
+    pub fn input(self, path: impl Into<PathBuf>) -> Self {
+        self.source(InputFile::new(path))
+    }
+    /// Add a source of tweets other than the files of the supported exports
+    pub fn source(mut self, source: impl TweetSource + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+    /// Write the notes to this directory, which must exist
+    pub fn output_dir(self, path: impl Into<PathBuf>) -> Self {
+        self.sink(DirectorySink::new(path))
+    }
+    /// Write the notes to a target other than a directory, such as a `MemorySink`
+    pub fn sink(mut self, sink: impl NoteSink + 'static) -> Self {
+        self.sink = RecordingSink::new(Box::new(sink));
+        self
+    }
+    /// Write the notes to this folder of the output, such as the folder of the new notes of a vault
+    pub fn notes_folder(mut self, path: impl Into<PathBuf>) -> Self {
+        self.notes_folder = path.into();
+        self
+    }
+    /// Copy the media of the archive to this folder of the output and embed them in the notes
+    #[cfg(feature = "native")]
+    pub fn attachments_folder(mut self, path: impl Into<PathBuf>) -> Self {
+        self.attachments_folder = Some(path.into());
+        self
+    }
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+    /// Write a note per day, month or year
+    pub fn granularity(mut self, granularity: Granularity) -> Self {
+        self.granularity = granularity;
+        self
+    }
+    /// Convert only the tweets matching the filter
+    pub fn filter(mut self, filter: TweetFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+    /// Bucket the tweets into notes and hours in this timezone
+    pub fn timezone(mut self, timezone: Timezone) -> Self {
+        self.timezone = timezone;
+        self
+    }
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+    /// Drop or do not link the accounts and hashtags of the list, on top of the filter
+    pub fn ignore_list(mut self, ignore_list: IgnoreList) -> Self {
+        self.ignore_list = ignore_list;
+        self
+    }
+    /// Link the mentions of the mapped accounts to the notes of the people in the vault
+    pub fn people_map(mut self, people_map: PeopleMap) -> Self {
+        self.people_map = people_map;
+        self
+    }
+    pub fn redactions(mut self, redactions: &[Redaction]) -> Self {
+        self.redactions = redactions.to_vec();
+        self
+    }
+    /// Keep the Markdown syntax in the tweets instead of escaping it
+    pub fn keep_markdown(mut self, keep_markdown: bool) -> Self {
+        self.keep_markdown = keep_markdown;
+        self
+    }
+    /// Convert only the `n` oldest tweets matching the filter
+    pub fn limit(mut self, n: usize) -> Self {
+        self.limit = Some(n);
+        self.sample = None;
+        self
+    }
+    /// Convert only `n` tweets chosen at random from those matching the filter
+    pub fn sample(mut self, n: usize, seed: u64) -> Self {
+        self.sample = Some((n, seed));
+        self.limit = None;
+        self
+    }
+    /// Parse this many files and write this many notes concurrently, instead of the number of
+    /// CPUs; 1 keeps the conversion on a single thread
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+    /// Split the notes of the periods with more than `max` tweets into `tweets_YYYYMM_1.md`,
+    /// `tweets_YYYYMM_2.md` and so on, linking to each other, for the editor to stay responsive
+    pub fn max_tweets_per_file(mut self, max: usize) -> Self {
+        self.max_tweets_per_file = Some(max.max(1));
+        self
+    }
+    /// Write the notes of each account to a subfolder named after it
+    pub fn split_accounts(mut self, split_accounts: bool) -> Self {
+        self.split_accounts = split_accounts;
+        self
+    }
+    /// Also write an index of the tweets for the search subcommand
+    pub fn search_index(mut self, search_index: bool) -> Self {
+        self.search_index = search_index;
+        self
+    }
+    /// Also export the stats of each period to a CSV file, or a JSON file for other extensions
+    pub fn stats_export(mut self, path: impl Into<PathBuf>) -> Self {
+        self.stats_export = Some(path.into());
+        self
+    }
+    /// Download the media missing from the archive, such as the ones of the tweets of the API,
+    /// to the attachments folder
+    #[cfg(feature = "native")]
+    pub fn download_media(mut self, download_media: bool) -> Self {
+        self.download_media = download_media;
+        self
+    }
+    /// Fetch the titles of the linked pages, cached in the folder of the notes, and render the
+    /// links as `[title](url)`
+    #[cfg(feature = "native")]
+    pub fn link_titles(mut self, link_titles: bool) -> Self {
+        self.link_titles = link_titles;
+        self
+    }
+    /// Point the links at their snapshots in the Wayback Machine, checking whether they are dead
+    /// for `WaybackMode::ReplaceDead`
+    pub fn wayback(mut self, mode: WaybackMode) -> Self {
+        self.wayback = Some(mode);
+        self
+    }
+    /// Read the sources one after another and convert the tweets a period at a time, keeping the
+    /// other periods in temporary files, for archives too large for the memory
+    ///
+    /// The options needing all the tweets at once, such as the person notes, fall back to the
+    /// usual conversion.
+    #[cfg(feature = "native")]
+    pub fn low_memory(mut self, low_memory: bool) -> Self {
+        self.low_memory = low_memory;
+        self
+    }
+    /// Convert the deleted tweets of the archive too, which are left out otherwise
+    pub fn deleted_tweets(mut self, deleted_tweets: DeletedTweets) -> Self {
+        self.deleted_tweets = Some(deleted_tweets);
+        self
+    }
+    /// Also collect the tweets carrying each of the hashtags in monthly notes of their own
+    pub fn hashtag_notes(mut self, hashtags: &[String]) -> Self {
+        self.hashtag_notes = hashtags
+            .iter()
+            .map(|hashtag| hashtag.trim_start_matches(['#', '＃']).to_string())
+            .collect();
+        self
+    }
+    /// Also write a note of each of the `n` accounts the most tweets mention or reply to, embedding
+    /// these tweets
+    pub fn person_notes(mut self, n: usize) -> Self {
+        self.person_notes = Some(n);
+        self
+    }
+    /// Write all the Markdown to a single file with a heading per year and month, instead of a
+    /// note per period
+    pub fn single_file(mut self, single_file: bool) -> Self {
+        self.single_file = single_file;
+        self
+    }
+    /// Link the tweets to their pages under this account, instead of the one of the `account.js`
+    pub fn screen_name(mut self, screen_name: impl Into<String>) -> Self {
+        self.screen_name = Some(screen_name.into());
+        self
+    }
+    /// Look up the tweets replied to and quoted, and quote them in the notes
+    #[cfg(feature = "native")]
+    pub fn resolve_references(mut self, lookup: ReferenceLookup) -> Self {
+        self.reference_lookup = Some(lookup);
+        self
+    }
+
+    /// Convert the tweets of the sources and write the notes
+    ///
+    /// Failures confined to a note or a lookup are logged and counted as warnings in the report.
+    pub fn run(&self) -> Result<ConversionReport> {
+        self.sink.check()?;
+        self.sink.take_written();
+        #[cfg(feature = "native")]
+        self.start_run_log()?;
+        #[cfg(feature = "native")]
+        self.hook_failures.store(0, Ordering::Relaxed);
+        let mut report = ConversionReport::new();
+        let filter = self.effective_filter();
+        if let Some(start) = filter.start {
+            info!("Filtering out the tweets before {}", start);
+        }
+        if let Some(end) = filter.end {
+            info!("Filtering out the tweets at or after {}", end);
+        }
+
+        let metadata = self
+            .sources
+            .iter()
+            .map(|source| source.metadata())
+            .collect::<Result<Vec<SourceMetadata>>>()?;
+        #[cfg(feature = "native")]
+        if self.low_memory {
+            match self.needs_all_tweets(&metadata) {
+                None => return self.run_by_period(&metadata, &filter, report),
+                Some(option) => warn!(
+                    "Converting all the tweets at once, since {} needs them all",
+                    option
+                ),
+            }
+        }
+        let tweets_by_period = self.load_tweets(&metadata, &filter, &mut report)?;
+        #[cfg(feature = "native")]
+        let tweets_by_period = self.look_up_references(tweets_by_period, &mut report);
+
+        let media_dir = metadata.iter().find_map(|m| m.media_dir.as_deref());
+        let mut formatter = self.build_formatter();
+        #[cfg(feature = "native")]
+        // The media are embedded only where they are imported to
+        if let (Some(folder), OutputFormat::Markdown) = (&self.attachments_folder, self.format) {
+            let attachments =
+                self.import_media(folder, media_dir, &tweets_by_period, &mut report)?;
+            formatter = formatter.with_attachments(folder, attachments);
+        }
+        formatter = self.resolve_links(formatter, &tweets_by_period, &mut report)?;
+        let screen_name = self.account_screen_name(&metadata);
+        if let Some(screen_name) = screen_name {
+            formatter = formatter.with_screen_name(screen_name);
+        }
+        formatter = formatter.with_full_note_names(
+            self.config
+                .layout
+                .colliding_note_names(&chronological(&tweets_by_period)),
+        );
+        // The tweets of the Moments and of the person notes are marked for these notes to embed
+        // them
+        if self.format == OutputFormat::Markdown {
+            let tweets = chronological(&tweets_by_period);
+            let people = match self.person_notes {
+                Some(n) => people::most_interacted(&tweets, n, screen_name),
+                None => Vec::new(),
+            };
+            let embedded_ids = metadata
+                .iter()
+                .flat_map(|m| m.moments.iter())
+                .flat_map(|moment| moment.tweet_ids())
+                .chain(
+                    people
+                        .iter()
+                        .flat_map(|(_, tweets)| tweets.iter().map(|t| t.id())),
+                )
+                .map(str::to_string)
+                .collect::<HashSet<String>>();
+            formatter = formatter.with_block_ids(embedded_ids);
+            let note_of_tweet = self.note_of_tweet(&tweets_by_period);
+            self.write_moment_notes(&metadata, &note_of_tweet)?;
+            self.write_person_notes(&people, &note_of_tweet)?;
+        }
+        self.write_hashtag_notes(&formatter, &tweets_by_period)?;
+        if let Some(path) = &self.stats_export {
+            let mut periods = tweets_by_period.iter().collect::<Vec<_>>();
+            periods.sort_by_key(|(period, _)| **period);
+            let stats = periods
+                .into_iter()
+                .filter_map(|(_, tweets)| PeriodStats::new(tweets, self.granularity))
+                .collect::<Vec<PeriodStats>>();
+            stats::export(path, &stats)?;
+            info!(
+                target: event::FILE_WRITTEN,
+                path:% = path.display();
+                "Exported the stats to {}",
+                path.display()
+            );
+        }
+        if self.split_accounts {
+            let mut tweets_by_account: HashMap<Option<String>, TweetsByPeriod> = HashMap::new();
+            for (period, tweets) in tweets_by_period {
+                for tweet in tweets {
+                    tweets_by_account
+                        .entry(tweet.account().map(str::to_string))
+                        .or_default()
+                        .entry(period)
+                        .or_default()
+                        .push(tweet);
+                }
+            }
+            for (account, tweets_by_period) in tweets_by_account {
+                // The tweets of the sources without an account are written to the root
+                let output_dir = match account {
+                    Some(account) => self.notes_folder.join(account),
+                    None => self.notes_folder.clone(),
+                };
+                self.write_output(
+                    &output_dir,
+                    &formatter,
+                    media_dir,
+                    &tweets_by_period,
+                    &mut report,
+                )?;
+            }
+        } else {
+            self.write_output(
+                &self.notes_folder,
+                &formatter,
+                media_dir,
+                &tweets_by_period,
+                &mut report,
+            )?;
+        }
+
+        self.finish_run(&metadata, report)
+    }
+
+    /// Look up the tweets replied to and quoted, with the lookup given to `resolve_references`
+    #[cfg(feature = "native")]
+    fn look_up_references(
+        &self,
+        mut tweets_by_period: TweetsByPeriod,
+        report: &mut ConversionReport,
+    ) -> TweetsByPeriod {
+        if let Some(reference_lookup) = self.reference_lookup {
+            let result = match reference_lookup {
+                ReferenceLookup::Api => {
+                    ApiClient::from_config(&self.config.api).and_then(|client| {
+                        resolve_references(|ids| client.lookup_tweets(ids), &mut tweets_by_period)
+                    })
+                }
+                ReferenceLookup::Oembed => resolve_references(
+                    |ids| Ok(api::lookup_oembed_all(ids, &self.config.api)),
+                    &mut tweets_by_period,
+                ),
+            };
+            match result {
+                Ok(count) => info!("Looked up {} tweets replied to or quoted", count),
+                Err(e) => {
+                    warn!("Failed to look up the tweets replied to or quoted: {}", e);
+                    report.warnings += 1;
+                }
+            }
+        }
+        tweets_by_period
+    }
+
+    /// Write the notes of the whole sources and run the hook of the end of the run
+    fn finish_run(
+        &self,
+        metadata: &[SourceMetadata],
+        mut report: ConversionReport,
+    ) -> Result<ConversionReport> {
+        self.write_spaces_note(metadata)?;
+
+        #[cfg(feature = "native")]
+        if let Some(command) = &self.config.hooks.after_run {
+            self.run_hook(command, &self.notes_folder);
+        }
+        #[cfg(feature = "native")]
+        {
+            report.warnings += self.hook_failures.swap(0, Ordering::Relaxed);
+        }
+        report.finish();
+        #[cfg(feature = "native")]
+        self.write_run_manifest()?;
+        Ok(report)
+    }
+
+    /// Start the log the files written by the run are appended to, after recording the run
+    /// interrupted last time, if any
+    ///
+    /// Nothing is recorded for the sinks other than a local directory, such as the previews.
+    #[cfg(feature = "native")]
+    fn start_run_log(&self) -> Result<()> {
+        let Some(local_path) = self.sink.local_path(Path::new(runs::FILE_NAME)) else {
+            return Ok(());
+        };
+        RunManifest::recover(&local_path)?;
+        self.sink.set_run_log(RunLog::create(
+            &local_path.with_file_name(runs::LOG_FILE_NAME),
+            chrono::Local::now().to_rfc3339(),
+            self.settings_hash()?,
+        )?);
+        Ok(())
+    }
+
+    /// Record the files written by the run in the manifest of the runs of the output directory,
+    /// in place of its log
+    #[cfg(feature = "native")]
+    fn write_run_manifest(&self) -> Result<()> {
+        let Some(run_log) = self.sink.take_run_log() else {
+            return Ok(());
+        };
+        let path = Path::new(runs::FILE_NAME);
+        let Some(local_path) = self.sink.local_path(path) else {
+            return Ok(());
+        };
+        let mut manifest = RunManifest::load(&local_path)?;
+        manifest.runs.push(run_log.finish(self.sink.take_written()));
+        self.sink
+            .write(path, &serde_json::to_vec_pretty(&manifest)?)?;
+        let log_path = local_path.with_file_name(runs::LOG_FILE_NAME);
+        std::fs::remove_file(&log_path).map_err(|source| Error::Output {
+            path: log_path.display().to_string(),
+            source,
+        })
+    }
+
+    /// Hash of the settings deciding the output of the run, regardless of the inputs and of
+    /// where and how fast it is written
+    ///
+    /// The access to the API and the hooks are left out, so that the hash stays the same when only
+    /// they change and never depends on the bearer token.
+    #[cfg(feature = "native")]
+    fn settings_hash(&self) -> Result<String> {
+        use crate::config::{FormatterConfig, MediaConfig, ReplacementRule};
+        use crate::templates::{frontmatter::Frontmatter, layout::Layout};
+
+        /// The settings in a stable encoding, JSON of the fields in this order
+        #[derive(Serialize)]
+        struct Settings<'a> {
+            notes_folder: &'a Path,
+            attachments_folder: Option<&'a Path>,
+            format: OutputFormat,
+            granularity: Granularity,
+            filter: &'a TweetFilter,
+            /// The zone or the UTC offset of the machine for the local timezone
+            timezone: String,
+            replacements: &'a [ReplacementRule],
+            formatter: &'a FormatterConfig,
+            layout: &'a Layout,
+            frontmatter: &'a Frontmatter,
+            media: &'a MediaConfig,
+            ignore_list: &'a IgnoreList,
+            people_map: &'a PeopleMap,
+            redactions: &'a [Redaction],
+            keep_markdown: bool,
+            limit: Option<usize>,
+            sample: Option<(usize, u64)>,
+            max_tweets_per_file: Option<usize>,
+            split_accounts: bool,
+            search_index: bool,
+            reference_lookup: Option<ReferenceLookup>,
+            deleted_tweets: Option<DeletedTweets>,
+            hashtag_notes: &'a [String],
+            person_notes: Option<usize>,
+            single_file: bool,
+            screen_name: Option<&'a str>,
+            stats_export: Option<&'a Path>,
+            download_media: bool,
+            link_titles: bool,
+            wayback: Option<WaybackMode>,
+        }
+        let config = &self.config;
+        let settings = Settings {
+            notes_folder: &self.notes_folder,
+            attachments_folder: self.attachments_folder.as_deref(),
+            format: self.format,
+            granularity: self.granularity,
+            filter: &self.filter,
+            timezone: self.timezone.resolved().to_string(),
+            replacements: &config.replacements,
+            formatter: &config.formatter,
+            layout: &config.layout,
+            frontmatter: &config.frontmatter,
+            media: &config.media,
+            ignore_list: &self.ignore_list,
+            people_map: &self.people_map,
+            redactions: &self.redactions,
+            keep_markdown: self.keep_markdown,
+            limit: self.limit,
+            sample: self.sample,
+            max_tweets_per_file: self.max_tweets_per_file,
+            split_accounts: self.split_accounts,
+            search_index: self.search_index,
+            reference_lookup: self.reference_lookup,
+            deleted_tweets: self.deleted_tweets,
+            hashtag_notes: &self.hashtag_notes,
+            person_notes: self.person_notes,
+            single_file: self.single_file,
+            screen_name: self.screen_name.as_deref(),
+            stats_export: self.stats_export.as_deref(),
+            download_media: self.download_media,
+            link_titles: self.link_titles,
+            wayback: self.wayback,
+        };
+        Ok(media::sha256_hex(&serde_json::to_vec(&settings)?))
+    }
+
+    /// The option needing all the tweets in memory at once, if any, which rules out the
+    /// conversion a period at a time
+    #[cfg(feature = "native")]
+    fn needs_all_tweets(&self, metadata: &[SourceMetadata]) -> Option<&'static str> {
+        let moments = metadata.iter().any(|m| !m.moments.is_empty());
+        if !matches!(self.format, OutputFormat::Markdown | OutputFormat::Org) {
+            Some("--format")
+        } else if self.single_file {
+            Some("--single-file")
+        } else if self.split_accounts {
+            Some("--split-accounts")
+        } else if self.reference_lookup.is_some() {
+            Some("--resolve-references")
+        } else if self.person_notes.is_some() {
+            Some("--person-notes")
+        } else if !self.hashtag_notes.is_empty() {
+            Some("--hashtag-notes")
+        } else if self.stats_export.is_some() {
+            Some("--stats-export")
+        } else if self.search_index {
+            Some("--search-index")
+        } else if self.limit.is_some() || self.sample.is_some() {
+            Some("--limit or --sample")
+        } else if moments && self.format == OutputFormat::Markdown {
+            Some("the notes of the Moments")
+        } else {
+            None
+        }
+    }
+
+    /// Convert the tweets a period at a time, the sources read one after another into temporary
+    /// files by period
+    ///
+    /// The versions of an edited tweet are merged only within a period.
+    #[cfg(feature = "native")]
+    fn run_by_period(
+        &self,
+        metadata: &[SourceMetadata],
+        filter: &TweetFilter,
+        mut report: ConversionReport,
+    ) -> Result<ConversionReport> {
+        let attribute = self.attributes_accounts(metadata);
+        let mut spill = Spill::new()?;
+        let mut ids = HashSet::new();
+        for (source, metadata) in self.sources.iter().zip(metadata.iter()) {
+            let account = metadata
+                .account
+                .as_ref()
+                .filter(|_| attribute)
+                .map(|account| account.username.as_str());
+            debug!("Reading the tweets of {}", metadata.name);
+            let grouped =
+                self.read_source(source.as_ref(), account, filter, &mut |period, tweet| {
+                    spill.push(period, tweet)
+                })?;
+            report.tweets_parsed += grouped.parsed;
+            report.tweets_filtered_out += grouped.filtered_out;
+            for (field, count) in grouped.unknown_fields {
+                *report.unknown_fields.entry(field).or_default() += count;
+            }
+            ids.extend(grouped.ids);
+        }
+        if !report.unknown_fields.is_empty() {
+            warn!(
+                "The archive has fields not understood, the export format may have changed: {}",
+                report.unknown_fields_summary()
+            );
+        }
+
+        let media_dir = metadata.iter().find_map(|m| m.media_dir.as_deref());
+        let mut formatter = self.build_formatter();
+        if let Some(screen_name) = self.account_screen_name(metadata) {
+            formatter = formatter.with_screen_name(screen_name);
+        }
+        let mut written = 0;
+        for period in spill.periods() {
+            let mut tweets = spill.take(period)?;
+            if filter.needs_own_tweet_ids() {
+                let before = tweets.len();
+                tweets.retain(|tweet| filter.matches_thread(tweet, &ids));
+                report.tweets_filtered_out += before - tweets.len();
+            }
+            if tweets
+                .iter()
+                .any(|tweet| tweet.initial_tweet_id().is_some())
+            {
+                tweets = edits::collapse(tweets).0;
+            }
+            if tweets.is_empty() {
+                continue;
+            }
+            let tweets_by_period = HashMap::from([(period, tweets)]);
+            if let (Some(folder), OutputFormat::Markdown) = (&self.attachments_folder, self.format)
+            {
+                let attachments =
+                    self.import_media(folder, media_dir, &tweets_by_period, &mut report)?;
+                formatter = formatter.with_attachments(folder, attachments);
+            }
+            formatter = self.resolve_links(formatter, &tweets_by_period, &mut report)?;
+            // The tweets taking the same Zettelkasten ID are in the same period, unless its
+            // format leaves out the date
+            formatter = formatter.with_full_note_names(
+                self.config
+                    .layout
+                    .colliding_note_names(&chronological(&tweets_by_period)),
+            );
+            self.write_output(
+                &self.notes_folder,
+                &formatter,
+                media_dir,
+                &tweets_by_period,
+                &mut report,
+            )?;
+            written += 1;
+        }
+        if report.tweets_filtered_out > 0 {
+            info!(
+                target: event::TWEETS_SKIPPED,
+                count = report.tweets_filtered_out;
+                "Skipped {} tweets by the filters", report.tweets_filtered_out
+            );
+        }
+        if written == 0 {
+            if report.tweets_parsed > 0 {
+                warn!("No tweets matched the filters");
+            } else {
+                warn!("There are no tweets to convert");
+            }
+            report.warnings += 1;
+        }
+        self.finish_run(metadata, report)
+    }
+
+    /// Screen name of the account, given or the one of the sources
+    fn account_screen_name<'a>(&'a self, metadata: &'a [SourceMetadata]) -> Option<&'a str> {
+        self.screen_name.as_deref().or_else(|| {
+            metadata
+                .iter()
+                .find_map(|m| m.account.as_ref())
+                .map(|account| account.username.as_str())
+        })
+    }
+
+    /// Whether the tweets are attributed to their accounts, which they are only when they come
+    /// from several accounts
+    fn attributes_accounts(&self, metadata: &[SourceMetadata]) -> bool {
+        let usernames = metadata
+            .iter()
+            .filter_map(|m| m.account.as_ref())
+            .map(|account| account.username.as_str())
+            .collect::<HashSet<&str>>();
+        usernames.len() > 1 || self.split_accounts
+    }
+
+    /// Write the note listing the Spaces of the sources, if any
+    fn write_spaces_note(&self, metadata: &[SourceMetadata]) -> Result<()> {
+        let spaces = metadata
+            .iter()
+            .flat_map(|m| m.spaces.iter().cloned())
+            .collect::<Vec<Space>>();
+        if spaces.is_empty() || self.format != OutputFormat::Markdown {
+            return Ok(());
+        }
+        let account_id = metadata
+            .iter()
+            .find_map(|m| m.account.as_ref())
+            .map(|account| account.account_id.as_str());
+        let note = spaces::render_note(&spaces, account_id, &self.timezone);
+        self.save(
+            &self.notes_folder.join(spaces::NOTE_NAME),
+            note.as_bytes(),
+            "the Spaces",
+        )
+    }
+
+    /// Write a note per Moment of the sources, embedding the tweets from the notes of the periods
+    fn write_moment_notes(
+        &self,
+        metadata: &[SourceMetadata],
+        note_of_tweet: &HashMap<&str, String>,
+    ) -> Result<()> {
+        for moment in metadata.iter().flat_map(|m| m.moments.iter()) {
+            let note =
+                moments::render_note(moment, |id| note_of_tweet.get(id).cloned(), &self.timezone);
+            self.save(
+                &self
+                    .notes_folder
+                    .join(moment.note_name())
+                    .with_extension("md"),
+                note.as_bytes(),
+                "the Moment",
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write a note per account of `people`, embedding the tweets mentioning or replying to it, at
+    /// the note the mentions link to
+    fn write_person_notes(
+        &self,
+        people: &[(String, Vec<&Tweet>)],
+        note_of_tweet: &HashMap<&str, String>,
+    ) -> Result<()> {
+        for (screen_name, tweets) in people {
+            let note = people::render_person_note(screen_name, tweets, |id| {
+                note_of_tweet.get(id).cloned()
+            });
+            let folder = self
+                .config
+                .formatter
+                .people_folder
+                .as_deref()
+                .unwrap_or(people::DEFAULT_FOLDER);
+            let path = people::note_path(folder, &self.people_map, screen_name);
+            self.save(
+                &self.notes_folder.join(format!("{}.md", path)),
+                note.as_bytes(),
+                "the person note",
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Name of the note of the period each tweet is written to, by the IDs of the tweets
+    fn note_of_tweet<'a>(&self, tweets_by_period: &'a TweetsByPeriod) -> HashMap<&'a str, String> {
+        let mut note_of_tweet = HashMap::new();
+        for note in self.period_notes(tweets_by_period) {
+            let name = match self.single_file {
+                true => single_file::NOTE_NAME.to_string(),
+                false => note.name(),
+            };
+            for tweet in note.tweets {
+                note_of_tweet.insert(tweet.id(), name.clone());
+            }
+        }
+        note_of_tweet
+    }
+
+    /// Write the tweets carrying each hashtag of `hashtag_notes` to `hashtags/<hashtag>/YYYY-MM.md`
+    fn write_hashtag_notes(
+        &self,
+        formatter: &Formatter,
+        tweets_by_period: &TweetsByPeriod,
+    ) -> Result<()> {
+        if self.hashtag_notes.is_empty() || self.format != OutputFormat::Markdown {
+            return Ok(());
+        }
+        let template = MonthlyTweetsTemplate::new()?;
+        let tweets = chronological(tweets_by_period);
+        for hashtag in self.hashtag_notes.iter() {
+            let tagged = tweets
+                .iter()
+                .filter(|tweet| tweet.has_hashtag(hashtag))
+                .copied()
+                .collect::<Vec<&Tweet>>();
+            for month in tagged.chunk_by(|a, b| {
+                Granularity::Month.key(&a.created_at()) == Granularity::Month.key(&b.created_at())
+            }) {
+                let data = MonthlyTweetsTemplateInput::new(
+                    month,
+                    formatter,
+                    &self.config.layout,
+                    &self.config.frontmatter,
+                    Granularity::Month,
+                )?
+                .for_hashtag(hashtag);
+                let mut content = Vec::new();
+                template.render(&data, &mut content)?;
+                let path = self
+                    .notes_folder
+                    .join(HASHTAG_NOTES_DIR)
+                    .join(hashtag)
+                    .join(month[0].created_at().format("%Y-%m").to_string())
+                    .with_extension("md");
+                self.save(&path, &content, "the tweets of the hashtag")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The filter with the accounts and hashtags of the ignore list
+    fn effective_filter(&self) -> TweetFilter {
+        let mut filter = self.filter.clone();
+        filter
+            .ignored_accounts
+            .extend(self.ignore_list.dropped_accounts.iter().cloned());
+        filter
+            .ignored_hashtags
+            .extend(self.ignore_list.dropped_hashtags.iter().cloned());
+        filter.include_deleted |= self.deleted_tweets.is_some();
+        filter
+    }
+
+    fn group_by_period(&self, tweets: Vec<Tweet>) -> TweetsByPeriod {
+        let mut tweets_by_period: TweetsByPeriod = HashMap::new();
+        for tweet in tweets {
+            tweets_by_period
+                .entry(self.granularity.key(&tweet.created_at()))
+                .or_default()
+                .push(tweet);
+        }
+        tweets_by_period
+    }
+
+    /// Read the sources concurrently and group the tweets matching the filter by period
+    fn load_tweets(
+        &self,
+        metadata: &[SourceMetadata],
+        filter: &TweetFilter,
+        report: &mut ConversionReport,
+    ) -> Result<TweetsByPeriod> {
+        let jobs = self.jobs.unwrap_or_else(parallel::default_jobs);
+        let attribute = self.attributes_accounts(metadata);
+        let sources = self
+            .sources
+            .iter()
+            .zip(metadata.iter())
+            .collect::<Vec<(&Box<dyn TweetSource>, &SourceMetadata)>>();
+        let results = parallel::map_bounded(&sources, jobs, |(source, metadata)| {
+            let account = metadata
+                .account
+                .as_ref()
+                .filter(|_| attribute)
+                .map(|account| account.username.as_str());
+            debug!("Reading the tweets of {}", metadata.name);
+            self.load_and_group_tweets(source.as_ref(), account, filter)
+        });
+        let mut tweets_by_period: TweetsByPeriod = HashMap::new();
+        let mut ids = HashSet::new();
+        for result in results {
+            let grouped = result?;
+            report.tweets_parsed += grouped.parsed;
+            report.tweets_filtered_out += grouped.filtered_out;
+            for (field, count) in grouped.unknown_fields {
+                *report.unknown_fields.entry(field).or_default() += count;
+            }
+            ids.extend(grouped.ids);
+            for (period, tweets) in grouped.tweets_by_period {
+                tweets_by_period.entry(period).or_default().extend(tweets);
+            }
+        }
+        if !report.unknown_fields.is_empty() {
+            warn!(
+                "The archive has fields not understood, the export format may have changed: {}",
+                report.unknown_fields_summary()
+            );
+        }
+        // Replies can be told apart from own threads only after the whole archive is read
+        if filter.needs_own_tweet_ids() {
+            for tweets in tweets_by_period.values_mut() {
+                let before = tweets.len();
+                tweets.retain(|tweet| filter.matches_thread(tweet, &ids));
+                report.tweets_filtered_out += before - tweets.len();
+            }
+            tweets_by_period.retain(|_, tweets| !tweets.is_empty());
+        }
+        // The versions of an edited tweet can fall in different periods
+        if tweets_by_period
+            .values()
+            .flatten()
+            .any(|tweet| tweet.initial_tweet_id().is_some())
+        {
+            let (tweets, merged) =
+                edits::collapse(tweets_by_period.into_values().flatten().collect());
+            info!("Merged {} earlier versions of edited tweets", merged);
+            tweets_by_period = self.group_by_period(tweets);
+        }
+        // Take a subset of the whole archive for trial runs
+        if self.limit.is_some() || self.sample.is_some() {
+            let tweets = tweets_by_period
+                .into_values()
+                .flatten()
+                .collect::<Vec<Tweet>>();
+            let before = tweets.len();
+            let tweets = match (self.limit, self.sample) {
+                (Some(n), _) => sampling::limit(tweets, n),
+                (None, Some((n, seed))) => sampling::sample(tweets, n, seed),
+                (None, None) => tweets,
+            };
+            report.tweets_filtered_out += before - tweets.len();
+            tweets_by_period = self.group_by_period(tweets);
+        }
+        if report.tweets_filtered_out > 0 {
+            info!(
+                target: event::TWEETS_SKIPPED,
+                count = report.tweets_filtered_out;
+                "Skipped {} tweets by the filters", report.tweets_filtered_out
+            );
+        }
+        if tweets_by_period.is_empty() {
+            if report.tweets_parsed > 0 {
+                warn!("No tweets matched the filters");
+            } else {
+                warn!("There are no tweets to convert");
+            }
+            report.warnings += 1;
+        }
+        Ok(tweets_by_period)
+    }
+
+    /// Group the tweets of a source by period while they are read, dropping those not matching the filter
+    ///
+    /// The tweets are attributed to `account` when it is given.
+    fn load_and_group_tweets(
+        &self,
+        source: &dyn TweetSource,
+        account: Option<&str>,
+        filter: &TweetFilter,
+    ) -> Result<GroupedTweets> {
+        let mut tweets_by_period: TweetsByPeriod = HashMap::new();
+        let mut grouped = self.read_source(source, account, filter, &mut |period, tweet| {
+            tweets_by_period.entry(period).or_default().push(tweet);
+            Ok(())
+        })?;
+        grouped.tweets_by_period = tweets_by_period;
+        Ok(grouped)
+    }
+
+    /// Read the tweets of a source, passing those matching the filter to `on_tweet` with their
+    /// periods, and count them
+    fn read_source(
+        &self,
+        source: &dyn TweetSource,
+        account: Option<&str>,
+        filter: &TweetFilter,
+        on_tweet: &mut dyn FnMut(i32, Tweet) -> Result<()>,
+    ) -> Result<GroupedTweets> {
+        let mut grouped = GroupedTweets::default();
+        let mut error = None;
+        source.read_tweets(&mut |mut tweet| {
+            if error.is_some() {
+                return;
+            }
+            grouped.parsed += 1;
+            for field in tweet.take_unknown_fields() {
+                *grouped.unknown_fields.entry(field).or_default() += 1;
+            }
+            if filter.needs_own_tweet_ids() {
+                grouped.ids.insert(tweet.id().to_string());
+            }
+            let tweet = match account {
+                Some(account) => tweet.with_account(account.to_string()),
+                None => tweet,
+            };
+            let tweet = tweet.in_timezone(&self.timezone);
+            if !filter.matches(&tweet) {
+                grouped.filtered_out += 1;
+                return;
+            }
+            if let Err(e) = on_tweet(self.granularity.key(&tweet.created_at()), tweet) {
+                error = Some(e);
+            }
+        })?;
+        match error {
+            Some(e) => Err(e),
+            None => Ok(grouped),
+        }
+    }
+
+    /// Build the Formatter from the settings and the configuration
+    fn build_formatter(&self) -> Formatter {
+        let config = &self.config;
+        let mut formatter = Formatter::new()
+            .with_ignore_list(&self.ignore_list)
+            .with_people_map(&self.people_map)
+            .with_redactions(&self.redactions)
+            .with_replacements(&config.replacements)
+            .with_mention_style(config.formatter.mentions)
+            .with_hashtag_style(config.formatter.hashtags)
+            .with_cashtag_style(config.formatter.cashtags)
+            .with_emoji_style(config.formatter.emoji)
+            .with_display_names(config.formatter.display_names)
+            .with_embed_style(config.media.embeds);
+        if let Some(folder) = &config.formatter.people_folder {
+            formatter = formatter.with_people_folder(folder);
+        }
+        // Joplin and Day One have no wikilinks, so the mentions link to the profiles and the tags
+        // stay text
+        if matches!(self.format, OutputFormat::Joplin | OutputFormat::Dayone) {
+            formatter = formatter
+                .with_mention_style(match config.formatter.mentions {
+                    MentionStyle::Plain => MentionStyle::Plain,
+                    _ => MentionStyle::Profile,
+                })
+                .with_hashtag_style(match config.formatter.hashtags {
+                    HashtagStyle::Wikilink => HashtagStyle::Tag,
+                    style => style,
+                })
+                .with_cashtag_style(match config.formatter.cashtags {
+                    CashtagStyle::Wikilink => CashtagStyle::Plain,
+                    style => style,
+                });
+        }
+        if let Some(stages) = &config.formatter.stages {
+            formatter = formatter.with_stages(stages);
+        }
+        // Org has a markup of its own
+        if self.keep_markdown || self.format == OutputFormat::Org {
+            formatter = formatter.without_stage(Stage::MarkdownEscaping);
+        }
+        debug!("Formatter pipeline: {}", formatter.stage_names().join(", "));
+        formatter
+    }
+
+    /// Write the tweets in the format of the conversion to `output_dir` of the sink
+    fn write_output(
+        &self,
+        output_dir: &Path,
+        formatter: &Formatter,
+        media_dir: Option<&Path>,
+        tweets_by_period: &TweetsByPeriod,
+        report: &mut ConversionReport,
+    ) -> Result<()> {
+        match self.format {
+            OutputFormat::Markdown | OutputFormat::Org => {
+                if self.single_file && self.format == OutputFormat::Markdown {
+                    self.write_single_file(output_dir, formatter, tweets_by_period, report)?;
+                } else {
+                    self.write_notes(output_dir, formatter, tweets_by_period, report)?;
+                }
+            }
+            OutputFormat::Logseq => {
+                self.write_logseq_journals(output_dir, formatter, tweets_by_period)?
+            }
+            OutputFormat::Journal => {
+                self.write_journals(output_dir, formatter, tweets_by_period)?
+            }
+            OutputFormat::Joplin | OutputFormat::Dayone => {
+                self.write_journal_export(output_dir, formatter, media_dir, tweets_by_period)?
+            }
+            _ => self.write_export(output_dir, tweets_by_period, report)?,
+        }
+        if self.search_index {
+            let index = SearchIndex::build(&chronological(tweets_by_period), self.granularity);
+            let index_dir = output_dir.join(search::INDEX_DIR_NAME);
+            let files = index.files()?;
+            for (path, content) in files.iter() {
+                self.sink.write(&index_dir.join(path), content)?;
+            }
+            info!(
+                target: event::FILE_WRITTEN,
+                path = self.event_path(&index_dir).as_str(), count = files.len();
+                "Saved the search index to {}",
+                self.sink.describe(&index_dir)
+            );
+        }
+        Ok(())
+    }
+
+    /// Write a file to the sink and log it as `what`
+    fn save(&self, path: &Path, content: &[u8], what: &str) -> Result<()> {
+        self.sink.write(path, content)?;
+        self.saved(path, what);
+        Ok(())
+    }
+
+    /// Write a file to the sink as `write` produces it, for the big archives, and log it as `what`
+    fn save_streamed(
+        &self,
+        path: &Path,
+        what: &str,
+        write: &mut dyn FnMut(&mut dyn Write) -> io::Result<()>,
+    ) -> Result<()> {
+        self.sink.write_streamed(path, write)?;
+        self.saved(path, what);
+        Ok(())
+    }
+
+    /// Log the file saved to `path` and run the hook after it
+    fn saved(&self, path: &Path, what: &str) {
+        info!(
+            target: event::FILE_WRITTEN,
+            path = self.event_path(path).as_str();
+            "Saved {} to {}",
+            what,
+            self.sink.describe(path)
+        );
+        #[cfg(feature = "native")]
+        if let Some(command) = &self.config.hooks.after_note {
+            self.run_hook(command, path);
+        }
+    }
+
+    /// Path of the file at `path` of the sink in the events, the local one if it is on the disk
+    fn event_path(&self, path: &Path) -> String {
+        self.sink
+            .local_path(path)
+            .unwrap_or_else(|| path.to_path_buf())
+            .display()
+            .to_string()
+    }
+
+    /// Run a hook on the file or folder at `path` of the sink, which must be a local one
+    ///
+    /// A failure of the hook is only logged and counted as a warning of the run, since the files
+    /// are written already.
+    #[cfg(feature = "native")]
+    fn run_hook(&self, command: &str, path: &Path) {
+        let Some(local_path) = self.sink.local_path(path) else {
+            debug!(
+                "Skipped the hook on {}, which is not a local file",
+                path.display()
+            );
+            return;
+        };
+        if let Err(e) = hook::run(command, &local_path) {
+            warn!("{}", e);
+            self.hook_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Give the Formatter the snapshots of the linked pages, which cannot be checked without the
+    /// native feature
+    #[cfg(not(feature = "native"))]
+    fn resolve_links(
+        &self,
+        formatter: Formatter,
+        _tweets_by_period: &TweetsByPeriod,
+        _report: &mut ConversionReport,
+    ) -> Result<Formatter> {
+        match self.wayback {
+            Some(mode) if self.format == OutputFormat::Markdown => {
+                Ok(formatter.with_wayback(mode, HashSet::new()))
+            }
+            _ => Ok(formatter),
+        }
+    }
+
+    /// Give the Formatter the titles of the linked pages and their snapshots, as enabled, in the
+    /// Markdown notes
+    #[cfg(feature = "native")]
+    fn resolve_links(
+        &self,
+        mut formatter: Formatter,
+        tweets_by_period: &TweetsByPeriod,
+        report: &mut ConversionReport,
+    ) -> Result<Formatter> {
+        if self.format != OutputFormat::Markdown {
+            return Ok(formatter);
+        }
+        if self.link_titles {
+            formatter =
+                formatter.with_link_titles(self.fetch_link_titles(tweets_by_period, report)?);
+        }
+        match self.wayback {
+            Some(WaybackMode::ReplaceDead) => {
+                let dead_links = self.check_links(tweets_by_period, report)?;
+                formatter = formatter.with_wayback(WaybackMode::ReplaceDead, dead_links);
+            }
+            Some(mode) => formatter = formatter.with_wayback(mode, HashSet::new()),
+            None => {}
+        }
+        Ok(formatter)
+    }
+
+    /// Fetch the titles of the links of the tweets not in the cache yet, and return the titles
+    /// of all the links
+    #[cfg(feature = "native")]
+    fn fetch_link_titles(
+        &self,
+        tweets_by_period: &TweetsByPeriod,
+        report: &mut ConversionReport,
+    ) -> Result<HashMap<String, String>> {
+        let cache_path = self.notes_folder.join(links::CACHE_FILE_NAME);
+        let mut cache = match self.sink.local_path(&cache_path) {
+            Some(path) => LinkTitles::load(&path)?,
+            None => LinkTitles::default(),
+        };
+        let urls = linked_pages(tweets_by_period)
+            .into_iter()
+            .filter(|url| !cache.contains(url))
+            .collect::<Vec<&str>>();
+        if urls.is_empty() {
+            return Ok(cache.found());
+        }
+        info!("Fetching the titles of {} links", urls.len());
+        let api = &self.config.api;
+        let results = parallel::map_bounded(&urls, api.max_concurrent_requests, |url| {
+            links::fetch_title(url, api.timeout())
+        });
+        let mut failed = 0;
+        for (url, result) in urls.into_iter().zip(results) {
+            // The links that failed are not fetched again, most of them are gone for good
+            let title = result.unwrap_or_else(|e| {
+                debug!("Failed to fetch the title of {}: {}", url, e);
+                failed += 1;
+                None
+            });
+            cache.titles.insert(url.to_string(), title);
+        }
+        if failed > 0 {
+            warn!("Failed to fetch the titles of {} links", failed);
+            report.warnings += 1;
+        }
+        // Streamed notes have no folder to keep the cache in
+        if !self.sink.is_stream() {
+            self.sink
+                .write(&cache_path, &serde_json::to_vec_pretty(&cache)?)?;
+        }
+        Ok(cache.found())
+    }
+
+    /// Check the links of the tweets not in the cache yet, and return the dead ones
+    #[cfg(feature = "native")]
+    fn check_links(
+        &self,
+        tweets_by_period: &TweetsByPeriod,
+        report: &mut ConversionReport,
+    ) -> Result<HashSet<String>> {
+        let cache_path = self.notes_folder.join(links::CHECKS_FILE_NAME);
+        let mut cache = match self.sink.local_path(&cache_path) {
+            Some(path) => LinkChecks::load(&path)?,
+            None => LinkChecks::default(),
+        };
+        let mut dead_links = HashSet::new();
+        let urls = linked_pages(tweets_by_period)
+            .into_iter()
+            .filter(|url| !cache.dead.contains_key(*url))
+            .collect::<Vec<&str>>();
+        if !urls.is_empty() {
+            info!("Checking {} links", urls.len());
+            let api = &self.config.api;
+            let results = parallel::map_bounded(&urls, api.max_concurrent_requests, |url| {
+                links::check_link(url, api.timeout())
+            });
+            let mut failed = 0;
+            for (url, result) in urls.into_iter().zip(results) {
+                match result {
+                    Ok(LinkCheck::Alive) => {
+                        cache.dead.insert(url.to_string(), false);
+                    }
+                    Ok(LinkCheck::Dead) => {
+                        cache.dead.insert(url.to_string(), true);
+                    }
+                    Ok(LinkCheck::Unresolved) => {
+                        dead_links.insert(url.to_string());
+                    }
+                    Err(e) => {
+                        debug!("Failed to check {}: {}", url, e);
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                warn!(
+                    "Failed to check {} links, checked again in the next run",
+                    failed
+                );
+                report.warnings += 1;
+            }
+            if !self.sink.is_stream() {
+                self.sink
+                    .write(&cache_path, &serde_json::to_vec_pretty(&cache)?)?;
+            }
+        }
+        dead_links.extend(
+            cache
+                .dead
+                .into_iter()
+                .filter(|(_, dead)| *dead)
+                .map(|(url, _)| url),
+        );
+        if !dead_links.is_empty() {
+            info!(
+                "Replacing {} dead links with their snapshots",
+                dead_links.len()
+            );
+        }
+        Ok(dead_links)
+    }
+
+    /// Copy the media of the tweets found in `media_dir` to `folder`, or download them, and
+    /// return the names of the files there for the notes to embed, by the names of the media
+    ///
+    /// The media of the same content as one imported already, such as a meme posted again,
+    /// share its file instead of being imported again.
+    ///
+    /// The files recorded in the manifest of the folder are skipped, so that only the new media
+    /// and the ones failed or cut short in the last run are imported again. The media are
+    /// downloaded `max_concurrent_requests` at a time, and the manifest is saved after every few
+    /// files, so an interrupted run resumes from where it stopped.
+    #[cfg(feature = "native")]
+    fn import_media(
+        &self,
+        folder: &Path,
+        media_dir: Option<&Path>,
+        tweets_by_period: &TweetsByPeriod,
+        report: &mut ConversionReport,
+    ) -> Result<HashMap<String, String>> {
+        let manifest_path = folder.join(media::MANIFEST_FILE_NAME);
+        let mut manifest = match self.sink.local_path(&manifest_path) {
+            Some(path) => MediaManifest::load(&path)?,
+            None => MediaManifest::default(),
+        };
+        // The file of each media, shared by the media of the same content
+        let mut attachments = HashMap::new();
+        let (mut imported, mut skipped, mut shared) = (0, 0, 0);
+        let save = |manifest: &MediaManifest| -> Result<()> {
+            self.sink
+                .write(&manifest_path, &serde_json::to_vec_pretty(manifest)?)
+        };
+        // The media to copy or download, as the name, the path, whether it existed, the copy in
+        // the archive and the URL
+        let mut pending = Vec::new();
+        let mut queued = HashSet::new();
+        for tweet in tweets_by_period.values().flatten() {
+            for media in tweet.entities().media.iter() {
+                let Some(name) = media.file_name(tweet.id()) else {
+                    continue;
+                };
+                if attachments.contains_key(&name) || queued.contains(&name) {
+                    continue;
+                }
+                let attachment = manifest.attachment(&name).to_string();
+                let local_path = self.sink.local_path(&folder.join(&attachment));
+                let archive_path = media_dir
+                    .map(|dir| dir.join(&name))
+                    .filter(|path| path.is_file());
+                if let Some(path) = &local_path {
+                    if manifest.is_current(&name, path)
+                        || self.recover_media(
+                            &mut manifest,
+                            &name,
+                            path,
+                            archive_path.as_deref(),
+                            &media.media_url_https,
+                        )
+                    {
+                        attachments.insert(name, attachment);
+                        skipped += 1;
+                        continue;
+                    }
+                }
+                let output_file_path = folder.join(&name);
+                let local_path = self.sink.local_path(&output_file_path);
+                if archive_path.is_none() && !self.download_media {
+                    continue;
+                }
+                // A download is renamed to its final path before it is written to the sink
+                let existed = local_path.as_ref().is_some_and(|path| path.exists());
+                queued.insert(name.clone());
+                pending.push((
+                    name,
+                    output_file_path,
+                    local_path,
+                    existed,
+                    archive_path,
+                    media.media_url_https.as_str(),
+                ));
+            }
+        }
+        // The downloads run concurrently, a batch between the saves of the manifest at a time
+        let jobs = self.config.api.max_concurrent_requests;
+        let timeout = self.config.api.timeout();
+        for batch in pending.chunks(media::MANIFEST_SAVE_INTERVAL) {
+            let contents = parallel::map_bounded(
+                batch,
+                jobs,
+                |(_, _, local_path, _, archive_path, url)| match (archive_path, local_path) {
+                    (Some(path), _) => (
+                        path.display().to_string(),
+                        std::fs::read(path).map_err(Error::from),
+                    ),
+                    (None, Some(path)) => (url.to_string(), media::download_to(url, path, timeout)),
+                    (None, None) => (url.to_string(), media::download(url, timeout)),
+                },
+            );
+            for ((name, output_file_path, local_path, existed, _, _), (source, content)) in
+                batch.iter().zip(contents)
+            {
+                let result = content
+                    .map(|content| self.process_photo(output_file_path, content, report))
+                    .and_then(|content| {
+                        let sha256 = media::sha256_hex(&content);
+                        if let Some(original) = self.shared_media(folder, &manifest, &sha256, name)
+                        {
+                            // A downloaded duplicate is in the folder already
+                            if let Some(path) = local_path.as_ref().filter(|path| path.is_file()) {
+                                std::fs::remove_file(path)?;
+                            }
+                            manifest.record_duplicate(name, &source, &original);
+                            return Ok(original);
+                        }
+                        self.sink.record(output_file_path, *existed);
+                        self.sink.write(output_file_path, &content)?;
+                        manifest.record(name, &source, &content);
+                        Ok(name.clone())
+                    });
+                match result {
+                    Ok(attachment) => {
+                        if attachment == *name {
+                            imported += 1;
+                        } else {
+                            shared += 1;
+                        }
+                        attachments.insert(name.clone(), attachment);
+                    }
+                    Err(e) => {
+                        warn!("Failed to import {}: {}", source, e);
+                        manifest.record_failure(name, &source, &e);
+                        report.warnings += 1;
+                    }
+                }
+            }
+            save(&manifest)?;
+        }
+        if imported > 0 {
+            info!(
+                "Imported {} media to {}",
+                imported,
+                self.sink.describe(folder)
+            );
+        }
+        if shared > 0 {
+            info!("Shared the files of {} media with the same content", shared);
+        }
+        if skipped > 0 {
+            info!("Skipped {} media imported already", skipped);
+        }
+        if !manifest.files.is_empty() {
+            save(&manifest)?;
+        }
+        Ok(attachments)
+    }
+
+    /// The file imported already with the content of the hash, other than `name`, if it is still
+    /// in the folder
+    #[cfg(feature = "native")]
+    fn shared_media(
+        &self,
+        folder: &Path,
+        manifest: &MediaManifest,
+        sha256: &str,
+        name: &str,
+    ) -> Option<String> {
+        let original = manifest
+            .find_by_hash(sha256)
+            .filter(|original| *original != name)?;
+        match self.sink.local_path(&folder.join(original)) {
+            Some(path) if !manifest.is_current(original, &path) => None,
+            _ => Some(original.to_string()),
+        }
+    }
+
+    /// Shrink the photo to import to `path` and remove its metadata, as configured
+    ///
+    /// A photo `magick` fails to shrink is imported at its original size, with a warning.
+    #[cfg(feature = "native")]
+    fn process_photo(
+        &self,
+        path: &Path,
+        content: Vec<u8>,
+        report: &mut ConversionReport,
+    ) -> Vec<u8> {
+        let config = &self.config.media;
+        if !image::is_photo(path) || (!config.strip_metadata && config.max_dimension.is_none()) {
+            return content;
+        }
+        let mut processed = content;
+        if let Some(max_dimension) = config.max_dimension {
+            let extension = path.extension().unwrap_or_default().to_string_lossy();
+            match image::resize(&processed, &extension, max_dimension) {
+                Ok(resized) => processed = resized,
+                Err(e) => {
+                    warn!(
+                        "Failed to shrink {}, importing it at its original size: {}",
+                        path.display(),
+                        e
+                    );
+                    report.warnings += 1;
+                }
+            }
+        }
+        if config.strip_metadata {
+            if let Some(stripped) = image::strip_metadata(&processed) {
+                processed = stripped;
+            }
+        }
+        processed
+    }
+
+    /// Record a file left in the attachments folder by a run interrupted before saving the
+    /// manifest, if it is complete: of the size of the file of the archive, or downloaded, since
+    /// the downloads are renamed to their final names only once complete
+    #[cfg(feature = "native")]
+    fn recover_media(
+        &self,
+        manifest: &mut MediaManifest,
+        name: &str,
+        path: &Path,
+        archive_path: Option<&Path>,
+        url: &str,
+    ) -> bool {
+        if manifest.files.contains_key(name) || !path.is_file() {
+            return false;
+        }
+        let source = match archive_path {
+            Some(archive_path) => {
+                let size = |path| std::fs::metadata(path).map(|metadata| metadata.len()).ok();
+                if size(archive_path) != size(path) {
+                    return false;
+                }
+                archive_path.display().to_string()
+            }
+            None if self.download_media => url.to_string(),
+            None => return false,
+        };
+        match std::fs::read(path) {
+            Ok(content) => {
+                manifest.record(name, &source, &content);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Render the note of each period, or Org files, and write them to `output_dir`
+    fn write_notes(
+        &self,
+        output_dir: &Path,
+        formatter: &Formatter,
+        tweets_by_period: &TweetsByPeriod,
+        report: &mut ConversionReport,
+    ) -> Result<()> {
+        let (template, extension) = match self.format {
+            OutputFormat::Org => (MonthlyTweetsTemplate::org()?, "org"),
+            _ => (MonthlyTweetsTemplate::new()?, "md"),
+        };
+        let full_tweet_template = FullTweetTemplate::new()?;
+
+        let notes = self.period_notes(tweets_by_period);
+        // Each note is rendered independently, so the notes are written in parallel
+        let results = parallel::map_bounded(&notes, self.write_jobs(), |note| {
+            let output_file_path = output_dir.join(note.name()).with_extension(extension);
+            let result = self
+                .write_note(&template, formatter, note, &output_file_path)
+                .and_then(|()| {
+                    // The Org files list the tweets in full
+                    if self.format == OutputFormat::Org {
+                        return Ok(());
+                    }
+                    self.write_full_notes(&full_tweet_template, formatter, &note.tweets, output_dir)
+                });
+            (
+                note.period,
+                note.is_deleted,
+                note.part,
+                note.tweets.len(),
+                output_file_path,
+                result,
+            )
+        });
+        for (period, is_deleted, part, tweet_count, output_file_path, result) in results {
+            let name = output_file_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let status = if result.is_ok() {
+                NoteStatus::Written
+            } else {
+                NoteStatus::Failed
+            };
+            report.notes.push(NoteSummary {
+                name,
+                tweet_count,
+                status,
+            });
+            match result {
+                Ok(()) => {
+                    info!(
+                        target: event::FILE_WRITTEN,
+                        path = self.event_path(&output_file_path).as_str(),
+                        count = tweet_count;
+                        "Saved the tweets to {}",
+                        self.sink.describe(&output_file_path)
+                    );
+                    // The parts of a period count as one
+                    if part.is_none_or(|(index, _)| index == 1) {
+                        report.months_written += 1;
+                    }
+                    #[cfg(feature = "native")]
+                    if let Some(command) = &self.config.hooks.after_note {
+                        self.run_hook(command, &output_file_path);
+                    }
+                    // The periods are in order, so the last note written is the latest
+                    if self.format == OutputFormat::Markdown && !is_deleted {
+                        report.latest_note = self.sink.local_path(&output_file_path);
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to write the tweets for {}: {}", period, e);
+                    report.warnings += 1;
+                    report.files_skipped += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write all the tweets to a single Markdown file in `output_dir`
+    fn write_single_file(
+        &self,
+        output_dir: &Path,
+        formatter: &Formatter,
+        tweets_by_period: &TweetsByPeriod,
+        report: &mut ConversionReport,
+    ) -> Result<()> {
+        let output_file_path = output_dir.join(single_file::FILE_NAME);
+        let content = single_file::render(
+            &chronological(tweets_by_period),
+            formatter,
+            &self.config.layout,
+        );
+        self.save(&output_file_path, content.as_bytes(), "the tweets")?;
+        report.months_written += tweets_by_period.len();
+        report.latest_note = self.sink.local_path(&output_file_path);
+        Ok(())
+    }
+
+    /// The notes of the periods in order, the deleted tweets of each period in a note of their
+    /// own when they are kept apart, and the periods with too many tweets split into parts
+    fn period_notes<'a>(&self, tweets_by_period: &'a TweetsByPeriod) -> Vec<PeriodNote<'a>> {
+        let mut notes = Vec::new();
+        for (period, tweets) in tweets_by_period.iter() {
+            let (deleted, kept): (Vec<&Tweet>, Vec<&Tweet>) = tweets.iter().partition(|tweet| {
+                self.deleted_tweets == Some(DeletedTweets::Separate) && tweet.is_deleted()
+            });
+            for (is_deleted, mut tweets) in [(false, kept), (true, deleted)] {
+                if tweets.is_empty() {
+                    continue;
+                }
+                let max = self.max_tweets_per_file.unwrap_or(usize::MAX);
+                if tweets.len() <= max {
+                    notes.push(PeriodNote {
+                        period: *period,
+                        is_deleted,
+                        part: None,
+                        tweets,
+                    });
+                    continue;
+                }
+                // The parts follow the order of the tweets in the notes
+                tweets.sort_by(|a, b| self.config.layout.compare(a, b));
+                let count = tweets.len().div_ceil(max);
+                for (i, part) in tweets.chunks(max).enumerate() {
+                    notes.push(PeriodNote {
+                        period: *period,
+                        is_deleted,
+                        part: Some((i + 1, count)),
+                        tweets: part.to_vec(),
+                    });
+                }
+            }
+        }
+        notes.sort_by_key(|note| (note.period, note.is_deleted, note.part));
+        notes
+    }
+
+    /// Render the note of a period and write it to `output_file_path`
+    fn write_note(
+        &self,
+        template: &MonthlyTweetsTemplate,
+        formatter: &Formatter,
+        note: &PeriodNote,
+        output_file_path: &Path,
+    ) -> Result<()> {
+        let mut data = MonthlyTweetsTemplateInput::new(
+            &note.tweets,
+            formatter,
+            &self.config.layout,
+            &self.config.frontmatter,
+            self.granularity,
+        )?;
+        if note.is_deleted {
+            data = data.for_deleted_tweets();
+        }
+        if let Some((index, count)) = note.part {
+            let part_name = |index| note.name_of_part(index);
+            data = data.for_part(
+                index,
+                count,
+                (index > 1).then(|| part_name(index - 1)),
+                (index < count).then(|| part_name(index + 1)),
+            );
+        }
+        let mut content = Vec::new();
+        template.render(&data, &mut content)?;
+        self.sink.write(output_file_path, &content)
+    }
+
+    /// Write the tweets truncated in the note of the period to notes of their own
+    fn write_full_notes(
+        &self,
+        template: &FullTweetTemplate,
+        formatter: &Formatter,
+        tweets: &[&Tweet],
+        output_dir: &Path,
+    ) -> Result<()> {
+        let config = &self.config;
+        for tweet in tweets.iter().filter(|tw| config.layout.truncates(tw)) {
+            let data = FullTweetTemplateInput::new(
+                tweet,
+                formatter,
+                &config.layout,
+                &config.frontmatter,
+                self.granularity,
+            );
+            let mut content = Vec::new();
+            template.render(&data, &mut content)?;
+            self.save(&data.output_file_path(output_dir), &content, "the tweet")?;
+        }
+        Ok(())
+    }
+
+    /// Write the tweets to the journal pages of a Logseq graph
+    fn write_logseq_journals(
+        &self,
+        output_dir: &Path,
+        formatter: &Formatter,
+        tweets_by_period: &TweetsByPeriod,
+    ) -> Result<()> {
+        let journals_dir = output_dir.join(logseq::JOURNALS_DIR);
+        let tweets = chronological(tweets_by_period);
+        let days = tweets
+            .chunk_by(|a, b| a.created_at().date_naive() == b.created_at().date_naive())
+            .collect::<Vec<&[&Tweet]>>();
+        parallel::map_bounded(&days, self.write_jobs(), |day| {
+            self.save(
+                &journals_dir.join(logseq::journal_file_name(&day[0].created_at())),
+                logseq::render_journal(day, formatter).as_bytes(),
+                "the tweets",
+            )
+        })
+        .into_iter()
+        .collect()
+    }
+
+    /// Append the tweets not in the ledger yet to the journals of their years
+    fn write_journals(
+        &self,
+        output_dir: &Path,
+        formatter: &Formatter,
+        tweets_by_period: &TweetsByPeriod,
+    ) -> Result<()> {
+        let ledger_path = output_dir.join(journal::LEDGER_FILE_NAME);
+        let mut ledger = match self.sink.local_path(&ledger_path) {
+            Some(path) => JournalLedger::load(&path)?,
+            None => JournalLedger::default(),
+        };
+        let tweets = chronological(tweets_by_period)
+            .into_iter()
+            .filter(|tweet| !ledger.contains(tweet.id()))
+            .collect::<Vec<&Tweet>>();
+        if tweets.is_empty() {
+            info!("The journals have all the tweets already");
+            return Ok(());
+        }
+        for tweets_of_year in tweets.chunk_by(|a, b| a.created_at().year() == b.created_at().year())
+        {
+            let year = tweets_of_year[0].created_at().year();
+            let path = output_dir.join(journal::journal_file_name(year));
+            let existing = match self.sink.local_path(&path) {
+                Some(local) => match std::fs::read_to_string(&local) {
+                    Ok(content) => content,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+                    Err(source) => {
+                        return Err(Error::Input {
+                            path: local.display().to_string(),
+                            source,
+                        })
+                    }
+                },
+                None => String::new(),
+            };
+            let mut content = if existing.is_empty() {
+                journal::render_title(year)
+            } else {
+                existing
+            };
+            content.push_str(&journal::render_entries(
+                tweets_of_year,
+                formatter,
+                &self.config.layout,
+            ));
+            // Recorded year by year before the journal is written, so that an interrupted run
+            // never appends a year twice
+            ledger.record(tweets_of_year);
+            if !self.sink.is_stream() {
+                self.sink
+                    .write(&ledger_path, &serde_json::to_vec_pretty(&ledger)?)?;
+            }
+            self.save(&path, content.as_bytes(), "the journal")?;
+        }
+        info!("Appended {} tweets to the journals", tweets.len());
+        Ok(())
+    }
+
+    /// Number of notes written concurrently, one when they are streamed one after another
+    fn write_jobs(&self) -> usize {
+        if self.sink.is_stream() {
+            1
+        } else {
+            self.jobs.unwrap_or_else(parallel::default_jobs)
+        }
+    }
+
+    /// Write the tweets to a file to import into Joplin or Day One, with the media in `media_dir`
+    fn write_journal_export(
+        &self,
+        output_dir: &Path,
+        formatter: &Formatter,
+        media_dir: Option<&Path>,
+        tweets_by_period: &TweetsByPeriod,
+    ) -> Result<()> {
+        let output_file_path = output_dir.join(self.format.file_name().unwrap_or_default());
+        let tweets = chronological(tweets_by_period);
+        self.save_streamed(
+            &output_file_path,
+            "the tweets",
+            &mut |writer| match self.format {
+                OutputFormat::Dayone => dayone::write_dayone(&tweets, formatter, media_dir, writer),
+                _ => joplin::write_jex(&tweets, formatter, &self.config.layout, media_dir, writer),
+            },
+        )
+    }
+
+    /// Write the tweets to a single file in `output_dir`
+    fn write_export(
+        &self,
+        output_dir: &Path,
+        tweets_by_period: &TweetsByPeriod,
+        report: &mut ConversionReport,
+    ) -> Result<()> {
+        let format = self.format;
+        let tweets = chronological(tweets_by_period);
+        let output_file_path = output_dir.join(format.file_name().unwrap_or_default());
+        let mut content = Vec::new();
+        match format {
+            OutputFormat::Markdown
+            | OutputFormat::Logseq
+            | OutputFormat::Org
+            | OutputFormat::Joplin
+            | OutputFormat::Dayone
+            | OutputFormat::Journal => {
+                unreachable!("{:?} is not written by write_export", format)
+            }
+            OutputFormat::Csv => export::write_csv(&tweets, &mut content),
+            OutputFormat::Json => export::write_json(&tweets, &mut content),
+            OutputFormat::Sqlite => export::write_sql(&tweets, &mut content),
+        }
+        .map_err(|source| Error::Output {
+            path: self.sink.describe(&output_file_path),
+            source,
+        })?;
+        self.save(&output_file_path, &content, "the tweets")?;
+        #[cfg(not(feature = "native"))]
+        if format == OutputFormat::Sqlite {
+            warn!(
+                "The database is not created without the native feature; load {} into it with sqlite3",
+                self.sink.describe(&output_file_path)
+            );
+            report.warnings += 1;
+        }
+        #[cfg(feature = "native")]
+        if format == OutputFormat::Sqlite {
+            // The database is created by the sqlite3 command from the script on disk
+            let Some(script_path) = self.sink.local_path(&output_file_path) else {
+                return Ok(());
+            };
+            let database_path = script_path.with_extension("db");
+            let existed = database_path.exists();
+            match export::load_into_sqlite(&script_path, &database_path) {
+                Ok(()) => {
+                    self.sink
+                        .record(&output_file_path.with_extension("db"), existed);
+                    info!(
+                        target: event::FILE_WRITTEN,
+                        path:% = database_path.display();
+                        "Saved the database to {}",
+                        database_path.display()
+                    )
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to create the database: {}; load {} into it with sqlite3 manually",
+                        e,
+                        script_path.display()
+                    );
+                    report.warnings += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tweets of a source grouped by period
+#[derive(Default)]
+struct GroupedTweets {
+    tweets_by_period: TweetsByPeriod,
+    /// IDs of all tweets in the source, collected only when the filter needs them
+    ids: HashSet<String>,
+    parsed: usize,
+    filtered_out: usize,
+    /// The fields of the archive not understood, with the number of tweets having them
+    unknown_fields: BTreeMap<String, usize>,
+}
+
+/// A note of the tweets of a period, or of a part of them when the period is split
+struct PeriodNote<'a> {
+    period: i32,
+    is_deleted: bool,
+    /// The index of the part from 1 and the number of parts
+    part: Option<(usize, usize)>,
+    tweets: Vec<&'a Tweet>,
+}
+impl PeriodNote<'_> {
+    /// Name of the note, such as `tweets_202303` or `tweets_202303_2` for a part
+    fn name(&self) -> String {
+        match self.part {
+            Some((index, _)) => self.name_of_part(index),
+            None => format!("{}_{}", self.prefix(), self.period),
+        }
+    }
+    fn name_of_part(&self, index: usize) -> String {
+        format!("{}_{}_{}", self.prefix(), self.period, index)
+    }
+    fn prefix(&self) -> &'static str {
+        if self.is_deleted {
+            "deleted_tweets"
+        } else {
+            "tweets"
+        }
+    }
+}
+
+/// All the tweets, oldest first
+fn chronological(tweets_by_period: &TweetsByPeriod) -> Vec<&Tweet> {
+    let mut tweets = tweets_by_period.values().flatten().collect::<Vec<&Tweet>>();
+    tweets.sort_by(|a, b| (a.created_at(), a.id()).cmp(&(b.created_at(), b.id())));
+    tweets
+}
+
+/// Quote the tweets by others that the tweets reply to or quote, looked up by `lookup`
+#[cfg(feature = "native")]
+fn resolve_references<F>(lookup: F, tweets_by_period: &mut TweetsByPeriod) -> Result<usize>
+where
+    F: Fn(&[&str]) -> Result<HashMap<String, LookedUpTweet>>,
+{
+    let own_ids = tweets_by_period
+        .values()
+        .flatten()
+        .map(|tweet| tweet.id().to_string())
+        .collect::<HashSet<String>>();
+    let mut references = Vec::new();
+    for tweet in tweets_by_period.values().flatten() {
+        if let Some(id) = tweet.in_reply_to_status_id() {
+            // Own threads are in the notes already
+            if !own_ids.contains(id) {
+                references.push((
+                    tweet.id().to_string(),
+                    ReferenceKind::RepliedTo,
+                    id.to_string(),
+                ));
+            }
+        }
+        for id in tweet.quoted_status_ids() {
+            references.push((
+                tweet.id().to_string(),
+                ReferenceKind::Quoted,
+                id.to_string(),
+            ));
+        }
+    }
+    let mut ids = references
+        .iter()
+        .map(|(_, _, id)| id.as_str())
+        .collect::<Vec<&str>>();
+    ids.sort_unstable();
+    ids.dedup();
+    let looked_up = lookup(&ids)?;
+    let mut references_by_tweet: HashMap<String, Vec<ReferencedTweet>> = HashMap::new();
+    for (tweet_id, kind, id) in references {
+        if let Some(referenced) = looked_up.get(&id) {
+            references_by_tweet
+                .entry(tweet_id)
+                .or_default()
+                .push(ReferencedTweet {
+                    kind,
+                    id,
+                    author: referenced.author.clone(),
+                    text: referenced.text.clone(),
+                });
+        }
+    }
+    for tweet in tweets_by_period.values_mut().flatten() {
+        for reference in references_by_tweet.remove(tweet.id()).unwrap_or_default() {
+            tweet.add_reference(reference);
+        }
+    }
+    Ok(looked_up.len())
+}
+
+/// The links of the tweets to web pages other than tweets, without duplicates
+#[cfg(feature = "native")]
+fn linked_pages(tweets_by_period: &TweetsByPeriod) -> Vec<&str> {
+    tweets_by_period
+        .values()
+        .flatten()
+        .flat_map(|tweet| tweet.entities().urls.iter())
+        .map(|entity| entity.expanded_url.as_str())
+        .filter(|url| links::is_web_page(url))
+        .collect::<BTreeSet<&str>>()
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Converter;
+    use crate::config::Config;
+    use crate::export::OutputFormat;
+    use crate::granularity::Granularity;
+    use crate::people::PeopleMap;
+    #[cfg(feature = "native")]
+    use crate::report::ConversionReport;
+    #[cfg(feature = "native")]
+    use crate::runs::{self, RunManifest};
+    #[cfg(feature = "native")]
+    use crate::sink::FileChange;
+    use crate::sink::MemorySink;
+    #[cfg(feature = "native")]
+    use crate::timezone::Timezone;
+    use crate::tweet::parse_tweets;
+    #[cfg(feature = "native")]
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_run() {
+        let dir = std::env::temp_dir().join(format!("twitter2obsidian-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tweets_file_path = dir.join("tweets.js");
+        std::fs::write(
+            &tweets_file_path,
+            r#"window.YTD.tweets.part0 = [
+                {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "朝ごはん"}},
+                {"tweet": {"id_str": "2", "created_at": "Mon Jul 10 04:12:48 +0000 2023", "full_text": "昼ごはん"}}
+            ]"#,
+        )
+        .unwrap();
+        let report = Converter::new()
+            .input(&tweets_file_path)
+            .output_dir(&dir)
+            .timezone("UTC".parse().unwrap())
+            .granularity(Granularity::Year)
+            .run()
+            .unwrap();
+        assert_eq!(report.tweets_parsed, 2);
+        assert_eq!(report.months_written, 1);
+        let note = std::fs::read_to_string(dir.join("tweets_2023.md")).unwrap();
+        assert!(note.contains("# 2023年 のツイート"));
+        assert!(note.contains("昼ごはん"));
+        #[cfg(feature = "native")]
+        {
+            let manifest = RunManifest::load(&dir.join(runs::FILE_NAME)).unwrap();
+            assert_eq!(manifest.runs.len(), 1);
+            assert_eq!(
+                manifest.runs[0].files.get(Path::new("tweets_2023.md")),
+                Some(&FileChange::Created)
+            );
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_run_journal() {
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-journal-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tweets_file_path = dir.join("tweets.js");
+        let write_tweets = |tweets: &str| {
+            std::fs::write(
+                &tweets_file_path,
+                format!("window.YTD.tweets.part0 = [{}]", tweets),
+            )
+            .unwrap()
+        };
+        let convert = || {
+            Converter::new()
+                .input(&tweets_file_path)
+                .output_dir(&dir)
+                .timezone("UTC".parse().unwrap())
+                .format(OutputFormat::Journal)
+                .run()
+                .unwrap()
+        };
+        let first = r#"{"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "朝ごはん"}}"#;
+        write_tweets(first);
+        convert();
+        // The tweets appended already are not appended again
+        write_tweets(&format!(
+            r#"{}, {{"tweet": {{"id_str": "2", "created_at": "Mon Jul 10 04:12:48 +0000 2023", "full_text": "昼ごはん"}}}}"#,
+            first
+        ));
+        convert();
+        convert();
+        let journal = std::fs::read_to_string(dir.join("journal_2023.md")).unwrap();
+        assert_eq!(journal.matches("# 2023年のツイート").count(), 1);
+        assert_eq!(journal.matches("朝ごはん").count(), 1);
+        assert_eq!(journal.matches("昼ごはん").count(), 1);
+        assert!(journal.find("朝ごはん") < journal.find("昼ごはん"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_run_journal_records_ledger_first() {
+        /// A sink keeping the order of the files written
+        #[derive(Debug, Default)]
+        struct OrderSink(std::sync::Mutex<Vec<std::path::PathBuf>>);
+        impl crate::sink::NoteSink for OrderSink {
+            fn write(&self, path: &std::path::Path, _: &[u8]) -> crate::error::Result<()> {
+                self.0.lock().unwrap().push(path.to_path_buf());
+                Ok(())
+            }
+        }
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "朝ごはん"}}]"#,
+        )
+        .unwrap();
+        let sink = Arc::new(OrderSink::default());
+        Converter::new()
+            .source(tweets)
+            .sink(sink.clone())
+            .format(OutputFormat::Journal)
+            .run()
+            .unwrap();
+        // An interrupted run never appends the tweets twice
+        let paths = sink.0.lock().unwrap();
+        let position = |name: &str| paths.iter().position(|path| path.ends_with(name));
+        assert!(position("journal-ledger.json") < position("journal_2023.md"));
+        assert!(position("journal-ledger.json").is_some());
+    }
+    #[test]
+    fn test_run_joplin() {
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-joplin-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tweets_file_path = dir.join("tweets.js");
+        std::fs::write(
+            &tweets_file_path,
+            r#"window.YTD.tweets.part0 = [
+                {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "@friend #rust $TSLA",
+                    "entities": {"user_mentions": [{"screen_name": "friend"}], "hashtags": [{"text": "rust"}], "symbols": [{"text": "TSLA"}]}}}
+            ]"#,
+        )
+        .unwrap();
+        let config: Config = serde_json::from_str(
+            r#"{"formatter": {"mentions": "people", "hashtags": "wikilink", "cashtags": "wikilink"}}"#,
+        )
+        .unwrap();
+        Converter::new()
+            .input(&tweets_file_path)
+            .output_dir(&dir)
+            .config(config)
+            .format(OutputFormat::Joplin)
+            .run()
+            .unwrap();
+        let jex = std::fs::read(dir.join("tweets.jex")).unwrap();
+        let jex = String::from_utf8_lossy(&jex);
+        assert!(jex.contains("[@friend](https://twitter.com/friend) #rust $TSLA"));
+        assert!(!jex.contains("[["));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_process_photo_keeps_unshrinkable_photo() {
+        let mut config = Config::default();
+        config.media.max_dimension = Some(100);
+        let converter = Converter::new().config(config);
+        let mut report = ConversionReport::new();
+        // magick fails on it, or is not installed
+        let content = converter.process_photo(
+            Path::new("attachments/1-cat.jpg"),
+            b"not a photo".to_vec(),
+            &mut report,
+        );
+        assert_eq!(content, b"not a photo");
+        assert_eq!(report.warnings, 1);
+    }
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_settings_hash() {
+        let hash = |config: &str, granularity| {
+            Converter::new()
+                .config(serde_json::from_str(config).unwrap())
+                .granularity(granularity)
+                .settings_hash()
+                .unwrap()
+        };
+        let base = hash("{}", Granularity::Month);
+        // The token and the hooks do not change the notes
+        assert_eq!(
+            hash(
+                r#"{"api": {"bearer_token": "secret"}, "hooks": {"after_run": "git commit"}}"#,
+                Granularity::Month
+            ),
+            base
+        );
+        assert_ne!(hash("{}", Granularity::Year), base);
+        assert_ne!(
+            hash(r#"{"layout": {"truncate_at": 140}}"#, Granularity::Month),
+            base
+        );
+        // The local timezone is the zone of the machine
+        let zone_hash = |timezone: &str| {
+            Converter::new()
+                .timezone(timezone.parse().unwrap())
+                .settings_hash()
+                .unwrap()
+        };
+        assert_ne!(zone_hash("Asia/Tokyo"), zone_hash("UTC"));
+        assert_eq!(
+            zone_hash("local"),
+            zone_hash(&Timezone::Local.resolved().to_string())
+        );
+    }
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_clean_after_download() {
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-rerun-{}", std::process::id()));
+        let vault = dir.join("vault");
+        std::fs::create_dir_all(&vault).unwrap();
+        std::fs::write(vault.join("Home.md"), "").unwrap();
+        std::fs::write(dir.join("cat.jpg"), "meow").unwrap();
+        let tweets_file_path = dir.join("tweets.js");
+        std::fs::write(
+            &tweets_file_path,
+            format!(
+                r#"window.YTD.tweets.part0 = [
+                    {{"tweet": {{"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "猫 https://t.co/img", "entities": {{"media": [{{"url": "https://t.co/img", "media_url_https": "file://{}"}}]}}}}}}
+                ]"#,
+                dir.join("cat.jpg").display()
+            ),
+        )
+        .unwrap();
+        let convert = |format| {
+            Converter::new()
+                .input(&tweets_file_path)
+                .output_dir(&vault)
+                .attachments_folder("attachments")
+                .download_media(true)
+                .format(format)
+                .run()
+                .unwrap()
+        };
+        convert(OutputFormat::Markdown);
+        assert!(vault.join("attachments/1-cat.jpg").is_file());
+        convert(OutputFormat::Sqlite);
+        let manifest = RunManifest::load(&vault.join(runs::FILE_NAME)).unwrap();
+        assert_eq!(
+            manifest.runs[0]
+                .files
+                .get(Path::new("attachments/1-cat.jpg")),
+            Some(&FileChange::Created)
+        );
+        if vault.join("tweets.db").exists() {
+            assert_eq!(
+                manifest.runs[1].files.get(Path::new("tweets.db")),
+                Some(&FileChange::Created)
+            );
+        }
+        let paths = manifest.files_to_clean(&vault);
+        runs::clean(&vault, &paths, false).unwrap();
+        let left = std::fs::read_dir(&vault)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(left, vec!["Home.md"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_run_zettelkasten_collisions() {
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-zettel-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tweets_file_path = dir.join("tweets.js");
+        // Two long tweets in the same second
+        std::fs::write(
+            &tweets_file_path,
+            r#"window.YTD.tweets.part0 = [
+                {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "朝ごはんはパン"}},
+                {"tweet": {"id_str": "2", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "昼ごはんはうどん"}}
+            ]"#,
+        )
+        .unwrap();
+        let config: Config = serde_json::from_str(
+            r#"{"layout": {"truncate_at": 3, "note_names": "zettelkasten", "zettel_id_format": "%Y%m%d%H%M%S"}}"#,
+        )
+        .unwrap();
+        Converter::new()
+            .input(&tweets_file_path)
+            .output_dir(&dir)
+            .config(config)
+            .timezone("UTC".parse().unwrap())
+            .granularity(Granularity::Year)
+            .run()
+            .unwrap();
+        let read = |path: &str| std::fs::read_to_string(dir.join(path)).unwrap();
+        assert!(read("long_tweets/20230311041248.md").contains("朝ごはんはパン"));
+        assert!(read("long_tweets/20230311041248-2.md").contains("昼ごはんはうどん"));
+        let note = read("tweets_2023.md");
+        assert!(note.contains("[[20230311041248|続きを読む]]"));
+        assert!(note.contains("[[20230311041248-2|続きを読む]]"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[cfg(unix)]
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_run_counts_hook_failures() {
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-hooks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tweets_file_path = dir.join("tweets.js");
+        std::fs::write(
+            &tweets_file_path,
+            r#"window.YTD.tweets.part0 = [
+                {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "朝ごはん"}}
+            ]"#,
+        )
+        .unwrap();
+        let mut config = Config::default();
+        config.hooks.after_note = Some("false".to_string());
+        config.hooks.after_run = Some("false".to_string());
+        let report = Converter::new()
+            .input(&tweets_file_path)
+            .output_dir(&dir)
+            .config(config)
+            .granularity(Granularity::Year)
+            .run()
+            .unwrap();
+        // The note and the end of the run
+        assert_eq!(report.warnings, 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_run_with_source() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "朝ごはん"}}]"#,
+        )
+        .unwrap();
+        let sink = Arc::new(MemorySink::new());
+        let report = Converter::new()
+            .source(tweets)
+            .sink(sink.clone())
+            .format(OutputFormat::Json)
+            .run()
+            .unwrap();
+        assert_eq!(report.tweets_parsed, 1);
+        let json = sink.read_to_string("tweets.json").unwrap();
+        assert!(json.contains("朝ごはん"));
+    }
+    #[test]
+    fn test_run_person_notes() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "@friend 朝ごはん", "entities": {"user_mentions": [{"screen_name": "friend", "indices": ["0", "7"]}]}}},
+                {"tweet": {"id_str": "2", "created_at": "Sun Mar 12 04:12:48 +0000 2023", "full_text": "@matz 昼ごはん", "entities": {"user_mentions": [{"screen_name": "matz", "indices": ["0", "5"]}]}}}
+            ]"#,
+        )
+        .unwrap();
+        let config = serde_json::from_str::<Config>(
+            r#"{"formatter": {"mentions": "people", "people_folder": "Contacts"}}"#,
+        )
+        .unwrap();
+        let sink = Arc::new(MemorySink::new());
+        Converter::new()
+            .source(tweets)
+            .sink(sink.clone())
+            .config(config)
+            .people_map(PeopleMap::parse("@matz -> People/Yukihiro Matsumoto").unwrap())
+            .granularity(Granularity::Year)
+            .person_notes(2)
+            .run()
+            .unwrap();
+        // The person notes are where the mentions link to
+        let note = sink.read_to_string("tweets_2023.md").unwrap();
+        assert!(note.contains("[[Contacts/@friend|@friend]]"));
+        assert!(note.contains("[[People/Yukihiro Matsumoto|@matz]]"));
+        assert!(sink
+            .read_to_string("Contacts/@friend.md")
+            .unwrap()
+            .contains("![[tweets_2023#^1]]"));
+        assert!(sink
+            .read_to_string("People/Yukihiro Matsumoto.md")
+            .unwrap()
+            .contains("![[tweets_2023#^2]]"));
+    }
+    #[test]
+    fn test_run_split_notes() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "朝ごはん"}},
+                {"tweet": {"id_str": "2", "created_at": "Sun Mar 12 04:12:48 +0000 2023", "full_text": "昼ごはん"}},
+                {"tweet": {"id_str": "3", "created_at": "Mon Mar 13 04:12:48 +0000 2023", "full_text": "晩ごはん"}}
+            ]"#,
+        )
+        .unwrap();
+        let sink = Arc::new(MemorySink::new());
+        let report = Converter::new()
+            .source(tweets)
+            .sink(sink.clone())
+            .max_tweets_per_file(2)
+            .run()
+            .unwrap();
+        assert_eq!(report.months_written, 1);
+        assert_eq!(report.notes.len(), 2);
+        let first = sink.read_to_string("tweets_202303_1.md").unwrap();
+        assert!(first.contains("# 2023年03月 (1/2) のツイート"));
+        assert!(first.contains("次のノート: [[tweets_202303_2]]"));
+        assert!(first.contains("朝ごはん") && first.contains("昼ごはん"));
+        let second = sink.read_to_string("tweets_202303_2.md").unwrap();
+        assert!(second.contains("前のノート: [[tweets_202303_1]]"));
+        assert!(!second.contains("次のノート"));
+        assert!(second.contains("晩ごはん"));
+    }
+}
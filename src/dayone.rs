@@ -0,0 +1,427 @@
+use crate::templates::Formatter;
+use crate::tweet::Tweet;
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the journal, which is also the name of the JSON file in the zip file
+const JOURNAL_NAME: &str = "Twitter";
+
+/// A Day One export, the `entries` of a journal
+#[derive(Debug, Serialize)]
+struct Journal {
+    metadata: Metadata,
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Serialize)]
+struct Metadata {
+    version: &'static str,
+}
+
+/// An entry of a day, with the tweets of the day concatenated
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    uuid: String,
+    creation_date: String,
+    modified_date: String,
+    text: String,
+    tags: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    photos: Vec<Photo>,
+}
+
+/// A photo attached to an entry, stored as `photos/<md5>.<type>` in the zip file
+#[derive(Debug, Serialize)]
+struct Photo {
+    identifier: String,
+    /// MD5 of the file, which Day One checks, filled in as the file is written to the zip file
+    md5: String,
+    #[serde(rename = "type")]
+    photo_type: String,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+/// Write the tweets as a Day One export, a zip file of the JSON journal and the photos
+///
+/// The tweets must be in chronological order. Each day is an entry with the tweets concatenated,
+/// and the photos found in `media_dir` are attached to it.
+pub fn write_dayone<W: Write>(
+    tweets: &[&Tweet],
+    formatter: &Formatter,
+    media_dir: Option<&Path>,
+    writer: W,
+) -> io::Result<()> {
+    let now = Utc::now().fixed_offset();
+    let mut entries = tweets
+        .chunk_by(|a, b| a.created_at().date_naive() == b.created_at().date_naive())
+        .map(|day| render_entry(day, formatter, media_dir, &now))
+        .collect::<Vec<Entry>>();
+    let mut zip = ZipWriter::new(writer);
+    // The photos are written one by one, so that only one of them is in memory at a time
+    for photo in entries.iter_mut().flat_map(|entry| entry.photos.iter_mut()) {
+        let data = std::fs::read(&photo.path)?;
+        photo.md5 = md5(&data).iter().map(|b| format!("{:02x}", b)).collect();
+        let path = format!("photos/{}.{}", photo.md5, photo.photo_type);
+        zip.append(&path, &data, &now)?;
+    }
+    let journal = Journal {
+        metadata: Metadata { version: "1.0" },
+        entries,
+    };
+    let json = serde_json::to_vec_pretty(&journal)?;
+    zip.append(&format!("{}.json", JOURNAL_NAME), &json, &now)?;
+    zip.finish()
+}
+
+fn render_entry(
+    day: &[&Tweet],
+    formatter: &Formatter,
+    media_dir: Option<&Path>,
+    now: &DateTime<FixedOffset>,
+) -> Entry {
+    let mut photos = Vec::new();
+    let text = day
+        .iter()
+        .map(|tweet| {
+            let mut text = format!(
+                "**{}** {}",
+                tweet.created_at().format("%H:%M"),
+                formatter.format_tweet(tweet)
+            );
+            for path in media_dir
+                .into_iter()
+                .flat_map(|dir| photo_files(tweet, dir))
+            {
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+                let identifier = hex_id(&format!("photo/{}", file_name)).to_uppercase();
+                text.push_str(&format!("\n![](dayone-moment://{})", identifier));
+                photos.push(Photo {
+                    md5: String::new(),
+                    identifier,
+                    photo_type: match path.extension().and_then(|ext| ext.to_str()) {
+                        Some("png") => "png".to_string(),
+                        _ => "jpeg".to_string(),
+                    },
+                    path,
+                });
+            }
+            text
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n");
+    Entry {
+        uuid: hex_id(&format!("entry/{}", day[0].created_at().date_naive())).to_uppercase(),
+        creation_date: timestamp(&day[0].created_at()),
+        modified_date: timestamp(now),
+        text,
+        tags: vec![JOURNAL_NAME],
+        photos,
+    }
+}
+
+/// Photos of the tweet in the archive, named `<tweet id>-<file name>`
+fn photo_files(tweet: &Tweet, media_dir: &Path) -> Vec<PathBuf> {
+    tweet
+        .entities()
+        .media
+        .iter()
+        .filter(|media| media.media_type.is_empty() || media.media_type == "photo")
+        .filter_map(|media| media.media_url_https.rsplit('/').next())
+        .map(|file_name| media_dir.join(format!("{}-{}", tweet.id(), file_name)))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// 32 hex digits derived from `seed`, so that the entries are the same on every run
+fn hex_id(seed: &str) -> String {
+    Sha256::digest(seed.as_bytes())
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn timestamp(date: &DateTime<FixedOffset>) -> String {
+    date.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Writer of a zip file of stored, uncompressed, files
+///
+/// ZIP64 is not supported, so more than 65535 files or 4 GiB make `append` fail.
+struct ZipWriter<W: Write> {
+    writer: W,
+    /// Central directory headers of the files written so far
+    central_directory: Vec<u8>,
+    entries: u16,
+    offset: u32,
+}
+impl<W: Write> ZipWriter<W> {
+    fn new(writer: W) -> Self {
+        Self {
+            writer,
+            central_directory: Vec::new(),
+            entries: 0,
+            offset: 0,
+        }
+    }
+    fn append(
+        &mut self,
+        path: &str,
+        data: &[u8],
+        modified: &DateTime<FixedOffset>,
+    ) -> io::Result<()> {
+        let (time, date) = dos_date_time(modified);
+        let crc = crc32(data);
+        let name_len = u16::try_from(path.len()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "the file name is too long")
+        })?;
+        let size = u32::try_from(data.len()).map_err(|_| over_limit("4 GiB"))?;
+        let entries = self
+            .entries
+            .checked_add(1)
+            .ok_or_else(|| over_limit("65535 files"))?;
+        let end = self
+            .offset
+            .checked_add(30 + name_len as u32)
+            .and_then(|offset| offset.checked_add(size))
+            .ok_or_else(|| over_limit("4 GiB"))?;
+        // Fields shared by the local and the central headers, from the version needed to extract
+        let mut common = Vec::new();
+        common.extend_from_slice(&10u16.to_le_bytes());
+        // UTF-8 file names
+        common.extend_from_slice(&0x0800u16.to_le_bytes());
+        // Stored
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&time.to_le_bytes());
+        common.extend_from_slice(&date.to_le_bytes());
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&name_len.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+
+        let mut local = Vec::new();
+        local.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        local.extend_from_slice(&common);
+        local.extend_from_slice(path.as_bytes());
+        self.writer.write_all(&local)?;
+        self.writer.write_all(data)?;
+
+        let central = &mut self.central_directory;
+        central.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        // Version made by
+        central.extend_from_slice(&10u16.to_le_bytes());
+        central.extend_from_slice(&common);
+        // Comment length, disk number, internal and external attributes
+        central.extend_from_slice(&[0u8; 10]);
+        central.extend_from_slice(&self.offset.to_le_bytes());
+        central.extend_from_slice(path.as_bytes());
+
+        self.entries = entries;
+        self.offset = end;
+        Ok(())
+    }
+    /// Write the central directory and its end record
+    fn finish(mut self) -> io::Result<()> {
+        let central_size =
+            u32::try_from(self.central_directory.len()).map_err(|_| over_limit("4 GiB"))?;
+        self.writer.write_all(&self.central_directory)?;
+        let mut end = Vec::new();
+        end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        end.extend_from_slice(&[0u8; 4]);
+        end.extend_from_slice(&self.entries.to_le_bytes());
+        end.extend_from_slice(&self.entries.to_le_bytes());
+        end.extend_from_slice(&central_size.to_le_bytes());
+        end.extend_from_slice(&self.offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        self.writer.write_all(&end)?;
+        self.writer.flush()
+    }
+}
+
+fn over_limit(limit: &str) -> io::Error {
+    io::Error::other(format!(
+        "the Day One export is over the {} a zip file without ZIP64 can hold",
+        limit
+    ))
+}
+
+/// Time and date in the MS-DOS format of zip files
+fn dos_date_time(date: &DateTime<FixedOffset>) -> (u16, u16) {
+    use chrono::{Datelike, Timelike};
+    let time = ((date.hour() << 11) | (date.minute() << 5) | (date.second() / 2)) as u16;
+    let year = date.year().clamp(1980, 2107) as u32 - 1980;
+    let date = ((year << 9) | (date.month() << 5) | date.day()) as u16;
+    (time, date)
+}
+
+/// CRC-32 of the zip file format
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data.iter() {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// MD5 of the data (RFC 1321)
+fn md5(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    // The integer parts of abs(sin(i + 1)) * 2^32
+    const K: [u32; 64] = [
+        0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613,
+        0xfd469501, 0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193,
+        0xa679438e, 0x49b40821, 0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d,
+        0x02441453, 0xd8a1e681, 0xe7d3fbc8, 0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed,
+        0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a, 0xfffa3942, 0x8771f681, 0x6d9d6122,
+        0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70, 0x289b7ec6, 0xeaa127fa,
+        0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665, 0xf4292244,
+        0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+        0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb,
+        0xeb86d391,
+    ];
+    // The data is padded with 0x80, zeros and its length in bits to a multiple of 64 bytes
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for block in message.chunks_exact(64) {
+        let words: Vec<u32> = block
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(K[i])
+                .wrapping_add(words[g])
+                .rotate_left(SHIFTS[i / 16 * 4 + i % 4]);
+            (a, b, c, d) = (d, b.wrapping_add(rotated), b, c);
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+    let mut digest = [0u8; 16];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, md5, render_entry, write_dayone, ZipWriter};
+    use crate::templates::Formatter;
+    use crate::tweet::parse_tweets;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+    #[test]
+    fn test_md5() {
+        let hex = |data: &[u8]| {
+            md5(data)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        };
+        assert_eq!(hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(hex(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            hex(b"The quick brown fox jumps over the lazy dog"),
+            "9e107d9d372bb6826bd81d3542a419d6"
+        );
+        // Data filling the last block, padded to two
+        assert_eq!(
+            hex("1234567890".repeat(8).as_bytes()),
+            "57edf4a22be3c955ac49da2e2107b67a"
+        );
+    }
+    #[test]
+    fn test_zip_writer_limits() {
+        let now = chrono::Utc::now().fixed_offset();
+        let mut zip = ZipWriter::new(Vec::new());
+        zip.entries = u16::MAX;
+        assert!(zip.append("a.txt", b"a", &now).is_err());
+        let mut zip = ZipWriter::new(Vec::new());
+        zip.offset = u32::MAX - 32;
+        assert!(zip.append("a.txt", b"a", &now).is_err());
+        assert!(zip.writer.is_empty());
+        let mut zip = ZipWriter::new(Vec::new());
+        zip.append("a.txt", b"a", &now).unwrap();
+        assert_eq!(zip.offset, 30 + 5 + 1);
+    }
+    #[test]
+    fn test_write_dayone_photo_md5() {
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-dayone-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("1-cat.jpg"), b"abc").unwrap();
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "cat",
+                "entities": {"media": [{"url": "https://t.co/cat", "media_url_https": "https://pbs.twimg.com/media/cat.jpg", "type": "photo"}]}}}]"#,
+        )
+        .unwrap();
+        let mut zip = Vec::new();
+        write_dayone(
+            &tweets.iter().collect::<Vec<_>>(),
+            &Formatter::new(),
+            Some(&dir),
+            &mut zip,
+        )
+        .unwrap();
+        let zip = String::from_utf8_lossy(&zip);
+        assert!(zip.contains("photos/900150983cd24fb0d6963f7d28e17f72.jpeg"));
+        assert!(zip.contains(r#""md5": "900150983cd24fb0d6963f7d28e17f72""#));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_render_entry() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "朝ごはん"}},
+                {"tweet": {"created_at": "Sat Mar 11 05:00:00 +0000 2023", "full_text": "@hoge 昼"}}
+            ]"#,
+        )
+        .unwrap();
+        let tweets = tweets
+            .into_iter()
+            .map(|tw| tw.in_timezone(&"UTC".parse().unwrap()))
+            .collect::<Vec<_>>();
+        let entry = render_entry(
+            &tweets.iter().collect::<Vec<_>>(),
+            &Formatter::new(),
+            None,
+            &chrono::Utc::now().fixed_offset(),
+        );
+        assert_eq!(entry.creation_date, "2023-03-11T04:12:48Z");
+        assert_eq!(entry.text, "**04:12** 朝ごはん\n\n**05:00** [[@hoge]] 昼");
+        assert_eq!(entry.uuid.len(), 32);
+    }
+}
@@ -0,0 +1,68 @@
+//! Edited tweets, of which the archive has a tweet per version
+use crate::tweet::{Tweet, TweetVersion};
+use std::collections::HashMap;
+
+/// Keep only the latest version of each edited tweet, with the earlier ones attached to it
+///
+/// Returns the tweets and the number of earlier versions merged.
+pub fn collapse(tweets: Vec<Tweet>) -> (Vec<Tweet>, usize) {
+    let mut collapsed = Vec::with_capacity(tweets.len());
+    let mut chains: HashMap<String, Vec<Tweet>> = HashMap::new();
+    for tweet in tweets {
+        match tweet.initial_tweet_id() {
+            Some(initial_tweet_id) => chains
+                .entry(initial_tweet_id.to_string())
+                .or_default()
+                .push(tweet),
+            None => collapsed.push(tweet),
+        }
+    }
+    let mut merged = 0;
+    for mut versions in chains.into_values() {
+        versions.sort_by_key(|tweet| tweet.created_at());
+        let mut latest = versions.pop().unwrap();
+        merged += versions.len();
+        latest.set_earlier_versions(
+            versions
+                .into_iter()
+                .map(|tweet| TweetVersion {
+                    created_at: tweet.created_at(),
+                    full_text: tweet.full_text().to_string(),
+                })
+                .collect(),
+        );
+        collapsed.push(latest);
+    }
+    (collapsed, merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::collapse;
+    use crate::tweet::parse_tweets;
+
+    #[test]
+    fn test_collapse() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"id_str": "3", "created_at": "Sat Mar 11 04:20:00 +0000 2023", "full_text": "typo fixed", "edit_info": {"edit": {"initialTweetId": "1", "editControlInitial": {"editTweetIds": ["1", "2", "3"]}}}}},
+                {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:00:00 +0000 2023", "full_text": "tpyo", "edit_info": {"initial": {"editTweetIds": ["1", "2", "3"], "isEditEligible": true}}}},
+                {"tweet": {"id_str": "2", "created_at": "Sat Mar 11 04:10:00 +0000 2023", "full_text": "typo", "edit_info": {"edit": {"initialTweetId": "1"}}}},
+                {"tweet": {"id_str": "4", "created_at": "Sat Mar 11 05:00:00 +0000 2023", "full_text": "never edited", "edit_info": {"initial": {"editTweetIds": ["4"]}}}}
+            ]"#,
+        )
+        .unwrap();
+        let (mut tweets, merged) = collapse(tweets);
+        assert_eq!(merged, 2);
+        tweets.sort_by_key(|tweet| tweet.created_at());
+        assert_eq!(tweets.len(), 2);
+        assert_eq!(tweets[0].id(), "3");
+        let earlier = tweets[0]
+            .earlier_versions()
+            .iter()
+            .map(|version| version.full_text.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(earlier, vec!["tpyo", "typo"]);
+        assert!(tweets[1].earlier_versions().is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+use crate::exit_code;
+use std::io;
+use thiserror::Error;
+
+/// Errors that can occur while converting tweets
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to read the file {path}: {source}")]
+    Input { path: String, source: io::Error },
+    #[error("failed to write the file {path}: {source}")]
+    Output { path: String, source: io::Error },
+    #[error("failed to parse the JSON data: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("failed to parse the file {path}: {message}")]
+    Parse { path: String, message: String },
+    #[error("failed to parse the date: {0}")]
+    Date(#[from] chrono::ParseError),
+    #[error("invalid configuration file {path}: {message}")]
+    Config { path: String, message: String },
+    #[error("invalid entry on line {line} of the ignore list: {entry}")]
+    IgnoreList { line: usize, entry: String },
+    #[error("invalid entry on line {line} of the people file: {entry}")]
+    PeopleMap { line: usize, entry: String },
+    #[error("there are no tweets to render")]
+    NoTweets,
+    #[error("the output directory {0} does not exist or is not writable")]
+    OutputNotWritable(String),
+    #[error("failed to load the template: {0}")]
+    Template(#[from] handlebars::TemplateError),
+    #[error("failed to render the template: {0}")]
+    Render(#[from] handlebars::RenderError),
+    #[error("the request to {url} failed: {message}")]
+    Api { url: String, message: String },
+    #[error("the hook {command} failed: {message}")]
+    Hook { command: String, message: String },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+impl Error {
+    /// The exit code of the command line tool for this error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Input { .. } => exit_code::INPUT_MISSING,
+            Error::Json(_) | Error::Parse { .. } | Error::Date(_) => exit_code::PARSE_FAILURE,
+            Error::OutputNotWritable(_) => exit_code::OUTPUT_NOT_WRITABLE,
+            Error::Template(_) | Error::Render(_) => exit_code::TEMPLATE_ERROR,
+            Error::Config { .. }
+            | Error::IgnoreList { .. }
+            | Error::PeopleMap { .. }
+            | Error::NoTweets
+            | Error::Output { .. }
+            | Error::Api { .. }
+            | Error::Hook { .. }
+            | Error::Io(_) => 1,
+        }
+    }
+}
+
+/// A specialized Result type for this crate
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,22 @@
+//! Log targets of the structured events emitted during a conversion
+//!
+//! Scripts driving the tool with `--log-format json` can branch on the `event` field, and read
+//! the `path` of the file written or the `count` of the tweets from the fields of the same names;
+//! warnings are recognizable by their `level`.
+
+/// A note file was written
+pub const FILE_WRITTEN: &str = "twitter2obsidian::file_written";
+/// Tweets were dropped before rendering (filtered out, failed to group, ...)
+pub const TWEETS_SKIPPED: &str = "twitter2obsidian::tweets_skipped";
+/// The end-of-run conversion report
+pub const RUN_REPORT: &str = "twitter2obsidian::run_report";
+
+/// Name of the event logged to `target`, if it is one of the events
+pub fn name(target: &str) -> Option<&'static str> {
+    match target {
+        FILE_WRITTEN => Some("file_written"),
+        TWEETS_SKIPPED => Some("tweets_skipped"),
+        RUN_REPORT => Some("run_report"),
+        _ => None,
+    }
+}
@@ -0,0 +1,12 @@
+//! Exit codes of the command line tool
+//!
+//! `1` is used for any other failure and `2` is reserved for invalid command line arguments.
+
+/// The input file does not exist or cannot be read
+pub const INPUT_MISSING: u8 = 3;
+/// The input file is not valid tweet data
+pub const PARSE_FAILURE: u8 = 4;
+/// The output directory does not exist or is not writable
+pub const OUTPUT_NOT_WRITABLE: u8 = 5;
+/// The template cannot be loaded
+pub const TEMPLATE_ERROR: u8 = 6;
@@ -0,0 +1,388 @@
+use crate::tweet::{Source, Tweet};
+use regex::Regex;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+#[cfg(feature = "native")]
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Format the tweets are converted to
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum OutputFormat {
+    /// Monthly Markdown notes for Obsidian
+    #[default]
+    Markdown,
+    /// A single CSV file, one row per tweet
+    Csv,
+    /// A single JSON file of the normalized tweets
+    Json,
+    /// A SQL script of tweets, entities and media tables, loaded into a SQLite database
+    Sqlite,
+    /// Logseq journal pages, one per day
+    Logseq,
+    /// Monthly Org files for Emacs
+    Org,
+    /// A Joplin export (JEX) of a notebook per year and a note per month
+    Joplin,
+    /// A Day One export of an entry per day
+    Dayone,
+    /// A Markdown journal per year, to which each run appends the new tweets
+    Journal,
+}
+impl OutputFormat {
+    /// Name of the file written in the output directory, for the formats written to a single file
+    pub fn file_name(&self) -> Option<&'static str> {
+        match self {
+            OutputFormat::Markdown
+            | OutputFormat::Logseq
+            | OutputFormat::Org
+            | OutputFormat::Journal => None,
+            OutputFormat::Csv => Some("tweets.csv"),
+            OutputFormat::Json => Some("tweets.json"),
+            OutputFormat::Sqlite => Some("tweets.sql"),
+            OutputFormat::Joplin => Some("tweets.jex"),
+            OutputFormat::Dayone => Some("tweets_dayone.zip"),
+        }
+    }
+}
+
+/// Kind of the tweet, as written in the exports
+pub fn tweet_type(tweet: &Tweet) -> &'static str {
+    if tweet.is_retweet() {
+        "retweet"
+    } else if tweet.is_reply() {
+        "reply"
+    } else {
+        "tweet"
+    }
+}
+
+/// Write the tweets as CSV with a header row
+pub fn write_csv<W: Write>(tweets: &[&Tweet], mut writer: W) -> io::Result<()> {
+    writeln!(
+        writer,
+        "id,created_at,type,text,favorite_count,retweet_count"
+    )?;
+    for tweet in tweets.iter() {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{}",
+            csv_field(tweet.id()),
+            tweet.created_at().to_rfc3339(),
+            tweet_type(tweet),
+            csv_field(&tweet.expanded_text()),
+            tweet.favorite_count(),
+            tweet.retweet_count()
+        )?;
+    }
+    writer.flush()
+}
+
+/// A tweet as written in the JSON export
+#[derive(Debug, Serialize)]
+pub struct NormalizedTweet<'a> {
+    id: &'a str,
+    created_at: String,
+    #[serde(rename = "type")]
+    tweet_type: &'static str,
+    /// The text with the entities decoded and the links expanded, without "RT @author: "
+    text: String,
+    lang: Option<&'a str>,
+    in_reply_to_status_id: Option<&'a str>,
+    in_reply_to_screen_name: Option<&'a str>,
+    /// Author of the retweeted tweet
+    retweeted_screen_name: Option<String>,
+    hashtags: Vec<&'a str>,
+    mentions: Vec<&'a str>,
+    urls: Vec<&'a str>,
+    favorite_count: u64,
+    retweet_count: u64,
+    source: Source,
+}
+impl<'a> NormalizedTweet<'a> {
+    pub fn new(tweet: &'a Tweet) -> Self {
+        static RE_RETWEET: OnceLock<Regex> = OnceLock::new();
+        let re_retweet = RE_RETWEET.get_or_init(|| Regex::new(r"^RT @([a-zA-Z0-9_]+): ").unwrap());
+        let text = tweet.expanded_text();
+        let (retweeted_screen_name, text) = match re_retweet.captures(&text) {
+            Some(caps) => (
+                Some(caps[1].to_string()),
+                text[caps.get(0).unwrap().end()..].to_string(),
+            ),
+            None => (None, text),
+        };
+        let entities = tweet.entities();
+        Self {
+            id: tweet.id(),
+            created_at: tweet.created_at().to_rfc3339(),
+            tweet_type: tweet_type(tweet),
+            text,
+            lang: tweet.lang(),
+            in_reply_to_status_id: tweet.in_reply_to_status_id(),
+            in_reply_to_screen_name: tweet.in_reply_to_screen_name(),
+            retweeted_screen_name,
+            hashtags: entities.hashtags.iter().map(|h| h.text.as_str()).collect(),
+            mentions: entities
+                .user_mentions
+                .iter()
+                .map(|m| m.screen_name.as_str())
+                .collect(),
+            urls: entities
+                .urls
+                .iter()
+                .map(|u| u.expanded_url.as_str())
+                .collect(),
+            favorite_count: tweet.favorite_count(),
+            retweet_count: tweet.retweet_count(),
+            source: tweet.source(),
+        }
+    }
+}
+
+/// Write the tweets as a JSON array of normalized tweets
+pub fn write_json<W: Write>(tweets: &[&Tweet], mut writer: W) -> io::Result<()> {
+    let tweets = tweets
+        .iter()
+        .map(|tw| NormalizedTweet::new(tw))
+        .collect::<Vec<NormalizedTweet>>();
+    serde_json::to_writer_pretty(&mut writer, &tweets)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+/// Write the tweets as a SQL script creating and filling the tables
+pub fn write_sql<W: Write>(tweets: &[&Tweet], mut writer: W) -> io::Result<()> {
+    writeln!(writer, "BEGIN TRANSACTION;")?;
+    writeln!(
+        writer,
+        "CREATE TABLE tweets (id TEXT PRIMARY KEY, created_at TEXT NOT NULL, type TEXT NOT NULL, \
+         text TEXT NOT NULL, lang TEXT, in_reply_to_status_id TEXT, in_reply_to_screen_name TEXT, \
+         favorite_count INTEGER NOT NULL, retweet_count INTEGER NOT NULL);"
+    )?;
+    writeln!(
+        writer,
+        "CREATE TABLE hashtags (tweet_id TEXT NOT NULL REFERENCES tweets(id), text TEXT NOT NULL);"
+    )?;
+    writeln!(
+        writer,
+        "CREATE TABLE mentions (tweet_id TEXT NOT NULL REFERENCES tweets(id), screen_name TEXT NOT NULL, name TEXT NOT NULL);"
+    )?;
+    writeln!(
+        writer,
+        "CREATE TABLE urls (tweet_id TEXT NOT NULL REFERENCES tweets(id), url TEXT NOT NULL, expanded_url TEXT NOT NULL);"
+    )?;
+    writeln!(
+        writer,
+        "CREATE TABLE media (tweet_id TEXT NOT NULL REFERENCES tweets(id), url TEXT NOT NULL, media_url TEXT NOT NULL, type TEXT NOT NULL);"
+    )?;
+    for tweet in tweets.iter() {
+        let id = sql_text(tweet.id());
+        writeln!(
+            writer,
+            "INSERT INTO tweets VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {});",
+            id,
+            sql_text(&tweet.created_at().to_rfc3339()),
+            sql_text(tweet_type(tweet)),
+            sql_text(&tweet.expanded_text()),
+            sql_optional(tweet.lang()),
+            sql_optional(tweet.in_reply_to_status_id()),
+            sql_optional(tweet.in_reply_to_screen_name()),
+            tweet.favorite_count(),
+            tweet.retweet_count()
+        )?;
+        let entities = tweet.entities();
+        for hashtag in entities.hashtags.iter() {
+            writeln!(
+                writer,
+                "INSERT INTO hashtags VALUES ({}, {});",
+                id,
+                sql_text(&hashtag.text)
+            )?;
+        }
+        for mention in entities.user_mentions.iter() {
+            writeln!(
+                writer,
+                "INSERT INTO mentions VALUES ({}, {}, {});",
+                id,
+                sql_text(&mention.screen_name),
+                sql_text(&mention.name)
+            )?;
+        }
+        for url in entities.urls.iter() {
+            writeln!(
+                writer,
+                "INSERT INTO urls VALUES ({}, {}, {});",
+                id,
+                sql_text(&url.url),
+                sql_text(&url.expanded_url)
+            )?;
+        }
+        for media in entities.media.iter() {
+            writeln!(
+                writer,
+                "INSERT INTO media VALUES ({}, {}, {}, {});",
+                id,
+                sql_text(&media.url),
+                sql_text(&media.media_url_https),
+                sql_text(&media.media_type)
+            )?;
+        }
+    }
+    writeln!(writer, "COMMIT;")?;
+    writer.flush()
+}
+
+/// Load the SQL script into a new SQLite database with the `sqlite3` command
+///
+/// The database is built in `<name>.part` next to it and replaces the existing one only once
+/// `sqlite3` succeeds, so a missing `sqlite3` or a broken script leaves the old database as it is.
+#[cfg(feature = "native")]
+pub fn load_into_sqlite(script_path: &Path, database_path: &Path) -> io::Result<()> {
+    load_with("sqlite3", script_path, database_path)
+}
+
+#[cfg(feature = "native")]
+fn load_with(command: &str, script_path: &Path, database_path: &Path) -> io::Result<()> {
+    let mut part = database_path.as_os_str().to_owned();
+    part.push(".part");
+    let part = PathBuf::from(part);
+    if part.exists() {
+        fs::remove_file(&part)?;
+    }
+    let status = Command::new(command)
+        .arg(&part)
+        .stdin(File::open(script_path)?)
+        .status();
+    match status {
+        Ok(status) if status.success() => fs::rename(&part, database_path),
+        result => {
+            let _ = fs::remove_file(&part);
+            match result {
+                Ok(status) => Err(io::Error::other(format!(
+                    "{} exited with {}",
+                    command, status
+                ))),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Err(io::Error::new(
+                    e.kind(),
+                    format!(
+                        "the {} command is not installed or not in the PATH",
+                        command
+                    ),
+                )),
+                Err(e) => Err(e),
+            }
+        }
+    }
+}
+
+fn sql_text(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+fn sql_optional(text: Option<&str>) -> String {
+    text.map_or("NULL".to_string(), sql_text)
+}
+
+/// Quote the field if it contains a separator, a quote or a line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "native")]
+    use super::{load_into_sqlite, load_with};
+    use super::{write_csv, write_sql, NormalizedTweet};
+    use crate::tweet::parse_tweets;
+
+    #[test]
+    fn test_write_csv() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "a, \"b\"\nc &amp; d", "favorite_count": "3", "retweet_count": "1"}},
+                {"tweet": {"id_str": "2", "created_at": "Sat Mar 11 05:00:00 +0000 2023", "full_text": "RT @hoge: x"}}
+            ]"#,
+        )
+        .unwrap();
+        let tweets = tweets
+            .into_iter()
+            .map(|tw| tw.in_timezone(&"UTC".parse().unwrap()))
+            .collect::<Vec<_>>();
+        let mut csv = Vec::new();
+        write_csv(&tweets.iter().collect::<Vec<_>>(), &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "id,created_at,type,text,favorite_count,retweet_count\n\
+             1,2023-03-11T04:12:48+00:00,tweet,\"a, \"\"b\"\"\nc & d\",3,1\n\
+             2,2023-03-11T05:00:00+00:00,retweet,RT @hoge: x,0,0\n"
+        );
+    }
+    #[test]
+    fn test_normalized_tweet() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "RT @hoge: Q&amp;A https://t.co/x", "entities": {"urls": [{"url": "https://t.co/x", "expanded_url": "https://example.com"}]}}}]"#,
+        )
+        .unwrap();
+        let json = serde_json::to_value(NormalizedTweet::new(&tweets[0])).unwrap();
+        assert_eq!(json["type"], "retweet");
+        assert_eq!(json["retweeted_screen_name"], "hoge");
+        assert_eq!(json["text"], "Q&A https://example.com");
+        assert_eq!(json["urls"][0], "https://example.com");
+    }
+    #[test]
+    fn test_write_sql() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "it's #rust", "lang": "en", "entities": {"hashtags": [{"text": "rust"}]}}}]"#,
+        )
+        .unwrap();
+        let mut sql = Vec::new();
+        write_sql(&tweets.iter().collect::<Vec<_>>(), &mut sql).unwrap();
+        let sql = String::from_utf8(sql).unwrap();
+        assert!(sql.contains("'it''s #rust', 'en', NULL, NULL, 0, 0);"));
+        assert!(sql.contains("INSERT INTO hashtags VALUES ('1', 'rust');"));
+        assert!(sql.ends_with("COMMIT;\n"));
+    }
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_load_into_sqlite_keeps_database_on_failure() {
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-sqlite-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (script, database) = (dir.join("tweets.sql"), dir.join("tweets.db"));
+        std::fs::write(&script, "THIS IS NOT SQL;\n").unwrap();
+        std::fs::write(&database, "old").unwrap();
+        // Fails whether sqlite3 is installed or not
+        assert!(load_into_sqlite(&script, &database).is_err());
+        assert_eq!(std::fs::read_to_string(&database).unwrap(), "old");
+        assert!(!dir.join("tweets.db.part").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_load_without_sqlite3() {
+        let dir = std::env::temp_dir().join(format!(
+            "twitter2obsidian-sqlite-missing-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (script, database) = (dir.join("tweets.sql"), dir.join("tweets.db"));
+        std::fs::write(&script, "BEGIN;\nCOMMIT;\n").unwrap();
+        let e = load_with("twitter2obsidian-no-such-sqlite3", &script, &database).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(
+            e.to_string(),
+            "the twitter2obsidian-no-such-sqlite3 command is not installed or not in the PATH"
+        );
+        assert!(!database.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
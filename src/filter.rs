@@ -0,0 +1,541 @@
+use crate::config::{serialize_optional_regex, serialize_regex, serialize_regexes};
+use crate::tweet::{Audience, Tweet};
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveDateTime, Timelike};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// Which replies to drop
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ReplyFilter {
+    /// Every reply
+    All,
+    /// Replies to other people, keeping replies in your own threads
+    Others,
+}
+
+/// A range of hours in a day, which may wrap around midnight (e.g. 22-06)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct HourRange {
+    /// The first hour in the range
+    start: u32,
+    /// The hour the range ends at, exclusive
+    end: u32,
+}
+impl HourRange {
+    /// Whether the hour (0-23) is in the range
+    pub fn contains(&self, hour: u32) -> bool {
+        if self.start < self.end {
+            self.start <= hour && hour < self.end
+        } else {
+            self.start <= hour || hour < self.end
+        }
+    }
+}
+impl FromStr for HourRange {
+    type Err = String;
+    /// Parse `START-END` where END is exclusive, e.g. `22-06` for 22:00 to 05:59
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{} is not a range of hours such as 22-06", s);
+        let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+        let start = start.trim().parse::<u32>().map_err(|_| invalid())?;
+        let end = end.trim().parse::<u32>().map_err(|_| invalid())?;
+        if start > 23 || end > 24 || start == end % 24 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            start,
+            end: end % 24,
+        })
+    }
+}
+
+/// Months given as `YYYY-MM` with the wildcards `*`, `?` and `[...]` of the shell, e.g.
+/// `2019-0[1-6]`, or as a range of months, e.g. `2016-04..2016-08`
+#[derive(Debug, Clone, Serialize)]
+pub enum MonthPattern {
+    Glob(#[serde(serialize_with = "serialize_regex")] Regex),
+    /// The first and the last months, inclusive
+    Range(NaiveDate, NaiveDate),
+}
+impl MonthPattern {
+    /// Whether the month of the date is one of the months
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        match self {
+            MonthPattern::Glob(regex) => {
+                regex.is_match(&format!("{:04}-{:02}", date.year(), date.month()))
+            }
+            MonthPattern::Range(first, last) => {
+                let month = date.with_day(1).unwrap_or(date);
+                *first <= month && month <= *last
+            }
+        }
+    }
+}
+impl FromStr for MonthPattern {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((first, last)) = s.split_once("..") {
+            let (first, last) = (parse_month(first.trim())?, parse_month(last.trim())?);
+            if first > last {
+                return Err(format!("{} ends before it starts", s));
+            }
+            return Ok(MonthPattern::Range(first, last));
+        }
+        if !s.contains(['*', '?', '[']) {
+            parse_month(s)?;
+        }
+        let invalid = || {
+            format!(
+                "{} is not a month in the form of YYYY-MM or a pattern of them",
+                s
+            )
+        };
+        if !s
+            .chars()
+            .all(|c| c.is_ascii_digit() || "-*?[]!".contains(c))
+        {
+            return Err(invalid());
+        }
+        let pattern = s.replace('*', ".*").replace('?', ".").replace("[!", "[^");
+        Regex::new(&format!("^{}$", pattern))
+            .map(MonthPattern::Glob)
+            .map_err(|_| invalid())
+    }
+}
+
+/// Conditions a tweet must satisfy to be converted
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TweetFilter {
+    /// Drop the tweets posted before this time
+    pub start: Option<NaiveDateTime>,
+    /// Drop the tweets posted at or after this time
+    pub end: Option<NaiveDateTime>,
+    /// Drop the tweets posted in these months
+    pub excluded_months: Vec<MonthPattern>,
+    /// Drop retweets
+    pub exclude_retweets: bool,
+    /// Drop replies
+    pub exclude_replies: Option<ReplyFilter>,
+    /// Keep only the tweets whose text matches this pattern
+    #[serde(serialize_with = "serialize_optional_regex")]
+    pub include_pattern: Option<Regex>,
+    /// Drop the tweets whose text matches any of these patterns
+    #[serde(serialize_with = "serialize_regexes")]
+    pub exclude_patterns: Vec<Regex>,
+    /// Keep only the tweets carrying any of these hashtags
+    pub hashtags: Vec<String>,
+    /// Keep only the tweets mentioning or replying to any of these users
+    pub with_users: Vec<String>,
+    /// Keep only the tweets in any of these languages
+    pub langs: Vec<String>,
+    /// Drop the tweets shorter than this many characters, not counting URLs
+    pub min_chars: Option<usize>,
+    /// Keep only the tweets posted in this range of hours
+    pub hours: Option<HourRange>,
+    /// Drop the tweets mentioning or replying to any of these users
+    pub ignored_accounts: Vec<String>,
+    /// Drop the tweets carrying any of these hashtags
+    pub ignored_hashtags: Vec<String>,
+    /// Drop the tweets posted to a Circle or a community
+    pub exclude_limited_audience: bool,
+    /// Drop the tweets flagged as possibly sensitive
+    pub exclude_sensitive: bool,
+    /// Keep the deleted tweets, which are dropped otherwise
+    pub include_deleted: bool,
+}
+impl TweetFilter {
+    /// Whether the tweet should be converted
+    pub fn matches(&self, tweet: &Tweet) -> bool {
+        let created_at = tweet.created_at().naive_local();
+        if self.start.is_some_and(|start| created_at < start) {
+            return false;
+        }
+        if self.end.is_some_and(|end| created_at >= end) {
+            return false;
+        }
+        if self
+            .excluded_months
+            .iter()
+            .any(|months| months.contains(created_at.date()))
+        {
+            return false;
+        }
+        if self.exclude_retweets && tweet.is_retweet() {
+            return false;
+        }
+        if self.exclude_limited_audience && tweet.audience() != Audience::Public {
+            return false;
+        }
+        if self.exclude_sensitive && tweet.is_possibly_sensitive() {
+            return false;
+        }
+        if !self.include_deleted && tweet.is_deleted() {
+            return false;
+        }
+        if self.exclude_replies == Some(ReplyFilter::All) && tweet.is_reply() {
+            return false;
+        }
+        if self.include_pattern.is_some() || !self.exclude_patterns.is_empty() {
+            let text = tweet.expanded_text();
+            if let Some(ref pattern) = self.include_pattern {
+                if !pattern.is_match(&text) {
+                    return false;
+                }
+            }
+            if self
+                .exclude_patterns
+                .iter()
+                .any(|pattern| pattern.is_match(&text))
+            {
+                return false;
+            }
+        }
+        if !self.hashtags.is_empty() && !self.hashtags.iter().any(|h| tweet.has_hashtag(h)) {
+            return false;
+        }
+        if !self.with_users.is_empty() && !self.with_users.iter().any(|u| tweet.involves_user(u)) {
+            return false;
+        }
+        if !self.langs.is_empty()
+            && !tweet
+                .lang()
+                .is_some_and(|lang| self.langs.iter().any(|l| l.eq_ignore_ascii_case(lang)))
+        {
+            return false;
+        }
+        if self
+            .min_chars
+            .is_some_and(|min_chars| tweet.plain_text().chars().count() < min_chars)
+        {
+            return false;
+        }
+        if self
+            .hours
+            .is_some_and(|hours| !hours.contains(tweet.created_at().hour()))
+        {
+            return false;
+        }
+        if self.ignored_accounts.iter().any(|u| tweet.involves_user(u))
+            || self.ignored_hashtags.iter().any(|h| tweet.has_hashtag(h))
+        {
+            return false;
+        }
+        true
+    }
+    /// Whether the filter needs the IDs of all tweets in the archive to tell own threads apart
+    pub fn needs_own_tweet_ids(&self) -> bool {
+        self.exclude_replies == Some(ReplyFilter::Others)
+    }
+    /// Whether the tweet should be converted, given the IDs of all tweets in the archive
+    ///
+    /// A reply to a tweet in the archive is a reply in your own thread.
+    pub fn matches_thread(&self, tweet: &Tweet, own_tweet_ids: &HashSet<String>) -> bool {
+        if self.exclude_replies == Some(ReplyFilter::Others) && tweet.is_reply() {
+            return tweet
+                .in_reply_to_status_id()
+                .is_some_and(|id| own_tweet_ids.contains(id));
+        }
+        true
+    }
+}
+
+/// Parse a month (YYYY-MM) into its first day
+pub fn parse_month(month: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map_err(|_| format!("{} is not a month in the form of YYYY-MM", month))
+}
+
+/// Parse a date (YYYY-MM-DD)
+pub fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("{} is not a date in the form of YYYY-MM-DD", date))
+}
+
+/// The exclusive end of a range ending with the given month
+pub fn end_of_month(month: NaiveDate) -> Option<NaiveDateTime> {
+    // 翌月初日にする
+    month.checked_add_months(Months::new(1)).map(|d| d.into())
+}
+
+/// The exclusive end of a range ending with the given day
+pub fn end_of_day(date: NaiveDate) -> Option<NaiveDateTime> {
+    date.checked_add_days(Days::new(1)).map(|d| d.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        end_of_day, end_of_month, parse_date, parse_month, HourRange, MonthPattern, ReplyFilter,
+        TweetFilter,
+    };
+    use crate::tweet::{parse_tweets, Audience, Tweet};
+    use chrono::{Local, NaiveDate, TimeZone};
+    use regex::Regex;
+    use std::collections::HashSet;
+
+    fn tweet(full_text: &str, is_reply: bool) -> Tweet {
+        Tweet::new_with_local_datetime(
+            Local.with_ymd_and_hms(2023, 3, 11, 4, 12, 48).unwrap(),
+            full_text.to_string(),
+            is_reply,
+        )
+    }
+
+    #[test]
+    fn test_excluded_months() {
+        let patterns = |s: &str| {
+            s.split(',')
+                .map(|p| p.parse::<MonthPattern>().unwrap())
+                .collect::<Vec<MonthPattern>>()
+        };
+        let filter = TweetFilter {
+            excluded_months: patterns("2016-04,2023-0[1-3]"),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&tweet("hello", false)));
+        for (s, excluded) in [
+            ("2023-03", true),
+            ("2023-*", true),
+            ("2023-0[!3]", false),
+            ("2022-12..2023-02", false),
+            ("2023-02..2023-03", true),
+        ] {
+            let filter = TweetFilter {
+                excluded_months: patterns(s),
+                ..Default::default()
+            };
+            assert_eq!(filter.matches(&tweet("hello", false)), !excluded, "{}", s);
+        }
+        assert!("2023-13".parse::<MonthPattern>().is_err());
+        assert!("2023-04..2023-01".parse::<MonthPattern>().is_err());
+        assert!("2023-(04|05)".parse::<MonthPattern>().is_err());
+    }
+    #[test]
+    fn test_date_range() {
+        let filter = TweetFilter {
+            start: NaiveDate::from_ymd_opt(2023, 3, 1).map(|d| d.into()),
+            end: NaiveDate::from_ymd_opt(2023, 4, 1).map(|d| d.into()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&tweet("hello", false)));
+        let filter = TweetFilter {
+            end: NaiveDate::from_ymd_opt(2023, 3, 11).map(|d| d.into()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&tweet("hello", false)));
+    }
+    #[test]
+    fn test_parse_month_and_date() {
+        assert_eq!(
+            parse_month("2023-03"),
+            Ok(NaiveDate::from_ymd_opt(2023, 3, 1).unwrap())
+        );
+        assert!(parse_month("2023-13").is_err());
+        assert!(parse_month("2023/03").is_err());
+        assert_eq!(
+            parse_date("2023-03-11"),
+            Ok(NaiveDate::from_ymd_opt(2023, 3, 11).unwrap())
+        );
+        assert!(parse_date("2023-02-30").is_err());
+        assert_eq!(
+            end_of_month(NaiveDate::from_ymd_opt(2023, 12, 1).unwrap()),
+            NaiveDate::from_ymd_opt(2024, 1, 1).map(|d| d.into())
+        );
+        assert_eq!(
+            end_of_day(NaiveDate::from_ymd_opt(2023, 2, 28).unwrap()),
+            NaiveDate::from_ymd_opt(2023, 3, 1).map(|d| d.into())
+        );
+    }
+    #[test]
+    fn test_hour_range() {
+        let late_night = "22-06".parse::<HourRange>().unwrap();
+        assert!(late_night.contains(22));
+        assert!(late_night.contains(0));
+        assert!(late_night.contains(5));
+        assert!(!late_night.contains(6));
+        assert!(!late_night.contains(12));
+        let evening = "18-24".parse::<HourRange>().unwrap();
+        assert!(evening.contains(23));
+        assert!(!evening.contains(0));
+        assert!("9".parse::<HourRange>().is_err());
+        assert!("25-03".parse::<HourRange>().is_err());
+        assert!("3-3".parse::<HourRange>().is_err());
+    }
+    #[test]
+    fn test_exclude_retweets() {
+        let filter = TweetFilter {
+            exclude_retweets: true,
+            ..Default::default()
+        };
+        assert!(filter.matches(&tweet("hello", false)));
+        assert!(!filter.matches(&tweet("RT @hoge: hello", false)));
+    }
+    #[test]
+    fn test_exclude_limited_audience() {
+        let filter = TweetFilter {
+            exclude_limited_audience: true,
+            ..Default::default()
+        };
+        assert!(filter.matches(&tweet("hello", false)));
+        assert!(!filter.matches(&tweet("hello", false).with_audience(Audience::Circle)));
+    }
+    #[test]
+    fn test_deleted_tweets() {
+        let deleted = tweet("hello", false).into_deleted();
+        assert!(!TweetFilter::default().matches(&deleted));
+        let filter = TweetFilter {
+            include_deleted: true,
+            ..Default::default()
+        };
+        assert!(filter.matches(&deleted));
+    }
+    #[test]
+    fn test_exclude_replies() {
+        let filter = TweetFilter {
+            exclude_replies: Some(ReplyFilter::All),
+            ..Default::default()
+        };
+        assert!(filter.matches(&tweet("hello", false)));
+        assert!(!filter.matches(&tweet("@hoge hello", true)));
+    }
+    #[test]
+    fn test_include_pattern() {
+        let filter = TweetFilter {
+            include_pattern: Some(Regex::new(r"(?i)twitter2obsidian").unwrap()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&tweet("Released Twitter2obsidian 1.0", false)));
+        assert!(!filter.matches(&tweet("hello", false)));
+    }
+    #[test]
+    fn test_include_pattern_expanded_text() {
+        let tweets = parse_tweets(
+            r#"[
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "Q&amp;A https://t.co/abc",
+                "entities": {"urls": [{"url": "https://t.co/abc", "expanded_url": "https://example.com/qa"}]}}}
+        ]"#,
+        )
+        .unwrap();
+        let filter = TweetFilter {
+            include_pattern: Some(Regex::new(r"example\.com").unwrap()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&tweets[0]));
+        let filter = TweetFilter {
+            include_pattern: Some(Regex::new(r"^Q&A ").unwrap()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&tweets[0]));
+    }
+    #[test]
+    fn test_exclude_patterns() {
+        let filter = TweetFilter {
+            exclude_patterns: vec![
+                Regex::new(r"^I'm at ").unwrap(),
+                Regex::new(r"#nowplaying").unwrap(),
+            ],
+            ..Default::default()
+        };
+        assert!(filter.matches(&tweet("hello", false)));
+        assert!(!filter.matches(&tweet("I'm at Shibuya Station", false)));
+        assert!(!filter.matches(&tweet("Song #nowplaying", false)));
+    }
+    #[test]
+    fn test_exclude_patterns_expanded_text() {
+        let tweets = parse_tweets(
+            r#"[
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "I'm at Shibuya &amp; Ebisu https://t.co/abc",
+                "entities": {"urls": [{"url": "https://t.co/abc", "expanded_url": "https://www.swarmapp.com/c/abc"}]}}},
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "hello https://t.co/def",
+                "entities": {"urls": [{"url": "https://t.co/def", "expanded_url": "https://example.com/"}]}}}
+        ]"#,
+        )
+        .unwrap();
+        let filter = TweetFilter {
+            exclude_patterns: vec![Regex::new(r"swarmapp\.com").unwrap()],
+            ..Default::default()
+        };
+        assert!(!filter.matches(&tweets[0]));
+        assert!(filter.matches(&tweets[1]));
+        let filter = TweetFilter {
+            exclude_patterns: vec![Regex::new(r"Shibuya & Ebisu").unwrap()],
+            ..Default::default()
+        };
+        assert!(!filter.matches(&tweets[0]));
+        assert!(filter.matches(&tweets[1]));
+    }
+    #[test]
+    fn test_hashtags() {
+        let tweets = parse_tweets(
+            r##"[
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "#rustlang",
+                "entities": {"hashtags": [{"text": "rustlang"}]}}},
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "not a #rustlangjp tweet",
+                "entities": {"hashtags": [{"text": "rustlangjp"}]}}}
+        ]"##,
+        )
+        .unwrap();
+        let filter = TweetFilter {
+            hashtags: vec!["rustlang".to_string()],
+            ..Default::default()
+        };
+        assert!(filter.matches(&tweets[0]));
+        assert!(!filter.matches(&tweets[1]));
+    }
+    #[test]
+    fn test_langs() {
+        let tweets = parse_tweets(
+            r#"[
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "こんにちは", "lang": "ja"}},
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "hello", "lang": "en"}},
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "https://t.co/abc"}}
+        ]"#,
+        )
+        .unwrap();
+        let filter = TweetFilter {
+            langs: vec!["ja".to_string()],
+            ..Default::default()
+        };
+        let kept = tweets.iter().filter(|tw| filter.matches(tw)).count();
+        assert_eq!(kept, 1);
+        assert!(filter.matches(&tweets[0]));
+    }
+    #[test]
+    fn test_min_chars() {
+        let filter = TweetFilter {
+            min_chars: Some(4),
+            ..Default::default()
+        };
+        assert!(filter.matches(&tweet("おはよう", false)));
+        assert!(!filter.matches(&tweet("lol https://t.co/abcdefg", false)));
+        assert!(!filter.matches(&tweet("&lt;3", false)));
+    }
+    #[test]
+    fn test_exclude_replies_to_others() {
+        let tweets = parse_tweets(
+            r#"[
+            {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "thread 1/2"}},
+            {"tweet": {"id_str": "2", "created_at": "Sat Mar 11 04:13:48 +0000 2023", "full_text": "@me 2/2", "in_reply_to_user_id": "10", "in_reply_to_status_id_str": "1"}},
+            {"tweet": {"id_str": "3", "created_at": "Sat Mar 11 04:14:48 +0000 2023", "full_text": "@hoge hi", "in_reply_to_user_id": "20", "in_reply_to_status_id_str": "99"}}
+        ]"#,
+        )
+        .unwrap();
+        let filter = TweetFilter {
+            exclude_replies: Some(ReplyFilter::Others),
+            ..Default::default()
+        };
+        let ids = tweets
+            .iter()
+            .map(|tw| tw.id().to_string())
+            .collect::<HashSet<String>>();
+        let kept = tweets
+            .iter()
+            .filter(|tw| filter.matches(tw) && filter.matches_thread(tw, &ids))
+            .map(|tw| tw.id())
+            .collect::<Vec<&str>>();
+        assert_eq!(kept, vec!["1", "2"]);
+    }
+}
@@ -0,0 +1,330 @@
+//! Anonymized samples of an archive, for bug reports and for trying templates safely
+//!
+//! The structure of the tweets is kept as it is, while the handles, the IDs, the words and the
+//! links are scrambled. Every string keeps its number of characters, and the mentions, the
+//! hashtags, the links and the HTML entities stay where they are, so the `indices` of the
+//! entities still point at them.
+use crate::sampling::SplitMix64;
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// File name of the sample of the tweets in the output folder
+pub const TWEETS_FILE_NAME: &str = "tweets.js";
+
+/// Keys whose values are links, replaced with ones to example.com, or other t.co links
+const URL_KEYS: &[&str] = &[
+    "url",
+    "expanded_url",
+    "display_url",
+    "media_url",
+    "media_url_https",
+    "expandedUrl",
+    "displayUrl",
+];
+/// Keys whose values are the names of places or people, replaced with scrambled words
+const NAME_KEYS: &[&str] = &["name", "full_name", "accountDisplayName"];
+/// Keys whose values are the handles of accounts
+const HANDLE_KEYS: &[&str] = &["screen_name", "in_reply_to_screen_name", "username"];
+/// Keys whose values are text, scrambled word by word
+const TEXT_KEYS: &[&str] = &["full_text", "text", "string_value", "description", "title"];
+
+/// Scrambles the tweets consistently: the same handle or ID becomes the same one everywhere
+pub struct Anonymizer {
+    rng: SplitMix64,
+    handles: HashMap<String, String>,
+    ids: HashMap<String, String>,
+    urls: HashMap<String, String>,
+}
+impl Anonymizer {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: SplitMix64::new(seed),
+            handles: HashMap::new(),
+            ids: HashMap::new(),
+            urls: HashMap::new(),
+        }
+    }
+    /// A random string of the characters, of `len` characters
+    fn random(&mut self, chars: &[u8], len: usize) -> String {
+        (0..len)
+            .map(|_| chars[self.rng.next_below(chars.len())] as char)
+            .collect()
+    }
+    /// The handle replacing `handle`, of the same length and case-insensitively the same one
+    pub fn handle(&mut self, handle: &str) -> String {
+        let key = handle.to_lowercase();
+        if let Some(scrambled) = self.handles.get(&key) {
+            return scrambled.clone();
+        }
+        let len = handle.chars().count().max(1);
+        let scrambled = self.random(b"abcdefghijklmnopqrstuvwxyz", 1)
+            + &self.random(b"abcdefghijklmnopqrstuvwxyz0123456789_", len - 1);
+        self.handles.insert(key, scrambled.clone());
+        scrambled
+    }
+    /// The ID replacing `id`, of the same number of digits
+    pub fn id(&mut self, id: &str) -> String {
+        if let Some(scrambled) = self.ids.get(id) {
+            return scrambled.clone();
+        }
+        let scrambled = self.random(b"123456789", 1) + &self.random(b"0123456789", id.len() - 1);
+        self.ids.insert(id.to_string(), scrambled.clone());
+        scrambled
+    }
+    /// Shuffle the characters of a word
+    fn shuffle(&mut self, word: &str) -> String {
+        let mut chars = word.chars().collect::<Vec<char>>();
+        for i in (1..chars.len()).rev() {
+            let j = self.rng.next_below(i + 1);
+            chars.swap(i, j);
+        }
+        chars.into_iter().collect()
+    }
+    /// Scramble a text word by word, keeping the whitespace, the `RT @handle:` of a retweet, the
+    /// hashtags and the HTML entities, and replacing the mentions and the links
+    pub fn text(&mut self, text: &str) -> String {
+        let mut scrambled = String::new();
+        let mut end = 0;
+        if text.starts_with("RT @") {
+            scrambled.push_str("RT ");
+            end = "RT ".len();
+        }
+        for part in kept_parts().find_iter(text) {
+            if part.start() < end {
+                continue;
+            }
+            scrambled.push_str(&self.words(&text[end..part.start()]));
+            scrambled.push_str(&self.part(part.as_str()));
+            end = part.end();
+        }
+        scrambled.push_str(&self.words(&text[end..]));
+        scrambled
+    }
+    /// Shuffle the characters of each word between the parts kept in place
+    fn words(&mut self, text: &str) -> String {
+        let mut scrambled = String::new();
+        let mut word = String::new();
+        for c in text.chars() {
+            if !c.is_whitespace() {
+                word.push(c);
+                continue;
+            }
+            scrambled.push_str(&self.shuffle(&word));
+            word.clear();
+            scrambled.push(c);
+        }
+        scrambled.push_str(&self.shuffle(&word));
+        scrambled
+    }
+    fn part(&mut self, part: &str) -> String {
+        if part.starts_with("http") {
+            return self.url(part);
+        }
+        match part.strip_prefix(['@', '＠']) {
+            Some(handle) => format!(
+                "{}{}",
+                &part[..part.len() - handle.len()],
+                self.handle(handle)
+            ),
+            None => part.to_string(),
+        }
+    }
+    /// A link with a random path of the same length when possible, the same one for the same
+    /// link: another t.co link for a t.co link, and a link to example.com otherwise
+    fn url(&mut self, url: &str) -> String {
+        if let Some(scrambled) = self.urls.get(url) {
+            return scrambled.clone();
+        }
+        let prefix = match url.split_once("://") {
+            Some((scheme, rest)) if rest.starts_with("t.co/") => format!("{}://t.co/", scheme),
+            Some((scheme, _)) => format!("{}://example.com/", scheme),
+            None => "example.com/".to_string(),
+        };
+        let len = url.chars().count().saturating_sub(prefix.len()).max(1);
+        let scrambled = prefix.clone()
+            + &self.random(
+                b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789",
+                len,
+            );
+        self.urls.insert(url.to_string(), scrambled.clone());
+        scrambled
+    }
+    /// Anonymize a value of the archive in place, in the context of its key
+    pub fn value(&mut self, key: &str, value: &mut Value) {
+        // The locations are kept to the whole degrees, too coarse to find anyone
+        if key == "coordinates" || key == "geo" {
+            coarsen(value);
+            return;
+        }
+        match value {
+            Value::Object(object) => self.object(key, object),
+            Value::Array(values) => values.iter_mut().for_each(|v| self.value(key, v)),
+            Value::String(s) if is_id(key, s) => *s = self.id(s),
+            Value::String(s) if HANDLE_KEYS.contains(&key) => *s = self.handle(s),
+            Value::String(s) if URL_KEYS.contains(&key) => *s = self.url(s),
+            Value::String(s) if NAME_KEYS.contains(&key) || TEXT_KEYS.contains(&key) => {
+                *s = self.text(s)
+            }
+            Value::String(s) if key == "email" => *s = "user@example.com".to_string(),
+            Value::Number(n) if is_id(key, &n.to_string()) => {
+                *value = self.id(&n.to_string()).parse().map(Value::Number).unwrap()
+            }
+            _ => {}
+        }
+    }
+    fn object(&mut self, key: &str, object: &mut Map<String, Value>) {
+        // The hashtags are kept like in the text
+        if key == "hashtags" || key == "symbols" {
+            return;
+        }
+        for (key, value) in object.iter_mut() {
+            self.value(key, value);
+        }
+    }
+}
+
+/// The parts of a text kept in place while its words are shuffled: the HTML entities Twitter
+/// escapes, the mentions, the hashtags, the cashtags and the links
+fn kept_parts() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"&(?:amp|lt|gt);|[@＠][A-Za-z0-9_]+|[#＃$][\p{L}\p{N}_]+|https?://t\.co/[A-Za-z0-9]+|https?://\S+",
+        )
+        .unwrap()
+    })
+}
+
+/// Whether the value of the key is an ID, all digits in a key such as `id_str` or `tweetId`
+fn is_id(key: &str, value: &str) -> bool {
+    let key = key.to_lowercase();
+    (key.ends_with("id") || key.ends_with("id_str") || key.ends_with("ids"))
+        && !value.is_empty()
+        && value.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Round the coordinates to whole degrees
+fn coarsen(value: &mut Value) {
+    match value {
+        Value::Array(values) => values.iter_mut().for_each(coarsen),
+        Value::Object(object) => object.values_mut().for_each(coarsen),
+        Value::Number(n) => {
+            if let Some(rounded) = n
+                .as_f64()
+                .and_then(|f| serde_json::Number::from_f64(f.round()))
+            {
+                *n = rounded;
+            }
+        }
+        Value::String(s) => {
+            if let Ok(f) = s.parse::<f64>() {
+                *s = format!("{:.1}", f.round());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Pick `n` entries at random, the same ones for the same seed, keeping their order
+pub fn sample(entries: Vec<Value>, n: usize, seed: u64) -> Vec<Value> {
+    let mut indices = (0..entries.len()).collect::<Vec<usize>>();
+    let n = n.min(indices.len());
+    let mut rng = SplitMix64::new(seed);
+    for i in 0..n {
+        let j = i + rng.next_below(indices.len() - i);
+        indices.swap(i, j);
+    }
+    let mut picked = indices[..n].to_vec();
+    picked.sort_unstable();
+    let mut entries = entries
+        .into_iter()
+        .map(Some)
+        .collect::<Vec<Option<Value>>>();
+    picked
+        .into_iter()
+        .filter_map(|i| entries[i].take())
+        .collect()
+}
+
+/// Render the entries as a file of the archive, assigning them to `window.YTD.<name>.part0`
+pub fn to_script(name: &str, entries: &[Value]) -> serde_json::Result<String> {
+    Ok(format!(
+        "window.YTD.{}.part0 = {}\n",
+        name,
+        serde_json::to_string_pretty(entries)?
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sample, Anonymizer};
+    use serde_json::json;
+
+    #[test]
+    fn test_anonymize() {
+        let mut entry = json!({"tweet": {
+            "id_str": "1634420338812760065",
+            "full_text": "Hello @rustlang, see #rust https://t.co/abc",
+            "in_reply_to_screen_name": "RustLang",
+            "in_reply_to_status_id_str": "1634420338812760065",
+            "favorite_count": "3",
+            "entities": {
+                "hashtags": [{"text": "rust", "indices": ["21", "26"]}],
+                "user_mentions": [{"screen_name": "rustlang", "name": "Rust Language", "indices": ["6", "15"]}],
+                "urls": [{"url": "https://t.co/abc", "expanded_url": "https://www.rust-lang.org/"}]
+            },
+            "coordinates": {"type": "Point", "coordinates": [139.7005, 35.6595]},
+            "geo": {"type": "Point", "coordinates": ["35.6595", "139.7005"]}
+        }});
+        let mut anonymizer = Anonymizer::new(1);
+        anonymizer.value("", &mut entry);
+        let tweet = &entry["tweet"];
+        let id = tweet["id_str"].as_str().unwrap();
+        assert_ne!(id, "1634420338812760065");
+        assert_eq!(id.len(), 19);
+        assert_eq!(tweet["in_reply_to_status_id_str"], id);
+        assert_eq!(tweet["favorite_count"], "3");
+        let handle = tweet["in_reply_to_screen_name"].as_str().unwrap();
+        assert_eq!(handle.len(), 8);
+        assert_eq!(tweet["entities"]["user_mentions"][0]["screen_name"], handle);
+        let text = tweet["full_text"].as_str().unwrap();
+        assert_eq!(text.chars().count(), 43);
+        assert!(text.contains(&format!("@{}, ", handle)));
+        let url = tweet["entities"]["urls"][0]["url"].as_str().unwrap();
+        assert!(url.starts_with("https://t.co/"));
+        assert_ne!(url, "https://t.co/abc");
+        assert_eq!(url.len(), 16);
+        assert!(text.ends_with(&format!(" #rust {}", url)));
+        assert!(tweet["entities"]["urls"][0]["expanded_url"]
+            .as_str()
+            .unwrap()
+            .starts_with("https://example.com/"));
+        assert_eq!(tweet["entities"]["hashtags"][0]["text"], "rust");
+        assert_eq!(tweet["coordinates"]["coordinates"], json!([140.0, 36.0]));
+        assert_eq!(tweet["geo"]["coordinates"], json!(["36.0", "140.0"]));
+    }
+    #[test]
+    fn test_anonymize_retweet() {
+        let mut anonymizer = Anonymizer::new(1);
+        let text = "RT @rustlang: Q&amp;A at #RustConf(@hoge) https://t.co/abc";
+        let scrambled = anonymizer.text(text);
+        let handle = anonymizer.handle("rustlang");
+        assert!(scrambled.starts_with(&format!("RT @{}: ", handle)));
+        assert_eq!(scrambled.find("&amp;"), text.find("&amp;"));
+        assert_eq!(scrambled.find("#RustConf"), text.find("#RustConf"));
+        assert!(scrambled.contains(&format!("@{})", anonymizer.handle("hoge"))));
+        assert_eq!(scrambled.chars().count(), text.chars().count());
+        assert!(scrambled.ends_with(&anonymizer.url("https://t.co/abc")));
+    }
+    #[test]
+    fn test_sample() {
+        let entries = (0..10).map(|i| json!(i)).collect::<Vec<_>>();
+        let picked = sample(entries.clone(), 3, 7);
+        assert_eq!(picked.len(), 3);
+        assert!(picked.windows(2).all(|w| w[0].as_i64() < w[1].as_i64()));
+        assert_eq!(picked, sample(entries.clone(), 3, 7));
+        assert_eq!(sample(entries, 20, 7).len(), 10);
+    }
+}
@@ -0,0 +1,52 @@
+use chrono::{DateTime, Datelike, FixedOffset};
+use serde::Serialize;
+
+/// The period each note collects the tweets of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Granularity {
+    /// A note per day, `tweets_20230311`
+    Day,
+    /// A note per month, `tweets_202303`
+    #[default]
+    Month,
+    /// A note per year, `tweets_2023`
+    Year,
+}
+impl Granularity {
+    /// The period `date` is in as a number such as 202303, which sorts chronologically
+    pub fn key(&self, date: &DateTime<FixedOffset>) -> i32 {
+        match self {
+            Granularity::Day => date.year() * 10000 + date.month() as i32 * 100 + date.day() as i32,
+            Granularity::Month => date.year() * 100 + date.month() as i32,
+            Granularity::Year => date.year(),
+        }
+    }
+    /// Name of the note of the period `date` is in
+    pub fn note_name(&self, date: &DateTime<FixedOffset>) -> String {
+        format!("tweets_{}", self.key(date))
+    }
+    /// Heading of the period `date` is in, such as 2023年03月
+    pub fn title(&self, date: &DateTime<FixedOffset>) -> String {
+        match self {
+            Granularity::Day => date.format("%Y年%m月%d日").to_string(),
+            Granularity::Month => date.format("%Y年%m月").to_string(),
+            Granularity::Year => date.format("%Y年").to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Granularity;
+    use chrono::DateTime;
+
+    #[test]
+    fn test_note_name() {
+        let date = DateTime::parse_from_rfc3339("2023-03-11T04:12:48+09:00").unwrap();
+        assert_eq!(Granularity::Day.note_name(&date), "tweets_20230311");
+        assert_eq!(Granularity::Month.note_name(&date), "tweets_202303");
+        assert_eq!(Granularity::Year.note_name(&date), "tweets_2023");
+        assert_eq!(Granularity::Day.title(&date), "2023年03月11日");
+    }
+}
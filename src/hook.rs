@@ -0,0 +1,52 @@
+//! Commands of the user run on the files written by a conversion
+use crate::error::{Error, Result};
+use std::path::Path;
+use std::process::Command;
+
+/// Run `command` in the shell with `path` appended as its argument
+pub fn run(command: &str, path: &Path) -> Result<()> {
+    let mut shell = if cfg!(target_os = "windows") {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    } else {
+        // The path is given as "$1" so that it is never interpreted by the shell
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(format!("{} \"$1\"", command)).arg("sh");
+        shell
+    };
+    let hook_error = |message: String| Error::Hook {
+        command: command.to_string(),
+        message,
+    };
+    let output = shell
+        .arg(path)
+        .output()
+        .map_err(|e| hook_error(e.to_string()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(hook_error(format!("{}: {}", output.status, stderr.trim())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use std::path::Path;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run() {
+        let log =
+            std::env::temp_dir().join(format!("twitter2obsidian-hook-{}", std::process::id()));
+        let command = format!("echo >> '{}'", log.display());
+        run(&command, Path::new("notes/it's $HOME.md")).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            "notes/it's $HOME.md\n"
+        );
+        std::fs::remove_file(&log).unwrap();
+        assert!(run("exit 3", Path::new("a.md")).is_err());
+    }
+}
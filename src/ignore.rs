@@ -0,0 +1,90 @@
+use crate::error::{Error, Result};
+use serde::Serialize;
+use std::path::Path;
+
+/// Accounts and hashtags to leave out of the notes
+///
+/// The file lists one entry per line:
+///
+/// ```text
+/// // Drop the tweets mentioning or replying to the account
+/// @old_bot
+/// // Drop the tweets carrying the hashtag
+/// #shindanmaker
+/// // Keep the tweets, but do not link the account or tag the hashtag
+/// nolink @someone
+/// nolink #nowplaying
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct IgnoreList {
+    pub dropped_accounts: Vec<String>,
+    pub dropped_hashtags: Vec<String>,
+    pub unlinked_accounts: Vec<String>,
+    pub unlinked_hashtags: Vec<String>,
+}
+impl IgnoreList {
+    /// Load the ignore list from a file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|source| Error::Input {
+            path: path.display().to_string(),
+            source,
+        })?;
+        Self::parse(&content)
+    }
+    /// Parse the content of an ignore list
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut list = Self::default();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let (unlinked, entry) = match line.strip_prefix("nolink ") {
+                Some(entry) => (true, entry.trim()),
+                None => (false, line),
+            };
+            let invalid = || Error::IgnoreList {
+                line: i + 1,
+                entry: line.to_string(),
+            };
+            let (accounts, hashtags) = if unlinked {
+                (&mut list.unlinked_accounts, &mut list.unlinked_hashtags)
+            } else {
+                (&mut list.dropped_accounts, &mut list.dropped_hashtags)
+            };
+            if let Some(name) = entry.strip_prefix(['@', '＠']) {
+                accounts.push(name.to_lowercase());
+            } else if let Some(name) = entry.strip_prefix(['#', '＃']) {
+                hashtags.push(name.to_lowercase());
+            } else {
+                return Err(invalid());
+            }
+        }
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IgnoreList;
+
+    #[test]
+    fn test_parse() {
+        let list = IgnoreList::parse(
+            "// bots\n@Old_Bot\n#shindanmaker\n\nnolink @someone\nnolink #NowPlaying\n",
+        )
+        .unwrap();
+        assert_eq!(list.dropped_accounts, vec!["old_bot"]);
+        assert_eq!(list.dropped_hashtags, vec!["shindanmaker"]);
+        assert_eq!(list.unlinked_accounts, vec!["someone"]);
+        assert_eq!(list.unlinked_hashtags, vec!["nowplaying"]);
+    }
+    #[test]
+    fn test_parse_invalid_entry() {
+        let err = IgnoreList::parse("@bot\nsomeone\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid entry on line 2 of the ignore list: someone"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Processing of the photos imported to the vault, to keep the vault small and free of metadata
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Extensions of the photos, the media other than the videos
+const PHOTO_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// Whether the file is a photo, told by its extension
+pub fn is_photo(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| PHOTO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Remove the EXIF and XMP metadata of a JPEG or PNG, such as the camera and the location
+///
+/// Returns `None` for the other formats and for data that cannot be parsed.
+pub fn strip_metadata(content: &[u8]) -> Option<Vec<u8>> {
+    if content.starts_with(&[0xFF, 0xD8]) {
+        strip_jpeg(content)
+    } else if content.starts_with(PNG_SIGNATURE) {
+        strip_png(content)
+    } else {
+        None
+    }
+}
+
+/// Drop the APP1 segments of Exif and XMP before the image data
+fn strip_jpeg(content: &[u8]) -> Option<Vec<u8>> {
+    const SOS: u8 = 0xDA;
+    const EOI: u8 = 0xD9;
+    const APP1: u8 = 0xE1;
+    let mut stripped = content[..2].to_vec();
+    let mut i = 2;
+    loop {
+        // Markers may be padded with 0xFF
+        while content.get(i + 1) == Some(&0xFF) {
+            i += 1;
+        }
+        if content.get(i) != Some(&0xFF) {
+            return None;
+        }
+        let marker = *content.get(i + 1)?;
+        if marker == SOS || marker == EOI {
+            stripped.extend_from_slice(&content[i..]);
+            return Some(stripped);
+        }
+        let length = u16::from_be_bytes([*content.get(i + 2)?, *content.get(i + 3)?]) as usize;
+        let segment = content.get(i..i + 2 + length)?;
+        let payload = &segment[4.min(segment.len())..];
+        let is_metadata = marker == APP1
+            && (payload.starts_with(b"Exif\0") || payload.starts_with(b"http://ns.adobe.com/xap/"));
+        if !is_metadata {
+            stripped.extend_from_slice(segment);
+        }
+        i += segment.len();
+    }
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Drop the eXIf chunk and the text chunks, which hold XMP among others
+fn strip_png(content: &[u8]) -> Option<Vec<u8>> {
+    const METADATA_CHUNKS: &[&[u8]] = &[b"eXIf", b"tEXt", b"zTXt", b"iTXt"];
+    let mut stripped = PNG_SIGNATURE.to_vec();
+    let mut i = PNG_SIGNATURE.len();
+    while i < content.len() {
+        let length = u32::from_be_bytes(content.get(i..i + 4)?.try_into().ok()?) as usize;
+        // The length, the type, the data and the CRC
+        let chunk = content.get(i..i + 12 + length)?;
+        let chunk_type = &chunk[4..8];
+        if !METADATA_CHUNKS.contains(&chunk_type) {
+            stripped.extend_from_slice(chunk);
+        }
+        i += chunk.len();
+        if chunk_type == b"IEND" {
+            break;
+        }
+    }
+    Some(stripped)
+}
+
+/// Shrink a photo to fit in `max_dimension` pixels on its longer side with the `magick` command
+/// of ImageMagick, leaving smaller photos as they are
+pub fn resize(content: &[u8], extension: &str, max_dimension: u32) -> io::Result<Vec<u8>> {
+    let format = format!("{}:-", extension.to_ascii_lowercase());
+    let mut child = Command::new("magick")
+        .arg(&format)
+        .args(["-resize", &format!("{0}x{0}>", max_dimension)])
+        .arg(&format)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("failed to run magick: {}", e)))?;
+    // The photo is written from another thread for magick not to block on a full stdout
+    let mut stdin = child.stdin.take().unwrap();
+    let input = content.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    writer.join().unwrap()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "magick exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_photo, strip_metadata};
+    use std::path::Path;
+
+    #[test]
+    fn test_strip_jpeg() {
+        let mut jpeg = vec![0xFF, 0xD8];
+        let jfif = [0xFF, 0xE0, 0x00, 0x07, b'J', b'F', b'I', b'F', 0x00];
+        jpeg.extend_from_slice(&jfif);
+        jpeg.extend_from_slice(&[0xFF, 0xE1, 0x00, 0x0A, b'E', b'x', b'i', b'f', 0, 0, 1, 2]);
+        let image = [0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9];
+        jpeg.extend_from_slice(&image);
+        let stripped = strip_metadata(&jpeg).unwrap();
+        assert_eq!(stripped, [&[0xFF, 0xD8][..], &jfif, &image].concat());
+        assert!(strip_metadata(&jpeg[..8]).is_none());
+    }
+    #[test]
+    fn test_strip_png() {
+        let chunk = |chunk_type: &[u8], data: &[u8]| {
+            [
+                &(data.len() as u32).to_be_bytes()[..],
+                chunk_type,
+                data,
+                &[0, 0, 0, 0],
+            ]
+            .concat()
+        };
+        let header = chunk(b"IHDR", &[0; 13]);
+        let data = chunk(b"IDAT", b"pixels");
+        let end = chunk(b"IEND", b"");
+        let png = [
+            super::PNG_SIGNATURE,
+            &header,
+            &chunk(b"tEXt", b"Author\0me"),
+            &chunk(b"eXIf", b"MM\0*"),
+            &data,
+            &end,
+        ]
+        .concat();
+        assert_eq!(
+            strip_metadata(&png).unwrap(),
+            [super::PNG_SIGNATURE, &header, &data, &end].concat()
+        );
+        assert!(strip_metadata(b"GIF89a").is_none());
+    }
+    #[test]
+    fn test_is_photo() {
+        assert!(is_photo(Path::new("1-cat.JPG")));
+        assert!(!is_photo(Path::new("1-cat.mp4")));
+    }
+}
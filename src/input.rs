@@ -0,0 +1,408 @@
+use crate::account::{self, Account};
+use crate::archive::{self, ArchiveManifest};
+use crate::error::{Error, Result};
+use crate::joplin;
+use crate::moments::{self, Moment};
+use crate::spaces::{self, Space};
+use crate::tweet::{load_tweets, Audience, Tweet};
+use crate::{bluesky, mastodon, threads};
+use log::warn;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// File of the tweets posted to the Twitter Circle, next to `tweets.js` in the archive
+const CIRCLE_TWEETS_FILE_NAME: &str = "twitter-circle-tweet.js";
+
+/// File of the tweets deleted before the archive was exported, next to `tweets.js`
+const DELETED_TWEETS_FILE_NAME: &str = "deleted-tweets.js";
+
+/// What a source tells about the tweets besides the tweets themselves
+#[derive(Debug, Clone, Default)]
+pub struct SourceMetadata {
+    /// Name of the source for the logs, such as the path of the file
+    pub name: String,
+    /// The account the tweets were posted from, when the source tells it
+    pub account: Option<Account>,
+    /// Folder of the media files of the tweets, named `<tweet id>-<file name>`
+    pub media_dir: Option<PathBuf>,
+    /// The Spaces the account hosted or attended
+    pub spaces: Vec<Space>,
+    /// The Moments the account curated
+    pub moments: Vec<Moment>,
+}
+
+/// Where the tweets to convert come from
+///
+/// The grouping and the rendering only see the tweets and the metadata, so an importer of
+/// another service only has to implement this trait.
+pub trait TweetSource: fmt::Debug + Send + Sync {
+    fn metadata(&self) -> Result<SourceMetadata>;
+    /// Pass each tweet to `on_tweet` as soon as it is read
+    fn read_tweets(&self, on_tweet: &mut dyn FnMut(Tweet)) -> Result<()>;
+}
+
+/// Kind of export a file of posts comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// `tweets.js` of a Twitter archive, the root folder of an archive with a `data/manifest.js`,
+    /// or a plain JSON array of tweets
+    TwitterArchive,
+    /// `data/js/tweets` of the Grailbird archives exported until 2018, or one of its `YYYY_MM.js`
+    GrailbirdArchive,
+    /// `outbox.json` of a Mastodon export
+    MastodonOutbox,
+    /// Repository export (`.car`) of a Bluesky account
+    BlueskyRepo,
+    /// `threads_and_replies.json` of an Instagram data download
+    ThreadsPosts,
+}
+impl InputFormat {
+    /// Tell the kind of export from the name of the file
+    pub fn detect(path: &Path) -> Self {
+        if path.extension().and_then(|ext| ext.to_str()) == Some(bluesky::CAR_EXTENSION) {
+            return InputFormat::BlueskyRepo;
+        }
+        if path.is_dir() && ArchiveManifest::find(path).is_some() {
+            return InputFormat::TwitterArchive;
+        }
+        if path.is_dir() || is_grailbird_file(path) {
+            return InputFormat::GrailbirdArchive;
+        }
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some(mastodon::OUTBOX_FILE_NAME) => InputFormat::MastodonOutbox,
+            Some(threads::POSTS_FILE_NAME) => InputFormat::ThreadsPosts,
+            _ => InputFormat::TwitterArchive,
+        }
+    }
+}
+
+/// A file of any of the supported exports, the kind of which is told from its name
+#[derive(Debug, Clone)]
+pub struct InputFile {
+    path: PathBuf,
+    format: InputFormat,
+}
+impl InputFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let format = InputFormat::detect(&path);
+        Self { path, format }
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn format(&self) -> InputFormat {
+        self.format
+    }
+}
+impl TweetSource for InputFile {
+    /// The account is read from the `account.js`, the Spaces and the Moments from the
+    /// `spaces-metadata.js` and the `moment.js`, and the media are in the `tweets_media` folder
+    /// next to the file, as in the Twitter archive, or wherever the `manifest.js` of the archive
+    /// puts them
+    fn metadata(&self) -> Result<SourceMetadata> {
+        let manifest = self.manifest()?;
+        let manifest = manifest.as_ref();
+        let account = match self
+            .archive_files(manifest, archive::ACCOUNT, account::ACCOUNT_FILE_NAME)
+            .first()
+        {
+            Some(account_file_path) => Some(Account::load(account_file_path)?),
+            None => None,
+        };
+        let media_dir = manifest
+            .and_then(|manifest| manifest.media_dir(archive::TWEETS_MEDIA))
+            .or_else(|| {
+                self.path
+                    .parent()
+                    .map(|dir| dir.join(joplin::MEDIA_DIR))
+                    .filter(|dir| dir.is_dir())
+            });
+        let mut spaces = Vec::new();
+        for path in self.archive_files(manifest, archive::SPACES, spaces::SPACES_FILE_NAME) {
+            spaces.extend(Space::load(&path)?);
+        }
+        let mut moments = Vec::new();
+        for path in self.archive_files(manifest, archive::MOMENTS, moments::MOMENTS_FILE_NAME) {
+            moments.extend(Moment::load(&path)?);
+        }
+        Ok(SourceMetadata {
+            name: self.path.display().to_string(),
+            account,
+            media_dir,
+            spaces,
+            moments,
+        })
+    }
+    /// The tweets of the `twitter-circle-tweet.js` next to the file are marked as posted to the
+    /// Circle, and the ones missing from the file are added. So are the tweets of the
+    /// `deleted-tweets.js`, marked as deleted.
+    ///
+    /// The first file of the tweets of an archive with a `manifest.js` stands for all the parts
+    /// the manifest lists, `tweets-part1.js` and so on.
+    fn read_tweets(&self, on_tweet: &mut dyn FnMut(Tweet)) -> Result<()> {
+        let manifest = self.manifest()?;
+        let manifest = manifest.as_ref();
+        let mut circle_tweets = HashMap::new();
+        for circle_file_path in
+            self.archive_files(manifest, archive::CIRCLE_TWEETS, CIRCLE_TWEETS_FILE_NAME)
+        {
+            load_tweets(&circle_file_path, |tweet| {
+                circle_tweets.insert(tweet.id().to_string(), tweet);
+            })?;
+        }
+        let parts = manifest
+            .map(|manifest| manifest.files(archive::TWEETS))
+            .filter(|parts| self.path.is_dir() || parts.first() == Some(&self.path))
+            .unwrap_or_else(|| vec![self.path.clone()]);
+        let mut count = 0;
+        let mut on_posted_tweet = |tweet: Tweet| {
+            count += 1;
+            match circle_tweets.remove(tweet.id()) {
+                Some(_) => on_tweet(tweet.with_audience(Audience::Circle)),
+                None => on_tweet(tweet),
+            }
+        };
+        for part in parts.iter() {
+            load_posts(part, &mut on_posted_tweet)?;
+        }
+        if let Some(expected) = manifest
+            .filter(|_| parts.len() > 1 || self.path.is_dir())
+            .and_then(|manifest| manifest.count(archive::TWEETS))
+        {
+            if count != expected {
+                warn!(
+                    "{}: read {} tweets where the manifest of the archive lists {}, some parts may be missing",
+                    self.path.display(),
+                    count,
+                    expected
+                );
+            }
+        }
+        let mut remaining = circle_tweets.into_values().collect::<Vec<Tweet>>();
+        remaining.sort_by_key(|tweet| tweet.created_at());
+        for tweet in remaining {
+            on_tweet(tweet.with_audience(Audience::Circle));
+        }
+        for deleted_file_path in
+            self.archive_files(manifest, archive::DELETED_TWEETS, DELETED_TWEETS_FILE_NAME)
+        {
+            load_tweets(&deleted_file_path, |tweet| on_tweet(tweet.into_deleted()))?;
+        }
+        Ok(())
+    }
+}
+impl InputFile {
+    /// The manifest of the archive of the file, if it has one
+    fn manifest(&self) -> Result<Option<ArchiveManifest>> {
+        if self.format != InputFormat::TwitterArchive {
+            return Ok(None);
+        }
+        ArchiveManifest::find(&self.path)
+            .map(|manifest_path| ArchiveManifest::load(&manifest_path))
+            .transpose()
+    }
+    /// The other files of the archive of a type of data, as listed by the manifest, or else the
+    /// file of the name next to the file
+    fn archive_files(
+        &self,
+        manifest: Option<&ArchiveManifest>,
+        data_type: &str,
+        file_name: &str,
+    ) -> Vec<PathBuf> {
+        let mut files = manifest
+            .map(|manifest| manifest.files(data_type))
+            .unwrap_or_default();
+        if files.is_empty() {
+            files.extend(self.path.parent().map(|dir| dir.join(file_name)));
+        }
+        files.retain(|path| path.is_file() && *path != self.path);
+        files
+    }
+}
+
+/// Tweets already in memory, e.g. built by another tool or a test
+impl TweetSource for Vec<Tweet> {
+    fn metadata(&self) -> Result<SourceMetadata> {
+        Ok(SourceMetadata {
+            name: "memory".to_string(),
+            ..Default::default()
+        })
+    }
+    fn read_tweets(&self, on_tweet: &mut dyn FnMut(Tweet)) -> Result<()> {
+        self.iter().cloned().for_each(on_tweet);
+        Ok(())
+    }
+}
+
+/// Load the posts of a file of any of the supported exports as tweets
+///
+/// Each tweet is passed to `on_tweet` as soon as it is parsed.
+pub fn load_posts<F: FnMut(Tweet)>(path: &Path, on_tweet: F) -> Result<()> {
+    match InputFormat::detect(path) {
+        InputFormat::TwitterArchive => load_tweets(path, on_tweet),
+        InputFormat::GrailbirdArchive => load_grailbird(path, on_tweet),
+        InputFormat::MastodonOutbox => mastodon::load_outbox(path, on_tweet),
+        InputFormat::BlueskyRepo => bluesky::load_repo(path, on_tweet),
+        InputFormat::ThreadsPosts => threads::load_posts(path, on_tweet),
+    }
+}
+
+/// Whether the file is a `YYYY_MM.js` of the months of a Grailbird archive
+fn is_grailbird_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(".js"))
+        .is_some_and(|stem| {
+            stem.len() == 7
+                && stem
+                    .char_indices()
+                    .all(|(i, c)| if i == 4 { c == '_' } else { c.is_ascii_digit() })
+        })
+}
+
+/// Load the tweets of a month of a Grailbird archive, or of all the months in its folder in order
+fn load_grailbird<F: FnMut(Tweet)>(path: &Path, mut on_tweet: F) -> Result<()> {
+    if !path.is_dir() {
+        return load_tweets(path, on_tweet);
+    }
+    let input_error = |source| Error::Input {
+        path: path.display().to_string(),
+        source,
+    };
+    let mut months = std::fs::read_dir(path)
+        .map_err(input_error)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()
+        .map_err(input_error)?;
+    months.retain(|month| is_grailbird_file(month));
+    months.sort();
+    for month in months {
+        load_tweets(&month, &mut on_tweet)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputFile, InputFormat, TweetSource};
+    use crate::tweet::Audience;
+    use std::path::Path;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            InputFormat::detect(Path::new("archive/data/tweets.js")),
+            InputFormat::TwitterArchive
+        );
+        assert_eq!(
+            InputFormat::detect(Path::new("mastodon/outbox.json")),
+            InputFormat::MastodonOutbox
+        );
+        assert_eq!(
+            InputFormat::detect(Path::new("repo.car")),
+            InputFormat::BlueskyRepo
+        );
+        assert_eq!(
+            InputFormat::detect(Path::new("threads/threads_and_replies.json")),
+            InputFormat::ThreadsPosts
+        );
+        assert_eq!(
+            InputFormat::detect(Path::new("archive/data/js/tweets/2013_01.js")),
+            InputFormat::GrailbirdArchive
+        );
+    }
+    #[test]
+    fn test_read_tweets_with_circle_and_deleted() {
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-circle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tweet = |id: &str| {
+            format!(
+                r#"{{"tweet": {{"id_str": "{}", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "{}"}}}}"#,
+                id, id
+            )
+        };
+        std::fs::write(
+            dir.join("tweets.js"),
+            format!("window.YTD.tweets.part0 = [{}, {}]", tweet("1"), tweet("2")),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("twitter-circle-tweet.js"),
+            format!(
+                "window.YTD.twitter_circle_tweet.part0 = [{}, {}]",
+                tweet("2"),
+                tweet("3")
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("deleted-tweets.js"),
+            format!("window.YTD.deleted_tweets.part0 = [{}]", tweet("4")),
+        )
+        .unwrap();
+        let mut tweets = Vec::new();
+        InputFile::new(dir.join("tweets.js"))
+            .read_tweets(&mut |tweet| {
+                tweets.push((tweet.id().to_string(), tweet.audience(), tweet.is_deleted()))
+            })
+            .unwrap();
+        assert_eq!(
+            tweets,
+            vec![
+                ("1".to_string(), Audience::Public, false),
+                ("2".to_string(), Audience::Circle, false),
+                ("3".to_string(), Audience::Circle, false),
+                ("4".to_string(), Audience::Public, true)
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_read_tweets_with_manifest() {
+        let root =
+            std::env::temp_dir().join(format!("twitter2obsidian-parts-{}", std::process::id()));
+        let data = root.join("data");
+        std::fs::create_dir_all(data.join("media")).unwrap();
+        let tweet = |id: &str| {
+            format!(
+                r#"[{{"tweet": {{"id_str": "{}", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "{}"}}}}]"#,
+                id, id
+            )
+        };
+        std::fs::write(data.join("tweets.js"), tweet("1")).unwrap();
+        std::fs::write(data.join("tweets-part1.js"), tweet("2")).unwrap();
+        std::fs::write(data.join("old-tweets.js"), tweet("3")).unwrap();
+        std::fs::write(
+            data.join("manifest.js"),
+            r#"window.__THAR_CONFIG = {"dataTypes": {
+                "tweets": {"files": [{"fileName": "data/tweets.js", "count": "1"}, {"fileName": "data/tweets-part1.js", "count": "1"}]},
+                "tweetsMedia": {"mediaDirectory": "data/media"},
+                "deletedTweets": {"files": [{"fileName": "data/old-tweets.js", "count": "1"}]}
+            }}"#,
+        )
+        .unwrap();
+        assert_eq!(InputFormat::detect(&root), InputFormat::TwitterArchive);
+        for path in [root.clone(), data.join("tweets.js")] {
+            let input = InputFile::new(path);
+            let mut ids = Vec::new();
+            input
+                .read_tweets(&mut |tweet| ids.push(tweet.id().to_string()))
+                .unwrap();
+            assert_eq!(ids, vec!["1", "2", "3"]);
+            assert_eq!(
+                input.metadata().unwrap().media_dir,
+                Some(data.join("media"))
+            );
+        }
+        // A part given on its own is read alone
+        let mut ids = Vec::new();
+        InputFile::new(data.join("tweets-part1.js"))
+            .read_tweets(&mut |tweet| ids.push(tweet.id().to_string()))
+            .unwrap();
+        assert_eq!(ids, vec!["2", "3"]);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,310 @@
+use crate::templates::{layout::Layout, Formatter};
+use crate::tweet::Tweet;
+use chrono::{DateTime, Datelike, FixedOffset, SecondsFormat, Utc};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Folder of the archive the media of the tweets are in, next to `tweets.js`
+pub const MEDIA_DIR: &str = "tweets_media";
+
+/// Size of the blocks of a tar file
+const BLOCK_SIZE: usize = 512;
+
+/// Kinds of the items in a Joplin export, serialized as `type_`
+const TYPE_NOTE: u8 = 1;
+const TYPE_FOLDER: u8 = 2;
+const TYPE_RESOURCE: u8 = 4;
+
+/// Write the tweets as a Joplin export (JEX), a notebook per year holding a note per month
+///
+/// The tweets must be in chronological order. The media found in `media_dir` are attached to
+/// the notes as resources.
+pub fn write_jex<W: Write>(
+    tweets: &[&Tweet],
+    formatter: &Formatter,
+    layout: &Layout,
+    media_dir: Option<&Path>,
+    writer: W,
+) -> io::Result<()> {
+    // Joplin has no notes to link the truncated tweets to
+    let layout = Layout {
+        truncate_at: None,
+        ..layout.clone()
+    };
+    let mut tar = TarWriter::new(writer);
+    let now = Utc::now().fixed_offset();
+    for year in tweets.chunk_by(|a, b| a.created_at().year() == b.created_at().year()) {
+        let title = year[0].created_at().year().to_string();
+        let folder_id = item_id(&format!("folder/{}", title));
+        let folder = serialize_item(
+            &title,
+            None,
+            &[
+                ("id", folder_id.clone()),
+                ("created_time", timestamp(&now)),
+                ("updated_time", timestamp(&now)),
+                ("parent_id", String::new()),
+            ],
+            TYPE_FOLDER,
+        );
+        tar.append(&format!("{}.md", folder_id), folder.as_bytes(), &now)?;
+
+        for month in year.chunk_by(|a, b| a.created_at().month() == b.created_at().month()) {
+            let title = month[0].created_at().format("%Y-%m Tweets").to_string();
+            let mut body = String::new();
+            for tweet in month.iter() {
+                body.push_str(layout.render_tweet(tweet, formatter).trim_end());
+                body.push('\n');
+                for media_path in media_dir
+                    .into_iter()
+                    .flat_map(|dir| media_files(tweet, dir))
+                {
+                    let Ok(mut file) = File::open(&media_path) else {
+                        continue;
+                    };
+                    let size = file.metadata()?.len();
+                    let extension = media_path
+                        .extension()
+                        .map_or(String::new(), |ext| ext.to_string_lossy().to_lowercase());
+                    let file_name = media_path
+                        .file_name()
+                        .map_or(String::new(), |name| name.to_string_lossy().to_string());
+                    let resource_id = item_id(&format!("resource/{}", file_name));
+                    let resource = serialize_item(
+                        &file_name,
+                        None,
+                        &[
+                            ("id", resource_id.clone()),
+                            ("mime", mime_type(&extension).to_string()),
+                            ("filename", file_name.clone()),
+                            ("created_time", timestamp(&tweet.created_at())),
+                            ("updated_time", timestamp(&tweet.created_at())),
+                            ("file_extension", extension.clone()),
+                            ("size", size.to_string()),
+                        ],
+                        TYPE_RESOURCE,
+                    );
+                    tar.append(&format!("{}.md", resource_id), resource.as_bytes(), &now)?;
+                    tar.append_reader(
+                        &format!("resources/{}.{}", resource_id, extension),
+                        &mut file,
+                        size,
+                        &tweet.created_at(),
+                    )?;
+                    body.push_str(&format!("  ![{}](:/{})\n", file_name, resource_id));
+                }
+            }
+            let note_id = item_id(&format!("note/{}", title));
+            let note = serialize_item(
+                &title,
+                Some(&body),
+                &[
+                    ("id", note_id.clone()),
+                    ("parent_id", folder_id.clone()),
+                    ("created_time", timestamp(&month[0].created_at())),
+                    ("updated_time", timestamp(&now)),
+                    ("markup_language", "1".to_string()),
+                ],
+                TYPE_NOTE,
+            );
+            tar.append(&format!("{}.md", note_id), note.as_bytes(), &now)?;
+        }
+    }
+    tar.finish()
+}
+
+/// Files of the media of the tweet in the archive, named `<tweet id>-<file name>`
+fn media_files(tweet: &Tweet, media_dir: &Path) -> Vec<std::path::PathBuf> {
+    tweet
+        .entities()
+        .media
+        .iter()
+        .filter_map(|media| media.archive_path(tweet.id(), media_dir))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Serialize an item as Joplin does: the title, the body and the properties, separated by blank lines
+fn serialize_item(
+    title: &str,
+    body: Option<&str>,
+    properties: &[(&str, String)],
+    item_type: u8,
+) -> String {
+    let mut item = format!("{}\n\n", title);
+    if let Some(body) = body {
+        item.push_str(body.trim_end());
+        item.push_str("\n\n");
+    }
+    for (key, value) in properties.iter() {
+        item.push_str(&format!("{}: {}\n", key, value));
+    }
+    item.push_str(&format!("type_: {}", item_type));
+    item
+}
+
+/// Id of an item, 32 hex digits derived from `seed` so that the items are updated on reimport
+fn item_id(seed: &str) -> String {
+    Sha256::digest(seed.as_bytes())
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn timestamp(date: &DateTime<FixedOffset>) -> String {
+    date.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn mime_type(extension: &str) -> &'static str {
+    match extension {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Writer of an uncompressed ustar archive, which is what JEX files are
+struct TarWriter<W: Write> {
+    writer: W,
+}
+impl<W: Write> TarWriter<W> {
+    fn new(writer: W) -> Self {
+        Self { writer }
+    }
+    /// Append a regular file; `path` must fit in the 100 bytes of the name field
+    fn append(
+        &mut self,
+        path: &str,
+        data: &[u8],
+        modified: &DateTime<FixedOffset>,
+    ) -> io::Result<()> {
+        self.append_reader(path, &mut &data[..], data.len() as u64, modified)
+    }
+    /// Append a regular file of `size` bytes copied from `reader`, such as a file of the disk
+    fn append_reader(
+        &mut self,
+        path: &str,
+        reader: &mut dyn Read,
+        size: u64,
+        modified: &DateTime<FixedOffset>,
+    ) -> io::Result<()> {
+        if path.len() > 100 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the path is too long for a tar file: {}", path),
+            ));
+        }
+        let mut header = [0u8; BLOCK_SIZE];
+        header[..path.len()].copy_from_slice(path.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[108..116].copy_from_slice(b"0000000\0");
+        header[116..124].copy_from_slice(b"0000000\0");
+        header[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+        let mtime = modified.timestamp().max(0);
+        header[136..148].copy_from_slice(format!("{:011o}\0", mtime).as_bytes());
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        // The checksum is computed with its own field filled with spaces
+        header[148..156].copy_from_slice(b"        ");
+        let checksum = header.iter().map(|&b| b as u32).sum::<u32>();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+        self.writer.write_all(&header)?;
+        let copied = io::copy(&mut reader.take(size), &mut self.writer)?;
+        if copied != size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{} was cut short while written to the tar file", path),
+            ));
+        }
+        let padding = (BLOCK_SIZE - (size % BLOCK_SIZE as u64) as usize) % BLOCK_SIZE;
+        self.writer.write_all(&vec![0u8; padding])
+    }
+    /// Write the two empty blocks marking the end of the archive
+    fn finish(mut self) -> io::Result<()> {
+        self.writer.write_all(&[0u8; BLOCK_SIZE * 2])?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{serialize_item, write_jex, TarWriter, BLOCK_SIZE};
+    use crate::templates::{layout::Layout, Formatter};
+    use crate::tweet::parse_tweets;
+
+    #[test]
+    fn test_tar_writer() {
+        let mut tar = Vec::new();
+        let mut writer = TarWriter::new(&mut tar);
+        writer
+            .append("a.md", b"hello", &chrono::Utc::now().fixed_offset())
+            .unwrap();
+        writer.finish().unwrap();
+        assert_eq!(tar.len(), BLOCK_SIZE * 4);
+        assert_eq!(&tar[..4], b"a.md");
+        assert_eq!(&tar[124..136], b"00000000005\0");
+        assert_eq!(&tar[BLOCK_SIZE..BLOCK_SIZE + 5], b"hello");
+        let checksum = tar[..BLOCK_SIZE]
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| {
+                if (148..156).contains(&i) {
+                    32
+                } else {
+                    b as u32
+                }
+            })
+            .sum::<u32>();
+        assert_eq!(&tar[148..156], format!("{:06o}\0 ", checksum).as_bytes());
+    }
+    #[test]
+    fn test_serialize_item() {
+        assert_eq!(
+            serialize_item(
+                "2023-03 Tweets",
+                Some("- a\n"),
+                &[("id", "x".to_string())],
+                1
+            ),
+            "2023-03 Tweets\n\n- a\n\nid: x\ntype_: 1"
+        );
+    }
+    #[test]
+    fn test_write_jex() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"created_at": "Sat Dec 31 04:12:48 +0000 2022", "full_text": "a"}},
+                {"tweet": {"created_at": "Sun Jan 01 05:00:00 +0000 2023", "full_text": "b"}},
+                {"tweet": {"created_at": "Wed Feb 01 05:00:00 +0000 2023", "full_text": "c"}}
+            ]"#,
+        )
+        .unwrap();
+        let tweets = tweets
+            .into_iter()
+            .map(|tw| tw.in_timezone(&"UTC".parse().unwrap()))
+            .collect::<Vec<_>>();
+        let mut jex = Vec::new();
+        write_jex(
+            &tweets.iter().collect::<Vec<_>>(),
+            &Formatter::new(),
+            &Layout::default(),
+            None,
+            &mut jex,
+        )
+        .unwrap();
+        let jex = String::from_utf8_lossy(&jex);
+        // Two notebooks and three notes
+        assert_eq!(jex.matches("type_: 2").count(), 2);
+        assert_eq!(jex.matches("type_: 1").count(), 3);
+        assert!(jex.contains("2023-01 Tweets\n\n- 2023-01-01 05:00:00: b\n\n"));
+    }
+}
@@ -0,0 +1,109 @@
+//! The yearly journals, a Markdown file per year to which each run appends the new tweets
+//!
+//! The IDs of the tweets appended are recorded in a ledger next to the journals, so that a run on
+//! a newer archive appends only the tweets the journals do not have yet, and the entries edited
+//! in the journals are left as they are.
+use crate::error::{Error, Result};
+use crate::templates::layout::Layout;
+use crate::templates::Formatter;
+use crate::tweet::Tweet;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// File name of the ledger of the tweets appended, in the output directory
+pub const LEDGER_FILE_NAME: &str = "journal-ledger.json";
+
+/// The tweets appended to the journals in the earlier runs
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalLedger {
+    /// IDs of the tweets
+    #[serde(default)]
+    pub appended: BTreeSet<String>,
+}
+impl JournalLedger {
+    /// Load the ledger, or start an empty one when the file does not exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read(path) {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(source) => Err(Error::Input {
+                path: path.display().to_string(),
+                source,
+            }),
+        }
+    }
+    /// Whether the tweet was appended already
+    pub fn contains(&self, id: &str) -> bool {
+        self.appended.contains(id)
+    }
+    /// Record the tweets as appended
+    pub fn record(&mut self, tweets: &[&Tweet]) {
+        self.appended
+            .extend(tweets.iter().map(|tweet| tweet.id().to_string()));
+    }
+}
+
+/// File name of the journal of the year
+pub fn journal_file_name(year: i32) -> String {
+    format!("journal_{}.md", year)
+}
+
+/// The heading starting the journal of the year
+pub fn render_title(year: i32) -> String {
+    format!("# {}年のツイート\n", year)
+}
+
+/// Render the tweets, oldest first, as entries under the heading of their day
+///
+/// A day already in the journal gets a heading of its own again, below the last entries.
+pub fn render_entries(tweets: &[&Tweet], formatter: &Formatter, layout: &Layout) -> String {
+    // The date is in the heading of the day
+    let layout = Layout {
+        day_headings: true,
+        ..layout.clone()
+    };
+    let mut rendered = String::new();
+    for day in tweets.chunk_by(|a, b| a.created_at().date_naive() == b.created_at().date_naive()) {
+        rendered.push_str(&format!("\n## {}\n\n", layout.day_heading(day[0])));
+        for tweet in day {
+            rendered.push_str(&layout.render_tweet(tweet, formatter));
+            rendered.push('\n');
+        }
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_entries, render_title, JournalLedger};
+    use crate::templates::{layout::Layout, Formatter};
+    use crate::tweet::parse_tweets;
+
+    #[test]
+    fn test_render_entries() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "おはよう"}},
+                {"tweet": {"id_str": "2", "created_at": "Sat Mar 11 05:00:00 +0000 2023", "full_text": "@hoge それな"}},
+                {"tweet": {"id_str": "3", "created_at": "Sun Mar 12 05:00:00 +0000 2023", "full_text": "おやすみ"}}
+            ]"#,
+        )
+        .unwrap();
+        let tweets = tweets
+            .into_iter()
+            .map(|tw| tw.in_timezone(&"UTC".parse().unwrap()))
+            .collect::<Vec<_>>();
+        let tweets = tweets.iter().collect::<Vec<_>>();
+        let journal =
+            render_title(2023) + &render_entries(&tweets, &Formatter::new(), &Layout::default());
+        assert_eq!(
+            journal,
+            "# 2023年のツイート\n\n## 2023-03-11 (Sat)\n\n- 04:12:48: おはよう\n- 05:00:00: [[@hoge]] それな\n\n## 2023-03-12 (Sun)\n\n- 05:00:00: おやすみ\n"
+        );
+        let mut ledger = JournalLedger::default();
+        ledger.record(&tweets[..2]);
+        assert!(ledger.contains("2"));
+        assert!(!ledger.contains("3"));
+    }
+}
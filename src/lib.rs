@@ -1,2 +1,57 @@
+pub mod account;
+#[cfg(feature = "native")]
+pub mod api;
+pub mod archive;
+pub mod bluesky;
+#[cfg(feature = "cli")]
+pub mod browse;
+#[cfg(feature = "cli")]
+pub mod completions;
+pub mod config;
+pub mod converter;
+pub mod dayone;
+pub mod edits;
+pub mod error;
+pub mod event;
+pub mod exit_code;
+pub mod export;
+pub mod filter;
+pub mod fixture;
+pub mod granularity;
+#[cfg(feature = "native")]
+pub mod hook;
+pub mod ignore;
+#[cfg(feature = "native")]
+pub mod image;
+pub mod input;
+pub mod joplin;
+pub mod journal;
+pub mod links;
+pub mod locale;
+pub mod mastodon;
+#[cfg(feature = "native")]
+pub mod media;
+pub mod moments;
+pub mod normalize;
+#[cfg(feature = "native")]
+pub mod obsidian;
+pub mod parallel;
+pub mod people;
+pub mod report;
+#[cfg(feature = "native")]
+pub mod runs;
+pub mod sampling;
+pub mod search;
+pub mod sink;
+pub mod spaces;
+#[cfg(feature = "native")]
+pub mod spill;
+pub mod stats;
 pub mod templates;
+pub mod threads;
+pub mod timezone;
+pub mod tolerant;
 pub mod tweet;
+#[cfg(feature = "native")]
+pub mod watch;
+pub mod wrapper;
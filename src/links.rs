@@ -0,0 +1,238 @@
+//! The linked pages: their titles, fetched to render the links of the tweets as `[title](url)`,
+//! and whether they are still there, to point the dead ones at the Wayback Machine
+//!
+//! The titles and the checks are cached in the folder of the notes, so that the next runs fetch
+//! only the links of the new tweets. The links whose page has no title or could not be fetched
+//! are cached too, and stay as they are.
+use crate::error::{Error, Result};
+#[cfg(feature = "native")]
+use crate::media;
+use crate::tweet::decode_html_entities;
+use chrono::{DateTime, FixedOffset, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::OnceLock;
+#[cfg(feature = "native")]
+use std::time::Duration;
+
+/// File name of the cache of the titles in the folder of the notes
+pub const CACHE_FILE_NAME: &str = "link-titles.json";
+/// File name of the cache of the checks of the links in the folder of the notes
+pub const CHECKS_FILE_NAME: &str = "link-checks.json";
+/// Exit code of curl when the host cannot be resolved
+#[cfg(feature = "native")]
+const CURL_RESOLVE_ERROR: i32 = 6;
+/// Bytes of a page requested at most, enough for the `<head>` of most pages
+#[cfg(feature = "native")]
+const MAX_PAGE_SIZE: usize = 256 * 1024;
+/// Hosts of the links to tweets, whose pages have no useful title
+const SKIPPED_HOSTS: &[&str] = &["twitter.com", "x.com", "t.co", "mobile.twitter.com"];
+
+/// The titles of the links fetched in the earlier runs, by URL
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkTitles {
+    /// The title of each link, `None` when there was none
+    #[serde(default)]
+    pub titles: BTreeMap<String, Option<String>>,
+}
+impl LinkTitles {
+    /// Load the cache, or start an empty one when the file does not exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read(path) {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(source) => Err(Error::Input {
+                path: path.display().to_string(),
+                source,
+            }),
+        }
+    }
+    /// Whether the link was fetched already
+    pub fn contains(&self, url: &str) -> bool {
+        self.titles.contains_key(url)
+    }
+    /// The titles found, by URL
+    pub fn found(&self) -> HashMap<String, String> {
+        self.titles
+            .iter()
+            .filter_map(|(url, title)| Some((url.clone(), title.clone()?)))
+            .collect()
+    }
+}
+
+/// The links found dead or alive in the earlier runs, by URL
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkChecks {
+    /// Whether each link is dead
+    #[serde(default)]
+    pub dead: BTreeMap<String, bool>,
+}
+impl LinkChecks {
+    /// Load the cache, or start an empty one when the file does not exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read(path) {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(source) => Err(Error::Input {
+                path: path.display().to_string(),
+                source,
+            }),
+        }
+    }
+}
+
+/// Whether the link is worth fetching: a web page other than a tweet
+pub fn is_web_page(url: &str) -> bool {
+    let Some(rest) = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    else {
+        return false;
+    };
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    !SKIPPED_HOSTS.contains(&host.to_ascii_lowercase().as_str())
+}
+
+/// Fetch the page of the link with the `curl` command and return its title, giving up after
+/// `timeout`
+#[cfg(feature = "native")]
+pub fn fetch_title(url: &str, timeout: Duration) -> Result<Option<String>> {
+    let output = media::run_curl(
+        url,
+        media::curl(url, timeout)
+            .args([
+                "--compressed",
+                "--range",
+                &format!("0-{}", MAX_PAGE_SIZE - 1),
+            ])
+            .args(["--header", "Accept: text/html"]),
+    )?;
+    if !output.status.success() {
+        return Err(media::curl_error(url, &output));
+    }
+    let page = &output.stdout[..output.stdout.len().min(MAX_PAGE_SIZE)];
+    Ok(parse_title(&String::from_utf8_lossy(page)))
+}
+
+/// Whether the link is dead, checked with a HEAD request by the `curl` command
+///
+/// A link is dead when the server answers it is not found or gone, or when its host no longer
+/// resolves, which is not cached since being offline looks the same.
+#[cfg(feature = "native")]
+pub fn check_link(url: &str, timeout: Duration) -> Result<LinkCheck> {
+    let output = media::run_curl(
+        url,
+        media::curl(url, timeout).args(["--head", "--write-out", "\n%{http_code}"]),
+    )?;
+    if output.status.code() == Some(CURL_RESOLVE_ERROR) {
+        return Ok(LinkCheck::Unresolved);
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match stdout.lines().last().map(str::trim) {
+        Some("404" | "410") => Ok(LinkCheck::Dead),
+        _ if output.status.success() => Ok(LinkCheck::Alive),
+        // Servers refusing HEAD requests or failing for a while are not told dead
+        Some(code) if code.starts_with(['4', '5']) => Ok(LinkCheck::Alive),
+        _ => Err(media::curl_error(url, &output)),
+    }
+}
+
+/// The outcome of a check of a link
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkCheck {
+    Alive,
+    Dead,
+    /// Its host does not resolve
+    Unresolved,
+}
+
+/// The link to the snapshot of the page closest to the time of the tweet in the Wayback Machine
+pub fn wayback_url(url: &str, time: DateTime<FixedOffset>) -> String {
+    format!(
+        "https://web.archive.org/web/{}/{}",
+        time.with_timezone(&Utc).format("%Y%m%d%H%M%S"),
+        url
+    )
+}
+
+/// The `<title>` of an HTML page, with the entities decoded and the whitespace collapsed
+pub fn parse_title(html: &str) -> Option<String> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+    let title = decode_html_entities(re.captures(html)?.get(1)?.as_str());
+    let title = title.split_whitespace().collect::<Vec<&str>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "native")]
+    use super::{check_link, fetch_title, LinkCheck, LinkTitles};
+    use super::{is_web_page, parse_title, wayback_url};
+    use chrono::DateTime;
+    #[cfg(feature = "native")]
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_title() {
+        assert_eq!(
+            parse_title("<html><head><TITLE lang=\"en\">\n  Rust &amp; Obsidian\n</TITLE>"),
+            Some("Rust & Obsidian".to_string())
+        );
+        assert_eq!(parse_title("<title> </title>"), None);
+        assert_eq!(parse_title("<p>no title</p>"), None);
+    }
+    #[test]
+    fn test_is_web_page() {
+        assert!(is_web_page("https://example.com/a"));
+        assert!(!is_web_page("https://twitter.com/hoge/status/1"));
+        assert!(!is_web_page("https://www.x.com/hoge"));
+        assert!(!is_web_page("ftp://example.com"));
+    }
+    #[test]
+    fn test_wayback_url() {
+        let time = DateTime::parse_from_rfc3339("2013-03-11T13:12:48+09:00").unwrap();
+        assert_eq!(
+            wayback_url("https://example.com/a?b=c", time),
+            "https://web.archive.org/web/20130311041248/https://example.com/a?b=c"
+        );
+    }
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_fetch_title() {
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-links-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let page = dir.join("page.html");
+        std::fs::write(&page, "<title>A page</title>").unwrap();
+        let url = format!("file://{}", page.display());
+        let timeout = Duration::from_secs(10);
+        assert_eq!(
+            fetch_title(&url, timeout).unwrap(),
+            Some("A page".to_string())
+        );
+        assert!(fetch_title("file:///nonexistent", timeout).is_err());
+
+        let cache_path = dir.join(super::CACHE_FILE_NAME);
+        let mut cache = LinkTitles::load(&cache_path).unwrap();
+        cache.titles.insert(url.clone(), Some("A page".to_string()));
+        cache
+            .titles
+            .insert("https://example.com/".to_string(), None);
+        std::fs::write(&cache_path, serde_json::to_vec(&cache).unwrap()).unwrap();
+        let cache = LinkTitles::load(&cache_path).unwrap();
+        assert!(cache.contains("https://example.com/"));
+        assert_eq!(cache.found().len(), 1);
+
+        assert_eq!(check_link(&url, timeout).unwrap(), LinkCheck::Alive);
+        assert_eq!(
+            check_link("http://nonexistent.invalid/", timeout).unwrap(),
+            LinkCheck::Unresolved
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,49 @@
+use chrono::Weekday;
+use serde::{Deserialize, Serialize};
+
+/// Language of the names of the weekdays and the months in the notes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum Locale {
+    /// Sat, Mar
+    #[default]
+    En,
+    /// 土, 3月
+    Ja,
+}
+impl Locale {
+    /// Short name of the weekday
+    pub fn weekday(&self, weekday: Weekday) -> &'static str {
+        let names = match self {
+            Locale::En => ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+            Locale::Ja => ["月", "火", "水", "木", "金", "土", "日"],
+        };
+        names[weekday.num_days_from_monday() as usize]
+    }
+    /// Short name of the month, from 1 for January
+    pub fn month(&self, month: u32) -> String {
+        const NAMES: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        match self {
+            Locale::En => NAMES[month as usize - 1].to_string(),
+            Locale::Ja => format!("{}月", month),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Locale;
+    use chrono::Weekday;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(Locale::En.weekday(Weekday::Sat), "Sat");
+        assert_eq!(Locale::Ja.weekday(Weekday::Mon), "月");
+        assert_eq!(Locale::Ja.weekday(Weekday::Wed), "水");
+        assert_eq!(Locale::En.month(3), "Mar");
+        assert_eq!(Locale::Ja.month(12), "12月");
+    }
+}
@@ -1,128 +1,1018 @@
 /// A tool to convert Twitter data to Obsidian notes
-use anyhow::Result;
-use chrono::{Datelike, Months};
-use clap::Parser;
+use chrono::NaiveDate;
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum, ValueHint};
 use log::{error, info, warn};
+use regex::Regex;
 use std::{
-    collections::HashMap,
     fs::File,
-    io::{BufReader, Read},
+    io::{self, BufRead, BufWriter, IsTerminal, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+    thread,
+    time::Duration,
 };
 use twitter2obsidian::{
-    templates::monthly_tweets::{MonthlyTweetsTemplate, MonthlyTweetsTemplateInput},
-    tweet::{parse_tweets, Tweet},
+    account::{Account, ACCOUNT_FILE_NAME},
+    api::{self, ApiClient, ReferenceLookup},
+    browse::{self, BrowseCommand, Toggle},
+    completions::{self, Shell},
+    config::Config,
+    converter::{Converter, DeletedTweets},
+    error::{Error, Result},
+    event,
+    export::OutputFormat,
+    filter::{
+        end_of_day, end_of_month, parse_date, parse_month, HourRange, MonthPattern, ReplyFilter,
+        TweetFilter,
+    },
+    fixture::{self, Anonymizer},
+    granularity::Granularity,
+    ignore::IgnoreList,
+    locale::Locale,
+    obsidian::{self, Vault},
+    people::PeopleMap,
+    report::ConversionReport,
+    runs::{self, RunManifest},
+    search::IndexReader,
+    sink::{MemorySink, StdoutSink},
+    templates::{
+        layout::{SortKey, SortOrder},
+        Redaction, WaybackMode,
+    },
+    timezone::Timezone,
+    watch::{self, DropFolder},
+    wrapper::load_arrays,
 };
 
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+/// How often the folder given by --watch is checked for new archives
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Parser, Debug, Clone)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true
+)]
 struct Args {
-    #[arg(short = 'f', long, help = "Path to the JSON file of tweet data")]
-    tweets_file_path: String,
-    #[arg(short = 'o', long, help = "Path to the output directory")]
-    output_dir_path: String,
-    #[arg(short = 's', long, help = "Start month to filter the tweets (YYYY-MM)")]
-    start_month: Option<String>,
-    #[arg(short = 'e', long, help = "End month to filter the tweets (YYYY-MM)")]
-    end_month: Option<String>,
-}
-
-fn load_tweets(tweets_file_path: &str) -> Result<Vec<Tweet>> {
-    info!("Loading tweets from {}", tweets_file_path);
-    let file = match File::open(tweets_file_path) {
-        Ok(file) => file,
-        Err(e) => {
-            error!("Failed to open the file {}: {}", tweets_file_path, e,);
-            std::process::exit(1);
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(
+        short = 'f',
+        long,
+        required_unless_present = "watch",
+        value_hint = ValueHint::AnyPath,
+        help = "Path to the JSON file of tweet data, the folder of an extracted archive, a Mastodon outbox.json or a Bluesky .car (repeatable)"
+    )]
+    tweets_file_path: Vec<String>,
+    #[arg(
+        long,
+        conflicts_with = "tweets_file_path",
+        value_hint = ValueHint::DirPath,
+        help = "Folder to watch for archives (.zip, tweets.js, ...) to convert as they are placed in it"
+    )]
+    watch: Option<String>,
+    #[arg(
+        short = 'o',
+        long,
+        required_unless_present = "vault",
+        value_hint = ValueHint::DirPath,
+        help = "Path to the output directory, the folder of the vault given by --vault, or - for stdout"
+    )]
+    output_dir_path: Option<String>,
+    #[arg(
+        long,
+        value_hint = ValueHint::DirPath,
+        help = "Path to an Obsidian vault, whose folders of new notes and attachments are used"
+    )]
+    vault: Option<String>,
+    #[arg(
+        long,
+        help = "Download the media missing from the archive, such as the ones of fetched tweets, to the attachments folder"
+    )]
+    download_media: bool,
+    #[arg(
+        long,
+        help = "Fetch the titles of the linked pages to render the links as [title](url), cached in link-titles.json of the notes folder"
+    )]
+    link_titles: bool,
+    #[arg(
+        long,
+        value_enum,
+        help = "Point the links at their snapshots in the Wayback Machine: after each link, or in place of the dead ones"
+    )]
+    wayback: Option<WaybackMode>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = OutputFormat::Markdown,
+        help = "Format to convert the tweets to"
+    )]
+    format: OutputFormat,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Granularity::Month,
+        help = "Period each note collects the tweets of"
+    )]
+    granularity: Granularity,
+    #[arg(
+        long,
+        value_enum,
+        help = "Direction of the tweets within each note [default: asc by time, desc by engagement]"
+    )]
+    sort: Option<SortOrder>,
+    #[arg(
+        long,
+        value_enum,
+        help = "What the tweets are sorted by within each note"
+    )]
+    sort_by: Option<SortKey>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Language of the weekdays and the months in the day headings and the stats"
+    )]
+    locale: Option<Locale>,
+    #[arg(
+        long,
+        help = "Write the notes of each account to a subfolder named after it, with archives of several accounts"
+    )]
+    split_accounts: bool,
+    #[arg(
+        long,
+        help = "Write all the tweets to a single tweets.md with a heading per year and month"
+    )]
+    single_file: bool,
+    #[arg(
+        long,
+        help = "Screen name of the account, for the permalinks of the tweets when account.js is missing"
+    )]
+    screen_name: Option<String>,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Also collect the tweets carrying these hashtags in monthly notes per hashtag (comma separated)"
+    )]
+    hashtag_notes: Vec<String>,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Also write a note of each of the N accounts the most tweets mention or reply to"
+    )]
+    person_notes: Option<usize>,
+    #[arg(
+        short = 's',
+        long,
+        value_parser = parse_month,
+        conflicts_with = "start_date",
+        help = "Start month to filter the tweets (YYYY-MM)"
+    )]
+    start_month: Option<NaiveDate>,
+    #[arg(
+        short = 'e',
+        long,
+        value_parser = parse_month,
+        conflicts_with = "end_date",
+        help = "End month to filter the tweets (YYYY-MM)"
+    )]
+    end_month: Option<NaiveDate>,
+    #[arg(long, value_parser = parse_date, help = "Start date to filter the tweets (YYYY-MM-DD)")]
+    start_date: Option<NaiveDate>,
+    #[arg(long, value_parser = parse_date, help = "End date to filter the tweets, inclusive (YYYY-MM-DD)")]
+    end_date: Option<NaiveDate>,
+    #[arg(
+        long,
+        alias = "exclude",
+        value_delimiter = ',',
+        help = "Leave out the months, e.g. 2016-04, 2019-0[1-6] or 2016-04..2016-08, such as the ones curated by hand in the vault (comma separated)"
+    )]
+    exclude_months: Vec<MonthPattern>,
+    #[arg(
+        long,
+        default_value = "local",
+        help = "Timezone to bucket the tweets in (local, UTC, America/New_York, +09:00, ...)"
+    )]
+    timezone: Timezone,
+    #[arg(long, help = "Drop retweets from the notes and the stats")]
+    exclude_retweets: bool,
+    #[arg(
+        long,
+        help = "Exclude the tweets posted to a Twitter Circle or a community"
+    )]
+    exclude_circle: bool,
+    #[arg(long, help = "Exclude the tweets flagged as possibly sensitive")]
+    exclude_sensitive: bool,
+    #[arg(
+        long,
+        value_enum,
+        help = "Convert the tweets of deleted-tweets.js too, among the others or in notes of their own"
+    )]
+    deleted: Option<DeletedTweets>,
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "all",
+        help = "Drop replies; `others` keeps replies in your own threads"
+    )]
+    exclude_replies: Option<ReplyFilter>,
+    #[arg(long, help = "Convert only the tweets matching the regex")]
+    include_pattern: Option<Regex>,
+    #[arg(long, help = "Drop the tweets matching the regex (repeatable)")]
+    exclude_pattern: Vec<Regex>,
+    #[arg(
+        long,
+        help = "Convert only the tweets carrying the hashtag (repeatable)"
+    )]
+    hashtag: Vec<String>,
+    #[arg(
+        long,
+        help = "Convert only the tweets mentioning or replying to the user (repeatable)"
+    )]
+    with_user: Vec<String>,
+    #[arg(
+        long,
+        help = "Convert only the tweets in the language, e.g. ja or en (repeatable)"
+    )]
+    lang: Vec<String>,
+    #[arg(
+        long,
+        help = "Drop the tweets shorter than N characters, not counting URLs"
+    )]
+    min_chars: Option<usize>,
+    #[arg(
+        long,
+        help = "Convert only the tweets posted in the hours, end exclusive (e.g. 22-06)"
+    )]
+    hours: Option<HourRange>,
+    #[arg(short = 'c', long, value_hint = ValueHint::FilePath, help = "Path to the JSON configuration file")]
+    config: Option<String>,
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        help = "Path to a list of accounts and hashtags to drop or not to link"
+    )]
+    ignore_file: Option<String>,
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        help = "Path to a list of accounts and the notes of the people to link their mentions to"
+    )]
+    people_file: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        help = "Replace the parts of the tweets with placeholders (comma separated)"
+    )]
+    redact: Vec<Redaction>,
+    #[arg(
+        long,
+        help = "Keep the Markdown syntax in the tweets instead of escaping it"
+    )]
+    keep_markdown: bool,
+    #[arg(long, help = "Convert only the N oldest tweets matching the filters")]
+    limit: Option<usize>,
+    #[arg(
+        long,
+        conflicts_with = "limit",
+        help = "Convert only N tweets chosen at random from those matching the filters"
+    )]
+    sample: Option<usize>,
+    #[arg(long, default_value_t = 0, help = "Seed for --sample")]
+    seed: u64,
+    #[arg(
+        short = 'j',
+        long,
+        help = "Number of files parsed and notes written concurrently, 1 for a single thread [default: number of CPUs]"
+    )]
+    jobs: Option<usize>,
+    #[arg(
+        long,
+        help = "Split the notes of the periods with more tweets than this into tweets_YYYYMM_1.md, tweets_YYYYMM_2.md, ... linking to each other"
+    )]
+    max_tweets_per_file: Option<NonZeroUsize>,
+    #[arg(
+        long,
+        help = "Convert a period at a time, keeping the tweets of the others in temporary files, for archives too large for the memory"
+    )]
+    low_memory: bool,
+    #[arg(long, value_hint = ValueHint::FilePath, help = "Path to write the conversion report as a Markdown note")]
+    report_path: Option<String>,
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        help = "Path to export the stats of each note to, as CSV for a .csv file and JSON otherwise"
+    )]
+    stats_export: Option<String>,
+    #[arg(long, help = "Open the latest note in Obsidian after the conversion")]
+    open: bool,
+    #[arg(
+        long,
+        help = "Also write an index of the tweets for the search subcommand to the output directory"
+    )]
+    search_index: bool,
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "api",
+        help = "Look up the tweets replied to and quoted, and quote them in the notes"
+    )]
+    resolve_references: Option<ReferenceLookup>,
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, help = "Format of the log output")]
+    log_format: LogFormat,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Search the tweets in an index written by --search-index
+    Search {
+        #[arg(short, long, value_hint = ValueHint::DirPath, help = "Path to the search index directory")]
+        index: String,
+        #[arg(
+            required = true,
+            help = "Words to search for; the tweets containing all of them are listed"
+        )]
+        query: Vec<String>,
+    },
+    /// Print the completion script for a shell
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Fetch the recent tweets with the X API and convert them
+    Fetch {
+        #[arg(
+            long,
+            help = "User to fetch the tweets of, instead of the authenticated user"
+        )]
+        user: Option<String>,
+        #[arg(
+            long,
+            default_value_t = 100,
+            help = "Number of the most recent tweets to fetch"
+        )]
+        max_tweets: usize,
+        #[arg(
+            long,
+            default_value = "api_tweets.json",
+            value_hint = ValueHint::FilePath,
+            help = "Path to save the fetched tweets to, in the format of tweets.js"
+        )]
+        save: String,
+        #[arg(
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            help = "Options of the conversion, e.g. -o <OUTPUT_DIR_PATH>"
+        )]
+        convert_args: Vec<String>,
+    },
+    /// Write a small anonymized sample of an archive, for bug reports and for trying templates
+    GenFixture {
+        #[arg(short = 'f', long, value_hint = ValueHint::FilePath, help = "Path to the tweets.js of the archive")]
+        tweets_file_path: String,
+        #[arg(
+            short,
+            long,
+            value_hint = ValueHint::DirPath,
+            help = "Folder to write the tweets.js and the account.js of the sample to"
+        )]
+        output_dir_path: String,
+        #[arg(
+            short = 'n',
+            long,
+            default_value_t = 50,
+            help = "Number of tweets in the sample"
+        )]
+        count: usize,
+        #[arg(
+            long,
+            default_value_t = 0,
+            help = "Seed of the tweets picked and of the scrambling"
+        )]
+        seed: u64,
+    },
+    /// Preview the notes, leaving out kinds of tweets, before writing them
+    Browse {
+        #[arg(
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            help = "Options of the conversion, e.g. -f <TWEETS_FILE_PATH> -o <OUTPUT_DIR_PATH>"
+        )]
+        convert_args: Vec<String>,
+    },
+    /// Remove the files the earlier conversions created in the output directory
+    Clean {
+        #[arg(
+            short,
+            long,
+            value_hint = ValueHint::DirPath,
+            help = "Path to the output directory of the conversions, or the vault given by --vault"
+        )]
+        output_dir_path: String,
+        #[arg(
+            long,
+            help = "Move the files to the .trash folder of the output directory instead of deleting them"
+        )]
+        trash: bool,
+        #[arg(
+            long,
+            help = "List the files that would be removed, without removing them"
+        )]
+        dry_run: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LogFormat {
+    /// Human readable lines
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+fn init_logger(log_format: LogFormat) {
+    let mut builder = match log_format {
+        LogFormat::Text => env_logger::Builder::from_default_env(),
+        // The events are logged at the info level
+        LogFormat::Json => {
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
         }
     };
-    let mut reader = BufReader::new(file);
-    let mut content = String::new();
-    reader.read_to_string(&mut content)?;
-    // Advance the reader to the first "[" character
-    let content = content.trim_start_matches(|c| c != '[');
-
-    parse_tweets(content)
+    if let LogFormat::Json = log_format {
+        builder.format(|buf, record| {
+            let mut line = json_line(
+                &buf.timestamp().to_string(),
+                record.level(),
+                record.target(),
+                &record.args().to_string(),
+            );
+            // The fields of the events, such as their `path` and `count`
+            let _ = record.key_values().visit(&mut JsonFields(&mut line));
+            writeln!(buf, "{}", serde_json::Value::Object(line))
+        });
+    }
+    builder.init();
 }
 
-fn filter_tweet_by_start_month(tweets: Vec<Tweet>, start_month: &str) -> Vec<Tweet> {
-    info!("Filtering tweets by the start month: {}", start_month);
-    let start_month = chrono::NaiveDate::parse_from_str(&format!("{}-01", start_month), "%Y-%m-%d")
-        .expect("Failed to parse the start month");
-    tweets
-        .into_iter()
-        .filter(|tweet| tweet.created_at().naive_local() >= start_month.into())
-        .collect()
-}
-fn filter_tweet_by_end_month(tweets: Vec<Tweet>, end_month: &str) -> Vec<Tweet> {
-    info!("Filtering tweets by the end month: {}", end_month);
-    let mut end_month = chrono::NaiveDate::parse_from_str(&format!("{}-01", end_month), "%Y-%m-%d")
-        .expect("Failed to parse the end month");
-    // 翌月初日にする
-    end_month = end_month
-        .checked_add_months(Months::new(1))
-        .expect("Failed to calculate the end month");
-    tweets
-        .into_iter()
-        .filter(|tweet| tweet.created_at().naive_local() < end_month.into())
-        .collect()
+/// The envelope of the lines of `--log-format json`, with the `event` of the events
+fn json_line(
+    timestamp: &str,
+    level: log::Level,
+    target: &str,
+    message: &str,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut line = serde_json::Map::new();
+    line.insert("timestamp".to_string(), timestamp.into());
+    line.insert("level".to_string(), level.as_str().into());
+    line.insert("target".to_string(), target.into());
+    if let Some(name) = event::name(target) {
+        line.insert("event".to_string(), name.into());
+    }
+    line.insert("message".to_string(), message.into());
+    line
 }
 
-fn main() -> Result<()> {
-    env_logger::init();
-    let args = Args::parse();
-    let tweets = {
-        let tweets = load_tweets(&args.tweets_file_path)?;
-        // Filter the tweets by the start
-        let tweets = match args.start_month {
-            Some(ref start_month) => filter_tweet_by_start_month(tweets, start_month),
-            None => tweets,
-        };
-        // Filter the tweets by the end
-        let tweets = match args.end_month {
-            Some(ref end_month) => filter_tweet_by_end_month(tweets, end_month),
-            None => tweets,
+/// Adds the key-values of a log record to its JSON line, the numbers as numbers
+struct JsonFields<'a>(&'a mut serde_json::Map<String, serde_json::Value>);
+impl<'kvs> log::kv::VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> std::result::Result<(), log::kv::Error> {
+        let value = match value.to_u64() {
+            Some(n) => n.into(),
+            None => value.to_string().into(),
         };
-        tweets
-    };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
 
-    let mut tweets_by_yyyymm = HashMap::new();
-    for tweet in tweets.iter() {
-        let dt = &tweet.created_at();
-        let yyyymm = dt.year() * 100 + dt.month() as i32;
-        tweets_by_yyyymm
-            .entry(yyyymm)
-            .or_insert_with(Vec::new)
-            .push(tweet);
+fn print_report(report: &ConversionReport, log_format: LogFormat) {
+    match log_format {
+        LogFormat::Text => {
+            // The notes are colored only for a person reading the terminal
+            let color = io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+            if !report.notes.is_empty() {
+                eprint!("{}", report.notes_table(color));
+            }
+            eprintln!("{}", report)
+        }
+        LogFormat::Json => {
+            let mut line = json_line(
+                &chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                log::Level::Info,
+                event::RUN_REPORT,
+                "Finished the conversion",
+            );
+            line.insert("report".to_string(), serde_json::json!(report));
+            eprintln!("{}", serde_json::Value::Object(line))
+        }
     }
+}
 
-    let template = MonthlyTweetsTemplate::new()?;
+/// Client of the X API with the bearer token from the environment or the configuration
+fn api_client(config: &Config) -> ApiClient {
+    ApiClient::from_config(&config.api).unwrap_or_else(|_| {
+        Args::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                format!(
+                    "set the bearer token to {} or api.bearer_token of the configuration file",
+                    api::BEARER_TOKEN_ENV
+                ),
+            )
+            .exit()
+    })
+}
 
-    for (yyyymm, tweets) in tweets_by_yyyymm.iter() {
-        let data = match MonthlyTweetsTemplateInput::new(tweets) {
-            Ok(data) => data,
-            Err(e) => {
-                warn!("Failed to create the template input for {}: {}", yyyymm, e);
-                continue;
+fn main() -> ExitCode {
+    let args = Args::parse();
+    init_logger(args.log_format);
+    let result = match (&args.command, &args.watch) {
+        (Some(command), _) => run_command(command),
+        (None, Some(dir)) => watch(&args, dir),
+        (None, None) => run(&args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!("{}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn run_command(command: &Command) -> Result<()> {
+    match command {
+        Command::Search { index, query } => {
+            let index = IndexReader::open(Path::new(index))?;
+            let documents = index.search(&query.join(" "))?;
+            for document in documents.iter() {
+                println!(
+                    "{} [[{}]] {}",
+                    document.created_at,
+                    document.note,
+                    document.text.replace('\n', " ")
+                );
             }
-        };
+            info!("Found {} tweets", documents.len());
+        }
+        Command::Completions { shell } => {
+            completions::generate(*shell, &mut Args::command(), &mut io::stdout())?;
+        }
+        Command::Fetch {
+            user,
+            max_tweets,
+            save,
+            convert_args,
+        } => {
+            // The fetched tweets are converted like another part of the archive
+            let args = Args::try_parse_from(
+                ["twitter2obsidian", "-f", save.as_str()]
+                    .into_iter()
+                    .chain(convert_args.iter().map(String::as_str)),
+            )
+            .unwrap_or_else(|e| e.exit());
+            let config = match args.config {
+                Some(ref path) => Config::load(Path::new(path))?,
+                None => Config::default(),
+            };
+            let entries = api_client(&config).fetch_tweets(user.as_deref(), *max_tweets)?;
+            let output_error = |source| Error::Output {
+                path: save.clone(),
+                source,
+            };
+            let output_file = BufWriter::new(File::create(save).map_err(output_error)?);
+            serde_json::to_writer_pretty(output_file, &entries)?;
+            info!(
+                target: event::FILE_WRITTEN,
+                path = save.as_str(),
+                count = entries.len();
+                "Saved {} tweets to {}",
+                entries.len(),
+                save
+            );
+            run(&args)?;
+        }
+        Command::GenFixture {
+            tweets_file_path,
+            output_dir_path,
+            count,
+            seed,
+        } => {
+            let tweets_file_path = Path::new(tweets_file_path);
+            std::fs::create_dir_all(output_dir_path).map_err(|source| Error::Output {
+                path: output_dir_path.clone(),
+                source,
+            })?;
+            let entries = fixture::sample(load_arrays(tweets_file_path)?, *count, *seed);
+            let mut anonymizer = Anonymizer::new(*seed);
+            let mut files = vec![("tweets", fixture::TWEETS_FILE_NAME, entries)];
+            // The account is anonymized with the same handles and IDs as the tweets
+            if let Some(account_file_path) = Account::file_for(tweets_file_path) {
+                files.push((
+                    "account",
+                    ACCOUNT_FILE_NAME,
+                    load_arrays(&account_file_path)?,
+                ));
+            }
+            for (name, file_name, mut entries) in files {
+                for entry in entries.iter_mut() {
+                    anonymizer.value("", entry);
+                }
+                let path = Path::new(output_dir_path).join(file_name);
+                std::fs::write(&path, fixture::to_script(name, &entries)?).map_err(|source| {
+                    Error::Output {
+                        path: path.display().to_string(),
+                        source,
+                    }
+                })?;
+                info!(
+                    target: event::FILE_WRITTEN,
+                    path:% = path.display(),
+                    count = entries.len();
+                    "Saved {} anonymized entries to {}",
+                    entries.len(),
+                    path.display()
+                );
+            }
+        }
+        Command::Clean {
+            output_dir_path,
+            trash,
+            dry_run,
+        } => {
+            let root = Path::new(output_dir_path);
+            let manifest_path = root.join(runs::FILE_NAME);
+            if !manifest_path.is_file() && !root.join(runs::LOG_FILE_NAME).is_file() {
+                warn!(
+                    "No conversion is recorded in {}, so nothing is removed",
+                    manifest_path.display()
+                );
+                return Ok(());
+            }
+            // The files of an interrupted run are removed as well
+            let paths = RunManifest::load_with_interrupted(&manifest_path)?.files_to_clean(root);
+            if *dry_run {
+                for path in paths.iter() {
+                    println!("{}", root.join(path).display());
+                }
+                info!("Would remove {} files", paths.len());
+                return Ok(());
+            }
+            runs::clean(root, &paths, *trash)?;
+            if *trash {
+                info!(
+                    "Moved {} files created by the conversions to the trash",
+                    paths.len()
+                );
+            } else {
+                info!("Removed {} files created by the conversions", paths.len());
+            }
+        }
+        Command::Browse { convert_args } => {
+            let args = Args::try_parse_from(
+                std::iter::once("twitter2obsidian").chain(convert_args.iter().map(String::as_str)),
+            )
+            .unwrap_or_else(|e| e.exit());
+            browse(args)?;
+        }
+    }
+    Ok(())
+}
 
-        let output_file_path = format!("{}/tweets_{}.md", args.output_dir_path, yyyymm);
-        let mut output_file = match File::create(&output_file_path) {
-            Ok(file) => file,
+/// Preview the notes in memory, switching the filters, until they are written or left
+///
+/// The notes are written from the settings of the session, as they were last previewed.
+fn browse(args: Args) -> Result<()> {
+    let mut session = args;
+    // The options needing the network change the notes, and are left out of the preview
+    if session.download_media
+        || session.link_titles
+        || session.wayback == Some(WaybackMode::ReplaceDead)
+    {
+        warn!("--download-media, --link-titles and --wayback replace-dead are left out of the notes browsed and written");
+        session.download_media = false;
+        session.link_titles = false;
+        if session.wayback == Some(WaybackMode::ReplaceDead) {
+            session.wayback = None;
+        }
+    }
+    let mut notes = preview(&session)?;
+    list_notes(&notes);
+    let mut current = None;
+    let mut line = String::new();
+    loop {
+        eprint!("browse> ");
+        io::stderr().flush()?;
+        line.clear();
+        // The end of the input leaves without writing, like quit
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let command = match line.parse::<BrowseCommand>() {
+            Ok(command) => command,
             Err(e) => {
-                warn!("Failed to create the file({}): {}", output_file_path, e);
+                eprintln!("{}", e);
                 continue;
             }
         };
-        match template.render(&data, &mut output_file) {
-            Ok(_) => {
-                info!("Saved the tweets to {}", output_file_path)
+        let index = match command {
+            BrowseCommand::Show(n) => Some(n.saturating_sub(1)),
+            BrowseCommand::Next => Some(current.map_or(0, |i: usize| i + 1)),
+            BrowseCommand::Previous => Some(current.map_or(0, |i: usize| i.saturating_sub(1))),
+            _ => None,
+        };
+        if let Some(index) = index {
+            match notes.get(index) {
+                Some((path, content)) => {
+                    println!("==> {} <==\n{}", path.display(), content);
+                    current = Some(index);
+                }
+                None => eprintln!("There are {} notes", notes.len()),
             }
-            Err(e) => {
-                warn!("Failed to render the template for {}: {}", yyyymm, e);
+            continue;
+        }
+        match command {
+            BrowseCommand::List => list_notes(&notes),
+            BrowseCommand::Toggle(toggle) => {
+                let excluded = match toggle {
+                    Toggle::Retweets => {
+                        session.exclude_retweets = !session.exclude_retweets;
+                        session.exclude_retweets
+                    }
+                    // All the replies, then the replies to others only, then none of them
+                    Toggle::Replies => {
+                        session.exclude_replies = match session.exclude_replies {
+                            None => Some(ReplyFilter::All),
+                            Some(ReplyFilter::All) => Some(ReplyFilter::Others),
+                            Some(ReplyFilter::Others) => None,
+                        };
+                        session.exclude_replies.is_some()
+                    }
+                    Toggle::Circle => {
+                        session.exclude_circle = !session.exclude_circle;
+                        session.exclude_circle
+                    }
+                    Toggle::Sensitive => {
+                        session.exclude_sensitive = !session.exclude_sensitive;
+                        session.exclude_sensitive
+                    }
+                };
+                match (excluded, session.exclude_replies) {
+                    (true, Some(ReplyFilter::Others)) if toggle == Toggle::Replies => {
+                        eprintln!("Leaving out the replies to others, keeping your threads")
+                    }
+                    (true, _) => eprintln!("Leaving out the {}", toggle),
+                    (false, _) => eprintln!("Bringing back the {}", toggle),
+                }
+                notes = preview(&session)?;
+                current = None;
+                list_notes(&notes);
             }
+            BrowseCommand::Write => return run(&session),
+            BrowseCommand::Quit => return Ok(()),
+            BrowseCommand::Help => println!("{}", browse::HELP),
+            BrowseCommand::Show(_) | BrowseCommand::Next | BrowseCommand::Previous => {}
+        }
+    }
+}
+
+/// Render the notes in memory, with their paths, without writing anything
+fn preview(args: &Args) -> Result<Vec<(PathBuf, String)>> {
+    let mut args = args.clone();
+    args.stats_export = None;
+    let sink = Arc::new(MemorySink::new());
+    build_converter(&args)?.sink(sink.clone()).run()?;
+    Ok(sink
+        .files()
+        .into_iter()
+        .filter(|(path, _)| {
+            path.extension()
+                .is_some_and(|extension| extension == "md" || extension == "org")
+        })
+        .map(|(path, content)| (path, String::from_utf8_lossy(&content).into_owned()))
+        .collect())
+}
+
+/// List the notes with their numbers and numbers of tweets
+fn list_notes(notes: &[(PathBuf, String)]) {
+    for (i, (path, content)) in notes.iter().enumerate() {
+        let tweet_count = content
+            .lines()
+            .find_map(|line| line.strip_prefix("tweet_count: "))
+            .map(|count| format!(" ({} tweets)", count))
+            .unwrap_or_default();
+        println!("{:>4}. {}{}", i + 1, path.display(), tweet_count);
+    }
+}
+
+/// Convert the archives placed in `dir` one by one, until interrupted
+fn watch(args: &Args, dir: &str) -> Result<()> {
+    let mut folder = DropFolder::new(dir)?;
+    info!("Watching {} for archives", folder.dir().display());
+    loop {
+        for path in folder.poll()? {
+            info!("Converting {}", path.display());
+            // A broken archive must not stop the watch
+            let result = watch::posts_file(&path).and_then(|tweets_file_path| {
+                let mut args = args.clone();
+                args.tweets_file_path = vec![tweets_file_path.display().to_string()];
+                run(&args)
+            });
+            if let Err(e) = result {
+                error!("Failed to convert {}: {}", path.display(), e);
+            }
+        }
+        thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+fn run(args: &Args) -> Result<()> {
+    let report = build_converter(args)?.run()?;
+
+    print_report(&report, args.log_format);
+    if let Some(ref report_path) = args.report_path {
+        match std::fs::write(report_path, report.to_markdown()) {
+            Ok(_) => info!(
+                target: event::FILE_WRITTEN,
+                path = report_path.as_str();
+                "Saved the report to {}",
+                report_path
+            ),
+            Err(e) => warn!("Failed to write the report({}): {}", report_path, e),
+        }
+    }
+    if args.open {
+        match report.latest_note {
+            Some(ref note) => {
+                // Obsidian opens notes by their absolute paths
+                let note = std::fs::canonicalize(note).unwrap_or_else(|_| note.clone());
+                let uri = obsidian::open_uri(&note);
+                info!("Opening {}", uri);
+                if let Err(e) = obsidian::launch(&uri) {
+                    warn!("Failed to open {}: {}", uri, e);
+                }
+            }
+            None => warn!("There is no note to open"),
         }
     }
 
     Ok(())
 }
+
+/// The conversion set up by the options
+fn build_converter(args: &Args) -> Result<Converter> {
+    let mut config = match args.config {
+        Some(ref path) => Config::load(Path::new(path))?,
+        None => Config::default(),
+    };
+    // The flags take precedence over the layout of the configuration file
+    if let Some(sort) = args.sort {
+        config.layout.sort = Some(sort);
+    }
+    if let Some(sort_by) = args.sort_by {
+        config.layout.sort_by = sort_by;
+    }
+    if let Some(locale) = args.locale {
+        config.layout.locale = locale;
+    }
+    let ignore_list = match args.ignore_file {
+        Some(ref path) => IgnoreList::load(Path::new(path))?,
+        None => IgnoreList::default(),
+    };
+    let people_map = match args.people_file {
+        Some(ref path) => PeopleMap::load(Path::new(path))?,
+        None => PeopleMap::default(),
+    };
+    let filter = TweetFilter {
+        start: args.start_date.or(args.start_month).map(|d| d.into()),
+        end: match (args.end_date, args.end_month) {
+            (Some(end_date), _) => end_of_day(end_date),
+            (None, Some(end_month)) => end_of_month(end_month),
+            (None, None) => None,
+        },
+        exclude_retweets: args.exclude_retweets,
+        exclude_limited_audience: args.exclude_circle,
+        exclude_sensitive: args.exclude_sensitive,
+        exclude_replies: args.exclude_replies,
+        include_pattern: args.include_pattern.clone(),
+        exclude_patterns: args.exclude_pattern.clone(),
+        hashtags: args.hashtag.clone(),
+        with_users: args.with_user.clone(),
+        langs: args.lang.clone(),
+        min_chars: args.min_chars,
+        hours: args.hours,
+        excluded_months: args.exclude_months.clone(),
+        ..Default::default()
+    };
+    if let (Some(start), Some(end)) = (filter.start, filter.end) {
+        if start >= end {
+            Args::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("the start ({}) must be before the end ({})", start, end),
+                )
+                .exit();
+        }
+    }
+    if args.resolve_references == Some(ReferenceLookup::Api) {
+        api_client(&config);
+    }
+
+    let mut converter = match args.vault {
+        Some(ref root) => {
+            let vault = Vault::open(root)?;
+            let notes_folder = match args.output_dir_path {
+                Some(ref folder) => PathBuf::from(folder),
+                None => vault.notes_folder(),
+            };
+            let attachments_folder = match config.media.folder {
+                Some(ref folder) => obsidian::attachments_folder(folder, &notes_folder),
+                None => vault.attachments_folder(&notes_folder),
+            };
+            Converter::new()
+                .output_dir(vault.root())
+                .notes_folder(notes_folder)
+                .attachments_folder(attachments_folder)
+        }
+        // The notes are printed one after another with `-o -`, and the file of a single file
+        // format as it is
+        None if args.output_dir_path.as_deref() == Some("-") => match args.format.file_name() {
+            Some(_) => Converter::new().sink(StdoutSink::raw()),
+            None => Converter::new().sink(StdoutSink::new()),
+        },
+        // clap requires the output directory unless a vault or a subcommand is given
+        None => {
+            let converter =
+                Converter::new().output_dir(args.output_dir_path.as_deref().unwrap_or_default());
+            // The output directory stands for the vault when the media folder is configured
+            match config.media.folder {
+                Some(ref folder) => converter
+                    .attachments_folder(obsidian::attachments_folder(folder, Path::new(""))),
+                None => converter,
+            }
+        }
+    };
+    converter = converter
+        .format(args.format)
+        .granularity(args.granularity)
+        .filter(filter)
+        .timezone(args.timezone)
+        .config(config)
+        .ignore_list(ignore_list)
+        .people_map(people_map)
+        .redactions(&args.redact)
+        .keep_markdown(args.keep_markdown)
+        .split_accounts(args.split_accounts)
+        .single_file(args.single_file)
+        .hashtag_notes(&args.hashtag_notes)
+        .search_index(args.search_index)
+        .download_media(args.download_media)
+        .link_titles(args.link_titles)
+        .low_memory(args.low_memory);
+    for tweets_file_path in args.tweets_file_path.iter() {
+        converter = converter.input(tweets_file_path);
+    }
+    if let Some(mode) = args.wayback {
+        converter = converter.wayback(mode);
+    }
+    if let Some(n) = args.person_notes {
+        converter = converter.person_notes(n);
+    }
+    if let Some(screen_name) = &args.screen_name {
+        converter = converter.screen_name(screen_name);
+    }
+    if let Some(deleted_tweets) = args.deleted {
+        converter = converter.deleted_tweets(deleted_tweets);
+    }
+    if let Some(n) = args.limit {
+        converter = converter.limit(n);
+    }
+    if let Some(n) = args.sample {
+        converter = converter.sample(n, args.seed);
+    }
+    if let Some(jobs) = args.jobs {
+        converter = converter.jobs(jobs);
+    }
+    if let Some(max) = args.max_tweets_per_file {
+        converter = converter.max_tweets_per_file(max.get());
+    }
+    if let Some(ref path) = args.stats_export {
+        converter = converter.stats_export(path);
+    }
+    if let Some(reference_lookup) = args.resolve_references {
+        converter = converter.resolve_references(reference_lookup);
+    }
+    Ok(converter)
+}
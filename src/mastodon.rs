@@ -0,0 +1,192 @@
+use crate::error::{Error, Result};
+use crate::tweet::{html_to_text, Entities, Hashtag, MediaEntity, Source, Tweet, UserMention};
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use std::{fs::File, io::BufReader, path::Path};
+
+/// File name of the ActivityPub outbox in a Mastodon export
+pub const OUTBOX_FILE_NAME: &str = "outbox.json";
+
+/// The ActivityPub outbox of a Mastodon export
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Outbox {
+    ordered_items: Vec<Activity>,
+}
+
+/// A post (`Create`) or a boost (`Announce`)
+#[derive(Debug, Deserialize)]
+struct Activity {
+    #[serde(rename = "type")]
+    activity_type: String,
+    published: DateTime<FixedOffset>,
+    object: ActivityObject,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ActivityObject {
+    Note(Box<Note>),
+    /// The URL of the boosted post
+    Url(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Note {
+    id: String,
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    in_reply_to: Option<String>,
+    #[serde(default)]
+    tag: Vec<Tag>,
+    #[serde(default)]
+    attachment: Vec<Attachment>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Tag {
+    #[serde(rename = "type")]
+    tag_type: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Attachment {
+    #[serde(default)]
+    media_type: String,
+    url: String,
+}
+
+/// Load the posts and boosts of a Mastodon export's `outbox.json` as tweets
+///
+/// Boosts become retweets, `RT @user: <URL of the post>`, as the outbox has no text for them.
+pub fn load_outbox<F: FnMut(Tweet)>(outbox_file_path: &Path, mut on_tweet: F) -> Result<()> {
+    let file = File::open(outbox_file_path).map_err(|source| Error::Input {
+        path: outbox_file_path.display().to_string(),
+        source,
+    })?;
+    let outbox: Outbox = serde_json::from_reader(BufReader::new(file))?;
+    for activity in outbox.ordered_items {
+        if let Some(tweet) = activity_to_tweet(activity) {
+            on_tweet(tweet);
+        }
+    }
+    Ok(())
+}
+
+fn activity_to_tweet(activity: Activity) -> Option<Tweet> {
+    match (activity.activity_type.as_str(), activity.object) {
+        ("Create", ActivityObject::Note(note)) => {
+            let entities = Entities {
+                hashtags: note
+                    .tag
+                    .iter()
+                    .filter(|tag| tag.tag_type == "Hashtag")
+                    .map(|tag| Hashtag {
+                        text: tag.name.trim_start_matches('#').to_string(),
+                    })
+                    .collect(),
+                user_mentions: note
+                    .tag
+                    .iter()
+                    .filter(|tag| tag.tag_type == "Mention")
+                    .map(|tag| {
+                        // `@user@host`, of which only `@user` is in the text
+                        let handle = tag.name.trim_start_matches('@');
+                        UserMention {
+                            screen_name: handle.split('@').next().unwrap_or(handle).to_string(),
+                            name: handle.to_string(),
+                        }
+                    })
+                    .collect(),
+                media: note
+                    .attachment
+                    .iter()
+                    .map(|attachment| MediaEntity {
+                        url: String::new(),
+                        media_url_https: attachment.url.clone(),
+                        media_type: match attachment.media_type.split('/').next() {
+                            Some("video") => "video".to_string(),
+                            _ => "photo".to_string(),
+                        },
+                    })
+                    .collect(),
+                ..Default::default()
+            };
+            Some(Tweet::from_post(
+                Source::Mastodon,
+                status_id(&note.id),
+                activity.published,
+                &html_to_text(&note.content),
+                note.in_reply_to.as_deref().map(status_id),
+                entities,
+            ))
+        }
+        ("Announce", ActivityObject::Url(url)) => {
+            let author = account_name(&url).unwrap_or("unknown");
+            Some(Tweet::from_post(
+                Source::Mastodon,
+                status_id(&url),
+                activity.published,
+                &format!("RT @{}: {}", author, url),
+                None,
+                Entities::default(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// The ID of a status, the last segment of its URL
+fn status_id(url: &str) -> String {
+    url.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(url)
+        .to_string()
+}
+
+/// The account of a status URL such as `https://example.com/users/alice/statuses/1`
+fn account_name(url: &str) -> Option<&str> {
+    let mut segments = url.split('/');
+    segments.find(|&segment| segment == "users")?;
+    segments.next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{activity_to_tweet, Activity};
+
+    #[test]
+    fn test_activity_to_tweet() {
+        let activity: Activity = serde_json::from_str(
+            r##"{"type": "Create", "published": "2023-03-11T04:12:48Z", "object": {
+                "id": "https://example.com/users/me/statuses/109", "content": "<p>@alice #Rust</p>",
+                "inReplyTo": "https://example.com/users/alice/statuses/100",
+                "tag": [{"type": "Mention", "name": "@alice@example.com"}, {"type": "Hashtag", "name": "#rust"}]
+            }}"##,
+        )
+        .unwrap();
+        let tweet = activity_to_tweet(activity).unwrap();
+        assert_eq!(tweet.id(), "109");
+        assert_eq!(tweet.full_text(), "@alice #Rust");
+        assert_eq!(tweet.in_reply_to_status_id(), Some("100"));
+        assert!(tweet.has_hashtag("rust"));
+        assert!(tweet.involves_user("alice"));
+
+        let activity: Activity = serde_json::from_str(
+            r#"{"type": "Announce", "published": "2023-03-11T04:12:48Z", "object": "https://example.com/users/bob/statuses/42"}"#,
+        )
+        .unwrap();
+        let tweet = activity_to_tweet(activity).unwrap();
+        assert!(tweet.is_retweet());
+        assert_eq!(
+            tweet.full_text(),
+            "RT @bob: https://example.com/users/bob/statuses/42"
+        );
+    }
+}
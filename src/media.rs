@@ -0,0 +1,291 @@
+//! The media of the tweets imported to the attachments folder of the vault
+//!
+//! A manifest in the attachments folder records each file copied from the archive or downloaded,
+//! so that the next runs skip the files already there and retry only the ones that failed.
+//! Downloads go to a `.part` file renamed once complete, so an interrupted download is resumed
+//! from where it stopped and a file under its final name is never a partial one.
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// File name of the manifest in the attachments folder
+pub const MANIFEST_FILE_NAME: &str = "media-manifest.json";
+/// Number of files imported between the saves of the manifest, to keep the progress of a run
+/// interrupted halfway
+pub const MANIFEST_SAVE_INTERVAL: usize = 20;
+/// Exit code of curl when the server cannot resume a download
+const CURL_RANGE_ERROR: i32 = 33;
+
+/// A file of the attachments folder as it was last imported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaRecord {
+    /// The URL downloaded from, or the path in the archive copied from
+    pub source: String,
+    #[serde(default)]
+    pub size: u64,
+    /// SHA-256 of the content, in hex
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub sha256: String,
+    /// Why the last import failed, to retry it in the next run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The file of the same content the media share instead of a file of their own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<String>,
+}
+
+/// The files imported to the attachments folder, by their names
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaManifest {
+    #[serde(default)]
+    pub files: BTreeMap<String, MediaRecord>,
+}
+impl MediaManifest {
+    /// Load the manifest, or start an empty one when the file does not exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read(path) {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(source) => Err(Error::Input {
+                path: path.display().to_string(),
+                source,
+            }),
+        }
+    }
+    /// Whether the file was imported without an error and is still there in full at `path`
+    pub fn is_current(&self, name: &str, path: &Path) -> bool {
+        self.files.get(name).is_some_and(|record| {
+            record.error.is_none()
+                && std::fs::metadata(path).is_ok_and(|metadata| metadata.len() == record.size)
+        })
+    }
+    /// Record the file imported from `source`
+    pub fn record(&mut self, name: &str, source: &str, content: &[u8]) {
+        self.files.insert(
+            name.to_string(),
+            MediaRecord {
+                source: source.to_string(),
+                size: content.len() as u64,
+                sha256: sha256_hex(content),
+                error: None,
+                duplicate_of: None,
+            },
+        );
+    }
+    /// Record the failure to import the file from `source`
+    pub fn record_failure(&mut self, name: &str, source: &str, error: &Error) {
+        self.files.insert(
+            name.to_string(),
+            MediaRecord {
+                source: source.to_string(),
+                size: 0,
+                sha256: String::new(),
+                error: Some(error.to_string()),
+                duplicate_of: None,
+            },
+        );
+    }
+    /// Record the file imported from `source` as a duplicate of `original`, sharing its file
+    pub fn record_duplicate(&mut self, name: &str, source: &str, original: &str) {
+        let Some(record) = self.files.get(original) else {
+            return;
+        };
+        let record = MediaRecord {
+            source: source.to_string(),
+            duplicate_of: Some(original.to_string()),
+            ..record.clone()
+        };
+        self.files.insert(name.to_string(), record);
+    }
+    /// The file imported with the content of the hash, not as a duplicate
+    pub fn find_by_hash(&self, sha256: &str) -> Option<&str> {
+        self.files
+            .iter()
+            .find(|(_, record)| {
+                record.sha256 == sha256 && record.error.is_none() && record.duplicate_of.is_none()
+            })
+            .map(|(name, _)| name.as_str())
+    }
+    /// The file of the attachments folder the media of the name are in, shared with other media
+    /// or its own
+    pub fn attachment<'a>(&'a self, name: &'a str) -> &'a str {
+        self.files
+            .get(name)
+            .and_then(|record| record.duplicate_of.as_deref())
+            .unwrap_or(name)
+    }
+    /// Names of the files whose last import failed
+    pub fn failures(&self) -> impl Iterator<Item = &str> {
+        self.files
+            .iter()
+            .filter(|(_, record)| record.error.is_some())
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// SHA-256 of the content, in hex
+pub fn sha256_hex(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The file a download to `path` is written to until it completes, `<name>.part`
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+pub(crate) fn curl(url: &str, timeout: Duration) -> Command {
+    let mut command = Command::new("curl");
+    command
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args(["--max-time", &timeout.as_secs().max(1).to_string()])
+        .arg(url);
+    command
+}
+
+pub(crate) fn run_curl(url: &str, command: &mut Command) -> Result<std::process::Output> {
+    command.output().map_err(|e| Error::Api {
+        url: url.to_string(),
+        message: format!("failed to run curl: {}", e),
+    })
+}
+
+pub(crate) fn curl_error(url: &str, output: &std::process::Output) -> Error {
+    Error::Api {
+        url: url.to_string(),
+        message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    }
+}
+
+/// Download a file with the `curl` command, giving up after `timeout`
+pub fn download(url: &str, timeout: Duration) -> Result<Vec<u8>> {
+    let output = run_curl(url, &mut curl(url, timeout))?;
+    if !output.status.success() {
+        return Err(curl_error(url, &output));
+    }
+    Ok(output.stdout)
+}
+
+/// Download a file to `path` with the `curl` command, resuming the `.part` file of an earlier
+/// download if any, and return its content
+///
+/// The `.part` file is kept when the download fails, for the next run to resume it. It is
+/// downloaded again from the start when the server cannot resume it.
+pub fn download_to(url: &str, path: &Path, timeout: Duration) -> Result<Vec<u8>> {
+    let output_error = |source| Error::Output {
+        path: path.display().to_string(),
+        source,
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(output_error)?;
+    }
+    let part = part_path(path);
+    let mut output = run_curl(
+        url,
+        curl(url, timeout)
+            .args(["--continue-at", "-", "--output"])
+            .arg(&part),
+    )?;
+    if output.status.code() == Some(CURL_RANGE_ERROR) {
+        std::fs::remove_file(&part).map_err(output_error)?;
+        output = run_curl(url, curl(url, timeout).arg("--output").arg(&part))?;
+    }
+    if !output.status.success() {
+        return Err(curl_error(url, &output));
+    }
+    std::fs::rename(&part, path).map_err(output_error)?;
+    std::fs::read(path).map_err(output_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{download_to, part_path, MediaManifest};
+    use crate::error::Error;
+    use std::path::Path;
+    use std::time::Duration;
+
+    #[test]
+    fn test_manifest() {
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-media-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("1-cat.jpg");
+        std::fs::write(&path, "meow").unwrap();
+
+        let mut manifest = MediaManifest::load(&dir.join(super::MANIFEST_FILE_NAME)).unwrap();
+        assert!(!manifest.is_current("1-cat.jpg", &path));
+        manifest.record("1-cat.jpg", "archive/data/tweets_media/1-cat.jpg", b"meow");
+        assert!(manifest.is_current("1-cat.jpg", &path));
+        // A file cut short is imported again
+        std::fs::write(&path, "me").unwrap();
+        assert!(!manifest.is_current("1-cat.jpg", &path));
+
+        manifest.record_failure(
+            "2-dog.jpg",
+            "https://pbs.twimg.com/media/dog.jpg",
+            &Error::NoTweets,
+        );
+        assert_eq!(
+            manifest.failures().collect::<Vec<&str>>(),
+            vec!["2-dog.jpg"]
+        );
+        let json = serde_json::to_string(&manifest).unwrap();
+        assert_eq!(
+            serde_json::from_str::<MediaManifest>(&json).unwrap(),
+            manifest
+        );
+        assert_eq!(manifest.files["1-cat.jpg"].sha256.len(), 64);
+
+        // The same cat posted again shares the file
+        std::fs::write(&path, "meow").unwrap();
+        let sha256 = super::sha256_hex(b"meow");
+        assert_eq!(manifest.find_by_hash(&sha256), Some("1-cat.jpg"));
+        manifest.record_duplicate(
+            "3-cat.jpg",
+            "https://pbs.twimg.com/media/cat.jpg",
+            "1-cat.jpg",
+        );
+        assert_eq!(manifest.attachment("3-cat.jpg"), "1-cat.jpg");
+        assert_eq!(manifest.attachment("1-cat.jpg"), "1-cat.jpg");
+        assert!(manifest.is_current("3-cat.jpg", &path));
+        assert_eq!(manifest.find_by_hash(&sha256), Some("1-cat.jpg"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_download_resumes_part() {
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-resume-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source.jpg");
+        std::fs::write(&source, "meow meow").unwrap();
+        let path = dir.join("attachments").join("1-cat.jpg");
+        assert_eq!(
+            part_path(&path),
+            dir.join("attachments").join("1-cat.jpg.part")
+        );
+        // The half downloaded in an interrupted run is kept
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(part_path(&path), "meow").unwrap();
+        let url = format!("file://{}", source.display());
+        let content = download_to(&url, &path, Duration::from_secs(10)).unwrap();
+        assert_eq!(content, b"meow meow");
+        assert_eq!(std::fs::read(&path).unwrap(), b"meow meow");
+        assert!(!part_path(&path).exists());
+        assert!(download_to(
+            "file:///nonexistent",
+            &dir.join("lost.jpg"),
+            Duration::from_secs(10)
+        )
+        .is_err());
+        assert!(!Path::new(&dir.join("lost.jpg")).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+//! The Moments curated by the account, in the `moment.js` of the archive
+use crate::error::Result;
+use crate::timezone::Timezone;
+use crate::wrapper::load_arrays;
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt::Write;
+use std::path::Path;
+
+/// File of the Moments in the `data` folder of the Twitter archive
+pub const MOMENTS_FILE_NAME: &str = "moment.js";
+
+/// A collection of tweets with a title
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Moment {
+    pub moment_id: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub created_at: DateTime<FixedOffset>,
+    /// The tweets, as `{"tweet": {"tweetId": ...}}` objects
+    #[serde(default)]
+    tweets: Vec<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MomentEntry {
+    moment: Moment,
+}
+
+impl Moment {
+    /// Load the Moments from a `moment.js` file of the archive
+    pub fn load(moments_file_path: &Path) -> Result<Vec<Self>> {
+        let entries: Vec<MomentEntry> = load_arrays(moments_file_path)?;
+        Ok(entries.into_iter().map(|entry| entry.moment).collect())
+    }
+    /// IDs of the tweets of the Moment, in order
+    pub fn tweet_ids(&self) -> Vec<&str> {
+        self.tweets
+            .iter()
+            .filter_map(|tweet| {
+                tweet["tweet"]["tweetId"]
+                    .as_str()
+                    .or_else(|| tweet["tweetId"].as_str())
+                    .or_else(|| tweet.as_str())
+            })
+            .collect()
+    }
+    /// Name of the note of the Moment
+    pub fn note_name(&self) -> String {
+        format!("moment_{}", self.moment_id)
+    }
+}
+
+/// Render the note of a Moment
+///
+/// The tweets found in the notes, the name of which `note_of` tells, are embedded by their block
+/// IDs, and the others are linked to Twitter.
+pub fn render_note(
+    moment: &Moment,
+    note_of: impl Fn(&str) -> Option<String>,
+    timezone: &Timezone,
+) -> String {
+    let mut note = format!(
+        "---\ntags:\n  - ImportedNote/Twitter\ncreated_at: {}\n---\n\n# {}\n\n",
+        timezone.convert(&moment.created_at).to_rfc3339(),
+        moment.title
+    );
+    if !moment.description.is_empty() {
+        writeln!(note, "{}\n", moment.description).unwrap();
+    }
+    for id in moment.tweet_ids() {
+        match note_of(id) {
+            Some(note_name) => writeln!(note, "![[{}#^{}]]\n", note_name, id).unwrap(),
+            None => writeln!(note, "- https://twitter.com/i/web/status/{}\n", id).unwrap(),
+        }
+    }
+    note.truncate(note.trim_end().len());
+    note.push('\n');
+    note
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_note, MomentEntry};
+
+    #[test]
+    fn test_render_note() {
+        let entries: Vec<MomentEntry> = serde_json::from_str(
+            r#"[{"moment": {
+                "momentId": "99",
+                "createdAt": "2019-05-01T03:00:00.000Z",
+                "title": "令和",
+                "description": "改元の日",
+                "tweets": [
+                    {"momentId": "99", "tweet": {"coreData": {}, "tweetId": "1"}},
+                    {"momentId": "99", "tweet": {"coreData": {}, "tweetId": "2"}}
+                ]
+            }}]"#,
+        )
+        .unwrap();
+        let moment = &entries[0].moment;
+        assert_eq!(moment.note_name(), "moment_99");
+        let note = render_note(
+            moment,
+            |id| (id == "1").then(|| "tweets_201905".to_string()),
+            &"+09:00".parse().unwrap(),
+        );
+        assert_eq!(
+            note,
+            "---\ntags:\n  - ImportedNote/Twitter\ncreated_at: 2019-05-01T12:00:00+09:00\n---\n\n# 令和\n\n改元の日\n\n![[tweets_201905#^1]]\n\n- https://twitter.com/i/web/status/2\n"
+        );
+    }
+}
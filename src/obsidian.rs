@@ -0,0 +1,170 @@
+//! Integration with the Obsidian app
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The settings of `.obsidian/app.json` deciding where the files of a vault go
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AppConfig {
+    /// "root", "current" or "folder", the last one for `new_file_folder_path`
+    pub new_file_location: Option<String>,
+    pub new_file_folder_path: Option<String>,
+    /// "/" for the root of the vault, "./" or "./<folder>" for the folder of the note,
+    /// or a folder of the vault
+    pub attachment_folder_path: Option<String>,
+}
+
+/// An Obsidian vault, whose configuration decides where the notes and their media are written
+#[derive(Debug, Clone)]
+pub struct Vault {
+    root: PathBuf,
+    app: AppConfig,
+}
+impl Vault {
+    /// Read the configuration of the vault at `root`, the defaults of Obsidian if it has none
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        let path = root.join(".obsidian").join("app.json");
+        let app = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| Error::Config {
+                path: path.display().to_string(),
+                message: e.to_string(),
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => AppConfig::default(),
+            Err(source) => {
+                return Err(Error::Input {
+                    path: path.display().to_string(),
+                    source,
+                })
+            }
+        };
+        Ok(Self { root, app })
+    }
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    /// Folder new notes are created in, relative to the root
+    pub fn notes_folder(&self) -> PathBuf {
+        match (&self.app.new_file_location, &self.app.new_file_folder_path) {
+            (Some(location), Some(folder)) if location == "folder" => vault_folder(folder),
+            _ => PathBuf::new(),
+        }
+    }
+    /// Folder the attachments of the notes in `notes_folder` are saved in, relative to the root
+    pub fn attachments_folder(&self, notes_folder: &Path) -> PathBuf {
+        match self.app.attachment_folder_path.as_deref() {
+            Some(folder) => attachments_folder(folder, notes_folder),
+            None => PathBuf::new(),
+        }
+    }
+}
+
+/// An attachment folder setting resolved against the root: "/" for the root, "./" or
+/// "./<folder>" for the folder of the notes, or a folder of the vault
+pub fn attachments_folder(folder: &str, notes_folder: &Path) -> PathBuf {
+    if folder == "." || folder.starts_with("./") {
+        notes_folder.join(vault_folder(&folder[1..]))
+    } else {
+        vault_folder(folder)
+    }
+}
+
+/// A folder of the vault as a relative path, "/" being the root
+fn vault_folder(folder: &str) -> PathBuf {
+    PathBuf::from(folder.trim_matches('/'))
+}
+
+/// The `obsidian://open` URI opening the note at `path`, which must be absolute
+pub fn open_uri(path: &Path) -> String {
+    format!(
+        "obsidian://open?path={}",
+        percent_encode(&path.to_string_lossy())
+    )
+}
+
+/// Encode everything but the unreserved characters and `/` of a URI
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Open the URI with the handler registered in the desktop, which is Obsidian for `obsidian://`
+pub fn launch(uri: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let status = command.arg(uri).status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "the opener exited with {}",
+            status
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{open_uri, Vault};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_open_uri() {
+        assert_eq!(
+            open_uri(Path::new("/home/me/My Vault/tweets_202303.md")),
+            "obsidian://open?path=/home/me/My%20Vault/tweets_202303.md"
+        );
+        assert_eq!(
+            open_uri(Path::new("/vault/ツイート.md")),
+            "obsidian://open?path=/vault/%E3%83%84%E3%82%A4%E3%83%BC%E3%83%88.md"
+        );
+    }
+    #[test]
+    fn test_vault_folders() {
+        let root =
+            std::env::temp_dir().join(format!("twitter2obsidian-vault-{}", std::process::id()));
+        std::fs::create_dir_all(root.join(".obsidian")).unwrap();
+        let vault = Vault::open(&root).unwrap();
+        assert_eq!(vault.notes_folder(), PathBuf::new());
+        assert_eq!(vault.attachments_folder(Path::new("")), PathBuf::new());
+
+        std::fs::write(
+            root.join(".obsidian/app.json"),
+            r#"{"newFileLocation": "folder", "newFileFolderPath": "Inbox/", "attachmentFolderPath": "./assets", "alwaysUpdateLinks": true}"#,
+        )
+        .unwrap();
+        let vault = Vault::open(&root).unwrap();
+        assert_eq!(vault.notes_folder(), PathBuf::from("Inbox"));
+        assert_eq!(
+            vault.attachments_folder(&vault.notes_folder()),
+            PathBuf::from("Inbox/assets")
+        );
+        std::fs::write(
+            root.join(".obsidian/app.json"),
+            r#"{"newFileLocation": "current", "newFileFolderPath": "Inbox", "attachmentFolderPath": "/Files"}"#,
+        )
+        .unwrap();
+        let vault = Vault::open(&root).unwrap();
+        assert_eq!(vault.notes_folder(), PathBuf::new());
+        assert_eq!(
+            vault.attachments_folder(Path::new("Twitter")),
+            PathBuf::from("Files")
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
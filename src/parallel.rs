@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// The number of jobs to use when the user does not specify one
+///
+/// WebAssembly has no threads, so everything runs on the calling one there.
+pub fn default_jobs() -> usize {
+    if cfg!(target_arch = "wasm32") {
+        return 1;
+    }
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Apply `f` to every item on at most `jobs` threads, returning the results in the order of `items`
+pub fn map_bounded<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let jobs = jobs.clamp(1, items.len().max(1));
+    if jobs == 1 || cfg!(target_arch = "wasm32") {
+        return items.iter().map(f).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<Option<R>>>());
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(i) else {
+                    break;
+                };
+                let result = f(item);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every item is processed"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_map_bounded_keeps_order() {
+        let items = (0..100).collect::<Vec<usize>>();
+        let actual = super::map_bounded(&items, 4, |i| i * 2);
+        let expected = items.iter().map(|i| i * 2).collect::<Vec<usize>>();
+        assert_eq!(actual, expected);
+    }
+}
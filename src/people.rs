@@ -0,0 +1,192 @@
+use crate::error::{Error, Result};
+use crate::tweet::Tweet;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Folder the person notes are written to and the `people` mention style links to, by default
+pub const DEFAULT_FOLDER: &str = "People";
+
+/// Notes of the vault the mentions of the accounts link to, instead of `@handle` notes
+///
+/// The file lists one account per line:
+///
+/// ```text
+/// // The account and the note of the person, relative to the vault
+/// @matz -> People/Yukihiro Matsumoto
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct PeopleMap {
+    /// The notes by the lowercased screen names
+    notes: BTreeMap<String, String>,
+}
+impl PeopleMap {
+    /// Load the mapping from a file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|source| Error::Input {
+            path: path.display().to_string(),
+            source,
+        })?;
+        Self::parse(&content)
+    }
+    /// Parse the content of a mapping file
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut map = Self::default();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let invalid = || Error::PeopleMap {
+                line: i + 1,
+                entry: line.to_string(),
+            };
+            let (account, note) = line.split_once("->").ok_or_else(invalid)?;
+            let account = account
+                .trim()
+                .strip_prefix(['@', '＠'])
+                .filter(|name| !name.is_empty())
+                .ok_or_else(invalid)?;
+            let note = note.trim();
+            let note = note.strip_suffix(".md").unwrap_or(note);
+            if note.is_empty() {
+                return Err(invalid());
+            }
+            map.notes.insert(account.to_lowercase(), note.to_string());
+        }
+        Ok(map)
+    }
+    /// The note of the person behind the account, if it is mapped
+    pub fn note_for(&self, screen_name: &str) -> Option<&str> {
+        self.notes
+            .get(&screen_name.to_lowercase())
+            .map(String::as_str)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+}
+
+/// Path of the note of an account, without the extension: its note in `people_map`, or `@handle`
+/// in `folder`
+pub fn note_path(folder: &str, people_map: &PeopleMap, screen_name: &str) -> String {
+    match people_map.note_for(screen_name) {
+        Some(note) => note.to_string(),
+        None => format!("{}/@{}", folder.trim_end_matches('/'), screen_name),
+    }
+}
+
+/// The `n` accounts the most tweets mention or reply to, other than `own_screen_name`, with these
+/// tweets in order
+///
+/// The accounts with as many tweets are ordered by their screen names.
+pub fn most_interacted<'a>(
+    tweets: &[&'a Tweet],
+    n: usize,
+    own_screen_name: Option<&str>,
+) -> Vec<(String, Vec<&'a Tweet>)> {
+    let own_screen_name = own_screen_name.map(str::to_lowercase);
+    // The screen names as first seen, and the tweets, by the lowercased screen names
+    let mut accounts: HashMap<String, (String, Vec<&Tweet>)> = HashMap::new();
+    for tweet in tweets {
+        let mut screen_names = tweet
+            .in_reply_to_screen_name()
+            .into_iter()
+            .chain(
+                tweet
+                    .entities()
+                    .user_mentions
+                    .iter()
+                    .map(|m| m.screen_name.as_str()),
+            )
+            .map(|name| (name.to_lowercase(), name))
+            .filter(|(key, _)| Some(key) != own_screen_name.as_ref())
+            .collect::<Vec<(String, &str)>>();
+        screen_names.sort();
+        screen_names.dedup_by(|a, b| a.0 == b.0);
+        for (key, name) in screen_names {
+            accounts
+                .entry(key)
+                .or_insert_with(|| (name.to_string(), Vec::new()))
+                .1
+                .push(tweet);
+        }
+    }
+    let mut accounts = accounts.into_values().collect::<Vec<_>>();
+    accounts.sort_by(|(a, a_tweets), (b, b_tweets)| {
+        b_tweets
+            .len()
+            .cmp(&a_tweets.len())
+            .then_with(|| a.to_lowercase().cmp(&b.to_lowercase()))
+    });
+    accounts.truncate(n);
+    accounts
+}
+
+/// Render the note of an account, embedding the tweets from the notes `note_of` tells by their
+/// block IDs
+pub fn render_person_note(
+    screen_name: &str,
+    tweets: &[&Tweet],
+    note_of: impl Fn(&str) -> Option<String>,
+) -> String {
+    let mut note = format!(
+        "---\ntags:\n  - ImportedNote/Twitter\ntweet_count: {}\n---\n\n# @{}\n",
+        tweets.len(),
+        screen_name
+    );
+    for tweet in tweets {
+        if let Some(note_name) = note_of(tweet.id()) {
+            write!(note, "\n![[{}#^{}]]\n", note_name, tweet.id()).unwrap();
+        }
+    }
+    note
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{most_interacted, note_path, render_person_note, PeopleMap};
+    use crate::tweet::{parse_tweets, Tweet};
+
+    #[test]
+    fn test_parse() {
+        let map = PeopleMap::parse(
+            "// friends\n@matz -> People/Yukihiro Matsumoto\n＠Hoge->People/Hoge.md\n",
+        )
+        .unwrap();
+        assert_eq!(map.note_for("Matz"), Some("People/Yukihiro Matsumoto"));
+        assert_eq!(map.note_for("hoge"), Some("People/Hoge"));
+        assert_eq!(map.note_for("fuga"), None);
+        assert_eq!(
+            note_path("People", &map, "matz"),
+            "People/Yukihiro Matsumoto"
+        );
+        assert_eq!(note_path("Contacts/", &map, "fuga"), "Contacts/@fuga");
+        assert!(PeopleMap::parse("matz -> People/Matz").is_err());
+        assert!(PeopleMap::parse("@matz People/Matz").is_err());
+    }
+    #[test]
+    fn test_most_interacted() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "@Fuga @hoge hi",
+                    "in_reply_to_screen_name": "Fuga", "entities": {"user_mentions": [{"screen_name": "Fuga"}, {"screen_name": "hoge"}]}}},
+                {"tweet": {"id_str": "2", "created_at": "Sat Mar 11 05:12:48 +0000 2023", "full_text": "@fuga @me yo",
+                    "entities": {"user_mentions": [{"screen_name": "fuga"}, {"screen_name": "me"}]}}}
+            ]"#,
+        )
+        .unwrap();
+        let tweets = tweets.iter().collect::<Vec<&Tweet>>();
+        let accounts = most_interacted(&tweets, 2, Some("Me"));
+        let counts = accounts
+            .iter()
+            .map(|(name, tweets)| (name.as_str(), tweets.len()))
+            .collect::<Vec<(&str, usize)>>();
+        assert_eq!(counts, vec![("Fuga", 2), ("hoge", 1)]);
+        assert_eq!(
+            render_person_note("Fuga", &accounts[0].1, |_| Some("tweets_202303".to_string())),
+            "---\ntags:\n  - ImportedNote/Twitter\ntweet_count: 2\n---\n\n# @Fuga\n\n![[tweets_202303#^1]]\n\n![[tweets_202303#^2]]\n"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+/// What became of a note of a period
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NoteStatus {
+    Written,
+    Failed,
+}
+
+/// A note of a period written, or failed to be, in the run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NoteSummary {
+    /// File name of the note without the extension, such as `tweets_202303`
+    pub name: String,
+    pub tweet_count: usize,
+    pub status: NoteStatus,
+}
+
+const GREEN: &str = "\x1b[32m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+/// Summary of a conversion run
+#[derive(Debug, Serialize)]
+pub struct ConversionReport {
+    finished_at: DateTime<Local>,
+    pub tweets_parsed: usize,
+    pub tweets_filtered_out: usize,
+    pub months_written: usize,
+    pub files_skipped: usize,
+    pub warnings: usize,
+    /// The fields of the archive not understood, with the number of tweets having them
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub unknown_fields: BTreeMap<String, usize>,
+    /// The notes of the periods, in order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<NoteSummary>,
+    /// The note of the latest period written to the local filesystem
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_note: Option<PathBuf>,
+}
+impl Default for ConversionReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl ConversionReport {
+    pub fn new() -> Self {
+        Self {
+            finished_at: Local::now(),
+            tweets_parsed: 0,
+            tweets_filtered_out: 0,
+            months_written: 0,
+            files_skipped: 0,
+            warnings: 0,
+            unknown_fields: BTreeMap::new(),
+            notes: Vec::new(),
+            latest_note: None,
+        }
+    }
+    /// Mark the run as finished now
+    pub fn finish(&mut self) {
+        self.finished_at = Local::now();
+    }
+    /// The fields not understood with their counts, such as "edit_controls (12), views (3)"
+    pub fn unknown_fields_summary(&self) -> String {
+        self.unknown_fields
+            .iter()
+            .map(|(field, count)| format!("{} ({})", field, count))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+    /// Render the notes as an aligned table for the terminal, the status colored with `color`
+    pub fn notes_table(&self, color: bool) -> String {
+        let width = self
+            .notes
+            .iter()
+            .map(|note| note.name.len())
+            .chain(["note".len()])
+            .max()
+            .unwrap_or_default();
+        let mut table = format!("  {:<width$}  {:>6}  status\n", "note", "tweets");
+        for note in &self.notes {
+            let (status, style) = match note.status {
+                NoteStatus::Written => ("written", GREEN),
+                NoteStatus::Failed => ("FAILED", BOLD_RED),
+            };
+            let status = if color {
+                format!("{}{}{}", style, status, RESET)
+            } else {
+                status.to_string()
+            };
+            table.push_str(&format!(
+                "  {:<width$}  {:>6}  {}\n",
+                note.name, note.tweet_count, status
+            ));
+        }
+        table
+    }
+    /// Render the report as a Markdown note
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!(
+            "# ツイートの変換レポート\n\n\
+             {} に実行した変換の結果です。\n\n\
+             | 項目 | 件数 |\n\
+             | :-- | --: |\n\
+             | 読み込んだツイート | {} |\n\
+             | 除外したツイート | {} |\n\
+             | 書き出した月 | {} |\n\
+             | スキップしたファイル | {} |\n\
+             | 警告 | {} |\n",
+            self.finished_at.format("%Y-%m-%d %H:%M:%S"),
+            self.tweets_parsed,
+            self.tweets_filtered_out,
+            self.months_written,
+            self.files_skipped,
+            self.warnings,
+        );
+        if !self.unknown_fields.is_empty() {
+            markdown.push_str(
+                "\n## 未対応の項目\n\n\
+                 アーカイブの形式が変わった可能性があります。\n\n\
+                 | 項目 | ツイート数 |\n\
+                 | :-- | --: |\n",
+            );
+            for (field, count) in &self.unknown_fields {
+                markdown.push_str(&format!("| `{}` | {} |\n", field, count));
+            }
+        }
+        markdown
+    }
+}
+impl fmt::Display for ConversionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Conversion report")?;
+        writeln!(f, "  tweets parsed:       {}", self.tweets_parsed)?;
+        writeln!(f, "  tweets filtered out: {}", self.tweets_filtered_out)?;
+        writeln!(f, "  months written:      {}", self.months_written)?;
+        writeln!(f, "  files skipped:       {}", self.files_skipped)?;
+        write!(f, "  warnings:            {}", self.warnings)?;
+        if !self.unknown_fields.is_empty() {
+            write!(
+                f,
+                "\n  unknown fields:      {}",
+                self.unknown_fields_summary()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_to_markdown() {
+        let mut report = super::ConversionReport::new();
+        report.tweets_parsed = 10;
+        report.tweets_filtered_out = 3;
+        report.months_written = 2;
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("| 読み込んだツイート | 10 |"));
+        assert!(markdown.contains("| 除外したツイート | 3 |"));
+        assert!(markdown.contains("| 書き出した月 | 2 |"));
+        assert!(markdown.contains("| スキップしたファイル | 0 |"));
+        assert!(!markdown.contains("未対応の項目"));
+    }
+    #[test]
+    fn test_notes_table() {
+        let mut report = super::ConversionReport::new();
+        for (name, tweet_count, status) in [
+            ("tweets_202302", 1, super::NoteStatus::Written),
+            ("tweets_202303", 120, super::NoteStatus::Failed),
+        ] {
+            report.notes.push(super::NoteSummary {
+                name: name.to_string(),
+                tweet_count,
+                status,
+            });
+        }
+        assert_eq!(
+            report.notes_table(false),
+            "  note           tweets  status\n\
+             \x20 tweets_202302       1  written\n\
+             \x20 tweets_202303     120  FAILED\n"
+        );
+        assert!(report
+            .notes_table(true)
+            .contains("120  \x1b[1;31mFAILED\x1b[0m\n"));
+    }
+    #[test]
+    fn test_unknown_fields() {
+        let mut report = super::ConversionReport::new();
+        report.unknown_fields.insert("views".to_string(), 3);
+        report
+            .unknown_fields
+            .insert("edit_controls".to_string(), 12);
+        assert_eq!(
+            report.unknown_fields_summary(),
+            "edit_controls (12), views (3)"
+        );
+        assert!(report.to_markdown().contains("| `views` | 3 |"));
+        assert!(report
+            .to_string()
+            .ends_with("\n  unknown fields:      edit_controls (12), views (3)"));
+    }
+}
@@ -0,0 +1,378 @@
+//! The record of the runs of the conversion into an output directory
+//!
+//! After each run, the files it wrote are recorded with the time of the run and a hash of its
+//! settings, so that a later run can be checked to reproduce it, and the files the conversions
+//! created can be removed without touching the other files of the vault.
+//!
+//! While a run is in progress, the files are appended to a log as they are written, which the
+//! next run or `clean` adds to the record when the run is interrupted.
+use crate::error::{Error, Result};
+use crate::sink::FileChange;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// File name of the record of the runs, at the root of the output directory
+pub const FILE_NAME: &str = "twitter2obsidian-runs.json";
+/// File name of the log of the run in progress, next to the record of the runs
+pub const LOG_FILE_NAME: &str = "twitter2obsidian-run.log";
+/// Folder the files removed by `clean --trash` are moved to, the trash of an Obsidian vault
+pub const TRASH_DIR: &str = ".trash";
+
+/// A run of the conversion
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Run {
+    /// When the run started, in RFC 3339; not recorded by the older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
+    /// When the run finished, in RFC 3339, or none when it was interrupted
+    pub finished_at: Option<String>,
+    /// Version of twitter2obsidian
+    pub version: String,
+    /// SHA-256 of the settings of the run, the same for the runs with the same settings
+    pub settings_hash: String,
+    /// The files written, by their paths relative to the output directory
+    pub files: BTreeMap<PathBuf, FileChange>,
+}
+
+/// The runs into an output directory, oldest first
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunManifest {
+    #[serde(default)]
+    pub runs: Vec<Run>,
+}
+impl RunManifest {
+    /// Load the record at `path`, with the run interrupted before it could be recorded, if any
+    pub fn load_with_interrupted(path: &Path) -> Result<Self> {
+        let mut manifest = Self::load(path)?;
+        manifest
+            .runs
+            .extend(RunLog::read(&path.with_file_name(LOG_FILE_NAME))?);
+        Ok(manifest)
+    }
+    /// Add the run interrupted before it could be recorded, if any, to the record at `path`, and
+    /// remove its log
+    pub fn recover(path: &Path) -> Result<()> {
+        let log_path = path.with_file_name(LOG_FILE_NAME);
+        if !log_path.exists() {
+            return Ok(());
+        }
+        let manifest = Self::load_with_interrupted(path)?;
+        let output_error = |path: &Path| {
+            let path = path.display().to_string();
+            move |source| Error::Output { path, source }
+        };
+        std::fs::write(path, serde_json::to_vec_pretty(&manifest)?).map_err(output_error(path))?;
+        std::fs::remove_file(&log_path).map_err(output_error(&log_path))
+    }
+    /// Load the record, or start an empty one when the file does not exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read(path) {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(source) => Err(Error::Input {
+                path: path.display().to_string(),
+                source,
+            }),
+        }
+    }
+    /// The files created by any of the runs
+    pub fn created_files(&self) -> BTreeSet<&Path> {
+        self.runs
+            .iter()
+            .flat_map(|run| run.files.iter())
+            .filter(|(_, change)| **change == FileChange::Created)
+            .map(|(path, _)| path.as_path())
+            .collect()
+    }
+    /// The files created by the runs and still in `root`
+    ///
+    /// The paths leading out of `root` are left out, so that an edited record removes nothing
+    /// else.
+    pub fn files_to_clean(&self, root: &Path) -> Vec<PathBuf> {
+        self.created_files()
+            .into_iter()
+            .filter(|path| path.components().all(|c| matches!(c, Component::Normal(_))))
+            .filter(|path| root.join(path).is_file())
+            .map(Path::to_path_buf)
+            .collect()
+    }
+}
+
+/// The start of a run, the first line of its log
+#[derive(Serialize, Deserialize)]
+struct LogHeader {
+    started_at: String,
+    version: String,
+    settings_hash: String,
+}
+
+/// A file written by the run, a line of its log
+#[derive(Serialize, Deserialize)]
+struct LogEntry {
+    path: PathBuf,
+    change: FileChange,
+}
+
+/// The log of the run in progress, a line of JSON per file written after the one of the run
+#[derive(Debug)]
+pub struct RunLog {
+    file: File,
+    started_at: String,
+    settings_hash: String,
+}
+impl RunLog {
+    /// Start the log of a run at `path`
+    pub fn create(path: &Path, started_at: String, settings_hash: String) -> Result<Self> {
+        let output_error = |source| Error::Output {
+            path: path.display().to_string(),
+            source,
+        };
+        let mut file = File::create(path).map_err(output_error)?;
+        let header = LogHeader {
+            started_at,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            settings_hash,
+        };
+        writeln!(file, "{}", serde_json::to_string(&header)?).map_err(output_error)?;
+        Ok(Self {
+            file,
+            started_at: header.started_at,
+            settings_hash: header.settings_hash,
+        })
+    }
+    /// Append a file written by the run
+    pub fn record(&mut self, path: &Path, change: FileChange) -> io::Result<()> {
+        let entry = LogEntry {
+            path: path.to_path_buf(),
+            change,
+        };
+        writeln!(self.file, "{}", serde_json::to_string(&entry)?)
+    }
+    /// The run with the files written, finished now
+    ///
+    /// The log is left for `RunManifest::recover` until it is removed once the run is recorded.
+    pub fn finish(self, files: BTreeMap<PathBuf, FileChange>) -> Run {
+        Run {
+            started_at: Some(self.started_at),
+            finished_at: Some(chrono::Local::now().to_rfc3339()),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            settings_hash: self.settings_hash,
+            files,
+        }
+    }
+    /// The interrupted run of the log at `path`, if there is one
+    ///
+    /// A line cut short by the interruption is left out.
+    fn read(path: &Path) -> Result<Option<Run>> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => {
+                return Err(Error::Input {
+                    path: path.display().to_string(),
+                    source,
+                })
+            }
+        };
+        let mut lines = content.lines();
+        let Some(header) = lines
+            .next()
+            .and_then(|line| serde_json::from_str::<LogHeader>(line).ok())
+        else {
+            return Ok(None);
+        };
+        let files = lines
+            .filter_map(|line| serde_json::from_str::<LogEntry>(line).ok())
+            .map(|entry| (entry.path, entry.change))
+            .collect();
+        Ok(Some(Run {
+            started_at: Some(header.started_at),
+            finished_at: None,
+            version: header.version,
+            settings_hash: header.settings_hash,
+            files,
+        }))
+    }
+}
+
+/// Remove the files of `root` at `paths`, or move them to the trash folder of `root`, and the
+/// folders left empty by them, then the record of the runs
+pub fn clean(root: &Path, paths: &[PathBuf], trash: bool) -> Result<()> {
+    let output_error = |path: &Path| {
+        let path = path.display().to_string();
+        move |source| Error::Output { path, source }
+    };
+    for path in paths.iter() {
+        let source = root.join(path);
+        if trash {
+            let target = trash_path(&root.join(TRASH_DIR).join(path));
+            if let Some(dir) = target.parent() {
+                std::fs::create_dir_all(dir).map_err(output_error(dir))?;
+            }
+            std::fs::rename(&source, &target).map_err(output_error(&source))?;
+        } else {
+            std::fs::remove_file(&source).map_err(output_error(&source))?;
+        }
+        // The folders of the notes of the long tweets, of the attachments, ...
+        for dir in path.ancestors().skip(1) {
+            if dir.as_os_str().is_empty() || std::fs::remove_dir(root.join(dir)).is_err() {
+                break;
+            }
+        }
+    }
+    for path in [FILE_NAME, LOG_FILE_NAME].map(|name| root.join(name)) {
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(output_error(&path)(e))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// `path`, or `path` with a number added to its name like Obsidian does when it is taken
+fn trash_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (0..)
+        .map(|n| match n {
+            0 => path.to_path_buf(),
+            n => path.with_file_name(format!("{} {}{}", stem, n, extension)),
+        })
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Run, RunLog, RunManifest};
+    use crate::sink::FileChange;
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_created_files() {
+        let run = |files: &[(&str, FileChange)]| Run {
+            started_at: None,
+            finished_at: Some("2023-03-11T04:12:48+09:00".to_string()),
+            version: "0.1.0".to_string(),
+            settings_hash: "0".repeat(64),
+            files: files
+                .iter()
+                .map(|(path, change)| (PathBuf::from(path), *change))
+                .collect::<BTreeMap<PathBuf, FileChange>>(),
+        };
+        let manifest = RunManifest {
+            runs: vec![
+                run(&[
+                    ("tweets_202303.md", FileChange::Created),
+                    ("README.md", FileChange::Updated),
+                ]),
+                run(&[
+                    ("tweets_202303.md", FileChange::Updated),
+                    ("tweets_202304.md", FileChange::Created),
+                ]),
+            ],
+        };
+        let json = serde_json::to_string(&manifest).unwrap();
+        assert!(json.contains(r#""tweets_202303.md":"created""#));
+        let manifest = serde_json::from_str::<RunManifest>(&json).unwrap();
+        assert_eq!(
+            manifest.created_files().into_iter().collect::<Vec<&Path>>(),
+            vec![Path::new("tweets_202303.md"), Path::new("tweets_202304.md")]
+        );
+    }
+    #[test]
+    fn test_clean() {
+        let root =
+            std::env::temp_dir().join(format!("twitter2obsidian-clean-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("long_tweets")).unwrap();
+        for path in [
+            "tweets_202303.md",
+            "long_tweets/tweet_1.md",
+            "Home.md",
+            "twitter2obsidian-runs.json",
+        ] {
+            std::fs::write(root.join(path), "").unwrap();
+        }
+        let mut run = Run {
+            started_at: None,
+            finished_at: Some("2023-03-11T04:12:48+09:00".to_string()),
+            version: "0.1.0".to_string(),
+            settings_hash: "0".repeat(64),
+            files: BTreeMap::new(),
+        };
+        for path in [
+            "tweets_202303.md",
+            "long_tweets/tweet_1.md",
+            "tweets_202304.md",
+            "../outside.md",
+        ] {
+            run.files.insert(PathBuf::from(path), FileChange::Created);
+        }
+        run.files
+            .insert(PathBuf::from("Home.md"), FileChange::Updated);
+        let manifest = RunManifest { runs: vec![run] };
+        let paths = manifest.files_to_clean(&root);
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("long_tweets/tweet_1.md"),
+                PathBuf::from("tweets_202303.md")
+            ]
+        );
+        std::fs::create_dir_all(root.join(".trash")).unwrap();
+        std::fs::write(root.join(".trash/tweets_202303.md"), "").unwrap();
+        super::clean(&root, &paths, true).unwrap();
+        assert!(root.join(".trash/tweets_202303 1.md").is_file());
+        assert!(root.join(".trash/long_tweets/tweet_1.md").is_file());
+        assert!(!root.join("long_tweets").exists());
+        assert!(!root.join("twitter2obsidian-runs.json").exists());
+        assert!(root.join("Home.md").is_file());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+    #[test]
+    fn test_recover() {
+        let root =
+            std::env::temp_dir().join(format!("twitter2obsidian-recover-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let manifest_path = root.join(super::FILE_NAME);
+        let log_path = root.join(super::LOG_FILE_NAME);
+        let mut run_log = RunLog::create(
+            &log_path,
+            "2023-03-11T04:12:00+09:00".to_string(),
+            "0".repeat(64),
+        )
+        .unwrap();
+        run_log
+            .record(Path::new("tweets_202303.md"), FileChange::Created)
+            .unwrap();
+        run_log
+            .record(Path::new("Home.md"), FileChange::Updated)
+            .unwrap();
+        drop(run_log);
+        let manifest = RunManifest::load_with_interrupted(&manifest_path).unwrap();
+        assert_eq!(
+            manifest.created_files().into_iter().collect::<Vec<&Path>>(),
+            vec![Path::new("tweets_202303.md")]
+        );
+        RunManifest::recover(&manifest_path).unwrap();
+        assert!(!log_path.exists());
+        let manifest = RunManifest::load(&manifest_path).unwrap();
+        assert_eq!(manifest.runs.len(), 1);
+        assert_eq!(
+            manifest.runs[0].started_at.as_deref(),
+            Some("2023-03-11T04:12:00+09:00")
+        );
+        assert_eq!(manifest.runs[0].finished_at, None);
+        assert_eq!(manifest.runs[0].files.len(), 2);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+//! Subsets of the tweets for trial runs
+use crate::tweet::Tweet;
+
+/// A small seeded pseudo random number generator (SplitMix64)
+///
+/// Samples only need to be reproducible for a seed, not cryptographically random.
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// A number in `0..bound`
+    pub(crate) fn next_below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// Sort the tweets chronologically so that the subsets do not depend on the order they were read in
+fn sort_chronologically(tweets: &mut [Tweet]) {
+    tweets.sort_by(|a, b| {
+        a.created_at()
+            .cmp(&b.created_at())
+            .then_with(|| a.id().cmp(b.id()))
+    });
+}
+
+/// Keep the `n` oldest tweets
+pub fn limit(mut tweets: Vec<Tweet>, n: usize) -> Vec<Tweet> {
+    sort_chronologically(&mut tweets);
+    tweets.truncate(n);
+    tweets
+}
+
+/// Keep `n` tweets chosen at random, the same ones for the same seed
+pub fn sample(mut tweets: Vec<Tweet>, n: usize, seed: u64) -> Vec<Tweet> {
+    sort_chronologically(&mut tweets);
+    let n = n.min(tweets.len());
+    let mut rng = SplitMix64::new(seed);
+    // Partial Fisher-Yates shuffle of the first n elements
+    for i in 0..n {
+        let j = i + rng.next_below(tweets.len() - i);
+        tweets.swap(i, j);
+    }
+    tweets.truncate(n);
+    sort_chronologically(&mut tweets);
+    tweets
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tweet::Tweet;
+    use chrono::{Local, TimeZone};
+
+    fn tweets() -> Vec<Tweet> {
+        (1..=20)
+            .rev()
+            .map(|day| {
+                Tweet::new_with_local_datetime(
+                    Local.with_ymd_and_hms(2023, 3, day, 12, 0, 0).unwrap(),
+                    format!("tweet {}", day),
+                    false,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_limit() {
+        let actual = super::limit(tweets(), 3);
+        let texts = actual
+            .iter()
+            .map(|tw| tw.full_text())
+            .collect::<Vec<&str>>();
+        assert_eq!(texts, vec!["tweet 1", "tweet 2", "tweet 3"]);
+    }
+    #[test]
+    fn test_sample() {
+        let texts = |tweets: Vec<Tweet>| {
+            tweets
+                .iter()
+                .map(|tw| tw.full_text().to_string())
+                .collect::<Vec<String>>()
+        };
+        let first = texts(super::sample(tweets(), 5, 42));
+        assert_eq!(first.len(), 5);
+        assert_eq!(first, texts(super::sample(tweets(), 5, 42)));
+        assert_ne!(first, texts(super::sample(tweets(), 5, 7)));
+        assert_eq!(super::sample(tweets(), 100, 42).len(), 20);
+    }
+}
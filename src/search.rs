@@ -0,0 +1,255 @@
+use crate::error::{Error, Result};
+use crate::granularity::Granularity;
+use crate::tweet::Tweet;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Name of the index directory written in the output directory
+pub const INDEX_DIR_NAME: &str = "search_index";
+/// Number of the files the postings are spread over, by the hash of the terms
+const TERM_BUCKETS: u64 = 256;
+/// Number of the documents in each of the files of documents
+const CHUNK_SIZE: usize = 1000;
+
+/// A tweet in the search index
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Document {
+    pub id: String,
+    pub created_at: String,
+    /// Name of the note the tweet is in
+    pub note: String,
+    pub text: String,
+}
+
+/// The entry file of the index, `index.json`
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    documents: usize,
+}
+
+/// An inverted index of the tweet text
+///
+/// Words of letters and digits are indexed as they are, and runs of CJK characters as bigrams,
+/// since they are not separated by spaces. The index is written as a directory, with the
+/// postings in `terms/` by the hash of the terms and the documents in `documents/` by chunks of
+/// `CHUNK_SIZE`, so that a search reads only the files of its terms and of the tweets found.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    documents: Vec<Document>,
+    postings: BTreeMap<String, Vec<usize>>,
+}
+impl SearchIndex {
+    pub fn build(tweets: &[&Tweet], granularity: Granularity) -> Self {
+        let mut index = Self::default();
+        for (i, tweet) in tweets.iter().enumerate() {
+            let text = tweet.expanded_text();
+            for token in tokenize(&text).into_iter().collect::<BTreeSet<String>>() {
+                index.postings.entry(token).or_default().push(i);
+            }
+            index.documents.push(Document {
+                id: tweet.id().to_string(),
+                created_at: tweet.created_at().format("%Y-%m-%d %H:%M:%S").to_string(),
+                note: granularity.note_name(&tweet.created_at()),
+                text,
+            });
+        }
+        index
+    }
+    /// The files of the index, by their paths relative to the index directory
+    ///
+    /// Every bucket of terms is written, even empty, so that none is left from an older index.
+    pub fn files(&self) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+        let mut buckets = vec![BTreeMap::new(); TERM_BUCKETS as usize];
+        for (term, docs) in self.postings.iter() {
+            buckets[bucket(term) as usize].insert(term, docs);
+        }
+        let mut files = vec![(
+            PathBuf::from("index.json"),
+            serde_json::to_vec(&Manifest {
+                documents: self.documents.len(),
+            })?,
+        )];
+        for (i, postings) in buckets.iter().enumerate() {
+            files.push((term_path(i as u64), serde_json::to_vec(postings)?));
+        }
+        for (i, chunk) in self.documents.chunks(CHUNK_SIZE).enumerate() {
+            files.push((chunk_path(i), serde_json::to_vec(chunk)?));
+        }
+        Ok(files)
+    }
+}
+
+/// An index written by `SearchIndex::files`, read file by file as the searches need them
+#[derive(Debug)]
+pub struct IndexReader {
+    dir: PathBuf,
+    documents: usize,
+}
+impl IndexReader {
+    pub fn open(dir: &Path) -> Result<Self> {
+        let manifest: Manifest = read_json(&dir.join("index.json"))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            documents: manifest.documents,
+        })
+    }
+    /// The tweets containing all the words of the query (case-insensitive), oldest first
+    pub fn search(&self, query: &str) -> Result<Vec<Document>> {
+        let words = query
+            .split_whitespace()
+            .map(|word| word.to_lowercase())
+            .collect::<Vec<String>>();
+        let mut buckets = HashMap::<u64, BTreeMap<String, Vec<usize>>>::new();
+        let mut candidates: Option<BTreeSet<usize>> = None;
+        for token in words.iter().flat_map(|word| tokenize(word)) {
+            let terms = match buckets.entry(bucket(&token)) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let path = self.dir.join(term_path(*entry.key()));
+                    entry.insert(read_json(&path)?)
+                }
+            };
+            let postings = terms
+                .get(&token)
+                .map(|docs| docs.iter().copied().collect::<BTreeSet<usize>>())
+                .unwrap_or_default();
+            candidates = Some(match candidates {
+                Some(candidates) => candidates.intersection(&postings).copied().collect(),
+                None => postings,
+            });
+        }
+        let candidates =
+            candidates.unwrap_or_else(|| (0..self.documents).collect::<BTreeSet<usize>>());
+        let mut chunks = HashMap::<usize, Vec<Option<Document>>>::new();
+        let mut documents = Vec::new();
+        for i in candidates {
+            let docs = match chunks.entry(i / CHUNK_SIZE) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let docs: Vec<Document> = read_json(&self.dir.join(chunk_path(*entry.key())))?;
+                    entry.insert(docs.into_iter().map(Some).collect())
+                }
+            };
+            // The tokens only narrow the candidates down, for example to the tweets with both halves of a word
+            let Some(doc) = docs.get_mut(i % CHUNK_SIZE).and_then(Option::take) else {
+                continue;
+            };
+            let text = doc.text.to_lowercase();
+            if words.iter().all(|word| text.contains(word.as_str())) {
+                documents.push(doc);
+            }
+        }
+        Ok(documents)
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let content = std::fs::read_to_string(path).map_err(|source| Error::Input {
+        path: path.display().to_string(),
+        source,
+    })?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Bucket of the term, by its FNV-1a hash, which is the same on every platform and run
+fn bucket(term: &str) -> u64 {
+    let hash = term.bytes().fold(0xcbf29ce484222325_u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    hash % TERM_BUCKETS
+}
+
+fn term_path(bucket: u64) -> PathBuf {
+    Path::new("terms").join(format!("{:02x}.json", bucket))
+}
+
+fn chunk_path(chunk: usize) -> PathBuf {
+    Path::new("documents").join(format!("{}.json", chunk))
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}' // ひらがな・カタカナ
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{AC00}'..='\u{D7AF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FF66}'..='\u{FF9F}')
+}
+
+/// Lowercased words and CJK bigrams of the text
+///
+/// A single CJK character has no bigram, so it is left to the substring match of `search`.
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+    for c in text.chars().flat_map(char::to_lowercase).chain([' ']) {
+        if is_cjk(c) {
+            cjk_run.push(c);
+        } else if cjk_run.len() > 1 {
+            tokens.extend(
+                cjk_run
+                    .windows(2)
+                    .map(|pair| pair.iter().collect::<String>()),
+            );
+            cjk_run.clear();
+        } else {
+            cjk_run.clear();
+        }
+        if c.is_alphanumeric() && !is_cjk(c) {
+            word.push(c);
+        } else if !word.is_empty() {
+            tokens.push(std::mem::take(&mut word));
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, Granularity, IndexReader, SearchIndex};
+    use crate::tweet::parse_tweets;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(tokenize("Rust東京 meetup!"), vec!["rust", "東京", "meetup"]);
+        assert_eq!(tokenize("東京都"), vec!["東京", "京都"]);
+    }
+    #[test]
+    fn test_search() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "京都に行った"}},
+                {"tweet": {"id_str": "2", "created_at": "Sun Mar 12 04:12:48 +0000 2023", "full_text": "東京都の Rust meetup"}}
+            ]"#,
+        )
+        .unwrap();
+        let index = SearchIndex::build(&tweets.iter().collect::<Vec<_>>(), Granularity::Month);
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-search-{}", std::process::id()));
+        for (path, content) in index.files().unwrap() {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let reader = IndexReader::open(&dir).unwrap();
+        let ids = |query| {
+            reader
+                .search(query)
+                .unwrap()
+                .into_iter()
+                .map(|doc| doc.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("京都"), vec!["1", "2"]);
+        assert_eq!(ids("東京都"), vec!["2"]);
+        assert_eq!(ids("rust 京"), vec!["2"]);
+        assert_eq!(ids("python"), Vec::<String>::new());
+        assert_eq!(ids(""), vec!["1", "2"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,342 @@
+//! Where the notes and the other files of a conversion are written
+use crate::error::{Error, Result};
+#[cfg(feature = "native")]
+use crate::runs::RunLog;
+#[cfg(feature = "native")]
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// How a run changed a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileChange {
+    /// The file was not there before the run
+    Created,
+    /// The file was there already and was replaced or appended to
+    Updated,
+}
+
+/// A target the files of a conversion are written to, by their paths relative to its root
+pub trait NoteSink: fmt::Debug + Send + Sync {
+    /// Check that the files can be written before the conversion starts
+    fn check(&self) -> Result<()> {
+        Ok(())
+    }
+    /// Write the file at `path`, replacing it if it exists
+    fn write(&self, path: &Path, content: &[u8]) -> Result<()>;
+    /// Write the file at `path` with the content written by `write`, without holding it all in
+    /// memory where the sink allows it
+    fn write_streamed(
+        &self,
+        path: &Path,
+        write: &mut dyn FnMut(&mut dyn Write) -> io::Result<()>,
+    ) -> Result<()> {
+        let mut content = Vec::new();
+        write(&mut content).map_err(|source| Error::Output {
+            path: self.describe(path),
+            source,
+        })?;
+        self.write(path, &content)
+    }
+    /// Where the file at `path` is written, for the logs
+    fn describe(&self, path: &Path) -> String {
+        path.display().to_string()
+    }
+    /// Path of the file at `path` on the local filesystem, for the tools that need a real file
+    fn local_path(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+    /// Whether the files are written one after another to a stream, so that they must be written
+    /// in order rather than in parallel
+    fn is_stream(&self) -> bool {
+        false
+    }
+}
+
+/// A shared sink, so that a `MemorySink` can be read after the conversion
+impl<T: NoteSink + ?Sized> NoteSink for Arc<T> {
+    fn check(&self) -> Result<()> {
+        (**self).check()
+    }
+    fn write(&self, path: &Path, content: &[u8]) -> Result<()> {
+        (**self).write(path, content)
+    }
+    fn write_streamed(
+        &self,
+        path: &Path,
+        write: &mut dyn FnMut(&mut dyn Write) -> io::Result<()>,
+    ) -> Result<()> {
+        (**self).write_streamed(path, write)
+    }
+    fn describe(&self, path: &Path) -> String {
+        (**self).describe(path)
+    }
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        (**self).local_path(path)
+    }
+    fn is_stream(&self) -> bool {
+        (**self).is_stream()
+    }
+}
+
+/// A directory of the local filesystem, e.g. a folder of an Obsidian vault
+#[derive(Debug, Clone)]
+pub struct DirectorySink {
+    root: PathBuf,
+}
+impl DirectorySink {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+impl NoteSink for DirectorySink {
+    /// The directory must exist and be writable
+    fn check(&self) -> Result<()> {
+        let writable = match std::fs::metadata(&self.root) {
+            Ok(metadata) => metadata.is_dir() && !metadata.permissions().readonly(),
+            Err(_) => false,
+        };
+        if !writable {
+            return Err(Error::OutputNotWritable(self.root.display().to_string()));
+        }
+        Ok(())
+    }
+    fn write(&self, path: &Path, content: &[u8]) -> Result<()> {
+        let path = self.root.join(path);
+        let output_error = |source| Error::Output {
+            path: path.display().to_string(),
+            source,
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(output_error)?;
+        }
+        std::fs::write(&path, content).map_err(output_error)
+    }
+    fn write_streamed(
+        &self,
+        path: &Path,
+        write: &mut dyn FnMut(&mut dyn Write) -> io::Result<()>,
+    ) -> Result<()> {
+        let path = self.root.join(path);
+        let output_error = |source| Error::Output {
+            path: path.display().to_string(),
+            source,
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(output_error)?;
+        }
+        let mut writer = io::BufWriter::new(File::create(&path).map_err(output_error)?);
+        write(&mut writer)
+            .and_then(|()| writer.flush())
+            .map_err(output_error)
+    }
+    fn describe(&self, path: &Path) -> String {
+        self.root.join(path).display().to_string()
+    }
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        Some(self.root.join(path))
+    }
+}
+
+/// Files kept in memory, for tests, dry runs and comparisons with an existing vault
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    files: Mutex<BTreeMap<PathBuf, Vec<u8>>>,
+}
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// The files written so far, by their paths
+    pub fn files(&self) -> BTreeMap<PathBuf, Vec<u8>> {
+        self.files.lock().unwrap().clone()
+    }
+    /// The content of the file at `path` as text
+    pub fn read_to_string(&self, path: impl AsRef<Path>) -> Option<String> {
+        let files = self.files.lock().unwrap();
+        let content = files.get(path.as_ref())?;
+        Some(String::from_utf8_lossy(content).into_owned())
+    }
+}
+impl NoteSink for MemorySink {
+    fn write(&self, path: &Path, content: &[u8]) -> Result<()> {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), content.to_vec());
+        Ok(())
+    }
+}
+
+/// A sink recording the files written to it, and whether they were there before
+#[derive(Debug)]
+pub(crate) struct RecordingSink {
+    inner: Box<dyn NoteSink>,
+    written: Mutex<BTreeMap<PathBuf, FileChange>>,
+    /// The log of the run the files are appended to as they are written
+    #[cfg(feature = "native")]
+    run_log: Mutex<Option<RunLog>>,
+}
+impl RecordingSink {
+    pub(crate) fn new(inner: Box<dyn NoteSink>) -> Self {
+        Self {
+            inner,
+            written: Mutex::new(BTreeMap::new()),
+            #[cfg(feature = "native")]
+            run_log: Mutex::new(None),
+        }
+    }
+    /// Record a file written to `path` outside of the sink, such as by another command, and
+    /// whether it `existed` before the run wrote it
+    pub(crate) fn record(&self, path: &Path, existed: bool) {
+        // A file written twice stays created by the run
+        let mut written = self.written.lock().unwrap();
+        let Entry::Vacant(entry) = written.entry(path.to_path_buf()) else {
+            return;
+        };
+        let change = if existed {
+            FileChange::Updated
+        } else {
+            FileChange::Created
+        };
+        entry.insert(change);
+        #[cfg(feature = "native")]
+        if let Some(run_log) = self.run_log.lock().unwrap().as_mut() {
+            if let Err(e) = run_log.record(path, change) {
+                warn!("Failed to log {} to the run: {}", path.display(), e);
+            }
+        }
+    }
+    /// Append the files to `run_log` as they are written, until the log is taken back
+    #[cfg(feature = "native")]
+    pub(crate) fn set_run_log(&self, run_log: RunLog) {
+        *self.run_log.lock().unwrap() = Some(run_log);
+    }
+    #[cfg(feature = "native")]
+    pub(crate) fn take_run_log(&self) -> Option<RunLog> {
+        self.run_log.lock().unwrap().take()
+    }
+    /// The files written since the last call, by their paths
+    pub(crate) fn take_written(&self) -> BTreeMap<PathBuf, FileChange> {
+        std::mem::take(&mut *self.written.lock().unwrap())
+    }
+}
+impl NoteSink for RecordingSink {
+    fn check(&self) -> Result<()> {
+        self.inner.check()
+    }
+    fn write(&self, path: &Path, content: &[u8]) -> Result<()> {
+        let existed = self
+            .inner
+            .local_path(path)
+            .is_some_and(|local| local.exists());
+        self.inner.write(path, content)?;
+        self.record(path, existed);
+        Ok(())
+    }
+    fn write_streamed(
+        &self,
+        path: &Path,
+        write: &mut dyn FnMut(&mut dyn Write) -> io::Result<()>,
+    ) -> Result<()> {
+        let existed = self
+            .inner
+            .local_path(path)
+            .is_some_and(|local| local.exists());
+        self.inner.write_streamed(path, write)?;
+        self.record(path, existed);
+        Ok(())
+    }
+    fn describe(&self, path: &Path) -> String {
+        self.inner.describe(path)
+    }
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        self.inner.local_path(path)
+    }
+    fn is_stream(&self) -> bool {
+        self.inner.is_stream()
+    }
+}
+
+/// The standard output, where each file is preceded by a line of its path
+#[derive(Debug)]
+pub struct StdoutSink {
+    headers: bool,
+}
+impl StdoutSink {
+    pub fn new() -> Self {
+        StdoutSink { headers: true }
+    }
+    /// The standard output, where the content of the files is written as it is, for a single
+    /// file such as a CSV export to pipe into another program
+    pub fn raw() -> Self {
+        StdoutSink { headers: false }
+    }
+}
+impl Default for StdoutSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl NoteSink for StdoutSink {
+    fn write(&self, path: &Path, content: &[u8]) -> Result<()> {
+        let mut stdout = io::stdout().lock();
+        if !self.headers {
+            stdout.write_all(content)?;
+            return Ok(stdout.flush()?);
+        }
+        writeln!(stdout, "==> {} <==", path.display())?;
+        stdout.write_all(content)?;
+        if !content.ends_with(b"\n") {
+            writeln!(stdout)?;
+        }
+        Ok(())
+    }
+    fn describe(&self, path: &Path) -> String {
+        format!("stdout ({})", path.display())
+    }
+    fn is_stream(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DirectorySink, MemorySink, NoteSink};
+    use std::path::Path;
+
+    #[test]
+    fn test_memory_sink() {
+        let sink = MemorySink::new();
+        sink.write(Path::new("a/tweets_202303.md"), b"old").unwrap();
+        sink.write(Path::new("a/tweets_202303.md"), b"new").unwrap();
+        assert_eq!(sink.files().len(), 1);
+        assert_eq!(
+            sink.read_to_string("a/tweets_202303.md").as_deref(),
+            Some("new")
+        );
+    }
+    #[test]
+    fn test_directory_sink_write_streamed() {
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-sink-{}", std::process::id()));
+        let sink = DirectorySink::new(&dir);
+        sink.write_streamed(Path::new("a/tweets.jex"), &mut |writer| {
+            writer.write_all(b"part 1, ")?;
+            writer.write_all(b"part 2")
+        })
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("a/tweets.jex")).unwrap(),
+            "part 1, part 2"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+//! The Spaces hosted or attended, listed in the `spaces-metadata.js` of the archive
+use crate::error::Result;
+use crate::timezone::Timezone;
+use crate::wrapper::load_arrays;
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::fmt::Write;
+use std::path::Path;
+
+/// File of the Spaces in the `data` folder of the Twitter archive
+pub const SPACES_FILE_NAME: &str = "spaces-metadata.js";
+
+/// Name of the note listing the Spaces
+pub const NOTE_NAME: &str = "spaces.md";
+
+/// An audio conversation of the Spaces
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Space {
+    pub id: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    pub created_at: DateTime<FixedOffset>,
+    #[serde(default)]
+    pub ended_at: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub creator_user_id: String,
+    #[serde(default)]
+    pub host_user_ids: Vec<String>,
+    /// The speakers, as IDs or as objects with a `userId`
+    #[serde(default)]
+    pub speakers: Vec<Value>,
+    #[serde(default, deserialize_with = "deserialize_count")]
+    pub total_participated: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpaceEntry {
+    space: Space,
+}
+
+/// The part the account took in a Space
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Host,
+    Speaker,
+    Listener,
+}
+impl Role {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Role::Host => "ホスト",
+            Role::Speaker => "スピーカー",
+            Role::Listener => "リスナー",
+        }
+    }
+}
+
+impl Space {
+    /// The part the account of `account_id` took in the Space
+    pub fn role(&self, account_id: &str) -> Role {
+        if self.creator_user_id == account_id
+            || self.host_user_ids.iter().any(|id| id == account_id)
+        {
+            return Role::Host;
+        }
+        let is_speaker = self.speakers.iter().any(|speaker| {
+            speaker.as_str().or_else(|| speaker["userId"].as_str()) == Some(account_id)
+        });
+        if is_speaker {
+            Role::Speaker
+        } else {
+            Role::Listener
+        }
+    }
+    /// Load the Spaces from a `spaces-metadata.js` file of the archive
+    pub fn load(spaces_file_path: &Path) -> Result<Vec<Self>> {
+        let entries: Vec<SpaceEntry> = load_arrays(spaces_file_path)?;
+        Ok(entries.into_iter().map(|entry| entry.space).collect())
+    }
+}
+
+/// Render the note listing the Spaces chronologically, with the part of the account of
+/// `account_id` when it is known
+pub fn render_note(spaces: &[Space], account_id: Option<&str>, timezone: &Timezone) -> String {
+    let mut spaces = spaces.iter().collect::<Vec<&Space>>();
+    spaces.sort_by_key(|space| space.created_at);
+    let mut note = String::from(
+        "---\ntags:\n  - ImportedNote/Twitter\n---\n\n# スペース\n\n| 開始 | 終了 | タイトル | 役割 | 参加者数 |\n| --- | --- | --- | --- | --: |\n",
+    );
+    for space in spaces {
+        let created_at = timezone.convert(&space.created_at);
+        let ended_at = space
+            .ended_at
+            .map(|ended_at| timezone.convert(&ended_at).format("%H:%M").to_string())
+            .unwrap_or_default();
+        let title = space
+            .title
+            .as_deref()
+            .filter(|title| !title.is_empty())
+            .unwrap_or("(無題)")
+            .replace('|', "\\|");
+        let role = account_id
+            .map(|account_id| space.role(account_id).label())
+            .unwrap_or_default();
+        let participants = space
+            .total_participated
+            .map(|n| n.to_string())
+            .unwrap_or_default();
+        writeln!(
+            note,
+            "| {} | {} | {} | {} | {} |",
+            created_at.format("%Y-%m-%d %H:%M"),
+            ended_at,
+            title,
+            role,
+            participants
+        )
+        .unwrap();
+    }
+    note
+}
+
+/// The counts are strings in the archive
+fn deserialize_count<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<u64>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Number(n)) => n.as_u64(),
+        Some(Value::String(s)) => s.parse().ok(),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_note, Role, SpaceEntry};
+
+    #[test]
+    fn test_render_note() {
+        let entries: Vec<SpaceEntry> = serde_json::from_str(
+            r#"[
+                {"space": {"id": "1a", "title": "Rust もくもく会", "createdAt": "2023-03-11T12:00:00.000Z", "endedAt": "2023-03-11T13:30:00.000Z", "creatorUserId": "42", "hostUserIds": ["42"], "totalParticipated": "30"}},
+                {"space": {"id": "1b", "createdAt": "2023-03-01T12:00:00.000Z", "creatorUserId": "7", "speakers": [{"userId": "42"}]}}
+            ]"#,
+        )
+        .unwrap();
+        let spaces = entries
+            .into_iter()
+            .map(|entry| entry.space)
+            .collect::<Vec<_>>();
+        assert_eq!(spaces[0].role("42"), Role::Host);
+        assert_eq!(spaces[1].role("42"), Role::Speaker);
+        assert_eq!(spaces[1].role("8"), Role::Listener);
+        let note = render_note(&spaces, Some("42"), &"+09:00".parse().unwrap());
+        assert!(note.ends_with(
+            "| 2023-03-01 21:00 |  | (無題) | スピーカー |  |\n| 2023-03-11 21:00 | 22:30 | Rust もくもく会 | ホスト | 30 |\n"
+        ));
+    }
+}
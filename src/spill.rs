@@ -0,0 +1,149 @@
+//! The tweets of each period kept in temporary files while an archive is read, so that a huge
+//! archive is converted a period at a time without holding all its tweets in memory
+use crate::error::{Error, Result};
+use crate::tweet::Tweet;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of tweets kept in memory before they are appended to the files of their periods
+const FLUSH_THRESHOLD: usize = 5_000;
+
+/// Temporary files of the tweets by period, a JSON line per tweet, removed when dropped
+#[derive(Debug)]
+pub struct Spill {
+    dir: PathBuf,
+    buffers: BTreeMap<i32, Vec<Tweet>>,
+    buffered: usize,
+    flushed: BTreeSet<i32>,
+}
+impl Spill {
+    /// Create the folder of the files in the temporary directory
+    pub fn new() -> Result<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "twitter2obsidian-spill-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir).map_err(|source| Error::Output {
+            path: dir.display().to_string(),
+            source,
+        })?;
+        Ok(Self {
+            dir,
+            buffers: BTreeMap::new(),
+            buffered: 0,
+            flushed: BTreeSet::new(),
+        })
+    }
+    fn path(&self, period: i32) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", period))
+    }
+    /// Add a tweet of the period, written to its file once enough tweets are kept
+    pub fn push(&mut self, period: i32, tweet: Tweet) -> Result<()> {
+        self.buffers.entry(period).or_default().push(tweet);
+        self.buffered += 1;
+        if self.buffered >= FLUSH_THRESHOLD {
+            self.flush()?;
+        }
+        Ok(())
+    }
+    /// Append the tweets kept in memory to the files of their periods
+    pub fn flush(&mut self) -> Result<()> {
+        for (period, tweets) in std::mem::take(&mut self.buffers) {
+            let path = self.path(period);
+            let output_error = |source| Error::Output {
+                path: path.display().to_string(),
+                source,
+            };
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(output_error)?;
+            let mut writer = BufWriter::new(file);
+            for tweet in tweets {
+                serde_json::to_writer(&mut writer, &tweet)?;
+                writer.write_all(b"\n").map_err(output_error)?;
+            }
+            writer.flush().map_err(output_error)?;
+            self.flushed.insert(period);
+        }
+        self.buffered = 0;
+        Ok(())
+    }
+    /// The periods with tweets, in order
+    pub fn periods(&self) -> Vec<i32> {
+        self.flushed
+            .iter()
+            .chain(self.buffers.keys())
+            .copied()
+            .collect::<BTreeSet<i32>>()
+            .into_iter()
+            .collect()
+    }
+    /// Take the tweets of the period, removing its file
+    pub fn take(&mut self, period: i32) -> Result<Vec<Tweet>> {
+        let mut tweets = Vec::new();
+        if self.flushed.remove(&period) {
+            let path = self.path(period);
+            let input_error = |source| Error::Input {
+                path: path.display().to_string(),
+                source,
+            };
+            let file = File::open(&path).map_err(input_error)?;
+            for line in BufReader::new(file).lines() {
+                tweets.push(serde_json::from_str(&line.map_err(input_error)?)?);
+            }
+            std::fs::remove_file(&path).map_err(input_error)?;
+        }
+        if let Some(buffered) = self.buffers.remove(&period) {
+            self.buffered -= buffered.len();
+            tweets.extend(buffered);
+        }
+        Ok(tweets)
+    }
+}
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spill;
+    use crate::tweet::parse_tweets;
+
+    #[test]
+    fn test_spill() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "a #rust", "entities": {"hashtags": [{"text": "rust"}]}}},
+                {"tweet": {"id_str": "2", "created_at": "Wed Feb 01 00:00:00 +0000 2023", "full_text": "b"}},
+                {"tweet": {"id_str": "3", "created_at": "Sun Mar 12 04:12:48 +0000 2023", "full_text": "c"}}
+            ]"#,
+        )
+        .unwrap();
+        let mut spill = Spill::new().unwrap();
+        let dir = spill.dir.clone();
+        let mut tweets = tweets.into_iter();
+        spill.push(202303, tweets.next().unwrap()).unwrap();
+        spill.push(202302, tweets.next().unwrap()).unwrap();
+        spill.flush().unwrap();
+        spill.push(202303, tweets.next().unwrap()).unwrap();
+        assert_eq!(spill.periods(), vec![202302, 202303]);
+
+        let march = spill.take(202303).unwrap();
+        let ids = march.iter().map(|tweet| tweet.id()).collect::<Vec<&str>>();
+        assert_eq!(ids, vec!["1", "3"]);
+        assert_eq!(march[0].full_text(), "a #rust");
+        assert_eq!(march[0].entities().hashtags.len(), 1);
+        assert_eq!(spill.periods(), vec![202302]);
+        drop(spill);
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,216 @@
+//! The activity stats of the notes, exported as CSV or JSON to chart them outside Obsidian
+use crate::error::{Error, Result};
+use crate::granularity::Granularity;
+use crate::locale::Locale;
+use crate::tweet::Tweet;
+use chrono::{Datelike, Timelike, Weekday};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Format of the exported stats, told by the extension of the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    Csv,
+    Json,
+}
+impl StatsFormat {
+    /// CSV for a `.csv` file, JSON otherwise
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => StatsFormat::Csv,
+            _ => StatsFormat::Json,
+        }
+    }
+}
+
+/// Numbers of the tweets, the retweets and the replies among them
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct Counts {
+    pub tweet_count: usize,
+    pub retweet_count: usize,
+    pub reply_count: usize,
+}
+impl Counts {
+    fn add(&mut self, tweet: &Tweet) {
+        self.tweet_count += 1;
+        if tweet.is_retweet() {
+            self.retweet_count += 1;
+        }
+        if tweet.is_reply() {
+            self.reply_count += 1;
+        }
+    }
+}
+
+/// The counts of a part of a period, such as a day or an hour
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Breakdown {
+    pub key: String,
+    #[serde(flatten)]
+    pub counts: Counts,
+}
+
+/// The stats of the tweets of a note
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct PeriodStats {
+    /// The period, such as `2023-03` for a monthly note
+    pub period: String,
+    #[serde(flatten)]
+    pub counts: Counts,
+    /// The days with tweets, as `2023-03-11`
+    pub by_day: Vec<Breakdown>,
+    /// From Monday to Sunday
+    pub by_weekday: Vec<Breakdown>,
+    /// From 0 to 23
+    pub by_hour: Vec<Breakdown>,
+}
+impl PeriodStats {
+    /// The stats of the tweets of a period, in the timezone they are converted to
+    pub fn new(tweets: &[Tweet], granularity: Granularity) -> Option<Self> {
+        let first = tweets.iter().map(|tweet| tweet.created_at()).min()?;
+        let period = match granularity {
+            Granularity::Day => first.format("%Y-%m-%d"),
+            Granularity::Month => first.format("%Y-%m"),
+            Granularity::Year => first.format("%Y"),
+        }
+        .to_string();
+        let mut counts = Counts::default();
+        let mut by_day = BTreeMap::<String, Counts>::new();
+        let mut by_weekday = vec![Counts::default(); 7];
+        let mut by_hour = vec![Counts::default(); 24];
+        for tweet in tweets {
+            let created_at = tweet.created_at();
+            counts.add(tweet);
+            by_day
+                .entry(created_at.format("%Y-%m-%d").to_string())
+                .or_default()
+                .add(tweet);
+            by_weekday[created_at.weekday().num_days_from_monday() as usize].add(tweet);
+            by_hour[created_at.hour() as usize].add(tweet);
+        }
+        let breakdown = |(key, counts)| Breakdown { key, counts };
+        Some(Self {
+            period,
+            counts,
+            by_day: by_day.into_iter().map(breakdown).collect(),
+            by_weekday: by_weekday
+                .into_iter()
+                .enumerate()
+                .map(|(i, counts)| {
+                    let weekday = Weekday::try_from(i as u8).unwrap();
+                    breakdown((Locale::En.weekday(weekday).to_string(), counts))
+                })
+                .collect(),
+            by_hour: by_hour
+                .into_iter()
+                .enumerate()
+                .map(|(hour, counts)| breakdown((hour.to_string(), counts)))
+                .collect(),
+        })
+    }
+}
+
+/// Render the stats as CSV, a row per period and per part of it
+///
+/// The `breakdown` column is `total` for the whole period, or `day`, `weekday` or `hour`.
+pub fn to_csv(stats: &[PeriodStats]) -> String {
+    let mut csv = "period,breakdown,key,tweet_count,retweet_count,reply_count\n".to_string();
+    let mut row = |period: &str, breakdown: &str, key: &str, counts: &Counts| {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{}",
+            period, breakdown, key, counts.tweet_count, counts.retweet_count, counts.reply_count
+        )
+        .unwrap();
+    };
+    for period in stats {
+        row(&period.period, "total", "", &period.counts);
+        for (breakdown, parts) in [
+            ("day", &period.by_day),
+            ("weekday", &period.by_weekday),
+            ("hour", &period.by_hour),
+        ] {
+            for part in parts {
+                row(&period.period, breakdown, &part.key, &part.counts);
+            }
+        }
+    }
+    csv
+}
+
+/// Write the stats to a file, in the format told by its extension
+pub fn export(path: &Path, stats: &[PeriodStats]) -> Result<()> {
+    let content = match StatsFormat::from_path(path) {
+        StatsFormat::Csv => to_csv(stats),
+        StatsFormat::Json => serde_json::to_string_pretty(stats)?,
+    };
+    std::fs::write(path, content).map_err(|source| Error::Output {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_csv, PeriodStats, StatsFormat};
+    use crate::granularity::Granularity;
+    use crate::tweet::parse_tweets;
+    use std::path::Path;
+
+    #[test]
+    fn test_period_stats() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "a"}},
+                {"tweet": {"created_at": "Sat Mar 11 04:30:00 +0000 2023", "full_text": "RT @hoge: b"}},
+                {"tweet": {"created_at": "Mon Mar 13 23:00:00 +0000 2023", "full_text": "@hoge c", "in_reply_to_user_id": "1"}}
+            ]"#,
+        )
+        .unwrap();
+        let tweets = tweets
+            .into_iter()
+            .map(|tw| tw.in_timezone(&"UTC".parse().unwrap()))
+            .collect::<Vec<_>>();
+        let stats = PeriodStats::new(&tweets, Granularity::Month).unwrap();
+        assert_eq!(stats.period, "2023-03");
+        assert_eq!(
+            (
+                stats.counts.tweet_count,
+                stats.counts.retweet_count,
+                stats.counts.reply_count
+            ),
+            (3, 1, 1)
+        );
+        let days = stats
+            .by_day
+            .iter()
+            .map(|day| (day.key.as_str(), day.counts.tweet_count))
+            .collect::<Vec<(&str, usize)>>();
+        assert_eq!(days, vec![("2023-03-11", 2), ("2023-03-13", 1)]);
+        assert_eq!(stats.by_weekday[5].key, "Sat");
+        assert_eq!(stats.by_weekday[5].counts.tweet_count, 2);
+        assert_eq!(stats.by_hour[4].counts.retweet_count, 1);
+
+        let csv = to_csv(&[stats]);
+        assert!(csv.starts_with(
+            "period,breakdown,key,tweet_count,retweet_count,reply_count\n2023-03,total,,3,1,1\n"
+        ));
+        assert!(csv.contains("\n2023-03,day,2023-03-13,1,0,1\n"));
+        assert!(csv.contains("\n2023-03,weekday,Mon,1,0,1\n"));
+        assert!(csv.contains("\n2023-03,hour,23,1,0,1\n"));
+        assert!(PeriodStats::new(&[], Granularity::Month).is_none());
+    }
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            StatsFormat::from_path(Path::new("stats.CSV")),
+            StatsFormat::Csv
+        );
+        assert_eq!(
+            StatsFormat::from_path(Path::new("stats.json")),
+            StatsFormat::Json
+        );
+    }
+}
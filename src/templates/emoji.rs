@@ -0,0 +1,370 @@
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// How emoji are rendered
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmojiStyle {
+    /// Left as they are
+    #[default]
+    Keep,
+    /// Replaced with `:shortcode:`, kept as they are when there is no shortcode for them
+    Shortcode,
+    /// Removed
+    Strip,
+}
+impl EmojiStyle {
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            EmojiStyle::Keep => text.to_string(),
+            EmojiStyle::Shortcode => emoji_regex()
+                .replace_all(text, |caps: &Captures| match shortcode(&caps[0]) {
+                    Some(name) => format!(":{}:", name),
+                    None => caps[0].to_string(),
+                })
+                .to_string(),
+            EmojiStyle::Strip => emoji_regex().replace_all(text, "").to_string(),
+        }
+    }
+}
+
+/// An emoji with its variation selectors, skin tone modifiers and ZWJ sequences
+fn emoji_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        // ©, ® and the other Latin-1 pictographs are left as text
+        let base = r"(?:[\p{Extended_Pictographic}&&[^\x00-\xFF]]|[\u{1F1E6}-\u{1F1FF}])";
+        let modifiers = r"[\u{FE0F}\u{1F3FB}-\u{1F3FF}]*";
+        Regex::new(&format!(
+            r"{base}{modifiers}(?:\u{{200D}}{base}{modifiers})*"
+        ))
+        .unwrap()
+    })
+}
+
+/// Shortcode of the emoji, ignoring the variation selectors and skin tones
+fn shortcode(emoji: &str) -> Option<&'static str> {
+    let base = emoji
+        .chars()
+        .filter(|c| !matches!(c, '\u{FE0F}' | '\u{1F3FB}'..='\u{1F3FF}'))
+        .collect::<String>();
+    SHORTCODES
+        .iter()
+        .find(|(e, _)| *e == base)
+        .map(|(_, name)| *name)
+}
+
+/// Shortcodes of the common emoji, following the names used by GitHub and Slack
+const SHORTCODES: &[(&str, &str)] = &[
+    ("😀", "grinning"),
+    ("😃", "smiley"),
+    ("😄", "smile"),
+    ("😁", "grin"),
+    ("😆", "laughing"),
+    ("😅", "sweat_smile"),
+    ("🤣", "rofl"),
+    ("😂", "joy"),
+    ("🙂", "slightly_smiling_face"),
+    ("🙃", "upside_down_face"),
+    ("😉", "wink"),
+    ("😊", "blush"),
+    ("😇", "innocent"),
+    ("🥰", "smiling_face_with_three_hearts"),
+    ("😍", "heart_eyes"),
+    ("🤩", "star_struck"),
+    ("😘", "kissing_heart"),
+    ("😋", "yum"),
+    ("😛", "stuck_out_tongue"),
+    ("😜", "stuck_out_tongue_winking_eye"),
+    ("🤪", "zany_face"),
+    ("🤔", "thinking"),
+    ("🤗", "hugs"),
+    ("🤭", "hand_over_mouth"),
+    ("🤫", "shushing_face"),
+    ("😐", "neutral_face"),
+    ("😑", "expressionless"),
+    ("😶", "no_mouth"),
+    ("😏", "smirk"),
+    ("😒", "unamused"),
+    ("🙄", "roll_eyes"),
+    ("😬", "grimacing"),
+    ("😌", "relieved"),
+    ("😔", "pensive"),
+    ("😪", "sleepy"),
+    ("😴", "sleeping"),
+    ("😷", "mask"),
+    ("🤒", "face_with_thermometer"),
+    ("🤧", "sneezing_face"),
+    ("🥵", "hot_face"),
+    ("🥶", "cold_face"),
+    ("😵", "dizzy_face"),
+    ("🤯", "exploding_head"),
+    ("🥳", "partying_face"),
+    ("😎", "sunglasses"),
+    ("🤓", "nerd_face"),
+    ("😕", "confused"),
+    ("😟", "worried"),
+    ("🙁", "slightly_frowning_face"),
+    ("😮", "open_mouth"),
+    ("😲", "astonished"),
+    ("😳", "flushed"),
+    ("🥺", "pleading_face"),
+    ("😦", "frowning"),
+    ("😨", "fearful"),
+    ("😰", "cold_sweat"),
+    ("😥", "disappointed_relieved"),
+    ("😢", "cry"),
+    ("😭", "sob"),
+    ("😱", "scream"),
+    ("😖", "confounded"),
+    ("😣", "persevere"),
+    ("😞", "disappointed"),
+    ("😓", "sweat"),
+    ("😩", "weary"),
+    ("😫", "tired_face"),
+    ("🥱", "yawning_face"),
+    ("😤", "triumph"),
+    ("😡", "rage"),
+    ("😠", "angry"),
+    ("🤬", "cursing_face"),
+    ("😈", "smiling_imp"),
+    ("💀", "skull"),
+    ("💩", "hankey"),
+    ("🤡", "clown_face"),
+    ("👻", "ghost"),
+    ("👽", "alien"),
+    ("🤖", "robot"),
+    ("😺", "smiley_cat"),
+    ("🙈", "see_no_evil"),
+    ("🙉", "hear_no_evil"),
+    ("🙊", "speak_no_evil"),
+    ("💋", "kiss"),
+    ("💌", "love_letter"),
+    ("💘", "cupid"),
+    ("💖", "sparkling_heart"),
+    ("💗", "heartpulse"),
+    ("💓", "heartbeat"),
+    ("💕", "two_hearts"),
+    ("💔", "broken_heart"),
+    ("❤", "heart"),
+    ("🧡", "orange_heart"),
+    ("💛", "yellow_heart"),
+    ("💚", "green_heart"),
+    ("💙", "blue_heart"),
+    ("💜", "purple_heart"),
+    ("🖤", "black_heart"),
+    ("🤍", "white_heart"),
+    ("💯", "100"),
+    ("💢", "anger"),
+    ("💥", "boom"),
+    ("💫", "dizzy"),
+    ("💦", "sweat_drops"),
+    ("💨", "dash"),
+    ("💤", "zzz"),
+    ("👋", "wave"),
+    ("🤚", "raised_back_of_hand"),
+    ("✋", "hand"),
+    ("👌", "ok_hand"),
+    ("🤏", "pinching_hand"),
+    ("✌", "v"),
+    ("🤞", "crossed_fingers"),
+    ("🤟", "love_you_gesture"),
+    ("🤘", "metal"),
+    ("👈", "point_left"),
+    ("👉", "point_right"),
+    ("👆", "point_up_2"),
+    ("👇", "point_down"),
+    ("☝", "point_up"),
+    ("👍", "+1"),
+    ("👎", "-1"),
+    ("✊", "fist"),
+    ("👊", "facepunch"),
+    ("👏", "clap"),
+    ("🙌", "raised_hands"),
+    ("👐", "open_hands"),
+    ("🤝", "handshake"),
+    ("🙏", "pray"),
+    ("✍", "writing_hand"),
+    ("💪", "muscle"),
+    ("👀", "eyes"),
+    ("🧠", "brain"),
+    ("🙇", "bow"),
+    ("🤦", "facepalm"),
+    ("🤷", "shrug"),
+    ("🙆", "ok_woman"),
+    ("🙅", "no_good"),
+    ("🐶", "dog"),
+    ("🐱", "cat"),
+    ("🐭", "mouse"),
+    ("🐰", "rabbit"),
+    ("🦊", "fox_face"),
+    ("🐻", "bear"),
+    ("🐼", "panda_face"),
+    ("🐨", "koala"),
+    ("🐯", "tiger"),
+    ("🦁", "lion"),
+    ("🐮", "cow"),
+    ("🐷", "pig"),
+    ("🐸", "frog"),
+    ("🐵", "monkey_face"),
+    ("🐔", "chicken"),
+    ("🐧", "penguin"),
+    ("🐦", "bird"),
+    ("🐤", "baby_chick"),
+    ("🦀", "crab"),
+    ("🐟", "fish"),
+    ("🐳", "whale"),
+    ("🐍", "snake"),
+    ("🐢", "turtle"),
+    ("🦄", "unicorn"),
+    ("🐝", "bee"),
+    ("🦋", "butterfly"),
+    ("🐛", "bug"),
+    ("🌸", "cherry_blossom"),
+    ("🌹", "rose"),
+    ("🌻", "sunflower"),
+    ("🌷", "tulip"),
+    ("🌱", "seedling"),
+    ("🌲", "evergreen_tree"),
+    ("🍀", "four_leaf_clover"),
+    ("🍁", "maple_leaf"),
+    ("🌍", "earth_africa"),
+    ("🌏", "earth_asia"),
+    ("🌙", "crescent_moon"),
+    ("⭐", "star"),
+    ("🌟", "star2"),
+    ("✨", "sparkles"),
+    ("⚡", "zap"),
+    ("🔥", "fire"),
+    ("🌈", "rainbow"),
+    ("☀", "sunny"),
+    ("⛅", "partly_sunny"),
+    ("☁", "cloud"),
+    ("☔", "umbrella"),
+    ("❄", "snowflake"),
+    ("⛄", "snowman"),
+    ("🌊", "ocean"),
+    ("🍎", "apple"),
+    ("🍊", "tangerine"),
+    ("🍋", "lemon"),
+    ("🍌", "banana"),
+    ("🍉", "watermelon"),
+    ("🍇", "grapes"),
+    ("🍓", "strawberry"),
+    ("🍑", "peach"),
+    ("🍕", "pizza"),
+    ("🍔", "hamburger"),
+    ("🍟", "fries"),
+    ("🍣", "sushi"),
+    ("🍜", "ramen"),
+    ("🍙", "rice_ball"),
+    ("🍚", "rice"),
+    ("🍛", "curry"),
+    ("🍰", "cake"),
+    ("🎂", "birthday"),
+    ("🍩", "doughnut"),
+    ("🍪", "cookie"),
+    ("🍫", "chocolate_bar"),
+    ("🍺", "beer"),
+    ("🍻", "beers"),
+    ("🍷", "wine_glass"),
+    ("🍶", "sake"),
+    ("☕", "coffee"),
+    ("🍵", "tea"),
+    ("⚽", "soccer"),
+    ("⚾", "baseball"),
+    ("🏀", "basketball"),
+    ("🎾", "tennis"),
+    ("🏆", "trophy"),
+    ("🎮", "video_game"),
+    ("🎲", "game_die"),
+    ("🎯", "dart"),
+    ("🎵", "musical_note"),
+    ("🎶", "notes"),
+    ("🎤", "microphone"),
+    ("🎧", "headphones"),
+    ("🎸", "guitar"),
+    ("🎨", "art"),
+    ("🎬", "clapper"),
+    ("📷", "camera"),
+    ("🎉", "tada"),
+    ("🎊", "confetti_ball"),
+    ("🎁", "gift"),
+    ("🎄", "christmas_tree"),
+    ("🎃", "jack_o_lantern"),
+    ("🎍", "bamboo"),
+    ("🎌", "crossed_flags"),
+    ("🚀", "rocket"),
+    ("✈", "airplane"),
+    ("🚗", "car"),
+    ("🚃", "railway_car"),
+    ("🚄", "bullettrain_side"),
+    ("🚲", "bike"),
+    ("🏠", "house"),
+    ("🏢", "office"),
+    ("🗻", "mount_fuji"),
+    ("🗼", "tokyo_tower"),
+    ("⌚", "watch"),
+    ("📱", "iphone"),
+    ("💻", "computer"),
+    ("⌨", "keyboard"),
+    ("🖥", "desktop_computer"),
+    ("💡", "bulb"),
+    ("🔋", "battery"),
+    ("📚", "books"),
+    ("📖", "book"),
+    ("📝", "memo"),
+    ("✏", "pencil2"),
+    ("📌", "pushpin"),
+    ("📎", "paperclip"),
+    ("📅", "date"),
+    ("📈", "chart_with_upwards_trend"),
+    ("📉", "chart_with_downwards_trend"),
+    ("📦", "package"),
+    ("📢", "loudspeaker"),
+    ("🔔", "bell"),
+    ("🔑", "key"),
+    ("🔒", "lock"),
+    ("🔧", "wrench"),
+    ("🔨", "hammer"),
+    ("⚙", "gear"),
+    ("🔗", "link"),
+    ("💰", "moneybag"),
+    ("💸", "money_with_wings"),
+    ("✅", "white_check_mark"),
+    ("❌", "x"),
+    ("❓", "question"),
+    ("❗", "exclamation"),
+    ("⚠", "warning"),
+    ("🆗", "ok"),
+    ("🆕", "new"),
+    ("⭕", "o"),
+    ("🔴", "red_circle"),
+    ("🔵", "large_blue_circle"),
+    ("⬆", "arrow_up"),
+    ("⬇", "arrow_down"),
+    ("➡", "arrow_right"),
+    ("⬅", "arrow_left"),
+    ("🔄", "arrows_counterclockwise"),
+    ("🈁", "koko"),
+    ("🉐", "ideograph_advantage"),
+    ("㊗", "congratulations"),
+    ("🎏", "flags"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::EmojiStyle;
+
+    #[test]
+    fn test_emoji_styles() {
+        let text = "すごい🎉👍🏽 ❤️ ©2023 👨‍👩‍👧";
+        assert_eq!(EmojiStyle::Keep.apply(text), text);
+        assert_eq!(
+            EmojiStyle::Shortcode.apply(text),
+            "すごい:tada::+1: :heart: ©2023 👨‍👩‍👧"
+        );
+        assert_eq!(EmojiStyle::Strip.apply(text), "すごい  ©2023 ");
+    }
+}
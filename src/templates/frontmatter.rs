@@ -0,0 +1,78 @@
+use chrono::{DateTime, Datelike, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+/// Settings of the frontmatter of the generated notes
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Frontmatter {
+    /// Tag of the month of the note, with the `{year}` and `{month}` placeholders; no tag when empty
+    pub date_tag: String,
+    /// Aliases of the monthly notes, with the `{year}`, `{month}` and `{month_name}` placeholders
+    pub aliases: Vec<String>,
+}
+impl Default for Frontmatter {
+    fn default() -> Self {
+        Self {
+            date_tag: "{year}/{month}".to_string(),
+            aliases: vec![
+                "{month_name} {year} Tweets".to_string(),
+                "{year}-{month} Twitter".to_string(),
+            ],
+        }
+    }
+}
+impl Frontmatter {
+    /// Tags of a note of the month `date` is in
+    pub fn tags(&self, date: &DateTime<FixedOffset>) -> Vec<String> {
+        let tag = expand(self.date_tag.trim_start_matches('#'), date);
+        if tag.is_empty() {
+            Vec::new()
+        } else {
+            vec![tag]
+        }
+    }
+    /// Aliases of the note of the month `date` is in, quoted for YAML
+    pub fn aliases(&self, date: &DateTime<FixedOffset>) -> Vec<String> {
+        self.aliases
+            .iter()
+            .map(|alias| serde_json::to_string(&expand(alias, date)).unwrap())
+            .collect()
+    }
+}
+
+fn expand(pattern: &str, date: &DateTime<FixedOffset>) -> String {
+    pattern
+        .replace("{year}", &date.year().to_string())
+        .replace("{month_name}", &date.format("%B").to_string())
+        .replace("{month}", &format!("{:02}", date.month()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Frontmatter;
+    use chrono::DateTime;
+
+    #[test]
+    fn test_tags() {
+        let date = DateTime::parse_from_rfc3339("2023-03-11T04:12:48+09:00").unwrap();
+        assert_eq!(Frontmatter::default().tags(&date), vec!["2023/03"]);
+        let frontmatter = Frontmatter {
+            date_tag: "#twitter/{year}/{month}".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(frontmatter.tags(&date), vec!["twitter/2023/03"]);
+        let frontmatter = Frontmatter {
+            date_tag: String::new(),
+            ..Default::default()
+        };
+        assert!(frontmatter.tags(&date).is_empty());
+    }
+    #[test]
+    fn test_aliases() {
+        let date = DateTime::parse_from_rfc3339("2023-03-11T04:12:48+09:00").unwrap();
+        assert_eq!(
+            Frontmatter::default().aliases(&date),
+            vec![r#""March 2023 Tweets""#, r#""2023-03 Twitter""#]
+        );
+    }
+}
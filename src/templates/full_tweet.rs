@@ -0,0 +1,125 @@
+use super::frontmatter::Frontmatter;
+use super::layout::Layout;
+use super::Formatter;
+use crate::error::Result;
+use crate::granularity::Granularity;
+use crate::tweet::Tweet;
+use handlebars::Handlebars;
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Folder of the output directory the long tweets are written to
+pub const FULL_NOTES_DIR: &str = "long_tweets";
+
+/// The template, built into the binary so that no file has to be found at run time
+const TEMPLATE: &str = include_str!("full_tweet.hbs");
+
+/// input data for the full_tweet template
+#[derive(Debug, Serialize)]
+pub struct FullTweetTemplateInput {
+    id: String,
+    created_at: String,
+    tags: Vec<String>,
+    text: String,
+    /// Name of the note of the period the tweet is listed in
+    monthly_note: String,
+    /// URL of the tweet on Twitter, when the account is known
+    permalink: Option<String>,
+    #[serde(skip)]
+    note_name: String,
+}
+impl FullTweetTemplateInput {
+    pub fn new(
+        tweet: &Tweet,
+        formatter: &Formatter,
+        layout: &Layout,
+        frontmatter: &Frontmatter,
+        granularity: Granularity,
+    ) -> Self {
+        Self {
+            id: tweet.id().to_string(),
+            created_at: tweet.created_at().format("%Y-%m-%d %H:%M:%S").to_string(),
+            tags: frontmatter.tags(&tweet.created_at()),
+            text: formatter.format_indented(tweet.full_text(), tweet, ""),
+            monthly_note: granularity.note_name(&tweet.created_at()),
+            permalink: formatter.permalink(tweet),
+            note_name: layout.full_note_name(tweet, formatter),
+        }
+    }
+    /// Path of the note in the output directory
+    pub fn output_file_path(&self, output_dir_path: &Path) -> PathBuf {
+        output_dir_path
+            .join(FULL_NOTES_DIR)
+            .join(&self.note_name)
+            .with_extension("md")
+    }
+}
+
+/// A struct representing the full_tweet template, the note of a tweet truncated in the monthly note
+pub struct FullTweetTemplate<'a> {
+    handlebars: Handlebars<'a>,
+}
+impl<'a> FullTweetTemplate<'a> {
+    const TEMPLATE_NAME: &'static str = "full_tweet";
+    /// Create a new FullTweetTemplate
+    pub fn new() -> Result<Self> {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+        handlebars.register_template_string(Self::TEMPLATE_NAME, TEMPLATE)?;
+        Ok(Self { handlebars })
+    }
+
+    /// Render the note with the given input to `writer`
+    pub fn render(&self, input: &FullTweetTemplateInput, writer: impl Write) -> Result<()> {
+        self.handlebars
+            .render_to_write(Self::TEMPLATE_NAME, &input, writer)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FullTweetTemplate, FullTweetTemplateInput, Granularity};
+    use crate::templates::{frontmatter::Frontmatter, layout::Layout, Formatter};
+    use crate::tweet::parse_tweets;
+    use std::path::Path;
+
+    #[test]
+    fn test_new() {
+        assert!(FullTweetTemplate::new().is_ok());
+    }
+    #[test]
+    fn test_output_file_path() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"id_str": "42", "created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "長文"}}]"#,
+        )
+        .unwrap();
+        let input = FullTweetTemplateInput::new(
+            &tweets[0],
+            &Formatter::new(),
+            &Layout::default(),
+            &Frontmatter::default(),
+            Granularity::Month,
+        );
+        assert_eq!(
+            input.output_file_path(Path::new("out")),
+            Path::new("out/long_tweets/tweet_42.md")
+        );
+        let layout: Layout = serde_json::from_str(r#"{"note_names": "zettelkasten"}"#).unwrap();
+        let created_at = tweets[0].created_at();
+        assert_eq!(
+            layout.full_note_name(&tweets[0], &Formatter::new()),
+            created_at.format("%Y%m%d%H%M%S000").to_string()
+        );
+        let layout: Layout = serde_json::from_str(
+            r#"{"note_names": "zettelkasten", "zettel_id_format": "%Y-%m-%d %H%M"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            layout.full_note_name(&tweets[0], &Formatter::new()),
+            created_at.format("%Y-%m-%d %H%M").to_string()
+        );
+        assert!(serde_json::from_str::<Layout>(r#"{"zettel_id_format": "%Q"}"#).is_err());
+    }
+}
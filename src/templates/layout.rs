@@ -0,0 +1,928 @@
+use super::Formatter;
+use crate::locale::Locale;
+use crate::tweet::{
+    encode_html_entities, Geo, Poll, ReferenceKind, ReferencedTweet, Source, Tweet, TweetVersion,
+};
+use chrono::format::{Item, StrftimeItems};
+use chrono::Datelike;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// How replies are rendered in the monthly notes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReplyStyle {
+    /// On a single line like the other tweets
+    #[default]
+    Inline,
+    /// The replied-to account on the first line and the reply indented below it
+    Context,
+    /// `↩ @account:` in place of the mention of the replied-to account at the start
+    Arrow,
+}
+
+/// How retweets are rendered in the monthly notes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RetweetStyle {
+    /// On a single line like the other tweets
+    #[default]
+    Inline,
+    /// In a `> [!quote] RT @author` callout
+    Callout,
+}
+
+/// How the tweets flagged as possibly sensitive are rendered in the monthly notes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SensitiveStyle {
+    /// Like the other tweets
+    #[default]
+    Plain,
+    /// With a `#sensitive` tag
+    Tag,
+    /// In a folded `> [!warning]-` callout, opened only on a click
+    Fold,
+}
+
+/// How each tweet is laid out in the monthly notes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TweetStyle {
+    /// A bullet list item
+    #[default]
+    List,
+    /// A blockquote
+    Blockquote,
+    /// A `######` heading with the time, followed by the text
+    Heading,
+}
+
+/// Direction the tweets are sorted in within each note
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// What the tweets are sorted by within each note
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum SortKey {
+    /// The time they were posted
+    #[default]
+    Time,
+    /// The number of likes and retweets
+    Engagement,
+}
+
+/// How the notes of single tweets, such as the long ones, are named
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NoteNames {
+    /// `tweet_<id>`
+    #[default]
+    TweetId,
+    /// A Zettelkasten ID made of the time the tweet was posted
+    Zettelkasten,
+}
+
+/// Format of the Zettelkasten IDs, the one of the `id` of the monthly notes
+pub const ZETTEL_ID_FORMAT: &str = "%Y%m%d%H%M%S%3f";
+
+/// A section of the monthly notes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Section {
+    /// The heading of the summary and the numbers of tweets, retweets and replies
+    Summary,
+    /// The table of the tweets by hour
+    Hourly,
+    /// The tables of the tweets by weekday, by month and by place
+    Stats,
+    /// The tweets of the note
+    Tweets,
+    /// The tweets with the most likes and retweets
+    TopTweets,
+}
+impl Section {
+    /// The sections of the notes when the layout does not list them
+    pub const DEFAULT_SECTIONS: [Section; 4] = [
+        Section::Summary,
+        Section::Hourly,
+        Section::Stats,
+        Section::Tweets,
+    ];
+}
+
+/// Number of tweets listed in the top tweets section
+pub const TOP_TWEETS_COUNT: usize = 5;
+
+/// Layout of the tweets in the monthly notes
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Layout {
+    pub style: TweetStyle,
+    pub replies: ReplyStyle,
+    pub retweets: RetweetStyle,
+    /// Group the tweets under a `## YYYY-MM-DD (Mon)` heading per day
+    pub day_headings: bool,
+    /// Number of characters above which a tweet is truncated and linked to a note of its own
+    pub truncate_at: Option<usize>,
+    /// Link the location of the geotagged tweets to OpenStreetMap
+    pub map_links: bool,
+    /// List the versions of the edited tweets before the edits below them
+    pub show_edits: bool,
+    pub sensitive: SensitiveStyle,
+    /// Link the time of each tweet to its page on Twitter
+    pub permalinks: bool,
+    /// Ascending by time and descending by engagement when omitted
+    pub sort: Option<SortOrder>,
+    pub sort_by: SortKey,
+    /// Language of the weekdays and the months in the day headings and the stats
+    pub locale: Locale,
+    /// Sections of the notes, in order; the default ones when omitted
+    pub sections: Option<Vec<Section>>,
+    pub note_names: NoteNames,
+    /// `strftime` format of the Zettelkasten IDs; `ZETTEL_ID_FORMAT` when omitted
+    #[serde(deserialize_with = "deserialize_time_format")]
+    pub zettel_id_format: Option<String>,
+}
+impl Layout {
+    /// Name of the note a long tweet is written to in full
+    pub fn full_note_name(&self, tweet: &Tweet, formatter: &Formatter) -> String {
+        match self.note_names {
+            NoteNames::TweetId => format!("tweet_{}", tweet.id()),
+            NoteNames::Zettelkasten => formatter
+                .full_note_name(tweet)
+                .map_or_else(|| self.zettel_id(tweet), str::to_string),
+        }
+    }
+    /// Names of the notes of the long tweets whose Zettelkasten IDs are taken by older long
+    /// tweets, by the IDs of the tweets, for `Formatter::with_full_note_names`
+    ///
+    /// The tweets are given oldest first. The oldest tweet keeps the ID and the others get `-2`,
+    /// `-3`, ... appended to it.
+    pub fn colliding_note_names(&self, tweets: &[&Tweet]) -> HashMap<String, String> {
+        let mut names = HashMap::new();
+        if self.note_names != NoteNames::Zettelkasten {
+            return names;
+        }
+        let mut counts = HashMap::<String, usize>::new();
+        for tweet in tweets.iter().filter(|tweet| self.truncates(tweet)) {
+            let id = self.zettel_id(tweet);
+            let count = counts.entry(id.clone()).or_default();
+            *count += 1;
+            if *count > 1 {
+                names.insert(tweet.id().to_string(), format!("{}-{}", id, count));
+            }
+        }
+        names
+    }
+    fn zettel_id(&self, tweet: &Tweet) -> String {
+        tweet
+            .created_at()
+            .format(self.zettel_id_format.as_deref().unwrap_or(ZETTEL_ID_FORMAT))
+            .to_string()
+    }
+    /// Sections of the notes, in order
+    pub fn sections(&self) -> &[Section] {
+        self.sections
+            .as_deref()
+            .unwrap_or(&Section::DEFAULT_SECTIONS)
+    }
+    /// Render a tweet as an entry of the monthly note
+    pub fn render_tweet(&self, tweet: &Tweet, formatter: &Formatter) -> String {
+        // The date is in the day heading already
+        let date = if self.day_headings { "" } else { "%Y-%m-%d " };
+        let time_format = format!("{}%H:%M:%S", date);
+        let created_at = self.link_time(tweet, formatter, &time_format);
+        // Posts from the other platforms and accounts are told apart from the tweets
+        let attribution = [
+            (tweet.source() != Source::Twitter).then(|| tweet.source().name().to_string()),
+            tweet.account().map(|account| format!("@{}", account)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<String>>();
+        let source = if attribution.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", attribution.join(", "))
+        };
+        // The line introducing the tweet, and the prefix of the following lines
+        let (head, indent) = match self.style {
+            TweetStyle::List => (format!("- {}{}:", created_at, source), "  "),
+            TweetStyle::Blockquote => (format!("> {}{}", created_at, source), "> "),
+            TweetStyle::Heading => (
+                format!(
+                    "###### {}{}",
+                    self.link_time(tweet, formatter, &format!("{}%H:%M", date)),
+                    source
+                ),
+                "",
+            ),
+        };
+        let mut entry = match self.sensitive {
+            SensitiveStyle::Fold if tweet.is_possibly_sensitive() => {
+                let indent = format!("{}> ", indent);
+                format!(
+                    "{}\n{}[!warning]- センシティブな内容\n{}{}",
+                    head,
+                    indent,
+                    indent,
+                    formatter.format_indented(tweet.full_text(), tweet, &indent)
+                )
+            }
+            _ => self.render_body(tweet, formatter, head, indent),
+        };
+        if self.sensitive == SensitiveStyle::Tag && tweet.is_possibly_sensitive() {
+            entry.push_str(" #sensitive");
+        }
+        if tweet.is_deleted() {
+            entry.push_str(" #deleted");
+        }
+        // The tweets that were not public are told apart, e.g. before sharing the vault
+        if let Some(tag) = tweet.audience().tag() {
+            entry.push_str(&format!(" #{}", tag));
+        }
+        if let Some(block_id) = formatter.block_id(tweet) {
+            entry.push_str(&format!(" {}", block_id));
+        }
+        if self.show_edits {
+            for version in tweet.earlier_versions() {
+                entry.push_str(&render_version(version, tweet, formatter, indent));
+            }
+        }
+        for reference in tweet.references() {
+            entry.push_str(&render_reference(reference, formatter, indent));
+        }
+        if let Some(poll) = tweet.poll() {
+            entry.push_str(&render_poll(poll, formatter, indent));
+        }
+        if let Some(geo) = tweet.geo() {
+            entry.push_str(&self.render_geo(geo, formatter, indent));
+        }
+        match self.style {
+            TweetStyle::List => entry,
+            // A blank line keeps the blocks of consecutive tweets apart
+            TweetStyle::Blockquote | TweetStyle::Heading => entry + "\n",
+        }
+    }
+    /// Order of the tweets within a note
+    ///
+    /// With day headings, the days stay in chronological order and the tweets are sorted within
+    /// each day.
+    pub fn compare(&self, a: &Tweet, b: &Tweet) -> Ordering {
+        let days = if self.day_headings && self.sort_by == SortKey::Engagement {
+            a.created_at()
+                .date_naive()
+                .cmp(&b.created_at().date_naive())
+        } else {
+            Ordering::Equal
+        };
+        let ordering = match self.sort_by {
+            SortKey::Time => a.created_at().cmp(&b.created_at()),
+            SortKey::Engagement => engagement(a).cmp(&engagement(b)),
+        };
+        let ordering = match self.sort.unwrap_or(match self.sort_by {
+            SortKey::Time => SortOrder::Asc,
+            SortKey::Engagement => SortOrder::Desc,
+        }) {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        };
+        days.then(ordering)
+            .then_with(|| a.created_at().cmp(&b.created_at()))
+    }
+    /// Whether the tweet is too long to be rendered in full in the monthly note
+    pub fn truncates(&self, tweet: &Tweet) -> bool {
+        self.truncate_at
+            .is_some_and(|max_chars| cut_point(&tweet.expanded_text(), max_chars).is_some())
+    }
+    /// The text of the tweet as rendered in the monthly note, cut after `truncate_at` characters
+    /// when it `truncates`, with the link to its full note to follow
+    fn shown_text<'a>(&self, tweet: &'a Tweet, formatter: &Formatter) -> (Cow<'a, str>, String) {
+        match self.truncate_at {
+            Some(max_chars) if self.truncates(tweet) => {
+                // The text is cut as read, then escaped again for the formatter to decode
+                let text = encode_html_entities(truncate(&tweet.expanded_text(), max_chars));
+                let more = format!("… [[{}|続きを読む]]", self.full_note_name(tweet, formatter));
+                (Cow::Owned(text), more)
+            }
+            _ => (Cow::Borrowed(tweet.full_text()), String::new()),
+        }
+    }
+    /// The time the tweet was posted, linked to the tweet with `permalinks`
+    fn link_time(&self, tweet: &Tweet, formatter: &Formatter, time_format: &str) -> String {
+        let time = tweet.created_at().format(time_format).to_string();
+        match formatter.permalink(tweet) {
+            Some(url) if self.permalinks => format!("[{}]({})", time, url),
+            _ => time,
+        }
+    }
+    /// Heading of the day the tweet was posted
+    pub fn day_heading(&self, tweet: &Tweet) -> String {
+        let created_at = tweet.created_at();
+        format!(
+            "{} ({})",
+            created_at.format("%Y-%m-%d"),
+            self.locale.weekday(created_at.weekday())
+        )
+    }
+    /// Render the location of a geotagged tweet on a line below it
+    fn render_geo(&self, geo: &Geo, formatter: &Formatter, indent: &str) -> String {
+        let map_link = match geo.map_url() {
+            Some(url) if self.map_links => format!(" ([地図]({}))", url),
+            _ => String::new(),
+        };
+        format!(
+            "\n{}📍 {}{}",
+            indent,
+            formatter.format_text(&geo.name()),
+            map_link
+        )
+    }
+    fn render_body(
+        &self,
+        tweet: &Tweet,
+        formatter: &Formatter,
+        head: String,
+        indent: &str,
+    ) -> String {
+        let (full_text, more) = self.shown_text(tweet, formatter);
+        if self.retweets == RetweetStyle::Callout {
+            if let Some(caps) = retweet_regex().captures(&full_text) {
+                let body = &full_text[caps.get(0).unwrap().end()..];
+                let indent = format!("{}> ", indent);
+                return format!(
+                    "{}\n{}[!quote] RT {}\n{}{}{}",
+                    head,
+                    indent,
+                    formatter.format_text(&format!("@{}", &caps[1])),
+                    indent,
+                    formatter.format_indented(body, tweet, &indent),
+                    more
+                );
+            }
+        }
+        let text = match (self.replies, tweet.in_reply_to_screen_name()) {
+            (ReplyStyle::Arrow, Some(screen_name)) => strip_reply_mention(&full_text, screen_name),
+            _ => &full_text,
+        };
+        let body = format!("{}{}", formatter.format_indented(text, tweet, indent), more);
+        match (self.replies, tweet.in_reply_to_screen_name()) {
+            (ReplyStyle::Arrow, Some(screen_name)) => {
+                let arrow = format!("↩ {}:", formatter.format_text(&format!("@{}", screen_name)));
+                if self.style == TweetStyle::List {
+                    format!("{} {} {}", head, arrow, body)
+                } else {
+                    format!("{}\n{}{} {}", head, indent, arrow, body)
+                }
+            }
+            (ReplyStyle::Context, Some(screen_name)) => format!(
+                "{} ↩ {} への返信\n{}{}",
+                head,
+                formatter.format_text(&format!("@{}", screen_name)),
+                indent,
+                body
+            ),
+            _ if self.style == TweetStyle::List => format!("{} {}", head, body),
+            _ => format!("{}\n{}{}", head, indent, body),
+        }
+    }
+}
+
+/// The likes and retweets of a tweet
+pub(crate) fn engagement(tweet: &Tweet) -> u64 {
+    tweet.favorite_count() + tweet.retweet_count()
+}
+
+/// Render a tweet replied to or quoted as a quote below the tweet
+fn render_reference(reference: &ReferencedTweet, formatter: &Formatter, indent: &str) -> String {
+    let indent = format!("{}> ", indent);
+    let mark = match reference.kind {
+        ReferenceKind::RepliedTo => "↩ ",
+        ReferenceKind::Quoted => "",
+    };
+    format!(
+        "\n{}{}{}: {}",
+        indent,
+        mark,
+        formatter.format_text(&format!("@{}", reference.author)),
+        formatter.format_indented(&reference.text, &Tweet::default(), &indent)
+    )
+}
+
+/// Render a version of an edited tweet as a sub-bullet
+fn render_version(
+    version: &TweetVersion,
+    tweet: &Tweet,
+    formatter: &Formatter,
+    indent: &str,
+) -> String {
+    let item_indent = format!("{}  ", indent);
+    format!(
+        "\n{}- ✏️ 編集前 ({}): {}",
+        indent,
+        version.created_at.format("%Y-%m-%d %H:%M:%S"),
+        formatter.format_indented(&version.full_text, tweet, &item_indent)
+    )
+}
+
+/// Render the choices of a poll as a list below the tweet, with the votes when they are known
+fn render_poll(poll: &Poll, formatter: &Formatter, indent: &str) -> String {
+    let total = poll
+        .options
+        .iter()
+        .map(|option| option.votes)
+        .sum::<Option<u64>>();
+    let mut rendered = format!(
+        "\n{}📊 {}",
+        indent,
+        if poll.closed {
+            "投票結果"
+        } else {
+            "投票"
+        }
+    );
+    for option in poll.options.iter() {
+        rendered.push_str(&format!(
+            "\n{}- {}",
+            indent,
+            formatter.format_text(&option.label)
+        ));
+        match (option.votes, total) {
+            (Some(votes), Some(total)) if total > 0 => rendered.push_str(&format!(
+                ": {} 票 ({:.0}%)",
+                votes,
+                votes as f64 * 100.0 / total as f64
+            )),
+            (Some(votes), _) => rendered.push_str(&format!(": {} 票", votes)),
+            (None, _) => {}
+        }
+    }
+    rendered
+}
+
+/// Read a `strftime` format, rejecting the ones chrono cannot render
+fn deserialize_time_format<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    let format = String::deserialize(deserializer)?;
+    if format.is_empty() || StrftimeItems::new(&format).any(|item| item == Item::Error) {
+        return Err(serde::de::Error::custom(format!(
+            "invalid time format: {:?}",
+            format
+        )));
+    }
+    Ok(Some(format))
+}
+
+/// Where the text is cut after `max_chars` characters, or `None` when it is not longer
+///
+/// The URLs are not counted, as they are not read.
+fn cut_point(text: &str, max_chars: usize) -> Option<usize> {
+    static RE_URL: OnceLock<Regex> = OnceLock::new();
+    let re_url = RE_URL.get_or_init(|| Regex::new(r"https?://\S+").unwrap());
+    let text = text.trim_end();
+    let mut urls = re_url.find_iter(text).peekable();
+    let mut count = 0;
+    for (i, _) in text.char_indices() {
+        while urls.next_if(|url| url.end() <= i).is_some() {}
+        if urls.peek().is_some_and(|url| url.start() <= i) {
+            continue;
+        }
+        if count == max_chars {
+            return Some(i);
+        }
+        count += 1;
+    }
+    None
+}
+
+/// Cut the text after `max_chars` characters, without leaving a piece of URL, mention or hashtag
+fn truncate(text: &str, max_chars: usize) -> &str {
+    let Some(end) = cut_point(text, max_chars) else {
+        return text;
+    };
+    let preview = &text[..end];
+    if !text[end..].starts_with(|c: char| !c.is_whitespace()) {
+        return preview;
+    }
+    // The last word is cut, so it is dropped if it would become a broken link
+    let start = preview
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let last_word = &preview[start..];
+    if last_word.starts_with(['@', '#'])
+        || last_word.starts_with("http")
+        || "http".starts_with(last_word)
+    {
+        preview[..start].trim_end()
+    } else {
+        preview
+    }
+}
+
+/// The text of a reply without the mention of the replied-to account at its start
+fn strip_reply_mention<'a>(text: &'a str, screen_name: &str) -> &'a str {
+    let Some(rest) = text.strip_prefix('@') else {
+        return text;
+    };
+    match rest.get(..screen_name.len()) {
+        Some(name) if name.eq_ignore_ascii_case(screen_name) => {
+            let rest = &rest[screen_name.len()..];
+            if rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+                text
+            } else {
+                rest.trim_start()
+            }
+        }
+        _ => text,
+    }
+}
+
+/// "RT @author: " at the start of retweets
+fn retweet_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^RT @([a-zA-Z0-9_]+): ").unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        truncate, Layout, ReplyStyle, RetweetStyle, SensitiveStyle, SortKey, SortOrder, TweetStyle,
+    };
+    use crate::templates::Formatter;
+    use crate::tweet::{parse_tweets, ReferenceKind, ReferencedTweet, Source, Tweet};
+
+    #[test]
+    fn test_render_reply_with_context() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "@friend そうですね\n本当に", "in_reply_to_status_id_str": "1", "in_reply_to_screen_name": "friend"}}]"#,
+        )
+        .unwrap();
+        let tweet = tweets
+            .into_iter()
+            .next()
+            .unwrap()
+            .in_timezone(&"+09:00".parse().unwrap());
+        let formatter = Formatter::new();
+        let layout = Layout::default();
+        assert_eq!(
+            layout.render_tweet(&tweet, &formatter),
+            "- 2023-03-12 22:00:00: [[@friend]] そうですね\n  本当に"
+        );
+        let layout = Layout {
+            replies: ReplyStyle::Context,
+            ..Default::default()
+        };
+        assert_eq!(
+            layout.render_tweet(&tweet, &formatter),
+            "- 2023-03-12 22:00:00: ↩ [[@friend]] への返信\n  [[@friend]] そうですね\n  本当に"
+        );
+        let layout = Layout {
+            replies: ReplyStyle::Arrow,
+            ..Default::default()
+        };
+        assert_eq!(
+            layout.render_tweet(&tweet, &formatter),
+            "- 2023-03-12 22:00:00: ↩ [[@friend]]: そうですね\n  本当に"
+        );
+    }
+    #[test]
+    fn test_render_tweet_with_references() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "@friend それな", "in_reply_to_status_id_str": "1", "in_reply_to_screen_name": "friend"}}]"#,
+        )
+        .unwrap();
+        let mut tweet = tweets
+            .into_iter()
+            .next()
+            .unwrap()
+            .in_timezone(&"+09:00".parse().unwrap());
+        tweet.add_reference(ReferencedTweet {
+            kind: ReferenceKind::RepliedTo,
+            id: "1".to_string(),
+            author: "friend".to_string(),
+            text: "*寒い*\nね".to_string(),
+        });
+        assert_eq!(
+            Layout::default().render_tweet(&tweet, &Formatter::new()),
+            "- 2023-03-12 22:00:00: [[@friend]] それな\n  > ↩ [[@friend]]: \\*寒い\\*\n  > ね"
+        );
+    }
+    #[test]
+    fn test_render_tweet_with_geo() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "ラーメン", "geo": {"coordinates": ["35.6595", "139.7005"]}, "place": {"full_name": "Shibuya-ku, Tokyo"}}}]"#,
+        )
+        .unwrap();
+        let tweet = tweets[0].clone().in_timezone(&"+09:00".parse().unwrap());
+        let formatter = Formatter::new();
+        assert_eq!(
+            Layout::default().render_tweet(&tweet, &formatter),
+            "- 2023-03-12 22:00:00: ラーメン\n  📍 Shibuya-ku, Tokyo"
+        );
+        let layout = Layout {
+            map_links: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            layout.render_tweet(&tweet, &formatter),
+            "- 2023-03-12 22:00:00: ラーメン\n  📍 Shibuya-ku, Tokyo ([地図](https://www.openstreetmap.org/?mlat=35.6595&mlon=139.7005#map=16/35.6595/139.7005))"
+        );
+    }
+    #[test]
+    fn test_render_tweet_with_poll() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "どっち？", "card": {"name": "poll2choice_text_only", "binding_values": {"choice1_label": {"string_value": "犬"}, "choice1_count": {"string_value": "10"}, "choice2_label": {"string_value": "猫"}, "choice2_count": {"string_value": "30"}, "counts_are_final": {"boolean_value": true}}}}}]"#,
+        )
+        .unwrap();
+        let tweet = tweets[0].clone().in_timezone(&"+09:00".parse().unwrap());
+        assert_eq!(
+            Layout::default().render_tweet(&tweet, &Formatter::new()),
+            "- 2023-03-12 22:00:00: どっち？\n  📊 投票結果\n  - 犬: 10 票 (25%)\n  - 猫: 30 票 (75%)"
+        );
+    }
+    #[test]
+    fn test_render_tweet_with_edits() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"id_str": "1", "created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "tpyo", "edit_info": {"initial": {"editTweetIds": ["1", "2"]}}}},
+                {"tweet": {"id_str": "2", "created_at": "Sun Mar 12 13:05:00 +0000 2023", "full_text": "typo", "edit_info": {"edit": {"initialTweetId": "1"}}}}
+            ]"#,
+        )
+        .unwrap()
+        .into_iter()
+        .map(|tweet| tweet.in_timezone(&"+09:00".parse().unwrap()))
+        .collect();
+        let (tweets, _) = crate::edits::collapse(tweets);
+        let formatter = Formatter::new();
+        assert_eq!(
+            Layout::default().render_tweet(&tweets[0], &formatter),
+            "- 2023-03-12 22:05:00: typo"
+        );
+        let layout = Layout {
+            show_edits: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            layout.render_tweet(&tweets[0], &formatter),
+            "- 2023-03-12 22:05:00: typo\n  - ✏️ 編集前 (2023-03-12 22:00:00): tpyo"
+        );
+    }
+    #[test]
+    fn test_render_sensitive_tweet() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "閲覧注意\n本当に", "possibly_sensitive": true}}]"#,
+        )
+        .unwrap();
+        let tweet = tweets[0].clone().in_timezone(&"+09:00".parse().unwrap());
+        let formatter = Formatter::new();
+        let render = |sensitive| {
+            Layout {
+                sensitive,
+                ..Default::default()
+            }
+            .render_tweet(&tweet, &formatter)
+        };
+        assert_eq!(
+            render(SensitiveStyle::Tag),
+            "- 2023-03-12 22:00:00: 閲覧注意\n  本当に #sensitive"
+        );
+        assert_eq!(
+            render(SensitiveStyle::Fold),
+            "- 2023-03-12 22:00:00:\n  > [!warning]- センシティブな内容\n  > 閲覧注意\n  > 本当に"
+        );
+    }
+    #[test]
+    fn test_render_retweet_as_callout() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "RT @hoge: 1行目\n2行目"}}]"#,
+        )
+        .unwrap();
+        let tweet = tweets
+            .into_iter()
+            .next()
+            .unwrap()
+            .in_timezone(&"+09:00".parse().unwrap());
+        let layout = Layout {
+            retweets: RetweetStyle::Callout,
+            ..Default::default()
+        };
+        assert_eq!(
+            layout.render_tweet(&tweet, &Formatter::new()),
+            "- 2023-03-12 22:00:00:\n  > [!quote] RT [[@hoge]]\n  > 1行目\n  > 2行目"
+        );
+    }
+    #[test]
+    fn test_render_tweet_styles() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "1行目\n2行目"}}]"#,
+        )
+        .unwrap();
+        let tweet = tweets
+            .into_iter()
+            .next()
+            .unwrap()
+            .in_timezone(&"+09:00".parse().unwrap());
+        let render = |style| {
+            let layout = Layout {
+                style,
+                ..Default::default()
+            };
+            layout.render_tweet(&tweet, &Formatter::new())
+        };
+        assert_eq!(
+            render(TweetStyle::List),
+            "- 2023-03-12 22:00:00: 1行目\n  2行目"
+        );
+        assert_eq!(
+            render(TweetStyle::Blockquote),
+            "> 2023-03-12 22:00:00\n> 1行目\n> 2行目\n"
+        );
+        assert_eq!(
+            render(TweetStyle::Heading),
+            "###### 2023-03-12 22:00\n1行目\n2行目\n"
+        );
+    }
+    #[test]
+    fn test_render_tweet_with_permalink() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"id_str": "42", "created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "hi"}}]"#,
+        )
+        .unwrap();
+        let tweet = tweets[0].clone().in_timezone(&"+09:00".parse().unwrap());
+        let layout = Layout {
+            permalinks: true,
+            ..Default::default()
+        };
+        // The account is unknown, so there is nothing to link to
+        assert_eq!(
+            layout.render_tweet(&tweet, &Formatter::new()),
+            "- 2023-03-12 22:00:00: hi"
+        );
+        assert_eq!(
+            layout.render_tweet(&tweet, &Formatter::new().with_screen_name("@hoge")),
+            "- [2023-03-12 22:00:00](https://twitter.com/hoge/status/42): hi"
+        );
+    }
+    #[test]
+    fn test_render_tweet_with_attribution() {
+        let tweet = Tweet::from_post(
+            Source::Mastodon,
+            "1".to_string(),
+            "2023-03-12T13:00:00+09:00".parse().unwrap(),
+            "hi",
+            None,
+            Default::default(),
+        )
+        .in_timezone(&"+09:00".parse().unwrap())
+        .with_account("me".to_string());
+        assert_eq!(
+            Layout::default().render_tweet(&tweet, &Formatter::new()),
+            "- 2023-03-12 13:00:00 (Mastodon, @me): hi"
+        );
+    }
+    #[test]
+    fn test_render_tweet_with_day_headings() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "hello"}}]"#,
+        )
+        .unwrap();
+        let tweet = tweets
+            .into_iter()
+            .next()
+            .unwrap()
+            .in_timezone(&"+09:00".parse().unwrap());
+        let layout = Layout {
+            day_headings: true,
+            ..Default::default()
+        };
+        assert_eq!(layout.day_heading(&tweet), "2023-03-12 (Sun)");
+        assert_eq!(
+            layout.render_tweet(&tweet, &Formatter::new()),
+            "- 22:00:00: hello"
+        );
+    }
+    #[test]
+    fn test_sort() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"id_str": "1", "created_at": "Sat Mar 11 04:00:00 +0000 2023", "full_text": "a", "favorite_count": "1"}},
+                {"tweet": {"id_str": "2", "created_at": "Sat Mar 11 05:00:00 +0000 2023", "full_text": "b", "favorite_count": "9"}},
+                {"tweet": {"id_str": "3", "created_at": "Sun Mar 12 04:00:00 +0000 2023", "full_text": "c", "favorite_count": "5"}}
+            ]"#,
+        )
+        .unwrap()
+        .into_iter()
+        .map(|tw| tw.in_timezone(&"UTC".parse().unwrap()))
+        .collect::<Vec<Tweet>>();
+        let sorted = |layout: Layout| {
+            let mut tweets = tweets.iter().collect::<Vec<&Tweet>>();
+            tweets.sort_by(|a, b| layout.compare(a, b));
+            tweets.iter().map(|tweet| tweet.id()).collect::<Vec<&str>>()
+        };
+        assert_eq!(sorted(Layout::default()), vec!["1", "2", "3"]);
+        let layout = Layout {
+            sort: Some(SortOrder::Desc),
+            ..Default::default()
+        };
+        assert_eq!(sorted(layout), vec!["3", "2", "1"]);
+        let layout = Layout {
+            sort_by: SortKey::Engagement,
+            ..Default::default()
+        };
+        assert_eq!(sorted(layout), vec!["2", "3", "1"]);
+        // The days stay in order under their headings
+        let layout = Layout {
+            sort_by: SortKey::Engagement,
+            day_headings: true,
+            ..Default::default()
+        };
+        assert_eq!(sorted(layout), vec!["2", "1", "3"]);
+    }
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("あいうえお", 3), "あいう");
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(
+            truncate("see https://example.com", 10),
+            "see https://example.com"
+        );
+        assert_eq!(
+            truncate("see https://example.com and more", 8),
+            "see https://example.com and"
+        );
+        assert_eq!(truncate("see https://example.com", 2), "se");
+        assert_eq!(truncate("hi @someone", 6), "hi");
+        assert_eq!(truncate("hello world", 8), "hello wo");
+        assert_eq!(truncate("@friend そうですね", 5), "");
+    }
+    #[test]
+    fn test_render_long_tweet_truncated() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"id_str": "42", "created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "とても長いツイートです"}}]"#,
+        )
+        .unwrap();
+        let tweet = tweets
+            .into_iter()
+            .next()
+            .unwrap()
+            .in_timezone(&"+09:00".parse().unwrap());
+        let layout = Layout {
+            truncate_at: Some(5),
+            ..Default::default()
+        };
+        assert!(layout.truncates(&tweet));
+        assert_eq!(
+            layout.render_tweet(&tweet, &Formatter::new()),
+            "- 2023-03-12 22:00:00: とても長い… [[tweet_42|続きを読む]]"
+        );
+    }
+    #[test]
+    fn test_render_long_tweet_truncated_as_read() {
+        let tweets = parse_tweets(
+            r#"[
+            {"tweet": {"id_str": "42", "created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "Q&amp;A https://t.co/abc today",
+                "entities": {"urls": [{"url": "https://t.co/abc", "expanded_url": "https://example.com/qa"}]}}},
+            {"tweet": {"id_str": "43", "created_at": "Sun Mar 12 13:00:00 +0000 2023", "full_text": "RT @friend: とても長いツイートです"}}
+        ]"#,
+        )
+        .unwrap();
+        let tweets: Vec<Tweet> = tweets
+            .into_iter()
+            .map(|tw| tw.in_timezone(&"+09:00".parse().unwrap()))
+            .collect();
+        let layout = Layout {
+            truncate_at: Some(7),
+            retweets: RetweetStyle::Callout,
+            ..Default::default()
+        };
+        assert!(layout.truncates(&tweets[0]));
+        assert_eq!(
+            layout.render_tweet(&tweets[0], &Formatter::new()),
+            "- 2023-03-12 22:00:00: Q&A https://example.com/qa to… [[tweet_42|続きを読む]]"
+        );
+        let layout = Layout {
+            truncate_at: Some(15),
+            ..layout
+        };
+        assert!(layout.truncates(&tweets[1]));
+        assert_eq!(
+            layout.render_tweet(&tweets[1], &Formatter::new()),
+            "- 2023-03-12 22:00:00:\n  > [!quote] RT [[@friend]]\n  > とても… [[tweet_43|続きを読む]]"
+        );
+    }
+}
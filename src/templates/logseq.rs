@@ -0,0 +1,58 @@
+use super::Formatter;
+use crate::tweet::Tweet;
+use chrono::{DateTime, FixedOffset};
+
+/// Folder of the graph the journal pages are in
+pub const JOURNALS_DIR: &str = "journals";
+
+/// Indentation of the following lines of a block
+const BLOCK_INDENT: &str = "  ";
+
+/// File name of the journal page of the day, in the default `:journal/file-name-format` of Logseq
+pub fn journal_file_name(date: &DateTime<FixedOffset>) -> String {
+    date.format("%Y_%m_%d.md").to_string()
+}
+
+/// Render the tweets of a day as the blocks of a Logseq journal page
+///
+/// Each block starts with the time, so that a tweet starting with `TODO` or `LATER` does not
+/// become a task.
+pub fn render_journal(tweets: &[&Tweet], formatter: &Formatter) -> String {
+    tweets
+        .iter()
+        .map(|tweet| {
+            format!(
+                "- {} {}\n",
+                tweet.created_at().format("%H:%M:%S"),
+                formatter.format_indented(tweet.full_text(), tweet, BLOCK_INDENT)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{journal_file_name, render_journal};
+    use crate::templates::Formatter;
+    use crate::tweet::parse_tweets;
+
+    #[test]
+    fn test_render_journal() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "TODO 洗濯\n@hoge も"}},
+                {"tweet": {"created_at": "Sat Mar 11 05:00:00 +0000 2023", "full_text": "done"}}
+            ]"#,
+        )
+        .unwrap();
+        let tweets = tweets
+            .into_iter()
+            .map(|tw| tw.in_timezone(&"UTC".parse().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(journal_file_name(&tweets[0].created_at()), "2023_03_11.md");
+        assert_eq!(
+            render_journal(&tweets.iter().collect::<Vec<_>>(), &Formatter::new()),
+            "- 04:12:48 TODO 洗濯\n  [[@hoge]] も\n- 05:00:00 done\n"
+        );
+    }
+}
@@ -1,28 +1,1027 @@
+pub mod emoji;
+pub mod frontmatter;
+pub mod full_tweet;
+pub mod layout;
+pub mod logseq;
 pub mod monthly_tweets;
-use regex::Regex;
+pub mod single_file;
+pub mod tokens;
+use crate::config::ReplacementRule;
+use crate::ignore::IgnoreList;
+use crate::links;
+use crate::people::{self, PeopleMap};
+use crate::tweet::{decode_html_entities, Source, Tweet};
+use emoji::EmojiStyle;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tokens::Token;
+
+/// Parts of the tweets replaced with placeholders when the notes are shared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Redaction {
+    /// Replace @mentions with `@redacted`
+    Mentions,
+    /// Replace URLs with `[URL]`
+    Urls,
+}
+
+/// How the links are pointed at their snapshots in the Wayback Machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum WaybackMode {
+    /// Follow every link with ` ([archive](snapshot))`
+    Append,
+    /// Replace the links found dead with their snapshots
+    ReplaceDead,
+}
+
+/// Indentation of the following lines of a list item
+const LIST_INDENT: &str = "  ";
+
+/// How @mentions are rendered
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MentionStyle {
+    /// `[[@handle]]`
+    #[default]
+    Wikilink,
+    /// `@handle`, left as it is
+    Plain,
+    /// `[@handle](https://twitter.com/handle)`
+    Profile,
+    /// `[[People/@handle|@handle]]`, a link to the person note in the people folder
+    People,
+}
+
+/// How hashtags are rendered
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HashtagStyle {
+    /// `#foo`, an Obsidian tag
+    #[default]
+    Tag,
+    /// `[[foo]]`
+    Wikilink,
+    /// `\#foo`, escaped so that it stays out of the tag pane
+    Plain,
+}
+
+/// How cashtags such as `$TSLA` are rendered
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CashtagStyle {
+    /// `$TSLA`, left as it is
+    #[default]
+    Plain,
+    /// `#TSLA`, an Obsidian tag
+    Tag,
+    /// `[[$TSLA]]`
+    Wikilink,
+}
+
+/// How the media imported to the attachments folder are referenced
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmbedStyle {
+    /// `![[file.jpg]]`
+    #[default]
+    Wikilink,
+    /// `![](attachments/file.jpg)`, with the path from the root of the vault
+    Markdown,
+    /// `[[file.jpg]]`, linked but not embedded
+    Link,
+}
+
+/// A built-in stage of the Formatter pipeline
+///
+/// The names are the ones used in the `formatter.stages` list of the configuration file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stage {
+    /// Decode `&amp;`, `&lt;`, ... escaped by Twitter
+    EntityDecoding,
+    /// Replace the t.co links with the URLs they point to
+    UrlExpansion,
+    /// Escape the characters Markdown would interpret, outside URLs, mentions and hashtags
+    MarkdownEscaping,
+    /// Replace the emoji with shortcodes or remove them, as configured
+    EmojiConversion,
+    /// Replace URLs with `[URL]` when `--redact urls` is given
+    UrlRedaction,
+    /// Turn @mentions into links to the account notes
+    MentionLinking,
+    /// Render the hashtags in the configured style and escape the ones of the ignore list
+    HashtagRendering,
+    /// Render the cashtags in the configured style
+    CashtagRendering,
+    /// Separate numeric hashtags from the brackets and URLs glued to them
+    HashtagFixes,
+    /// Indent the following lines of multi-line tweets to keep them in the block
+    Indentation,
+    /// Apply the user-defined replacement rules
+    Replacements,
+}
+impl Stage {
+    /// All the built-in stages, in the default order
+    pub const DEFAULT_PIPELINE: [Stage; 11] = [
+        Stage::EntityDecoding,
+        Stage::UrlExpansion,
+        Stage::MarkdownEscaping,
+        Stage::EmojiConversion,
+        Stage::UrlRedaction,
+        Stage::MentionLinking,
+        Stage::HashtagRendering,
+        Stage::CashtagRendering,
+        Stage::HashtagFixes,
+        Stage::Indentation,
+        Stage::Replacements,
+    ];
+    /// Whether the stage transforms the entities or the text between them, which the stages
+    /// next to each other do in a single pass over the tokens of the text
+    fn works_on_tokens(&self) -> bool {
+        !matches!(
+            self,
+            Stage::EntityDecoding | Stage::Indentation | Stage::Replacements
+        )
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::EntityDecoding => "entity-decoding",
+            Stage::UrlExpansion => "url-expansion",
+            Stage::MarkdownEscaping => "markdown-escaping",
+            Stage::EmojiConversion => "emoji-conversion",
+            Stage::UrlRedaction => "url-redaction",
+            Stage::MentionLinking => "mention-linking",
+            Stage::HashtagRendering => "hashtag-rendering",
+            Stage::CashtagRendering => "cashtag-rendering",
+            Stage::HashtagFixes => "hashtag-fixes",
+            Stage::Indentation => "indentation",
+            Stage::Replacements => "replacements",
+        }
+    }
+}
+
+/// A stage added to the Formatter pipeline by library users
+pub trait FormatStage: Send + Sync {
+    /// Name of the stage, used in the logs
+    fn name(&self) -> &str;
+    /// Transform the text formatted by the previous stages
+    fn apply(&self, text: &str, tweet: &Tweet) -> String;
+}
+
+enum PipelineStage {
+    Builtin(Stage),
+    Custom(Box<dyn FormatStage>),
+}
 
 /// Formatter for tweet text
-struct Formatter {
-    re_account: Regex,
-    re_hash_number: Regex,
-    re_hash_url: Regex,
+///
+/// The text goes through an ordered pipeline of stages. The stages transforming the links, the
+/// mentions, the hashtags and the text between them run in a single pass over the tokens of the
+/// text, so that none of them rewrites what another one wrote, such as a mention in a link.
+pub struct Formatter {
+    pipeline: Vec<PipelineStage>,
+    redactions: Vec<Redaction>,
+    mention_style: MentionStyle,
+    hashtag_style: HashtagStyle,
+    cashtag_style: CashtagStyle,
+    emoji_style: EmojiStyle,
+    /// Folder of the notes linked by `MentionStyle::People`
+    people_folder: String,
+    /// Show the display names of the mentions as the text of their links
+    display_names: bool,
+    /// Notes of the people the mentions of their accounts link to
+    people_map: PeopleMap,
+    /// User-defined rules applied by the replacements stage
+    replacements: Vec<ReplacementRule>,
+    /// Lowercased screen names not to link
+    unlinked_accounts: Vec<String>,
+    /// Lowercased hashtags not to render as tags
+    unlinked_hashtags: Vec<String>,
+    /// Folder of the media of the archive, embedded in place of their links when set
+    media_dir: Option<PathBuf>,
+    /// Files of the attachments folder by the names of the media imported to them, embedded like
+    /// the ones of the archive
+    attachments: HashMap<String, String>,
+    /// The attachments folder, for the Markdown embeds
+    attachments_folder: PathBuf,
+    embed_style: EmbedStyle,
+    /// Titles of the linked pages by their URLs, rendered as the text of the links
+    link_titles: HashMap<String, String>,
+    wayback: Option<WaybackMode>,
+    /// URLs of the dead links, replaced with their snapshots by `WaybackMode::ReplaceDead`
+    dead_links: HashSet<String>,
+    /// IDs of the tweets marked with a block ID
+    block_ids: HashSet<String>,
+    /// Names of the notes of the long tweets whose Zettelkasten IDs are taken, by the IDs of the
+    /// tweets
+    full_note_names: HashMap<String, String>,
+    /// Screen name of the account, for the permalinks of the tweets not attributed to one
+    screen_name: Option<String>,
+}
+impl Default for Formatter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl Formatter {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
-            re_account: Regex::new(r"@([a-zA-Z0-9_]+)").unwrap(),
-            re_hash_number: Regex::new(r"#(\d+)([「」『』（）【】:：｜\|]+)").unwrap(),
-            re_hash_url: Regex::new(r"#(\d+)http").unwrap(),
-        }
-    }
-    fn format_text(&self, text: &str) -> String {
-        let mut text = text.replace("\n", "\n  ");
-        text = self.re_account.replace_all(&text, r"[[@$1]]").to_string();
-        text = self
-            .re_hash_number
-            .replace_all(&text, r"#$1 $2")
-            .to_string();
-        text = self.re_hash_url.replace_all(&text, r"#$1 http").to_string();
+            pipeline: Stage::DEFAULT_PIPELINE
+                .iter()
+                .map(|stage| PipelineStage::Builtin(*stage))
+                .collect(),
+            redactions: Vec::new(),
+            mention_style: MentionStyle::default(),
+            hashtag_style: HashtagStyle::default(),
+            cashtag_style: CashtagStyle::default(),
+            emoji_style: EmojiStyle::default(),
+            people_folder: people::DEFAULT_FOLDER.to_string(),
+            display_names: false,
+            people_map: PeopleMap::default(),
+            replacements: Vec::new(),
+            unlinked_accounts: Vec::new(),
+            unlinked_hashtags: Vec::new(),
+            media_dir: None,
+            attachments: HashMap::new(),
+            attachments_folder: PathBuf::new(),
+            embed_style: EmbedStyle::default(),
+            link_titles: HashMap::new(),
+            wayback: None,
+            dead_links: HashSet::new(),
+            block_ids: HashSet::new(),
+            full_note_names: HashMap::new(),
+            screen_name: None,
+        }
+    }
+    /// Replace the built-in stages with the given ones, in order
+    ///
+    /// The stages added with `with_stage` are kept at the end of the pipeline.
+    pub fn with_stages(mut self, stages: &[Stage]) -> Self {
+        self.pipeline
+            .retain(|stage| matches!(stage, PipelineStage::Custom(_)));
+        let custom = std::mem::take(&mut self.pipeline);
+        self.pipeline = stages
+            .iter()
+            .map(|stage| PipelineStage::Builtin(*stage))
+            .chain(custom)
+            .collect();
+        self
+    }
+    /// Remove a built-in stage from the pipeline
+    pub fn without_stage(mut self, stage: Stage) -> Self {
+        self.pipeline
+            .retain(|s| !matches!(s, PipelineStage::Builtin(s) if *s == stage));
+        self
+    }
+    /// Append a stage to the end of the pipeline
+    pub fn with_stage(mut self, stage: Box<dyn FormatStage>) -> Self {
+        self.pipeline.push(PipelineStage::Custom(stage));
+        self
+    }
+    /// Names of the stages of the pipeline, in order
+    pub fn stage_names(&self) -> Vec<&str> {
+        self.pipeline
+            .iter()
+            .map(|stage| match stage {
+                PipelineStage::Builtin(stage) => stage.name(),
+                PipelineStage::Custom(stage) => stage.name(),
+            })
+            .collect()
+    }
+    /// Leave the accounts and hashtags of the ignore list as plain text
+    pub fn with_ignore_list(mut self, ignore_list: &IgnoreList) -> Self {
+        self.unlinked_accounts = ignore_list.unlinked_accounts.clone();
+        self.unlinked_hashtags = ignore_list.unlinked_hashtags.clone();
+        self
+    }
+    /// Replace the given parts of the tweets with placeholders
+    pub fn with_redactions(mut self, redactions: &[Redaction]) -> Self {
+        self.redactions = redactions.to_vec();
+        self
+    }
+    /// Render the @mentions in the given style
+    pub fn with_mention_style(mut self, style: MentionStyle) -> Self {
+        self.mention_style = style;
+        self
+    }
+    /// Render the hashtags in the given style
+    pub fn with_hashtag_style(mut self, style: HashtagStyle) -> Self {
+        self.hashtag_style = style;
+        self
+    }
+    /// Render the cashtags in the given style
+    pub fn with_cashtag_style(mut self, style: CashtagStyle) -> Self {
+        self.cashtag_style = style;
+        self
+    }
+    /// Render the emoji in the given style
+    pub fn with_emoji_style(mut self, style: EmojiStyle) -> Self {
+        self.emoji_style = style;
+        self
+    }
+    /// Link the @mentions to the notes in the folder with `MentionStyle::People`
+    pub fn with_people_folder(mut self, folder: &str) -> Self {
+        self.people_folder = folder.trim_end_matches('/').to_string();
+        self
+    }
+    /// Show the display names the tweets carry for the mentions, e.g. `[[@handle|Name]]`
+    pub fn with_display_names(mut self, display_names: bool) -> Self {
+        self.display_names = display_names;
+        self
+    }
+    /// Link the mentions of the mapped accounts to the notes of the people
+    pub fn with_people_map(mut self, people_map: &PeopleMap) -> Self {
+        self.people_map = people_map.clone();
+        self
+    }
+    /// Embed the media found in `media_dir` as `![[file]]` in place of their links,
+    /// for the media copied to the attachments folder of the vault
+    pub fn with_media_embeds(mut self, media_dir: &Path) -> Self {
+        self.media_dir = Some(media_dir.to_path_buf());
+        self
+    }
+    /// Embed the media of these file names with the files they are imported to in `folder` of
+    /// the vault
+    pub fn with_attachments(mut self, folder: &Path, attachments: HashMap<String, String>) -> Self {
+        self.attachments_folder = folder.to_path_buf();
+        self.attachments = attachments;
+        self
+    }
+    pub fn with_embed_style(mut self, style: EmbedStyle) -> Self {
+        self.embed_style = style;
+        self
+    }
+    /// Render the links of these URLs as `[title](url)`
+    pub fn with_link_titles(mut self, titles: HashMap<String, String>) -> Self {
+        self.link_titles = titles;
+        self
+    }
+    /// Point the links at their snapshots in the Wayback Machine of the time of the tweets, the
+    /// `dead_links` for `WaybackMode::ReplaceDead`
+    pub fn with_wayback(mut self, mode: WaybackMode, dead_links: HashSet<String>) -> Self {
+        self.wayback = Some(mode);
+        self.dead_links = dead_links;
+        self
+    }
+    /// Mark the tweets of the given IDs with a `^<id>` block ID, for the notes linking to them
+    pub fn with_block_ids(mut self, ids: HashSet<String>) -> Self {
+        self.block_ids = ids;
+        self
+    }
+    /// The block ID of the tweet, if it is linked to from another note
+    pub fn block_id(&self, tweet: &Tweet) -> Option<String> {
+        self.block_ids
+            .contains(tweet.id())
+            .then(|| format!("^{}", tweet.id()))
+    }
+    /// Name the notes of the long tweets of the given IDs as given, in place of their Zettelkasten
+    /// IDs taken by other tweets
+    pub fn with_full_note_names(mut self, names: HashMap<String, String>) -> Self {
+        self.full_note_names = names;
+        self
+    }
+    /// The name given to the note of the long tweet, if its Zettelkasten ID is taken
+    pub fn full_note_name(&self, tweet: &Tweet) -> Option<&str> {
+        self.full_note_names.get(tweet.id()).map(String::as_str)
+    }
+    /// Link the tweets to their pages on Twitter under the account of `screen_name`
+    pub fn with_screen_name(mut self, screen_name: &str) -> Self {
+        self.screen_name = Some(screen_name.trim_start_matches('@').to_string());
+        self
+    }
+    /// URL of the tweet on Twitter, when the account it was posted from is known
+    pub fn permalink(&self, tweet: &Tweet) -> Option<String> {
+        if tweet.source() != Source::Twitter {
+            return None;
+        }
+        let screen_name = tweet.account().or(self.screen_name.as_deref())?;
+        Some(format!(
+            "https://twitter.com/{}/status/{}",
+            screen_name,
+            tweet.id()
+        ))
+    }
+    /// Apply the user-defined replacement rules in the replacements stage
+    pub fn with_replacements(mut self, replacements: &[ReplacementRule]) -> Self {
+        self.replacements = replacements.to_vec();
+        self
+    }
+    /// Whether the given part of the tweets is redacted
+    pub fn redacts(&self, redaction: Redaction) -> bool {
+        self.redactions.contains(&redaction)
+    }
+    /// Format the text of a tweet, using its entities
+    pub fn format_tweet(&self, tweet: &Tweet) -> String {
+        self.format(tweet.full_text(), tweet, LIST_INDENT)
+    }
+    /// Format a part of the text of a tweet, prefixing the following lines with `indent`
+    pub fn format_indented(&self, text: &str, tweet: &Tweet, indent: &str) -> String {
+        self.format(text, tweet, indent)
+    }
+    /// Format a text without entities
+    pub fn format_text(&self, text: &str) -> String {
+        self.format(text, &Tweet::default(), LIST_INDENT)
+    }
+    fn format(&self, text: &str, tweet: &Tweet, indent: &str) -> String {
+        let mut text = text.to_string();
+        let mut pass = Vec::new();
+        for stage in self.pipeline.iter() {
+            if let PipelineStage::Builtin(stage) = stage {
+                if stage.works_on_tokens() {
+                    pass.push(*stage);
+                    continue;
+                }
+            }
+            if !pass.is_empty() {
+                text = self.format_tokens(&text, tweet, &pass);
+                pass.clear();
+            }
+            text = match stage {
+                PipelineStage::Builtin(stage) => self.apply(*stage, text, indent),
+                PipelineStage::Custom(stage) => stage.apply(&text, tweet),
+            };
+        }
+        if !pass.is_empty() {
+            text = self.format_tokens(&text, tweet, &pass);
+        }
         text
     }
+    /// Apply a stage working on the whole text
+    fn apply(&self, stage: Stage, text: String, indent: &str) -> String {
+        match stage {
+            Stage::EntityDecoding => decode_html_entities(&text),
+            Stage::Indentation => text.replace('\n', &format!("\n{}", indent)),
+            Stage::Replacements => self
+                .replacements
+                .iter()
+                .fold(text, |text, rule| rule.apply(&text)),
+            _ => unreachable!("the {} stage works on the tokens", stage.name()),
+        }
+    }
+    /// Apply the stages working on the tokens in a single pass over the text
+    fn format_tokens(&self, text: &str, tweet: &Tweet, stages: &[Stage]) -> String {
+        let runs = |stage| stages.contains(&stage);
+        let mut embeds = if runs(Stage::UrlExpansion) {
+            self.media_embeds(tweet)
+        } else {
+            Vec::new()
+        };
+        let tokens = tokens::tokenize(text);
+        let mut formatted = String::with_capacity(text.len());
+        for (i, token) in tokens.iter().enumerate() {
+            match *token {
+                Token::Text(text) => {
+                    let mut text = if runs(Stage::MarkdownEscaping) {
+                        let mut escaped = String::with_capacity(text.len());
+                        escape_markdown(text, &mut escaped);
+                        escaped
+                    } else {
+                        text.to_string()
+                    };
+                    if runs(Stage::EmojiConversion) {
+                        text = self.emoji_style.apply(&text);
+                    }
+                    formatted.push_str(&text);
+                }
+                Token::Markup(markup) => formatted.push_str(markup),
+                Token::Url(url) => {
+                    if runs(Stage::UrlExpansion) {
+                        if let Some((_, files, embedded)) = embeds
+                            .iter_mut()
+                            .filter(|(link, _, _)| url.starts_with(link.as_str()))
+                            .max_by_key(|(link, _, _)| link.len())
+                        {
+                            formatted.push_str(&files.join(" "));
+                            *embedded = true;
+                            continue;
+                        }
+                    }
+                    let url = match tweet
+                        .entities()
+                        .urls
+                        .iter()
+                        .filter(|entity| runs(Stage::UrlExpansion) && url.starts_with(&entity.url))
+                        .max_by_key(|entity| entity.url.len())
+                    {
+                        Some(entity) => {
+                            format!("{}{}", entity.expanded_url, &url[entity.url.len()..])
+                        }
+                        None => url.to_string(),
+                    };
+                    if runs(Stage::UrlRedaction) && self.redacts(Redaction::Urls) {
+                        formatted.push_str("[URL]");
+                        continue;
+                    }
+                    let snapshot = match self.wayback {
+                        Some(_) if links::is_web_page(&url) => {
+                            Some(links::wayback_url(&url, tweet.created_at()))
+                        }
+                        _ => None,
+                    };
+                    let target = match (&snapshot, self.wayback) {
+                        (Some(snapshot), Some(WaybackMode::ReplaceDead))
+                            if self.dead_links.contains(&url) =>
+                        {
+                            snapshot
+                        }
+                        _ => &url,
+                    };
+                    if let Some(title) = self.link_titles.get(&url) {
+                        formatted.push('[');
+                        escape_markdown(title, &mut formatted);
+                        formatted.push_str("](");
+                        formatted.push_str(target);
+                        formatted.push(')');
+                    } else {
+                        formatted.push_str(target);
+                    }
+                    if let (Some(snapshot), Some(WaybackMode::Append)) = (snapshot, self.wayback) {
+                        formatted.push_str(&format!(" ([archive]({}))", snapshot));
+                    }
+                }
+                Token::Mention { written, handle } => {
+                    if runs(Stage::MentionLinking) && is_mention(tweet, handle) {
+                        formatted.push_str(&self.render_mention(handle, tweet));
+                    } else {
+                        formatted.push_str(written);
+                    }
+                }
+                Token::Cashtag { written, symbol } => {
+                    if runs(Stage::CashtagRendering) && is_cashtag(tweet, symbol) {
+                        formatted.push_str(&self.render_cashtag(symbol));
+                    } else {
+                        formatted.push_str(written);
+                    }
+                }
+                Token::Hashtag { written, hashtag } => {
+                    if runs(Stage::HashtagRendering) && is_hashtag(tweet, hashtag) {
+                        formatted.push_str(&self.render_hashtag(hashtag));
+                    } else {
+                        formatted.push_str(written);
+                    }
+                    // Numeric hashtags are separated from the brackets and links glued to them
+                    let glued = match tokens.get(i + 1) {
+                        Some(Token::Url(_)) => true,
+                        Some(Token::Text(text)) => text.starts_with([
+                            '「', '」', '『', '』', '（', '）', '【', '】', ':', '：', '｜', '|',
+                        ]),
+                        _ => false,
+                    };
+                    if runs(Stage::HashtagFixes)
+                        && glued
+                        && hashtag.bytes().all(|b| b.is_ascii_digit())
+                    {
+                        formatted.push(' ');
+                    }
+                }
+            }
+        }
+        // The text of the tweets from the API may leave out the link of the media
+        for (_, files, embedded) in embeds {
+            if !embedded {
+                formatted.push(' ');
+                formatted.push_str(&files.join(" "));
+            }
+        }
+        formatted
+    }
+    fn render_mention(&self, handle: &str, tweet: &Tweet) -> String {
+        if self.redacts(Redaction::Mentions) {
+            // Redacted mentions are not linked, otherwise they would all point at the same note
+            "@redacted".to_string()
+        } else if self.unlinked_accounts.contains(&handle.to_lowercase()) {
+            format!("@{}", handle)
+        } else {
+            self.format_mention(handle, self.display_name(tweet, handle))
+        }
+    }
+    fn render_hashtag(&self, hashtag: &str) -> String {
+        if self.unlinked_hashtags.contains(&hashtag.to_lowercase()) {
+            // Escaped hashtags are not recognized as tags by Obsidian
+            return format!("\\#{}", hashtag);
+        }
+        match self.hashtag_style {
+            HashtagStyle::Tag => format!("#{}", hashtag),
+            HashtagStyle::Wikilink => format!("[[{}]]", hashtag),
+            HashtagStyle::Plain => format!("\\#{}", hashtag),
+        }
+    }
+    fn render_cashtag(&self, symbol: &str) -> String {
+        match self.cashtag_style {
+            CashtagStyle::Plain => format!("${}", symbol),
+            // Obsidian tags take neither `$` nor `.`
+            CashtagStyle::Tag => format!("#{}", symbol.replace('.', "_")),
+            CashtagStyle::Wikilink => format!("[[${}]]", symbol),
+        }
+    }
+    /// The embeds of the media files of the tweet by their links, all the photos of a tweet
+    /// sharing the same link, and whether the link was found in the text
+    fn media_embeds(&self, tweet: &Tweet) -> Vec<(String, Vec<String>, bool)> {
+        if self.media_dir.is_none() && self.attachments.is_empty() {
+            return Vec::new();
+        }
+        let mut embeds: Vec<(String, Vec<String>, bool)> = Vec::new();
+        for media in tweet.entities().media.iter() {
+            let Some(file_name) = media.file_name(tweet.id()).and_then(|file_name| {
+                match self.attachments.get(&file_name) {
+                    Some(attachment) => Some(attachment.clone()),
+                    None => self
+                        .media_dir
+                        .as_ref()
+                        .is_some_and(|media_dir| media_dir.join(&file_name).is_file())
+                        .then_some(file_name),
+                }
+            }) else {
+                continue;
+            };
+            let embed = self.format_embed(&file_name);
+            match embeds.iter_mut().find(|(url, _, _)| *url == media.url) {
+                Some((_, files, _)) => files.push(embed),
+                None => embeds.push((media.url.clone(), vec![embed], false)),
+            }
+        }
+        embeds
+    }
+    fn format_embed(&self, file_name: &str) -> String {
+        match self.embed_style {
+            EmbedStyle::Wikilink => format!("![[{}]]", file_name),
+            EmbedStyle::Link => format!("[[{}]]", file_name),
+            EmbedStyle::Markdown => {
+                let path = self
+                    .attachments_folder
+                    .join(file_name)
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().replace(' ', "%20"))
+                    .collect::<Vec<String>>()
+                    .join("/");
+                format!("![]({})", path)
+            }
+        }
+    }
+    fn format_mention(&self, handle: &str, display_name: Option<String>) -> String {
+        let text = display_name.unwrap_or_else(|| format!("@{}", handle));
+        let note = self.people_map.note_for(handle);
+        match self.mention_style {
+            MentionStyle::Wikilink if note.is_some() => {
+                format!("[[{}|{}]]", note.unwrap_or_default(), text)
+            }
+            MentionStyle::Wikilink if text.starts_with('@') => format!("[[@{}]]", handle),
+            MentionStyle::Wikilink => format!("[[@{}|{}]]", handle, text),
+            MentionStyle::Plain => format!("@{}", handle),
+            MentionStyle::Profile => format!("[{}](https://twitter.com/{})", text, handle),
+            MentionStyle::People => format!(
+                "[[{}|{}]]",
+                people::note_path(&self.people_folder, &self.people_map, handle),
+                text
+            ),
+        }
+    }
+    /// Display name of a mention of the tweet, without the characters that would break the link
+    fn display_name(&self, tweet: &Tweet, handle: &str) -> Option<String> {
+        if !self.display_names {
+            return None;
+        }
+        let mention = tweet
+            .entities()
+            .user_mentions
+            .iter()
+            .find(|mention| mention.screen_name.eq_ignore_ascii_case(handle))?;
+        let name = mention
+            .name
+            .replace(['[', ']', '|', '(', ')'], " ")
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        (!name.is_empty()).then_some(name)
+    }
+}
+
+/// Whether the handle is one of the mentions of the tweet, any handle when the tweet has no
+/// mention entities such as the tweets of the older archives
+fn is_mention(tweet: &Tweet, handle: &str) -> bool {
+    let mentions = &tweet.entities().user_mentions;
+    mentions.is_empty()
+        || mentions
+            .iter()
+            .any(|mention| mention.screen_name.eq_ignore_ascii_case(handle))
+}
+
+/// Whether the hashtag is one of the hashtags of the tweet, any hashtag when the tweet has no
+/// hashtag entities
+fn is_hashtag(tweet: &Tweet, hashtag: &str) -> bool {
+    let hashtags = &tweet.entities().hashtags;
+    hashtags.is_empty()
+        || hashtags
+            .iter()
+            .any(|entity| entity.text.to_lowercase() == hashtag.to_lowercase())
+}
+
+/// Whether the cashtag is one of the symbols of the tweet, any cashtag when the tweet has no
+/// symbol entities
+fn is_cashtag(tweet: &Tweet, symbol: &str) -> bool {
+    let symbols = &tweet.entities().symbols;
+    symbols.is_empty()
+        || symbols
+            .iter()
+            .any(|entity| entity.text.eq_ignore_ascii_case(symbol))
+}
+
+/// Escape the characters with a meaning in Obsidian flavored Markdown
+fn escape_markdown(text: &str, escaped: &mut String) {
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '>' | '|' | '~') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CashtagStyle, EmbedStyle, FormatStage, Formatter, HashtagStyle, MentionStyle, Redaction,
+        Stage, WaybackMode,
+    };
+    use crate::ignore::IgnoreList;
+    use crate::people::PeopleMap;
+    use crate::tweet::{parse_tweets, Tweet};
+    use std::collections::{HashMap, HashSet};
+    use std::path::Path;
+
+    #[test]
+    fn test_format_text() {
+        let formatter = Formatter::new();
+        assert_eq!(
+            formatter.format_text("@hoge #1「すごい」\nnext line"),
+            "[[@hoge]] #1 「すごい」\n  next line"
+        );
+    }
+    #[test]
+    fn test_format_overlaps() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sat Dec 23 12:34:56 +0000 2023", "full_text": "@hoge: https://t.co/abc。 https://mastodon.social/@fuga #1https://t.co/def", "entities": {"urls": [{"url": "https://t.co/abc", "expanded_url": "https://example.com/a_b#c"}, {"url": "https://t.co/def", "expanded_url": "https://example.com/@piyo"}], "user_mentions": [{"screen_name": "hoge", "name": "Hoge #rust"}]}}}]"#,
+        )
+        .unwrap();
+        // The mentions and the hashtags in the links, expanded or not, are left as they are
+        assert_eq!(
+            Formatter::new()
+                .with_hashtag_style(HashtagStyle::Wikilink)
+                .with_display_names(true)
+                .format_tweet(&tweets[0]),
+            "[[@hoge|Hoge #rust]]: https://example.com/a_b#c。 https://mastodon.social/@fuga [[1]] https://example.com/@piyo"
+        );
+    }
+    #[test]
+    fn test_format_unicode_and_entities() {
+        let formatter = Formatter::new();
+        assert_eq!(
+            formatter.format_text("＠hoge ＃東京タワー mail me@example.com"),
+            "[[@hoge]] #東京タワー mail me@example.com"
+        );
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sat Dec 23 12:34:56 +0000 2023", "full_text": "@hoge @everyone #rust #notatag", "entities": {"user_mentions": [{"screen_name": "Hoge", "name": "ほげ"}], "hashtags": [{"text": "Rust"}]}}}]"#,
+        )
+        .unwrap();
+        // Only the mentions and the hashtags in the entities of the archive are linked
+        assert_eq!(
+            formatter
+                .with_hashtag_style(HashtagStyle::Wikilink)
+                .format_tweet(&tweets[0]),
+            "[[@hoge]] @everyone [[rust]] #notatag"
+        );
+    }
+    #[test]
+    fn test_format_link_titles() {
+        let titles = HashMap::from([(
+            "https://example.com/a".to_string(),
+            "A [good] page".to_string(),
+        )]);
+        let formatter = Formatter::new().with_link_titles(titles);
+        assert_eq!(
+            formatter.format_text("see https://example.com/a https://example.com/b"),
+            "see [A \\[good\\] page](https://example.com/a) https://example.com/b"
+        );
+        // Redacted links keep their titles out
+        assert_eq!(
+            formatter
+                .with_redactions(&[Redaction::Urls])
+                .format_text("https://example.com/a"),
+            "[URL]"
+        );
+    }
+    #[test]
+    fn test_format_wayback() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Mon Mar 11 04:12:48 +0000 2013", "full_text": "https://t.co/a https://t.co/b https://twitter.com/hoge/status/1", "entities": {"urls": [{"url": "https://t.co/a", "expanded_url": "https://example.com/a"}, {"url": "https://t.co/b", "expanded_url": "https://example.com/b"}]}}}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Formatter::new()
+                .with_wayback(WaybackMode::Append, HashSet::new())
+                .format_tweet(&tweets[0]),
+            "https://example.com/a ([archive](https://web.archive.org/web/20130311041248/https://example.com/a)) https://example.com/b ([archive](https://web.archive.org/web/20130311041248/https://example.com/b)) https://twitter.com/hoge/status/1"
+        );
+        let dead = HashSet::from(["https://example.com/b".to_string()]);
+        let titles = HashMap::from([("https://example.com/b".to_string(), "B".to_string())]);
+        assert_eq!(
+            Formatter::new()
+                .with_wayback(WaybackMode::ReplaceDead, dead)
+                .with_link_titles(titles)
+                .format_tweet(&tweets[0]),
+            "https://example.com/a [B](https://web.archive.org/web/20130311041248/https://example.com/b) https://twitter.com/hoge/status/1"
+        );
+    }
+    #[test]
+    fn test_cashtag_styles() {
+        let format = |style| {
+            Formatter::new()
+                .with_cashtag_style(style)
+                .format_text("$TSLA $BRK.B")
+        };
+        assert_eq!(format(CashtagStyle::Plain), "$TSLA $BRK.B");
+        assert_eq!(format(CashtagStyle::Tag), "#TSLA #BRK_B");
+        assert_eq!(format(CashtagStyle::Wikilink), "[[$TSLA]] [[$BRK.B]]");
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sat Dec 23 12:34:56 +0000 2023", "full_text": "$tsla $NOPE", "entities": {"symbols": [{"text": "TSLA"}]}}}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Formatter::new()
+                .with_cashtag_style(CashtagStyle::Wikilink)
+                .format_tweet(&tweets[0]),
+            "[[$tsla]] $NOPE"
+        );
+    }
+    #[test]
+    fn test_format_text_with_ignore_list() {
+        let ignore_list = IgnoreList::parse("nolink @bot\nnolink #NowPlaying").unwrap();
+        let formatter = Formatter::new().with_ignore_list(&ignore_list);
+        assert_eq!(
+            formatter.format_text("@bot @hoge #nowplaying #rust"),
+            "@bot [[@hoge]] \\#nowplaying #rust"
+        );
+    }
+    #[test]
+    fn test_format_text_with_redactions() {
+        let formatter = Formatter::new().with_redactions(&[Redaction::Mentions, Redaction::Urls]);
+        assert_eq!(
+            formatter.format_text("@hoge see https://example.com/@fuga #1「x」"),
+            "@redacted see [URL] #1 「x」"
+        );
+    }
+    #[test]
+    fn test_mention_styles() {
+        let format = |style| {
+            Formatter::new()
+                .with_mention_style(style)
+                .format_text("@hoge")
+        };
+        assert_eq!(format(MentionStyle::Wikilink), "[[@hoge]]");
+        assert_eq!(format(MentionStyle::Plain), "@hoge");
+        assert_eq!(
+            format(MentionStyle::Profile),
+            "[@hoge](https://twitter.com/hoge)"
+        );
+        assert_eq!(format(MentionStyle::People), "[[People/@hoge|@hoge]]");
+        let formatter = Formatter::new()
+            .with_mention_style(MentionStyle::People)
+            .with_people_folder("Contacts/Twitter/");
+        assert_eq!(
+            formatter.format_text("@hoge"),
+            "[[Contacts/Twitter/@hoge|@hoge]]"
+        );
+        let people_map = PeopleMap::parse("@matz -> People/Yukihiro Matsumoto").unwrap();
+        let formatter = Formatter::new().with_people_map(&people_map);
+        assert_eq!(
+            formatter.format_text("@matz @hoge"),
+            "[[People/Yukihiro Matsumoto|@matz]] [[@hoge]]"
+        );
+    }
+    #[test]
+    fn test_mention_display_names() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "@Hoge @fuga @piyo",
+                "entities": {"user_mentions": [{"screen_name": "hoge", "name": "ほげ [公式]"}, {"screen_name": "fuga", "name": ""}]}}}]"#,
+        )
+        .unwrap();
+        let formatter = Formatter::new().with_display_names(true);
+        assert_eq!(
+            formatter.format_tweet(&tweets[0]),
+            "[[@Hoge|ほげ 公式]] [[@fuga]] @piyo"
+        );
+        assert_eq!(
+            formatter
+                .with_mention_style(MentionStyle::Profile)
+                .format_tweet(&tweets[0]),
+            "[ほげ 公式](https://twitter.com/Hoge) [@fuga](https://twitter.com/fuga) @piyo"
+        );
+    }
+    #[test]
+    fn test_hashtag_styles() {
+        let ignore_list = IgnoreList::parse("nolink #NowPlaying").unwrap();
+        let format = |style| {
+            Formatter::new()
+                .with_ignore_list(&ignore_list)
+                .with_hashtag_style(style)
+                .format_text("#rust #nowplaying")
+        };
+        assert_eq!(format(HashtagStyle::Tag), "#rust \\#nowplaying");
+        assert_eq!(format(HashtagStyle::Wikilink), "[[rust]] \\#nowplaying");
+        assert_eq!(format(HashtagStyle::Plain), "\\#rust \\#nowplaying");
+    }
+    #[test]
+    fn test_format_text_escapes_markdown() {
+        let formatter = Formatter::new();
+        assert_eq!(
+            formatter.format_text("*bold* a|b > `x` @foo_bar #snake_case https://example.com/a_b"),
+            "\\*bold\\* a\\|b \\> \\`x\\` [[@foo_bar]] #snake_case https://example.com/a_b"
+        );
+        let formatter = Formatter::new().without_stage(Stage::MarkdownEscaping);
+        assert_eq!(formatter.format_text("*bold*"), "*bold*");
+    }
+    #[test]
+    fn test_format_tweet_expands_urls() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sat Dec 23 12:34:56 +0000 2023", "full_text": "Q&amp;A https://t.co/abc", "entities": {"urls": [{"url": "https://t.co/abc", "expanded_url": "https://example.com/qa"}]}}}]"#,
+        )
+        .unwrap();
+        let formatter = Formatter::new();
+        assert_eq!(
+            formatter.format_tweet(&tweets[0]),
+            "Q&A https://example.com/qa"
+        );
+    }
+    #[test]
+    fn test_format_tweet_embeds_media() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"id_str": "1", "created_at": "Sat Dec 23 12:34:56 +0000 2023", "full_text": "cats_and_dogs https://t.co/img", "entities": {"media": [{"url": "https://t.co/img", "media_url_https": "https://pbs.twimg.com/media/cat.jpg"}, {"url": "https://t.co/img", "media_url_https": "https://pbs.twimg.com/media/dog.jpg"}, {"url": "https://t.co/img", "media_url_https": "https://pbs.twimg.com/media/lost.jpg"}]}}}]"#,
+        )
+        .unwrap();
+        let media_dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-embeds-{}", std::process::id()));
+        std::fs::create_dir_all(&media_dir).unwrap();
+        std::fs::write(media_dir.join("1-cat.jpg"), "").unwrap();
+        std::fs::write(media_dir.join("1-dog.jpg"), "").unwrap();
+        assert_eq!(
+            Formatter::new().format_tweet(&tweets[0]),
+            "cats\\_and\\_dogs https://t.co/img"
+        );
+        // The files missing from the archive are not embedded
+        assert_eq!(
+            Formatter::new()
+                .with_media_embeds(&media_dir)
+                .format_tweet(&tweets[0]),
+            "cats\\_and\\_dogs ![[1-cat.jpg]] ![[1-dog.jpg]]"
+        );
+        // Nor the ones imported to the attachments folder, downloaded for instance
+        assert_eq!(
+            Formatter::new()
+                .with_attachments(
+                    Path::new(""),
+                    HashMap::from([("1-lost.jpg".to_string(), "1-lost.jpg".to_string())])
+                )
+                .format_tweet(&tweets[0]),
+            "cats\\_and\\_dogs ![[1-lost.jpg]]"
+        );
+        // The media of the same content share a file
+        let attachments = HashMap::from([
+            ("1-cat.jpg".to_string(), "0-cat.jpg".to_string()),
+            ("1-dog.jpg".to_string(), "1-dog.jpg".to_string()),
+        ]);
+        assert_eq!(
+            Formatter::new()
+                .with_attachments(Path::new("My Files/twitter"), attachments.clone())
+                .with_embed_style(EmbedStyle::Markdown)
+                .format_tweet(&tweets[0]),
+            "cats\\_and\\_dogs ![](My%20Files/twitter/0-cat.jpg) ![](My%20Files/twitter/1-dog.jpg)"
+        );
+        assert_eq!(
+            Formatter::new()
+                .with_attachments(Path::new(""), attachments)
+                .with_embed_style(EmbedStyle::Link)
+                .format_tweet(&tweets[0]),
+            "cats\\_and\\_dogs [[0-cat.jpg]] [[1-dog.jpg]]"
+        );
+        std::fs::remove_dir_all(&media_dir).unwrap();
+    }
+    #[test]
+    fn test_configured_pipeline() {
+        struct Shout;
+        impl FormatStage for Shout {
+            fn name(&self) -> &str {
+                "shout"
+            }
+            fn apply(&self, text: &str, _tweet: &Tweet) -> String {
+                text.to_uppercase()
+            }
+        }
+        let formatter = Formatter::new()
+            .with_stage(Box::new(Shout))
+            .with_stages(&[Stage::HashtagFixes]);
+        assert_eq!(formatter.stage_names(), vec!["hashtag-fixes", "shout"]);
+        assert_eq!(formatter.format_text("@hoge #1「x」"), "@HOGE #1 「X」");
+    }
 }
@@ -1,12 +1,20 @@
+use super::frontmatter::Frontmatter;
+use super::layout::{self, Layout, Section, TOP_TWEETS_COUNT};
 use super::Formatter;
+use crate::error::{Error, Result};
+use crate::granularity::Granularity;
+use crate::locale::Locale;
 use crate::tweet::Tweet;
-use anyhow::Result;
-use chrono::{DateTime, Datelike, Local, Timelike};
+use chrono::{DateTime, Datelike, FixedOffset, TimeZone, Timelike, Weekday};
 use handlebars::Handlebars;
-use log::error;
 use serde::Serialize;
-use std::fs::File;
-use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::Write;
+
+/// The templates, built into the binary so that no file has to be found at run time
+const TEMPLATE: &str = include_str!("monthly_tweets.hbs");
+const ORG_TEMPLATE: &str = include_str!("monthly_tweets.org.hbs");
 
 #[derive(Debug, Serialize, PartialEq)]
 struct TweetCountByHour {
@@ -26,17 +34,69 @@ impl TweetCountByHour {
     }
 }
 
+/// Number of tweets posted on a weekday
+#[derive(Debug, Serialize, PartialEq)]
+struct WeekdayCount {
+    weekday: String,
+    tweet_count: usize,
+}
+
+/// Number of tweets posted in a month, for the notes spanning several months
+#[derive(Debug, Serialize, PartialEq)]
+struct MonthCount {
+    month: String,
+    tweet_count: usize,
+}
+
+/// Number of tweets posted from a place
+#[derive(Debug, Serialize, PartialEq)]
+struct PlaceCount {
+    name: String,
+    tweet_count: usize,
+}
+
 #[derive(Debug, Serialize, PartialEq)]
 struct ActivityStats {
     tweet_count: usize,
     retweet_count: usize,
     reply_count: usize,
     tweet_count_by_hour: Vec<TweetCountByHour>,
+    /// From Monday to Sunday
+    tweet_count_by_weekday: Vec<WeekdayCount>,
+    /// Empty unless the tweets span several months
+    tweet_count_by_month: Vec<MonthCount>,
+    /// The places tweeted from, the most frequent first
+    places: Vec<PlaceCount>,
 }
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 struct FormattedTweet {
+    id: String,
     created_at: String,
+    /// Date, weekday and time as in `2023-03-11 Sat 04:12`, the form of Org timestamps
+    timestamp: String,
+    /// Heading of the day the tweet was posted
+    day: String,
     text: String,
+    /// The tweet rendered in the layout of the note
+    entry: String,
+}
+
+/// Tweets of a day, listed under its heading
+#[derive(Debug, Serialize)]
+struct DaySection {
+    heading: String,
+    tweets: Vec<FormattedTweet>,
+}
+
+/// The place of a note among the notes a heavy period is split into
+#[derive(Debug, Serialize)]
+struct NotePart {
+    /// From 1
+    index: usize,
+    count: usize,
+    /// Names of the notes of the parts before and after it
+    previous: Option<String>,
+    next: Option<String>,
 }
 
 /// input data for the monthly_tweets template
@@ -46,37 +106,96 @@ pub struct MonthlyTweetsTemplateInput {
     file_created_at: String,
     month: String,
     year: String,
+    /// Heading of the period of the note, such as 2023年03月
+    title: String,
+    tags: Vec<String>,
+    aliases: Vec<String>,
     stats: ActivityStats,
     tweets: Vec<FormattedTweet>,
+    /// The tweets grouped by day, empty unless the layout has day headings
+    days: Vec<DaySection>,
+    /// Set when the tweets of the period are split into several notes
+    part: Option<NotePart>,
+    /// Sections of the note, in order
+    sections: Vec<Section>,
+    /// The most liked and retweeted tweets, empty unless the note has a section of them
+    top_tweets: Vec<FormattedTweet>,
 }
 
 impl MonthlyTweetsTemplateInput {
-    fn format_tweets(tweets: &[&Tweet]) -> Vec<FormattedTweet> {
-        let formatter = Formatter::new();
-        let mut formatted_tweets = tweets
+    fn format_tweets(
+        tweets: &[&Tweet],
+        formatter: &Formatter,
+        layout: &Layout,
+    ) -> Vec<FormattedTweet> {
+        let mut tweets = tweets.to_vec();
+        tweets.sort_by(|a, b| layout.compare(a, b));
+        tweets
             .iter()
             .map(|tw| FormattedTweet {
+                id: tw.id().to_string(),
                 created_at: tw.created_at().format("%Y-%m-%d %H:%M:%S").to_string(),
-                text: formatter.format_text(tw.full_text()),
+                timestamp: tw.created_at().format("%Y-%m-%d %a %H:%M").to_string(),
+                text: formatter.format_tweet(tw),
+                day: layout.day_heading(tw),
+                entry: layout.render_tweet(tw, formatter),
             })
-            .collect::<Vec<FormattedTweet>>();
-        formatted_tweets.sort_by(|a, b| a.created_at.cmp(&b.created_at));
-        formatted_tweets
+            .collect::<Vec<FormattedTweet>>()
     }
-    fn extract_earliest_tweet_created_at(tweets: &[&Tweet]) -> DateTime<Local> {
-        let first_tweet = tweets
+    fn select_top_tweets(
+        tweets: &[&Tweet],
+        formatter: &Formatter,
+        layout: &Layout,
+    ) -> Vec<FormattedTweet> {
+        let mut tweets = tweets
             .iter()
-            .min_by(|a, b| a.created_at().cmp(&b.created_at()))
-            .unwrap();
-        first_tweet.created_at()
+            .copied()
+            .filter(|tw| layout::engagement(tw) > 0)
+            .collect::<Vec<&Tweet>>();
+        tweets.sort_by(|a, b| {
+            layout::engagement(b)
+                .cmp(&layout::engagement(a))
+                .then_with(|| a.created_at().cmp(&b.created_at()))
+        });
+        tweets.truncate(TOP_TWEETS_COUNT);
+        // Out of the tweets of their day, they need their date
+        let layout = Layout {
+            day_headings: false,
+            sort: None,
+            sort_by: layout::SortKey::Engagement,
+            ..layout.clone()
+        };
+        Self::format_tweets(&tweets, formatter, &layout)
     }
-    fn format_id(created_at: &DateTime<Local>) -> String {
-        created_at.format("%Y%m%d%H%M%S%3f").to_string()
+    fn group_by_day(tweets: &[FormattedTweet]) -> Vec<DaySection> {
+        let mut days: Vec<DaySection> = Vec::new();
+        for tweet in tweets.iter() {
+            match days.last_mut() {
+                Some(day) if day.heading == tweet.day => day.tweets.push(tweet.clone()),
+                _ => days.push(DaySection {
+                    heading: tweet.day.clone(),
+                    tweets: vec![tweet.clone()],
+                }),
+            }
+        }
+        days
+    }
+    fn extract_earliest_tweet_created_at(tweets: &[&Tweet]) -> Option<DateTime<FixedOffset>> {
+        tweets.iter().map(|tw| tw.created_at()).min()
     }
-    fn format_file_created_at(created_at: &DateTime<Local>) -> String {
+    fn format_id<Tz: TimeZone>(created_at: &DateTime<Tz>) -> String
+    where
+        Tz::Offset: Display,
+    {
+        created_at.format(layout::ZETTEL_ID_FORMAT).to_string()
+    }
+    fn format_file_created_at<Tz: TimeZone>(created_at: &DateTime<Tz>) -> String
+    where
+        Tz::Offset: Display,
+    {
         created_at.format("%Y-%m-%d %H:%M:%S").to_string()
     }
-    fn generate_activity_stats(tweets: &[&Tweet]) -> ActivityStats {
+    fn generate_activity_stats(tweets: &[&Tweet], locale: Locale) -> ActivityStats {
         let mut tweet_count_by_hour = [0; 24]
             .iter()
             .enumerate()
@@ -93,38 +212,146 @@ impl MonthlyTweetsTemplateInput {
                 tweet_count_by_hour[hour].reply_count += 1;
             }
         }
+        let mut weekdays = [0; 7];
+        let mut months = BTreeMap::new();
+        for tweet in tweets.iter() {
+            let created_at = tweet.created_at();
+            weekdays[created_at.weekday().num_days_from_monday() as usize] += 1;
+            *months.entry(created_at.month()).or_insert(0) += 1;
+        }
+        let tweet_count_by_weekday = weekdays
+            .iter()
+            .enumerate()
+            .map(|(i, &tweet_count)| WeekdayCount {
+                weekday: locale
+                    .weekday(Weekday::try_from(i as u8).unwrap())
+                    .to_string(),
+                tweet_count,
+            })
+            .collect();
+        let tweet_count_by_month = if months.len() > 1 {
+            months
+                .into_iter()
+                .map(|(month, tweet_count)| MonthCount {
+                    month: locale.month(month),
+                    tweet_count,
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
         let tweet_count = tweets.len();
         let retweet_count = tweets.iter().filter(|tw| tw.is_retweet()).count();
         let reply_count = tweets.iter().filter(|tw| tw.is_reply()).count();
+        let mut places: Vec<PlaceCount> = Vec::new();
+        for geo in tweets.iter().filter_map(|tw| tw.geo()) {
+            let name = geo.name();
+            match places.iter_mut().find(|place| place.name == name) {
+                Some(place) => place.tweet_count += 1,
+                None => places.push(PlaceCount {
+                    name,
+                    tweet_count: 1,
+                }),
+            }
+        }
+        places.sort_by(|a, b| {
+            b.tweet_count
+                .cmp(&a.tweet_count)
+                .then_with(|| a.name.cmp(&b.name))
+        });
         ActivityStats {
             tweet_count,
             retweet_count,
             reply_count,
             tweet_count_by_hour,
+            tweet_count_by_weekday,
+            tweet_count_by_month,
+            places,
         }
     }
 
+    /// Make the input the one of the note of the deleted tweets of the period
+    pub fn for_deleted_tweets(mut self) -> Self {
+        self.title = format!("{} (削除済み)", self.title);
+        self
+    }
+    /// Make the input the one of a part of the tweets of a period split into `count` notes,
+    /// linking to the notes of the parts before and after it
+    pub fn for_part(
+        mut self,
+        index: usize,
+        count: usize,
+        previous: Option<String>,
+        next: Option<String>,
+    ) -> Self {
+        self.title = format!("{} ({}/{})", self.title, index, count);
+        self.part = Some(NotePart {
+            index,
+            count,
+            previous,
+            next,
+        });
+        self
+    }
+    /// Make the input the one of the note of the tweets of the period carrying the hashtag
+    ///
+    /// The aliases are the ones of the note of the period, so they are left out.
+    pub fn for_hashtag(mut self, hashtag: &str) -> Self {
+        self.title = format!("{} #{}", self.title, hashtag);
+        self.aliases.clear();
+        self
+    }
     /// create a new MonthlyTweetsTemplateInput from the given tweets
-    pub fn new(tweets: &[&Tweet]) -> Result<Self> {
-        let (year, month, id, file_created_at) = {
-            let earliest_tweet_created_at = Self::extract_earliest_tweet_created_at(tweets);
+    ///
+    /// Returns `Error::NoTweets` if `tweets` is empty.
+    pub fn new(
+        tweets: &[&Tweet],
+        formatter: &Formatter,
+        layout: &Layout,
+        frontmatter: &Frontmatter,
+        granularity: Granularity,
+    ) -> Result<Self> {
+        let (year, month, title, tags, aliases, id, file_created_at) = {
+            let earliest_tweet_created_at =
+                Self::extract_earliest_tweet_created_at(tweets).ok_or(Error::NoTweets)?;
             (
                 earliest_tweet_created_at.year().to_string(),
                 format!("{:02}", earliest_tweet_created_at.month()),
+                granularity.title(&earliest_tweet_created_at),
+                frontmatter.tags(&earliest_tweet_created_at),
+                frontmatter.aliases(&earliest_tweet_created_at),
                 Self::format_id(&earliest_tweet_created_at),
                 Self::format_file_created_at(&earliest_tweet_created_at),
             )
         };
-        let stats = Self::generate_activity_stats(tweets);
-        let formatted_tweets = Self::format_tweets(tweets);
+        let stats = Self::generate_activity_stats(tweets, layout.locale);
+        let formatted_tweets = Self::format_tweets(tweets, formatter, layout);
+        let days = if layout.day_headings {
+            Self::group_by_day(&formatted_tweets)
+        } else {
+            Vec::new()
+        };
+        let sections = layout.sections().to_vec();
+        let top_tweets = if sections.contains(&Section::TopTweets) {
+            Self::select_top_tweets(tweets, formatter, layout)
+        } else {
+            Vec::new()
+        };
 
         Ok(Self {
             id,
             file_created_at,
             month,
             year,
+            title,
+            tags,
+            aliases,
             stats,
             tweets: formatted_tweets,
+            days,
+            part: None,
+            sections,
+            top_tweets,
         })
     }
 }
@@ -136,31 +363,24 @@ impl<'a> MonthlyTweetsTemplate<'a> {
     const TEMPLATE_NAME: &'static str = "monthly_tweets";
     /// Create a new MonthlyTweetsTemplate
     pub fn new() -> Result<Self> {
+        Self::from_template(TEMPLATE)
+    }
+    /// Create a new MonthlyTweetsTemplate rendering Org files instead of Markdown notes
+    pub fn org() -> Result<Self> {
+        Self::from_template(ORG_TEMPLATE)
+    }
+    fn from_template(template: &str) -> Result<Self> {
         let mut handlebars = Handlebars::new();
-        let tpl_path = MonthlyTweetsTemplate::get_template_path();
-        if let Err(e) = handlebars.register_template_file(Self::TEMPLATE_NAME, &tpl_path) {
-            error!(
-                "Failed to register the template file {}: {}",
-                tpl_path.display(),
-                e
-            );
-            std::process::exit(1);
-        }
+        // The notes are Markdown, and the text is already escaped by the Formatter
+        handlebars.register_escape_fn(handlebars::no_escape);
+        handlebars.register_template_string(Self::TEMPLATE_NAME, template)?;
         Ok(Self { handlebars })
     }
 
-    fn get_template_path() -> PathBuf {
-        let current_file_path = Path::new(file!());
-        let current_file_dir = current_file_path.parent().unwrap();
-        current_file_dir
-            .join(Self::TEMPLATE_NAME)
-            .with_extension("hbs")
-    }
-
-    /// Render file with the given input
-    pub fn render(&self, input: &MonthlyTweetsTemplateInput, file: &mut File) -> Result<()> {
+    /// Render the note with the given input to `writer`
+    pub fn render(&self, input: &MonthlyTweetsTemplateInput, writer: impl Write) -> Result<()> {
         self.handlebars
-            .render_to_write(Self::TEMPLATE_NAME, &input, file)?;
+            .render_to_write(Self::TEMPLATE_NAME, &input, writer)?;
         Ok(())
     }
 }
@@ -170,9 +390,9 @@ mod tests {
     use chrono::TimeZone;
 
     #[test]
-    fn test_get_template_path() {
-        let path = super::MonthlyTweetsTemplate::get_template_path();
-        assert!(path.exists());
+    fn test_new() {
+        assert!(super::MonthlyTweetsTemplate::new().is_ok());
+        assert!(super::MonthlyTweetsTemplate::org().is_ok());
     }
     #[test]
     fn test_format_id() {
@@ -192,6 +412,35 @@ mod tests {
         assert_eq!(file_created_at, "2023-03-11 04:12:48");
     }
     #[test]
+    fn test_for_hashtag() {
+        let tweets = crate::tweet::parse_tweets(
+            r##"[{"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "#rust"}}]"##,
+        )
+        .unwrap();
+        let input = super::MonthlyTweetsTemplateInput::new(
+            &tweets.iter().collect::<Vec<&super::Tweet>>(),
+            &super::Formatter::new(),
+            &super::Layout::default(),
+            &super::Frontmatter::default(),
+            super::Granularity::Month,
+        )
+        .unwrap()
+        .for_hashtag("rust");
+        assert_eq!(input.title, "2023年03月 #rust");
+        assert!(input.aliases.is_empty());
+    }
+    #[test]
+    fn test_new_with_no_tweets() {
+        let actual = super::MonthlyTweetsTemplateInput::new(
+            &[],
+            &super::Formatter::new(),
+            &super::Layout::default(),
+            &super::Frontmatter::default(),
+            super::Granularity::Month,
+        );
+        assert!(matches!(actual, Err(super::Error::NoTweets)));
+    }
+    #[test]
     fn test_generate_activity_stats() {
         let tweet1 = super::Tweet::new_with_local_datetime(
             chrono::Local
@@ -214,9 +463,10 @@ mod tests {
             "@hoge tweet3".to_string(),
             true,
         );
-        let actual = super::MonthlyTweetsTemplateInput::generate_activity_stats(&vec![
-            &tweet1, &tweet2, &tweet3,
-        ]);
+        let actual = super::MonthlyTweetsTemplateInput::generate_activity_stats(
+            &[&tweet1, &tweet2, &tweet3],
+            super::Locale::Ja,
+        );
         let expected = super::ActivityStats {
             tweet_count: 3,
             retweet_count: 1,
@@ -262,6 +512,23 @@ mod tests {
                     reply_count: 1,
                 },
             ],
+            tweet_count_by_weekday: [
+                ("月", 0),
+                ("火", 1),
+                ("水", 1),
+                ("木", 0),
+                ("金", 0),
+                ("土", 0),
+                ("日", 1),
+            ]
+            .into_iter()
+            .map(|(weekday, tweet_count)| super::WeekdayCount {
+                weekday: weekday.to_string(),
+                tweet_count,
+            })
+            .collect(),
+            tweet_count_by_month: Vec::new(),
+            places: Vec::new(),
         };
 
         for (actual, expected) in actual
@@ -274,5 +541,93 @@ mod tests {
         assert_eq!(actual.tweet_count, expected.tweet_count);
         assert_eq!(actual.retweet_count, expected.retweet_count);
         assert_eq!(actual.reply_count, expected.reply_count);
+        assert_eq!(
+            actual.tweet_count_by_weekday,
+            expected.tweet_count_by_weekday
+        );
+        assert_eq!(actual.tweet_count_by_month, expected.tweet_count_by_month);
+        assert_eq!(actual.places, expected.places);
+    }
+    #[test]
+    fn test_generate_place_stats() {
+        let tweets = crate::tweet::parse_tweets(
+            r#"[
+                {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "a", "place": {"full_name": "Kyoto"}}},
+                {"tweet": {"created_at": "Sat Mar 11 05:12:48 +0000 2023", "full_text": "b", "place": {"full_name": "Tokyo"}}},
+                {"tweet": {"created_at": "Sat Mar 11 06:12:48 +0000 2023", "full_text": "c", "place": {"full_name": "Tokyo"}}},
+                {"tweet": {"created_at": "Sat Mar 11 07:12:48 +0000 2023", "full_text": "d"}}
+            ]"#,
+        )
+        .unwrap();
+        let stats = super::MonthlyTweetsTemplateInput::generate_activity_stats(
+            &tweets.iter().collect::<Vec<&super::Tweet>>(),
+            super::Locale::En,
+        );
+        let places = stats
+            .places
+            .iter()
+            .map(|place| (place.name.as_str(), place.tweet_count))
+            .collect::<Vec<(&str, usize)>>();
+        assert_eq!(places, vec![("Tokyo", 2), ("Kyoto", 1)]);
+        assert!(stats.tweet_count_by_month.is_empty());
+    }
+    #[test]
+    fn test_generate_month_stats() {
+        let tweets = crate::tweet::parse_tweets(
+            r#"[
+                {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "a"}},
+                {"tweet": {"created_at": "Tue Jan 10 05:12:48 +0000 2023", "full_text": "b"}},
+                {"tweet": {"created_at": "Sun Mar 12 06:12:48 +0000 2023", "full_text": "c"}}
+            ]"#,
+        )
+        .unwrap();
+        let stats = super::MonthlyTweetsTemplateInput::generate_activity_stats(
+            &tweets.iter().collect::<Vec<&super::Tweet>>(),
+            super::Locale::Ja,
+        );
+        let months = stats
+            .tweet_count_by_month
+            .iter()
+            .map(|month| (month.month.as_str(), month.tweet_count))
+            .collect::<Vec<(&str, usize)>>();
+        assert_eq!(months, vec![("1月", 1), ("3月", 2)]);
+    }
+    #[test]
+    fn test_render_sections() {
+        let tweets = crate::tweet::parse_tweets(
+            r#"[
+                {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "a", "favorite_count": "1"}},
+                {"tweet": {"created_at": "Sun Mar 12 04:12:48 +0000 2023", "full_text": "b", "favorite_count": "3"}},
+                {"tweet": {"created_at": "Mon Mar 13 04:12:48 +0000 2023", "full_text": "c"}}
+            ]"#,
+        )
+        .unwrap();
+        let layout: super::Layout =
+            serde_json::from_str(r#"{"sections": ["top-tweets", "tweets"]}"#).unwrap();
+        let input = super::MonthlyTweetsTemplateInput::new(
+            &tweets.iter().collect::<Vec<&super::Tweet>>(),
+            &super::Formatter::new(),
+            &layout,
+            &super::Frontmatter::default(),
+            super::Granularity::Month,
+        )
+        .unwrap();
+        let mut content = Vec::new();
+        super::MonthlyTweetsTemplate::new()
+            .unwrap()
+            .render(&input, &mut content)
+            .unwrap();
+        let content = String::from_utf8(content).unwrap();
+        assert!(!content.contains("のサマリ"));
+        assert!(!content.contains("| 時間帯 |"));
+        let (top, list) = content.split_once("## 2023年03月 のツイート一覧").unwrap();
+        let top = top.split_once("## 2023年03月 の人気ツイート").unwrap().1;
+        let top = top
+            .lines()
+            .filter_map(|line| line.rsplit_once(": "))
+            .map(|(_, text)| text)
+            .collect::<Vec<&str>>();
+        assert_eq!(top, vec!["b", "a"]);
+        assert_eq!(list.matches("\n- ").count(), 3);
     }
 }
@@ -0,0 +1,81 @@
+use super::layout::Layout;
+use super::Formatter;
+use crate::tweet::Tweet;
+use chrono::Datelike;
+use std::fmt::Write;
+
+/// Name of the note all the tweets are written to with `--single-file`
+pub const NOTE_NAME: &str = "tweets";
+
+/// Name of the file of the note
+pub const FILE_NAME: &str = "tweets.md";
+
+/// Render all the tweets as a single Markdown file, under a heading per year and month
+///
+/// The tweets are rendered in full, since there is no note of the long tweets to link to.
+pub fn render(tweets: &[&Tweet], formatter: &Formatter, layout: &Layout) -> String {
+    let layout = Layout {
+        truncate_at: None,
+        ..layout.clone()
+    };
+    let mut tweets = tweets.to_vec();
+    tweets.sort_by_key(|tweet| tweet.created_at());
+    let mut content = format!(
+        "---\ntags:\n  - ImportedNote/Twitter\ntweet_count: {}\n---\n",
+        tweets.len()
+    );
+    for year in tweets.chunk_by(|a, b| a.created_at().year() == b.created_at().year()) {
+        write!(content, "\n# {}\n", year[0].created_at().format("%Y年")).unwrap();
+        for month in year.chunk_by(|a, b| a.created_at().month() == b.created_at().month()) {
+            write!(
+                content,
+                "\n## {}\n\n",
+                month[0].created_at().format("%Y年%m月")
+            )
+            .unwrap();
+            let mut month = month.to_vec();
+            month.sort_by(|a, b| layout.compare(a, b));
+            let mut day = None;
+            for tweet in month {
+                if layout.day_headings && day != Some(tweet.created_at().date_naive()) {
+                    day = Some(tweet.created_at().date_naive());
+                    write!(content, "### {}\n\n", layout.day_heading(tweet)).unwrap();
+                }
+                writeln!(content, "{}", layout.render_tweet(tweet, formatter)).unwrap();
+            }
+        }
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::templates::{layout::Layout, Formatter};
+    use crate::tweet::parse_tweets;
+
+    #[test]
+    fn test_render() {
+        let tweets = parse_tweets(
+            r#"[
+                {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "b"}},
+                {"tweet": {"created_at": "Sat Dec 31 23:00:00 +0000 2022", "full_text": "a"}},
+                {"tweet": {"created_at": "Sun Mar 12 05:00:00 +0000 2023", "full_text": "c"}}
+            ]"#,
+        )
+        .unwrap();
+        let tweets = tweets
+            .into_iter()
+            .map(|tw| tw.in_timezone(&"UTC".parse().unwrap()))
+            .collect::<Vec<_>>();
+        let content = render(
+            &tweets.iter().collect::<Vec<_>>(),
+            &Formatter::new(),
+            &Layout::default(),
+        );
+        assert_eq!(
+            content,
+            "---\ntags:\n  - ImportedNote/Twitter\ntweet_count: 3\n---\n\n# 2022年\n\n## 2022年12月\n\n- 2022-12-31 23:00:00: a\n\n# 2023年\n\n## 2023年03月\n\n- 2023-03-11 04:12:48: b\n- 2023-03-12 05:00:00: c\n"
+        );
+    }
+}
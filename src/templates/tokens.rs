@@ -0,0 +1,202 @@
+//! The tweet text split into the entities the Formatter transforms and the text between them
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// A part of the tweet text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// Text outside the entities
+    Text(&'a str),
+    /// A link, up to the next whitespace
+    Url(&'a str),
+    /// An @mention as written, with `@` or the full-width `＠`, and the handle
+    Mention { written: &'a str, handle: &'a str },
+    /// A hashtag as written, with `#` or the full-width `＃`, and the hashtag
+    Hashtag { written: &'a str, hashtag: &'a str },
+    /// A cashtag as written, such as `$TSLA`, and the symbol
+    Cashtag { written: &'a str, symbol: &'a str },
+    /// Markup written by an earlier stage, such as an embed, kept as it is
+    Markup(&'a str),
+}
+
+fn token_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?P<markup>!?\[\[[^\]\n]+\]\]|!\[\]\([^)\s]+\))",
+            r"|(?P<url>https?://\S+)",
+            r"|[@＠](?P<mention>[a-zA-Z0-9_]+)",
+            r"|[#＃](?P<hashtag>[\p{L}\p{M}\p{N}_]+)",
+            r"|\$(?P<cashtag>[A-Za-z]{1,6}(?:[._][A-Za-z]{1,2})?)",
+        ))
+        .unwrap()
+    })
+}
+
+/// Whether a mention may follow the character, which rules out the e-mail addresses
+fn may_precede_mention(c: char) -> bool {
+    !(c.is_ascii_alphanumeric() || "_!#$%&*@＠".contains(c))
+}
+
+/// Whether a hashtag may follow the character, which rules out such as `C#`
+fn may_precede_hashtag(c: char) -> bool {
+    !(c.is_alphanumeric() || c == '_' || c == '&')
+}
+
+/// Split the text into tokens in a single scan, the links taking precedence over the mentions and
+/// the hashtags in them
+///
+/// The mentions and the hashtags are told apart from the text like Twitter does, with the
+/// full-width `＠` and `＃` and the hashtags in any script, but not when they are glued to the word
+/// before them.
+pub fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut last = 0;
+    let mut start = 0;
+    while let Some(caps) = token_regex().captures_at(text, start) {
+        let m = caps.get(0).unwrap();
+        let previous = text[..m.start()].chars().next_back();
+        let glued = match (
+            caps.name("mention"),
+            caps.name("hashtag"),
+            caps.name("cashtag"),
+        ) {
+            (Some(_), _, _) => previous.is_some_and(|c| !may_precede_mention(c)),
+            (_, Some(_), _) => previous.is_some_and(|c| !may_precede_hashtag(c)),
+            // A cashtag stands alone, unlike `US$100` or `$TSLAQQ`
+            (_, _, Some(_)) => {
+                previous.is_some_and(|c| !c.is_whitespace())
+                    || text[m.end()..]
+                        .chars()
+                        .next()
+                        .is_some_and(|c| c.is_alphanumeric() || c == '_')
+            }
+            _ => false,
+        };
+        if glued {
+            // Part of the text, scanned again from the next character
+            start = m.start() + text[m.start()..].chars().next().unwrap().len_utf8();
+            continue;
+        }
+        let (token, end) = if let Some(markup) = caps.name("markup") {
+            (Token::Markup(markup.as_str()), m.end())
+        } else if let Some(url) = caps.name("url") {
+            (Token::Url(url.as_str()), m.end())
+        } else if let Some(mention) = caps.name("mention") {
+            let token = Token::Mention {
+                written: m.as_str(),
+                handle: mention.as_str(),
+            };
+            (token, m.end())
+        } else if let Some(cashtag) = caps.name("cashtag") {
+            let token = Token::Cashtag {
+                written: m.as_str(),
+                symbol: cashtag.as_str(),
+            };
+            (token, m.end())
+        } else {
+            let hashtag = caps.name("hashtag").unwrap();
+            // A link glued to the hashtag, as in `#1https://...`, is a link of its own
+            let end = hashtag
+                .as_str()
+                .match_indices("http")
+                .map(|(i, _)| hashtag.start() + i)
+                .find(|&i| text[i..].starts_with("http://") || text[i..].starts_with("https://"))
+                .filter(|&i| i > hashtag.start())
+                .unwrap_or(hashtag.end());
+            let token = Token::Hashtag {
+                written: &text[m.start()..end],
+                hashtag: &text[hashtag.start()..end],
+            };
+            (token, end)
+        };
+        if m.start() > last {
+            tokens.push(Token::Text(&text[last..m.start()]));
+        }
+        tokens.push(token);
+        last = end;
+        start = end;
+    }
+    if last < text.len() {
+        tokens.push(Token::Text(&text[last..]));
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, Token};
+
+    fn mention<'a>(written: &'a str, handle: &'a str) -> Token<'a> {
+        Token::Mention { written, handle }
+    }
+    fn hashtag<'a>(written: &'a str, hashtag: &'a str) -> Token<'a> {
+        Token::Hashtag { written, hashtag }
+    }
+    fn cashtag<'a>(written: &'a str, symbol: &'a str) -> Token<'a> {
+        Token::Cashtag { written, symbol }
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("@hoge see https://example.com/@fuga#top #rust!"),
+            vec![
+                mention("@hoge", "hoge"),
+                Token::Text(" see "),
+                Token::Url("https://example.com/@fuga#top"),
+                Token::Text(" "),
+                hashtag("#rust", "rust"),
+                Token::Text("!"),
+            ]
+        );
+        assert_eq!(
+            tokenize("#1https://t.co/abc ![[1-cat.jpg]]"),
+            vec![
+                hashtag("#1", "1"),
+                Token::Url("https://t.co/abc"),
+                Token::Text(" "),
+                Token::Markup("![[1-cat.jpg]]"),
+            ]
+        );
+        assert_eq!(tokenize(""), vec![]);
+    }
+    #[test]
+    fn test_tokenize_unicode() {
+        assert_eq!(
+            tokenize("＃東京タワー ＠hoge さん#ポケモンGO"),
+            vec![
+                hashtag("＃東京タワー", "東京タワー"),
+                Token::Text(" "),
+                mention("＠hoge", "hoge"),
+                Token::Text(" さん#ポケモンGO"),
+            ]
+        );
+        // Combining marks, as in the decomposed が, are part of the hashtag
+        assert_eq!(
+            tokenize("#か\u{3099}"),
+            vec![hashtag("#か\u{3099}", "か\u{3099}")]
+        );
+        // Neither an e-mail address nor C# has a mention or a hashtag
+        assert_eq!(
+            tokenize("mail me@example.com about C#"),
+            vec![Token::Text("mail me@example.com about C#")]
+        );
+        assert_eq!(
+            tokenize("日本語@hoge"),
+            vec![Token::Text("日本語"), mention("@hoge", "hoge")]
+        );
+    }
+    #[test]
+    fn test_tokenize_cashtags() {
+        assert_eq!(
+            tokenize("$TSLA and $BRK.B, not US$100 nor $100 nor $TOOLONGX"),
+            vec![
+                cashtag("$TSLA", "TSLA"),
+                Token::Text(" and "),
+                cashtag("$BRK.B", "BRK.B"),
+                Token::Text(", not US$100 nor $100 nor $TOOLONGX"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use crate::error::{Error, Result};
+use crate::tweet::{Entities, Hashtag, Source, Tweet};
+use chrono::DateTime;
+use regex::Regex;
+use serde::Deserialize;
+use std::{fs::File, io::BufReader, path::Path, sync::OnceLock};
+
+/// File name of the posts in the Threads part of an Instagram data download
+pub const POSTS_FILE_NAME: &str = "threads_and_replies.json";
+
+#[derive(Debug, Deserialize)]
+struct ThreadsExport {
+    #[serde(default)]
+    text_post_app_text_posts: Vec<Post>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Post {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    creation_timestamp: Option<i64>,
+    #[serde(default)]
+    media: Vec<Media>,
+}
+
+/// The text and the time of a post are in its first media, even for text-only posts
+#[derive(Debug, Deserialize)]
+struct Media {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    creation_timestamp: Option<i64>,
+}
+
+/// Load the posts of the `threads_and_replies.json` of an Instagram data download as tweets
+///
+/// The export has no IDs, so each post is identified by its timestamp.
+pub fn load_posts<F: FnMut(Tweet)>(posts_file_path: &Path, mut on_tweet: F) -> Result<()> {
+    let file = File::open(posts_file_path).map_err(|source| Error::Input {
+        path: posts_file_path.display().to_string(),
+        source,
+    })?;
+    let export: ThreadsExport = serde_json::from_reader(BufReader::new(file))?;
+    for post in export.text_post_app_text_posts {
+        if let Some(tweet) = post_to_tweet(post) {
+            on_tweet(tweet);
+        }
+    }
+    Ok(())
+}
+
+fn post_to_tweet(post: Post) -> Option<Tweet> {
+    let media = post.media.first();
+    let text = media
+        .map(|m| m.title.as_str())
+        .filter(|title| !title.is_empty())
+        .unwrap_or(&post.title);
+    let timestamp = media
+        .and_then(|m| m.creation_timestamp)
+        .or(post.creation_timestamp)?;
+    let created_at = DateTime::from_timestamp(timestamp, 0)?.fixed_offset();
+    let text = fix_mojibake(text);
+
+    static RE_HASHTAG: OnceLock<Regex> = OnceLock::new();
+    let re_hashtag = RE_HASHTAG.get_or_init(|| Regex::new(r"#(\w+)").unwrap());
+    let entities = Entities {
+        hashtags: re_hashtag
+            .captures_iter(&text)
+            .map(|caps| Hashtag {
+                text: caps[1].to_string(),
+            })
+            .collect(),
+        ..Default::default()
+    };
+    Some(Tweet::from_post(
+        Source::Threads,
+        timestamp.to_string(),
+        created_at,
+        &text,
+        None,
+        entities,
+    ))
+}
+
+/// Undo the encoding of the Meta exports, which write each byte of the UTF-8 text as a character
+fn fix_mojibake(text: &str) -> String {
+    let bytes = text
+        .chars()
+        .map(|c| u8::try_from(c as u32).ok())
+        .collect::<Option<Vec<u8>>>();
+    bytes
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .unwrap_or_else(|| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fix_mojibake, post_to_tweet, ThreadsExport};
+
+    #[test]
+    fn test_fix_mojibake() {
+        assert_eq!(fix_mojibake("\u{e3}\u{81}\u{82}"), "あ");
+        assert_eq!(fix_mojibake("café"), "café");
+        assert_eq!(fix_mojibake("あ"), "あ");
+    }
+    #[test]
+    fn test_post_to_tweet() {
+        let export: ThreadsExport = serde_json::from_str(
+            r#"{"text_post_app_text_posts": [{"media": [{"uri": "", "creation_timestamp": 1688647560, "title": "Hello #threads ã\u0081\u0082"}], "title": ""}]}"#,
+        )
+        .unwrap();
+        let tweet =
+            post_to_tweet(export.text_post_app_text_posts.into_iter().next().unwrap()).unwrap();
+        assert_eq!(tweet.id(), "1688647560");
+        assert_eq!(tweet.full_text(), "Hello #threads あ");
+        assert!(tweet.has_hashtag("threads"));
+        assert_eq!(tweet.created_at().timestamp(), 1688647560);
+    }
+}
@@ -0,0 +1,465 @@
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate, TimeZone};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// The zones of the tz database with their UTC offsets since 2000, written by scripts/tzdata.py
+const TZDATA: &str = include_str!("tzdata.txt");
+
+/// The timezone used to bucket tweets into months and hours
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timezone {
+    /// The timezone of the machine running the conversion
+    #[default]
+    Local,
+    /// A fixed offset from UTC
+    Fixed(FixedOffset),
+    /// A zone of the tz database, with its daylight saving time
+    Named(&'static Zone),
+}
+impl Timezone {
+    /// Convert a datetime into this timezone
+    pub fn convert<Tz: TimeZone>(&self, dt: &DateTime<Tz>) -> DateTime<FixedOffset> {
+        match self {
+            Timezone::Local => dt.with_timezone(&Local).fixed_offset(),
+            Timezone::Fixed(offset) => dt.with_timezone(offset),
+            Timezone::Named(zone) => {
+                let offset = zone.offset_at(dt.timestamp());
+                dt.with_timezone(&FixedOffset::east_opt(offset).unwrap())
+            }
+        }
+    }
+}
+impl Timezone {
+    /// This timezone, with `Local` resolved to the zone of the machine named by `TZ` or
+    /// `/etc/localtime`, or to its current UTC offset when the zone is unknown
+    pub fn resolved(&self) -> Timezone {
+        if *self != Timezone::Local {
+            return *self;
+        }
+        let from_env = std::env::var("TZ").ok();
+        let from_link = std::fs::read_link("/etc/localtime")
+            .ok()
+            .and_then(|target| {
+                let target = target.to_string_lossy().into_owned();
+                target
+                    .split_once("zoneinfo/")
+                    .map(|(_, name)| name.to_string())
+            });
+        from_env
+            .into_iter()
+            .chain(from_link)
+            .find_map(|name| Zone::find(name.trim_start_matches(':')))
+            .map_or_else(|| Timezone::Fixed(*Local::now().offset()), Timezone::Named)
+    }
+}
+impl fmt::Display for Timezone {
+    /// `local`, the name of the zone, or the UTC offset such as `+09:00`, as parsed
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Timezone::Local => f.write_str("local"),
+            Timezone::Fixed(offset) => write!(f, "{}", offset),
+            Timezone::Named(zone) => f.write_str(zone.name),
+        }
+    }
+}
+impl FromStr for Timezone {
+    type Err = String;
+    /// Parse `local`, a zone name such as `America/New_York`, or an offset such as `+09:00`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(Timezone::Local);
+        }
+        if let Some(zone) = Zone::find(s) {
+            return Ok(Timezone::Named(zone));
+        }
+        if s == "Z" {
+            return Ok(Timezone::Fixed(FixedOffset::east_opt(0).unwrap()));
+        }
+        s.parse::<FixedOffset>().map(Timezone::Fixed).map_err(|_| {
+            format!(
+                "unsupported timezone {}: use `local`, a UTC offset such as +09:00, or the name of a zone of the tz database such as America/New_York",
+                s
+            )
+        })
+    }
+}
+
+/// A zone of the tz database
+#[derive(PartialEq, Eq)]
+pub struct Zone {
+    name: &'static str,
+    /// The UTC offset before the first transition, in seconds
+    initial: i32,
+    /// The times the UTC offset changed at, with the offset from then on
+    transitions: Vec<(i64, i32)>,
+    /// The rule of the UTC offset after the last transition, if any
+    rule: Option<PosixRule>,
+}
+impl fmt::Debug for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Zone({})", self.name)
+    }
+}
+impl Zone {
+    /// The zone of the name or the alias, regardless of the case
+    pub fn find(name: &str) -> Option<&'static Zone> {
+        let database = database();
+        database
+            .names
+            .get(&name.to_ascii_lowercase())
+            .map(|&i| &database.zones[i])
+    }
+    /// The UTC offset at the UNIX time, in seconds
+    pub fn offset_at(&self, timestamp: i64) -> i32 {
+        let i = self
+            .transitions
+            .partition_point(|&(time, _)| time <= timestamp);
+        let offset = match i {
+            0 => self.initial,
+            i => self.transitions[i - 1].1,
+        };
+        match &self.rule {
+            Some(rule) if i == self.transitions.len() => rule.offset_at(timestamp),
+            _ => offset,
+        }
+    }
+    /// Parse a line of `TZDATA` describing a zone
+    fn parse(line: &'static str) -> Option<Self> {
+        let mut fields = line.split(' ');
+        let name = fields.next()?;
+        let rule = match fields.next()? {
+            "-" => None,
+            rule => Some(PosixRule::parse(rule)?),
+        };
+        let initial = fields.next()?.parse().ok()?;
+        let transitions = fields
+            .map(|field| {
+                let (time, offset) = field.split_once('=')?;
+                Some((time.parse().ok()?, offset.parse().ok()?))
+            })
+            .collect::<Option<Vec<(i64, i32)>>>()?;
+        Some(Self {
+            name,
+            initial,
+            transitions,
+            rule,
+        })
+    }
+}
+
+/// The zones of `TZDATA`, with their indices by their lowercased names and aliases
+struct Database {
+    zones: Vec<Zone>,
+    names: HashMap<String, usize>,
+}
+
+fn database() -> &'static Database {
+    static DATABASE: OnceLock<Database> = OnceLock::new();
+    DATABASE.get_or_init(|| {
+        let mut database = Database {
+            zones: Vec::new(),
+            names: HashMap::new(),
+        };
+        for line in TZDATA.lines().filter(|line| !line.starts_with('#')) {
+            let index = match line.split_once(" = ") {
+                Some((_, target)) => database.names[&target.to_ascii_lowercase()],
+                None => {
+                    let zone = Zone::parse(line)
+                        .unwrap_or_else(|| panic!("invalid zone in tzdata.txt: {}", line));
+                    database.zones.push(zone);
+                    database.zones.len() - 1
+                }
+            };
+            let name = line.split(' ').next().unwrap_or_default();
+            database.names.insert(name.to_ascii_lowercase(), index);
+        }
+        database
+    })
+}
+
+/// A TZ rule of POSIX, such as `EST5EDT,M3.2.0,M11.1.0`, giving the UTC offset of any time
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PosixRule {
+    /// The UTC offset of the standard time, in seconds
+    std_offset: i32,
+    dst: Option<DaylightSaving>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DaylightSaving {
+    offset: i32,
+    /// The day it starts on and the time of the day in the standard time, in seconds
+    start: (DayRule, i32),
+    /// The day it ends on and the time of the day in the daylight saving time, in seconds
+    end: (DayRule, i32),
+}
+
+/// A day of the year of a TZ rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DayRule {
+    /// `Jn`, from 1 to 365, February 29 never counted
+    Julian(u16),
+    /// `n`, from 0 to 365, February 29 counted
+    Ordinal(u16),
+    /// `Mm.w.d`, the `w`th (5 for the last) weekday `d` (0 for Sunday) of the month `m`
+    Weekday { month: u32, week: u32, weekday: u32 },
+}
+impl DayRule {
+    fn date(&self, year: i32) -> Option<NaiveDate> {
+        match *self {
+            DayRule::Julian(n) => {
+                let date = NaiveDate::from_yo_opt(year, n.into())?;
+                // Counted as in a year without February 29
+                if n >= 60 && date.leap_year() {
+                    date.succ_opt()
+                } else {
+                    Some(date)
+                }
+            }
+            DayRule::Ordinal(n) => NaiveDate::from_yo_opt(year, u32::from(n) + 1),
+            DayRule::Weekday {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let first_weekday = (weekday + 7 - first.weekday().num_days_from_sunday()) % 7;
+                let mut day = 1 + first_weekday + (week - 1) * 7;
+                // The 5th is the last one, which may be the 4th
+                while NaiveDate::from_ymd_opt(year, month, day).is_none() {
+                    day -= 7;
+                }
+                NaiveDate::from_ymd_opt(year, month, day)
+            }
+        }
+    }
+    fn parse(s: &str) -> Option<Self> {
+        if let Some(n) = s.strip_prefix('J') {
+            return Some(DayRule::Julian(
+                n.parse().ok().filter(|n| (1..=365).contains(n))?,
+            ));
+        }
+        if let Some(mwd) = s.strip_prefix('M') {
+            let mut fields = mwd.split('.').map(|field| field.parse::<u32>().ok());
+            let (month, week, weekday) = (fields.next()??, fields.next()??, fields.next()??);
+            if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+                return None;
+            }
+            return Some(DayRule::Weekday {
+                month,
+                week,
+                weekday,
+            });
+        }
+        Some(DayRule::Ordinal(s.parse().ok().filter(|n| *n <= 365)?))
+    }
+}
+
+impl PosixRule {
+    fn parse(s: &str) -> Option<Self> {
+        let (_, rest) = split_name(s)?;
+        let (std_offset, rest) = split_offset(rest)?;
+        let std_offset = -std_offset;
+        if rest.is_empty() {
+            return Some(Self {
+                std_offset,
+                dst: None,
+            });
+        }
+        let (_, rest) = split_name(rest)?;
+        let (offset, rest) = match rest.strip_prefix(',') {
+            Some(_) => (std_offset + 3600, rest),
+            None => {
+                let (offset, rest) = split_offset(rest)?;
+                (-offset, rest)
+            }
+        };
+        let mut days = rest.strip_prefix(',')?.split(',');
+        let mut day = || {
+            let day = days.next()?;
+            // 02:00 when the time is omitted
+            let (day, time) = match day.split_once('/') {
+                Some((day, time)) => (
+                    day,
+                    split_offset(time).filter(|(_, rest)| rest.is_empty())?.0,
+                ),
+                None => (day, 2 * 3600),
+            };
+            Some((DayRule::parse(day)?, time))
+        };
+        let (start, end) = (day()?, day()?);
+        Some(Self {
+            std_offset,
+            dst: Some(DaylightSaving { offset, start, end }),
+        })
+    }
+    fn offset_at(&self, timestamp: i64) -> i32 {
+        let Some(dst) = &self.dst else {
+            return self.std_offset;
+        };
+        let local = DateTime::from_timestamp(timestamp + i64::from(self.std_offset), 0);
+        let Some(year) = local.map(|local| local.year()) else {
+            return self.std_offset;
+        };
+        let at = |(day, time): (DayRule, i32), offset: i32| {
+            let date = day.date(year)?.and_hms_opt(0, 0, 0)?.and_utc().timestamp();
+            Some(date + i64::from(time) - i64::from(offset))
+        };
+        let (Some(start), Some(end)) = (at(dst.start, self.std_offset), at(dst.end, dst.offset))
+        else {
+            return self.std_offset;
+        };
+        // In the southern hemisphere, it starts late in the year and ends early in the next one
+        let in_dst = if start < end {
+            start <= timestamp && timestamp < end
+        } else {
+            !(end <= timestamp && timestamp < start)
+        };
+        if in_dst {
+            dst.offset
+        } else {
+            self.std_offset
+        }
+    }
+}
+
+/// Split the name of a time, such as `EST` or `<+09>`, from the rest of a TZ rule
+fn split_name(s: &str) -> Option<(&str, &str)> {
+    let end = match s.strip_prefix('<') {
+        Some(quoted) => quoted.find('>')? + 2,
+        None => s
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(s.len()),
+    };
+    (end >= 3).then(|| s.split_at(end))
+}
+
+/// Split an offset or a time of a TZ rule, such as `-5:30`, in seconds, from the rest of it
+fn split_offset(s: &str) -> Option<(i32, &str)> {
+    let (sign, unsigned) = match s.as_bytes().first()? {
+        b'-' => (-1, &s[1..]),
+        b'+' => (1, &s[1..]),
+        _ => (1, s),
+    };
+    let end = unsigned
+        .find(|c: char| !c.is_ascii_digit() && c != ':')
+        .unwrap_or(unsigned.len());
+    let mut seconds = 0;
+    for (i, field) in unsigned[..end].split(':').enumerate() {
+        if i > 2 {
+            return None;
+        }
+        seconds += field.parse::<i32>().ok()? * [3600, 60, 1][i];
+    }
+    Some((sign * seconds, &unsigned[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PosixRule, Timezone};
+    use chrono::{FixedOffset, TimeZone, Timelike, Utc};
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!(
+            "+09:00".parse(),
+            Ok(Timezone::Fixed(FixedOffset::east_opt(9 * 3600).unwrap()))
+        );
+        assert_eq!(
+            "-05:30".parse(),
+            Ok(Timezone::Fixed(
+                FixedOffset::west_opt(5 * 3600 + 1800).unwrap()
+            ))
+        );
+        assert_eq!("local".parse(), Ok(Timezone::Local));
+        assert_eq!(
+            "us/eastern".parse::<Timezone>(),
+            "America/New_York".parse::<Timezone>()
+        );
+        assert!("Mars/Olympus_Mons".parse::<Timezone>().is_err());
+    }
+    #[test]
+    fn test_display_resolved() {
+        for name in ["+09:00", "Asia/Tokyo", "local"] {
+            assert_eq!(name.parse::<Timezone>().unwrap().to_string(), name);
+        }
+        let tokyo = "Asia/Tokyo".parse::<Timezone>().unwrap();
+        assert_eq!(tokyo.resolved(), tokyo);
+        assert_ne!(Timezone::Local.resolved(), Timezone::Local);
+    }
+    #[test]
+    fn test_convert() {
+        let dt = Utc.with_ymd_and_hms(2023, 3, 31, 20, 0, 0).unwrap();
+        let converted = "Asia/Tokyo".parse::<Timezone>().unwrap().convert(&dt);
+        assert_eq!(converted.to_rfc3339(), "2023-04-01T05:00:00+09:00");
+        assert_eq!(converted.hour(), 5);
+    }
+    #[test]
+    fn test_convert_daylight_saving_time() {
+        let convert = |zone: &str, (y, m, d, h): (i32, u32, u32, u32)| {
+            let dt = Utc.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap();
+            zone.parse::<Timezone>().unwrap().convert(&dt).to_rfc3339()
+        };
+        let new_york = "America/New_York";
+        assert_eq!(
+            convert(new_york, (2023, 1, 15, 12)),
+            "2023-01-15T07:00:00-05:00"
+        );
+        assert_eq!(
+            convert(new_york, (2023, 7, 15, 12)),
+            "2023-07-15T08:00:00-04:00"
+        );
+        // Around the start of the daylight saving time, 2023-03-12 02:00 EST
+        assert_eq!(
+            convert(new_york, (2023, 3, 12, 6)),
+            "2023-03-12T01:00:00-05:00"
+        );
+        assert_eq!(
+            convert(new_york, (2023, 3, 12, 7)),
+            "2023-03-12T03:00:00-04:00"
+        );
+        // Before 2007, it started in April
+        assert_eq!(
+            convert(new_york, (2006, 3, 20, 12)),
+            "2006-03-20T07:00:00-05:00"
+        );
+        // After the transitions of the database, by the rule of the zone
+        assert_eq!(
+            convert(new_york, (2050, 7, 1, 12)),
+            "2050-07-01T08:00:00-04:00"
+        );
+        assert_eq!(
+            convert("Europe/London", (2023, 7, 1, 12)),
+            "2023-07-01T13:00:00+01:00"
+        );
+        // Brazil stopped the daylight saving time in 2019
+        let sao_paulo = "America/Sao_Paulo";
+        assert_eq!(
+            convert(sao_paulo, (2018, 1, 1, 12)),
+            "2018-01-01T10:00:00-02:00"
+        );
+        assert_eq!(
+            convert(sao_paulo, (2020, 1, 1, 12)),
+            "2020-01-01T09:00:00-03:00"
+        );
+        assert_eq!(
+            convert("Australia/Sydney", (2050, 1, 1, 0)),
+            "2050-01-01T11:00:00+11:00"
+        );
+    }
+    #[test]
+    fn test_posix_rule() {
+        let rule = PosixRule::parse("<+1030>-10:30<+11>-11,M10.1.0,M4.1.0").unwrap();
+        assert_eq!(rule.std_offset, 10 * 3600 + 1800);
+        assert_eq!(rule.dst.unwrap().offset, 11 * 3600);
+        let rule = PosixRule::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        let july = Utc
+            .with_ymd_and_hms(2060, 7, 1, 0, 0, 0)
+            .unwrap()
+            .timestamp();
+        assert_eq!(rule.offset_at(july), -4 * 3600);
+        assert!(PosixRule::parse("EST5EDT,M13.2.0,M11.1.0").is_none());
+    }
+}
@@ -0,0 +1,221 @@
+//! Reading of the JSON files trimmed or edited by hand
+//!
+//! People cut their `tweets.js` down in a text editor before converting it, which easily leaves
+//! a trailing comma, a byte order mark or an unclosed array. These are fixed on the fly, and
+//! counted so that the fixes can be reported.
+use std::io::{self, Read};
+
+/// What was fixed in a file while it was read
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Fixes {
+    pub byte_order_mark: bool,
+    /// Commas before a closing bracket or brace, which JSON does not allow
+    pub trailing_commas: usize,
+    /// Brackets and braces closed at the end of the file
+    pub closed_brackets: usize,
+}
+impl Fixes {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+    /// Description of the fixes for a warning, such as "removed 2 trailing commas"
+    pub fn describe(&self) -> String {
+        let mut fixes = Vec::new();
+        if self.byte_order_mark {
+            fixes.push("skipped the byte order mark".to_string());
+        }
+        if self.trailing_commas > 0 {
+            fixes.push(format!(
+                "removed {} trailing comma{}",
+                self.trailing_commas,
+                plural(self.trailing_commas)
+            ));
+        }
+        if self.closed_brackets > 0 {
+            fixes.push(format!(
+                "closed {} bracket{} left open at the end",
+                self.closed_brackets,
+                plural(self.closed_brackets)
+            ));
+        }
+        fixes.join(", ")
+    }
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// A reader of JSON fixing the deviations of the hand-edited files
+///
+/// The text of the strings is never changed.
+pub struct TolerantReader<R> {
+    inner: R,
+    fixes: Fixes,
+    /// Bytes ready to be read
+    output: Vec<u8>,
+    position: usize,
+    /// A comma and the whitespace after it, held until it is known whether a bracket follows
+    pending: Vec<u8>,
+    /// The brackets and braces open, as their closing characters
+    open: Vec<u8>,
+    in_string: bool,
+    escaped: bool,
+    started: bool,
+    finished: bool,
+}
+impl<R: Read> TolerantReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            fixes: Fixes::default(),
+            output: Vec::new(),
+            position: 0,
+            pending: Vec::new(),
+            open: Vec::new(),
+            in_string: false,
+            escaped: false,
+            started: false,
+            finished: false,
+        }
+    }
+    /// What was fixed so far
+    pub fn fixes(&self) -> Fixes {
+        self.fixes
+    }
+    /// Read a chunk of the inner reader into the output
+    fn fill(&mut self) -> io::Result<()> {
+        let mut chunk = [0; 8192];
+        let n = self.inner.read(&mut chunk)?;
+        let mut chunk = &chunk[..n];
+        if !self.started && n > 0 {
+            self.started = true;
+            if let Some(rest) = chunk.strip_prefix(b"\xEF\xBB\xBF") {
+                self.fixes.byte_order_mark = true;
+                chunk = rest;
+            }
+        }
+        if n == 0 {
+            self.finish();
+            return Ok(());
+        }
+        for &b in chunk {
+            self.push(b);
+        }
+        Ok(())
+    }
+    fn push(&mut self, b: u8) {
+        if self.in_string {
+            match b {
+                _ if self.escaped => self.escaped = false,
+                b'\\' => self.escaped = true,
+                b'"' => self.in_string = false,
+                _ => {}
+            }
+            self.output.push(b);
+            return;
+        }
+        match b {
+            b' ' | b'\t' | b'\r' | b'\n' if !self.pending.is_empty() => self.pending.push(b),
+            b',' => {
+                self.flush_pending();
+                self.pending.push(b);
+            }
+            b']' | b'}' => {
+                if !self.pending.is_empty() {
+                    self.fixes.trailing_commas += 1;
+                    self.pending.remove(0);
+                    self.flush_pending();
+                }
+                if self.open.last() == Some(&b) {
+                    self.open.pop();
+                }
+                self.output.push(b);
+            }
+            _ => {
+                self.flush_pending();
+                match b {
+                    b'"' => self.in_string = true,
+                    b'[' => self.open.push(b']'),
+                    b'{' => self.open.push(b'}'),
+                    _ => {}
+                }
+                self.output.push(b);
+            }
+        }
+    }
+    fn flush_pending(&mut self) {
+        self.output.append(&mut self.pending);
+    }
+    /// Close what is left open at the end of the file
+    fn finish(&mut self) {
+        self.finished = true;
+        if self.in_string {
+            return;
+        }
+        if !self.pending.is_empty() && !self.open.is_empty() {
+            self.fixes.trailing_commas += 1;
+            self.pending.clear();
+        }
+        self.flush_pending();
+        while let Some(closing) = self.open.pop() {
+            self.fixes.closed_brackets += 1;
+            self.output.push(closing);
+        }
+    }
+}
+impl<R: Read> Read for TolerantReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.output.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.output.clear();
+            self.position = 0;
+            self.fill()?;
+        }
+        let n = buf.len().min(self.output.len() - self.position);
+        buf[..n].copy_from_slice(&self.output[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TolerantReader;
+    use std::io::Read;
+
+    fn read(json: &str) -> (String, String) {
+        let mut reader = TolerantReader::new(json.as_bytes());
+        let mut fixed = String::new();
+        reader.read_to_string(&mut fixed).unwrap();
+        (fixed, reader.fixes().describe())
+    }
+
+    #[test]
+    fn test_tolerant_reader() {
+        assert_eq!(
+            read(r#"[{"a": "x,]"}]"#),
+            (r#"[{"a": "x,]"}]"#.to_string(), String::new())
+        );
+        assert_eq!(
+            read("\u{FEFF}[{\"a\": 1,\n},\n]"),
+            (
+                "[{\"a\": 1\n}\n]".to_string(),
+                "skipped the byte order mark, removed 2 trailing commas".to_string()
+            )
+        );
+        assert_eq!(
+            read(r#"[{"a": "\"["}, "#),
+            (
+                r#"[{"a": "\"["}]"#.to_string(),
+                "removed 1 trailing comma, closed 1 bracket left open at the end".to_string()
+            )
+        );
+    }
+}
@@ -1,24 +1,297 @@
-use anyhow::Result;
+use crate::error::{Error, Result};
+use crate::normalize;
+use crate::timezone::Timezone;
+use crate::tolerant::TolerantReader;
+use crate::wrapper::next_array;
 use chrono::prelude::*;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use log::warn;
+use regex::Regex;
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use serde_json::de::IoRead;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 
 /// A struct representing a tweet
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct Tweet {
-    created_at: DateTime<Local>,
+    id: String,
+    created_at: DateTime<FixedOffset>,
     full_text: String,
     is_reply: bool,
+    in_reply_to_status_id: Option<String>,
+    in_reply_to_screen_name: Option<String>,
+    lang: Option<String>,
+    entities: Entities,
+    favorite_count: u64,
+    retweet_count: u64,
+    #[serde(default)]
+    source: Source,
+    /// Tweets by others this tweet replies to or quotes, when they are looked up
+    #[serde(default)]
+    references: Vec<ReferencedTweet>,
+    /// Screen name of the account the tweet was posted from, when archives of several accounts are merged
+    #[serde(default)]
+    account: Option<String>,
+    /// Where the tweet was posted from, when it is geotagged
+    #[serde(default)]
+    geo: Option<Geo>,
+    #[serde(default)]
+    poll: Option<Poll>,
+    /// ID of the first version of the tweet, when it has been edited
+    #[serde(default)]
+    initial_tweet_id: Option<String>,
+    /// The versions before the edits, the oldest first, once the edit chain is collapsed
+    #[serde(default)]
+    earlier_versions: Vec<TweetVersion>,
+    #[serde(default)]
+    audience: Audience,
+    /// Whether Twitter flagged the media or the links of the tweet as possibly sensitive
+    #[serde(default)]
+    possibly_sensitive: bool,
+    /// Whether the tweet was deleted before the archive was exported
+    #[serde(default)]
+    deleted: bool,
+    #[serde(default)]
+    deleted_at: Option<DateTime<FixedOffset>>,
+    /// Fields of the archive not understood, to notice changes of the export format
+    #[serde(skip)]
+    unknown_fields: Vec<String>,
 }
+
+/// Who a tweet was shown to
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Audience {
+    #[default]
+    Public,
+    /// The Twitter Circle of the account
+    Circle,
+    /// The members of a community
+    Community,
+}
+impl Audience {
+    /// Tag of the tweets that were not public, without the leading "#"
+    pub fn tag(&self) -> Option<&'static str> {
+        match self {
+            Audience::Public => None,
+            Audience::Circle => Some("private-circle"),
+            Audience::Community => Some("community"),
+        }
+    }
+}
+
+/// A version of an edited tweet replaced by a later one
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TweetVersion {
+    pub created_at: DateTime<FixedOffset>,
+    pub full_text: String,
+}
+
+/// A poll attached to a tweet
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Poll {
+    pub options: Vec<PollOption>,
+    /// Whether the poll is closed, so that the counts are final
+    pub closed: bool,
+}
+
+/// A choice of a poll
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PollOption {
+    pub label: String,
+    /// Number of votes, when the data has them
+    pub votes: Option<u64>,
+}
+
+/// The location a geotagged tweet was posted from
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Geo {
+    /// Name of the place, such as "Shibuya-ku, Tokyo"
+    pub place: Option<String>,
+    /// Latitude and longitude of the exact location
+    pub coordinates: Option<(f64, f64)>,
+}
+impl Geo {
+    /// The place, or the coordinates when the place is unknown
+    pub fn name(&self) -> String {
+        match (&self.place, self.coordinates) {
+            (Some(place), _) => place.clone(),
+            (None, Some((latitude, longitude))) => format!("{}, {}", latitude, longitude),
+            (None, None) => String::new(),
+        }
+    }
+    /// Link to the location on OpenStreetMap, when the coordinates are known
+    pub fn map_url(&self) -> Option<String> {
+        let (latitude, longitude) = self.coordinates?;
+        Some(format!(
+            "https://www.openstreetmap.org/?mlat={0}&mlon={1}#map=16/{0}/{1}",
+            latitude, longitude
+        ))
+    }
+}
+
+/// Platform a post was imported from
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Source {
+    #[default]
+    Twitter,
+    Mastodon,
+    Bluesky,
+    Threads,
+}
+impl Source {
+    /// Name of the platform as shown in the notes
+    pub fn name(&self) -> &'static str {
+        match self {
+            Source::Twitter => "Twitter",
+            Source::Mastodon => "Mastodon",
+            Source::Bluesky => "Bluesky",
+            Source::Threads => "Threads",
+        }
+    }
+}
+
+/// A tweet by someone else that a tweet replies to or quotes, looked up with the API
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReferencedTweet {
+    pub kind: ReferenceKind,
+    pub id: String,
+    /// Screen name of the author
+    pub author: String,
+    pub text: String,
+}
+
+/// How a tweet refers to a [ReferencedTweet]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReferenceKind {
+    RepliedTo,
+    Quoted,
+}
+
+/// Entities extracted by Twitter from the text of a tweet
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Entities {
+    #[serde(default)]
+    pub hashtags: Vec<Hashtag>,
+    #[serde(default)]
+    pub user_mentions: Vec<UserMention>,
+    #[serde(default)]
+    pub symbols: Vec<Symbol>,
+    #[serde(default)]
+    pub urls: Vec<UrlEntity>,
+    #[serde(default)]
+    pub media: Vec<MediaEntity>,
+}
+
+/// A hashtag in a tweet
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Hashtag {
+    /// The hashtag without the leading "#"
+    pub text: String,
+}
+
+/// A cashtag in a tweet, such as `$TSLA`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Symbol {
+    /// The cashtag without the leading "$"
+    pub text: String,
+}
+
+/// A link in a tweet, shortened to t.co in the text
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UrlEntity {
+    /// The t.co URL as it appears in the text
+    pub url: String,
+    pub expanded_url: String,
+    #[serde(default)]
+    pub display_url: String,
+}
+
+/// A photo, video or GIF attached to a tweet
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MediaEntity {
+    /// The t.co URL as it appears in the text
+    pub url: String,
+    pub media_url_https: String,
+    /// "photo", "video" or "animated_gif"
+    #[serde(rename = "type", default)]
+    pub media_type: String,
+}
+impl MediaEntity {
+    /// Path of the file of the media in the `tweets_media` folder of an archive,
+    /// named `<tweet id>-<file name>`
+    pub fn archive_path(&self, tweet_id: &str, media_dir: &Path) -> Option<PathBuf> {
+        Some(media_dir.join(self.file_name(tweet_id)?))
+    }
+    /// Name of the file of the media, `<tweet id>-<file name>` as in the archive
+    pub fn file_name(&self, tweet_id: &str) -> Option<String> {
+        let file_name = self.media_url_https.rsplit('/').next()?;
+        (!file_name.is_empty()).then(|| format!("{}-{}", tweet_id, file_name))
+    }
+}
+
+/// A user mentioned in a tweet
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UserMention {
+    pub screen_name: String,
+    #[serde(default)]
+    pub name: String,
+}
+
 impl Tweet {
     pub fn new(created_at: String, full_text: String, is_reply: bool) -> Result<Self> {
         Ok(Self {
-            created_at: parse_twitter_date(&created_at)?.with_timezone(&Local),
+            created_at: Timezone::Local.convert(&parse_twitter_date(&created_at)?),
             full_text,
             is_reply,
+            ..Default::default()
         })
     }
-    pub fn created_at(&self) -> DateTime<Local> {
+    /// Build a tweet from a post imported from another platform
+    pub(crate) fn from_post(
+        source: Source,
+        id: String,
+        created_at: DateTime<FixedOffset>,
+        full_text: &str,
+        in_reply_to_status_id: Option<String>,
+        mut entities: Entities,
+    ) -> Self {
+        for hashtag in entities.hashtags.iter_mut() {
+            hashtag.text = normalize::nfc(&hashtag.text);
+        }
+        Self {
+            id,
+            created_at: Timezone::Local.convert(&created_at),
+            full_text: normalize::nfc(full_text),
+            is_reply: in_reply_to_status_id.is_some(),
+            in_reply_to_status_id,
+            entities,
+            source,
+            ..Default::default()
+        }
+    }
+    /// Convert the timestamp of the tweet into the given timezone
+    pub fn in_timezone(mut self, timezone: &Timezone) -> Self {
+        self.created_at = timezone.convert(&self.created_at);
+        self.deleted_at = self
+            .deleted_at
+            .map(|deleted_at| timezone.convert(&deleted_at));
+        self
+    }
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    pub fn created_at(&self) -> DateTime<FixedOffset> {
         self.created_at
     }
     pub fn full_text(&self) -> &str {
@@ -27,6 +300,137 @@ impl Tweet {
     pub fn is_reply(&self) -> bool {
         self.is_reply
     }
+    /// The ID of the tweet this tweet replies to
+    pub fn in_reply_to_status_id(&self) -> Option<&str> {
+        self.in_reply_to_status_id.as_deref()
+    }
+    /// The language of the tweet detected by Twitter (BCP 47, e.g. "ja", "en", "und")
+    pub fn lang(&self) -> Option<&str> {
+        self.lang.as_deref()
+    }
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
+    /// Number of likes at the time the archive was exported
+    pub fn favorite_count(&self) -> u64 {
+        self.favorite_count
+    }
+    /// Number of retweets at the time the archive was exported
+    pub fn retweet_count(&self) -> u64 {
+        self.retweet_count
+    }
+    pub fn source(&self) -> Source {
+        self.source
+    }
+    pub fn account(&self) -> Option<&str> {
+        self.account.as_deref()
+    }
+    /// Attribute the tweet to the account it was posted from
+    pub fn with_account(mut self, account: String) -> Self {
+        self.account = Some(account);
+        self
+    }
+    /// ID of the first version of the tweet, shared by all the versions of an edited tweet
+    pub fn initial_tweet_id(&self) -> Option<&str> {
+        self.initial_tweet_id.as_deref()
+    }
+    pub fn earlier_versions(&self) -> &[TweetVersion] {
+        &self.earlier_versions
+    }
+    pub(crate) fn set_earlier_versions(&mut self, versions: Vec<TweetVersion>) {
+        self.earlier_versions = versions;
+    }
+    pub fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+    /// When the tweet was deleted, if the archive tells it
+    pub fn deleted_at(&self) -> Option<DateTime<FixedOffset>> {
+        self.deleted_at
+    }
+    /// Take the names of the fields of the archive not understood
+    pub fn take_unknown_fields(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.unknown_fields)
+    }
+    /// Mark the tweet as deleted, e.g. when it is read from `deleted-tweets.js`
+    pub fn into_deleted(mut self) -> Self {
+        self.deleted = true;
+        self
+    }
+    pub fn is_possibly_sensitive(&self) -> bool {
+        self.possibly_sensitive
+    }
+    pub fn audience(&self) -> Audience {
+        self.audience
+    }
+    /// Mark the tweet as shown only to `audience`, e.g. when it is listed in the file of the Circle
+    pub fn with_audience(mut self, audience: Audience) -> Self {
+        self.audience = audience;
+        self
+    }
+    pub fn poll(&self) -> Option<&Poll> {
+        self.poll.as_ref()
+    }
+    pub fn geo(&self) -> Option<&Geo> {
+        self.geo.as_ref()
+    }
+    pub fn references(&self) -> &[ReferencedTweet] {
+        &self.references
+    }
+    pub fn add_reference(&mut self, reference: ReferencedTweet) {
+        self.references.push(reference);
+    }
+    /// IDs of the tweets quoted by this tweet, linked in the text
+    pub fn quoted_status_ids(&self) -> Vec<&str> {
+        static RE_STATUS: OnceLock<Regex> = OnceLock::new();
+        let re_status = RE_STATUS.get_or_init(|| {
+            Regex::new(r"^https?://(?:mobile\.)?(?:twitter|x)\.com/\w+/status(?:es)?/(\d+)")
+                .unwrap()
+        });
+        self.entities
+            .urls
+            .iter()
+            .filter_map(|url| re_status.captures(&url.expanded_url))
+            .map(|caps| caps.get(1).unwrap().as_str())
+            .collect()
+    }
+    /// Whether the tweet carries the hashtag (case-insensitive, with or without the leading "#")
+    pub fn has_hashtag(&self, hashtag: &str) -> bool {
+        let hashtag = hashtag.trim_start_matches(['#', '＃']);
+        self.entities
+            .hashtags
+            .iter()
+            .any(|h| h.text.to_lowercase() == hashtag.to_lowercase())
+    }
+    /// The screen name of the user this tweet replies to
+    pub fn in_reply_to_screen_name(&self) -> Option<&str> {
+        self.in_reply_to_screen_name.as_deref()
+    }
+    /// Whether the tweet mentions or replies to the user (case-insensitive, with or without the leading "@")
+    pub fn involves_user(&self, screen_name: &str) -> bool {
+        let screen_name = screen_name.trim_start_matches(['@', '＠']).to_lowercase();
+        self.in_reply_to_screen_name
+            .iter()
+            .chain(self.entities.user_mentions.iter().map(|m| &m.screen_name))
+            .any(|name| name.to_lowercase() == screen_name)
+    }
+    /// The text with HTML entities decoded and URLs removed, as people read it
+    pub fn plain_text(&self) -> String {
+        static RE_URL: OnceLock<Regex> = OnceLock::new();
+        let re_url = RE_URL.get_or_init(|| Regex::new(r"https?://\S+").unwrap());
+        let text = re_url.replace_all(&self.full_text, "");
+        decode_html_entities(text.trim())
+    }
+    /// The text with the HTML entities decoded and the t.co links expanded
+    pub fn expanded_text(&self) -> String {
+        let text = self
+            .entities
+            .urls
+            .iter()
+            .fold(self.full_text.clone(), |text, url| {
+                text.replace(&url.url, &url.expanded_url)
+            });
+        decode_html_entities(&text)
+    }
     pub fn is_retweet(&self) -> bool {
         self.full_text.starts_with("RT @")
     }
@@ -37,30 +441,510 @@ impl Tweet {
         is_reply: bool,
     ) -> Self {
         Self {
-            created_at,
+            created_at: created_at.fixed_offset(),
             full_text,
             is_reply,
+            ..Default::default()
+        }
+    }
+}
+
+/// An element of the array of tweets, in any of the generations of the archive
+///
+/// - Since 2020, `tweets.js` wraps each tweet in `{"tweet": {...}}`.
+/// - From 2018 to 2019, `tweet.js` lists the tweets as they are.
+/// - Until 2018, the Grailbird archive has a `data/js/tweets/YYYY_MM.js` per month, listing the
+///   tweets as they are, with `text` in place of `full_text` and ISO-like dates.
+///
+/// The element is told apart by its shape, so the files of every generation are read the same way.
+#[derive(Debug)]
+struct ArchiveEntry {
+    tweet: ArchiveTweet,
+    /// Fields of the tweet in neither `ArchiveTweet` nor `IGNORED_FIELDS`
+    unknown_fields: Vec<String>,
+}
+impl<'de> Deserialize<'de> for ArchiveEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let mut value = serde_json::Value::deserialize(deserializer)?;
+        let value = match value.get_mut("tweet") {
+            Some(tweet) if tweet.is_object() => tweet.take(),
+            _ => value,
+        };
+        let unknown_fields = value
+            .as_object()
+            .map(|object| {
+                object
+                    .keys()
+                    .filter(|key| !KNOWN_FIELDS.contains(&key.as_str()))
+                    .filter(|key| !IGNORED_FIELDS.contains(&key.as_str()))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        let tweet = ArchiveTweet::deserialize(value).map_err(de::Error::custom)?;
+        Ok(Self {
+            tweet,
+            unknown_fields,
+        })
+    }
+}
+/// The fields of the tweet read into `ArchiveTweet`, including the aliases
+const KNOWN_FIELDS: &[&str] = &[
+    "id_str",
+    "created_at",
+    "full_text",
+    "text",
+    "in_reply_to_user_id",
+    "in_reply_to_status_id_str",
+    "in_reply_to_screen_name",
+    "lang",
+    "entities",
+    "favorite_count",
+    "retweet_count",
+    "geo",
+    "coordinates",
+    "place",
+    "card",
+    "edit_info",
+    "limited_actions",
+    "community_id_str",
+    "community_id",
+    "possibly_sensitive",
+    "deleted_at",
+];
+/// The fields of the archive known but not needed for the notes
+const IGNORED_FIELDS: &[&str] = &[
+    "id",
+    "source",
+    "truncated",
+    "display_text_range",
+    "favorited",
+    "retweeted",
+    "in_reply_to_status_id",
+    "in_reply_to_user_id_str",
+    "extended_entities",
+    "contributors",
+    "scopes",
+    "self_thread",
+    "withheld_in_countries",
+    "withheld_copyright",
+    "withheld_scope",
+    "is_quote_status",
+    "user",
+    "retweeted_status",
+];
+/// The tweet object as it is stored in the Twitter archive
+#[derive(Debug, Deserialize)]
+struct ArchiveTweet {
+    #[serde(default)]
+    id_str: String,
+    created_at: String,
+    /// `text` in the Grailbird archive
+    #[serde(alias = "text")]
+    full_text: String,
+    /// A string, or a number in the Grailbird archive
+    #[serde(default, deserialize_with = "deserialize_id")]
+    in_reply_to_user_id: Option<String>,
+    #[serde(default)]
+    in_reply_to_status_id_str: Option<String>,
+    #[serde(default)]
+    in_reply_to_screen_name: Option<String>,
+    #[serde(default)]
+    lang: Option<String>,
+    #[serde(default)]
+    entities: Entities,
+    #[serde(default, deserialize_with = "deserialize_count")]
+    favorite_count: u64,
+    #[serde(default, deserialize_with = "deserialize_count")]
+    retweet_count: u64,
+    /// The location as latitude and longitude, an empty object in the Grailbird archive when none
+    #[serde(default, deserialize_with = "deserialize_non_empty")]
+    geo: Option<ArchivePoint>,
+    /// The location as longitude and latitude, the order of GeoJSON
+    #[serde(default, deserialize_with = "deserialize_non_empty")]
+    coordinates: Option<ArchivePoint>,
+    #[serde(default)]
+    place: Option<ArchivePlace>,
+    /// The card of a link or a poll, in the form of the v1.1 API
+    #[serde(default)]
+    card: Option<ArchiveCard>,
+    #[serde(default)]
+    edit_info: Option<EditInfo>,
+    /// The actions limited on the tweet, "limit_trusted_friends_tweet" for the Circle
+    #[serde(default)]
+    limited_actions: Option<String>,
+    #[serde(default, alias = "community_id")]
+    community_id_str: Option<String>,
+    #[serde(default)]
+    possibly_sensitive: Option<bool>,
+    /// When the tweet was deleted, in the tweets of `deleted-tweets.js`
+    #[serde(default)]
+    deleted_at: Option<String>,
+}
+/// The edit chain a tweet belongs to, in the archives exported since edits were introduced
+///
+/// The first version has `initial` with the IDs of all the versions, and the later ones have
+/// `edit` with the ID of the first version.
+#[derive(Debug, Deserialize)]
+struct EditInfo {
+    #[serde(default)]
+    initial: Option<EditControl>,
+    #[serde(default)]
+    edit: Option<Edit>,
+}
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EditControl {
+    #[serde(default)]
+    edit_tweet_ids: Vec<String>,
+}
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Edit {
+    initial_tweet_id: String,
+}
+/// A card of a tweet, whose values depend on its kind
+#[derive(Debug, Deserialize)]
+struct ArchiveCard {
+    /// Kind of the card, such as "poll2choice_text_only"
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    binding_values: HashMap<String, BindingValue>,
+}
+#[derive(Debug, Deserialize)]
+struct BindingValue {
+    #[serde(default)]
+    string_value: Option<String>,
+    #[serde(default)]
+    boolean_value: Option<bool>,
+}
+impl ArchiveCard {
+    /// The poll of a poll card, with up to 4 choices
+    fn poll(&self) -> Option<Poll> {
+        if !self.name.starts_with("poll") {
+            return None;
+        }
+        let string = |key: String| {
+            self.binding_values
+                .get(&key)
+                .and_then(|value| value.string_value.clone())
+        };
+        let options = (1..=4)
+            .map_while(|i| {
+                Some(PollOption {
+                    label: string(format!("choice{}_label", i))?,
+                    votes: string(format!("choice{}_count", i)).and_then(|n| n.parse().ok()),
+                })
+            })
+            .collect::<Vec<PollOption>>();
+        let closed = self
+            .binding_values
+            .get("counts_are_final")
+            .and_then(|value| value.boolean_value)
+            .unwrap_or(false);
+        (!options.is_empty()).then_some(Poll { options, closed })
+    }
+    /// The t.co URL of the card, left bare in the text
+    fn url(&self) -> Option<&str> {
+        self.binding_values.get("card_url")?.string_value.as_deref()
+    }
+}
+/// A point of `geo` or `coordinates` of the archive
+#[derive(Debug, Deserialize)]
+struct ArchivePoint {
+    #[serde(deserialize_with = "deserialize_point")]
+    coordinates: (f64, f64),
+}
+/// A place a tweet was tagged with
+#[derive(Debug, Deserialize)]
+struct ArchivePlace {
+    #[serde(default)]
+    full_name: String,
+    #[serde(default)]
+    name: String,
+}
+impl ArchiveTweet {
+    fn geo(&self) -> Option<Geo> {
+        let coordinates = match (&self.geo, &self.coordinates) {
+            (Some(geo), _) => Some(geo.coordinates),
+            (None, Some(point)) => Some((point.coordinates.1, point.coordinates.0)),
+            (None, None) => None,
+        };
+        let place = self
+            .place
+            .as_ref()
+            .map(|place| match place.full_name.as_str() {
+                "" => place.name.clone(),
+                full_name => full_name.to_string(),
+            })
+            .filter(|name| !name.is_empty());
+        if coordinates.is_none() && place.is_none() {
+            return None;
+        }
+        Some(Geo { place, coordinates })
+    }
+    fn audience(&self) -> Audience {
+        if self
+            .limited_actions
+            .as_deref()
+            .is_some_and(|actions| actions.contains("trusted_friends"))
+        {
+            Audience::Circle
+        } else if self.community_id_str.is_some() {
+            Audience::Community
+        } else {
+            Audience::Public
+        }
+    }
+    /// ID of the first version, when the tweet has been edited
+    fn initial_tweet_id(&self) -> Option<String> {
+        let edit_info = self.edit_info.as_ref()?;
+        match (&edit_info.initial, &edit_info.edit) {
+            (_, Some(edit)) => Some(edit.initial_tweet_id.clone()),
+            (Some(initial), None) if initial.edit_tweet_ids.len() > 1 => Some(self.id_str.clone()),
+            _ => None,
+        }
+    }
+}
+impl TryFrom<ArchiveTweet> for Tweet {
+    type Error = Error;
+    fn try_from(tw: ArchiveTweet) -> Result<Self> {
+        let geo = tw.geo();
+        let initial_tweet_id = tw.initial_tweet_id();
+        let audience = tw.audience();
+        let deleted_at = match tw.deleted_at {
+            Some(ref deleted_at) => Some(Timezone::Local.convert(&parse_twitter_date(deleted_at)?)),
+            None => None,
+        };
+        let poll = tw.card.as_ref().and_then(ArchiveCard::poll);
+        // The poll is rendered in place of the link to its card
+        let full_text = match tw.card.as_ref().and_then(ArchiveCard::url) {
+            Some(url) if poll.is_some() => tw.full_text.replace(url, "").trim_end().to_string(),
+            _ => tw.full_text.clone(),
+        };
+        // Decomposed text would not match the searches and links typed in Obsidian
+        let mut entities = tw.entities;
+        for hashtag in entities.hashtags.iter_mut() {
+            hashtag.text = normalize::nfc(&hashtag.text);
+        }
+        Ok(Self {
+            id: tw.id_str,
+            geo,
+            poll,
+            initial_tweet_id,
+            audience,
+            possibly_sensitive: tw.possibly_sensitive.unwrap_or(false),
+            deleted: deleted_at.is_some(),
+            deleted_at,
+            in_reply_to_status_id: tw.in_reply_to_status_id_str,
+            in_reply_to_screen_name: tw.in_reply_to_screen_name,
+            lang: tw.lang,
+            entities,
+            favorite_count: tw.favorite_count,
+            retweet_count: tw.retweet_count,
+            ..Tweet::new(
+                tw.created_at,
+                normalize::nfc(&full_text),
+                tw.in_reply_to_user_id.is_some(),
+            )?
+        })
+    }
+}
+
+/// A visitor passing each element of the tweets array to a callback as soon as it is read
+struct TweetsVisitor<'a, F> {
+    on_tweet: F,
+    error: &'a mut Option<Error>,
+}
+impl<'de, F: FnMut(Tweet)> Visitor<'de> for TweetsVisitor<'_, F> {
+    type Value = ();
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an array of tweets")
+    }
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> std::result::Result<(), A::Error> {
+        while let Some(entry) = seq.next_element::<ArchiveEntry>()? {
+            match Tweet::try_from(entry.tweet) {
+                Ok(mut tweet) => {
+                    tweet.unknown_fields = entry.unknown_fields;
+                    (self.on_tweet)(tweet)
+                }
+                Err(e) => {
+                    let message = e.to_string();
+                    *self.error = Some(e);
+                    return Err(de::Error::custom(message));
+                }
+            }
         }
+        Ok(())
     }
 }
 
+/// Parse JSON formatted tweets from a reader, passing each tweet to `on_tweet` one at a time
+///
+/// The whole array is never held in memory, so large archives can be grouped while they are read.
+pub fn parse_tweets_from_reader<R: Read, F: FnMut(Tweet)>(reader: R, on_tweet: F) -> Result<()> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    parse_tweet_array(&mut deserializer, on_tweet)?;
+    deserializer.end()?;
+    Ok(())
+}
+
+/// Parse an array of tweets, leaving the reader right after it
+fn parse_tweet_array<R: Read, F: FnMut(Tweet)>(
+    deserializer: &mut serde_json::Deserializer<IoRead<R>>,
+    on_tweet: F,
+) -> Result<()> {
+    let mut error = None;
+    let visitor = TweetsVisitor {
+        on_tweet,
+        error: &mut error,
+    };
+    deserializer
+        .deserialize_seq(visitor)
+        .map_err(|e| error.unwrap_or(Error::Json(e)))
+}
+
 /// Parse JSON formatted tweets and return a vector of Tweet
 pub fn parse_tweets(tweets: &str) -> Result<Vec<Tweet>> {
-    let data: Vec<Value> = serde_json::from_str(tweets).expect("Failed to parse JSON data");
-    data.iter()
-        .map(|tw| {
-            Tweet::new(
-                tw["tweet"]["created_at"].as_str().unwrap().to_string(),
-                tw["tweet"]["full_text"].as_str().unwrap().to_string(),
-                !tw["tweet"]["in_reply_to_user_id"].is_null(),
-            )
+    let mut parsed = Vec::new();
+    parse_tweets_from_reader(tweets.as_bytes(), |tweet| parsed.push(tweet))?;
+    Ok(parsed)
+}
+
+/// Load the tweets from a tweets.js (or plain JSON) file of the Twitter archive
+///
+/// Each tweet is passed to `on_tweet` as soon as it is parsed, and the arrays of all the
+/// `window.YTD` assignments in the file are read in order. The deviations of the files edited by
+/// hand, such as trailing commas, are fixed with a warning.
+pub fn load_tweets<F: FnMut(Tweet)>(tweets_file_path: &Path, mut on_tweet: F) -> Result<()> {
+    let input_error = |source| Error::Input {
+        path: tweets_file_path.display().to_string(),
+        source,
+    };
+    let file = File::open(tweets_file_path).map_err(input_error)?;
+    let mut tolerant = TolerantReader::new(file);
+    let mut reader = BufReader::new(&mut tolerant);
+    let mut result = Ok(());
+    while result.is_ok() {
+        match next_array(&mut reader) {
+            Ok(true) => {
+                let mut deserializer = serde_json::Deserializer::from_reader(&mut reader);
+                result = parse_tweet_array(&mut deserializer, &mut on_tweet);
+            }
+            Ok(false) => break,
+            Err(e) => result = Err(input_error(e)),
+        }
+    }
+    drop(reader);
+    let fixes = tolerant.fixes();
+    if !fixes.is_empty() {
+        warn!("{}: {}", tweets_file_path.display(), fixes.describe());
+    }
+    result
+}
+
+/// The counts are strings in the archive, but numbers in the API responses
+pub(crate) fn deserialize_count<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Count {
+        Number(u64),
+        String(String),
+    }
+    match Count::deserialize(deserializer)? {
+        Count::Number(n) => Ok(n),
+        Count::String(s) => s.parse().map_err(de::Error::custom),
+    }
+}
+
+/// The IDs are strings, but numbers in the Grailbird archive
+fn deserialize_id<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<String>, D::Error> {
+    Ok(
+        match Option::<serde_json::Value>::deserialize(deserializer)? {
+            Some(serde_json::Value::String(s)) => Some(s),
+            Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+            _ => None,
+        },
+    )
+}
+
+/// An object that may be empty or null when there is nothing to tell
+fn deserialize_non_empty<'de, D: Deserializer<'de>, T: de::DeserializeOwned>(
+    deserializer: D,
+) -> std::result::Result<Option<T>, D::Error> {
+    match Option::<serde_json::Value>::deserialize(deserializer)? {
+        Some(serde_json::Value::Object(map)) if map.is_empty() => Ok(None),
+        Some(value) => T::deserialize(value).map(Some).map_err(de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// The coordinates are strings in the archive, but numbers in the API responses
+fn deserialize_point<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<(f64, f64), D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Coordinate {
+        Number(f64),
+        String(String),
+    }
+    let coordinates = Vec::<Coordinate>::deserialize(deserializer)?
+        .into_iter()
+        .map(|coordinate| match coordinate {
+            Coordinate::Number(n) => Ok(n),
+            Coordinate::String(s) => s.parse().map_err(de::Error::custom),
         })
-        .collect()
+        .collect::<std::result::Result<Vec<f64>, D::Error>>()?;
+    match coordinates[..] {
+        [first, second] => Ok((first, second)),
+        _ => Err(de::Error::invalid_length(
+            coordinates.len(),
+            &"2 coordinates",
+        )),
+    }
+}
+
+/// Decode the HTML entities Twitter escapes in the text of tweets
+pub fn decode_html_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Escape the characters Twitter escapes in the text of tweets, undone by `decode_html_entities`
+pub fn encode_html_entities(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The text of HTML content such as a Mastodon post, with the paragraphs separated by blank lines
+pub fn html_to_text(html: &str) -> String {
+    static RE_BREAK: OnceLock<Regex> = OnceLock::new();
+    static RE_TAG: OnceLock<Regex> = OnceLock::new();
+    let re_break = RE_BREAK.get_or_init(|| Regex::new(r"<br\s*/?>").unwrap());
+    let re_tag = RE_TAG.get_or_init(|| Regex::new(r"<[^>]*>").unwrap());
+    let text = html.replace("</p><p>", "\n\n");
+    let text = re_break.replace_all(&text, "\n");
+    // The hidden parts of shortened links are kept, which restores the full URLs
+    let text = re_tag.replace_all(&text, "");
+    decode_html_entities(text.trim())
 }
 
 /// Parse a Twitter formatted date string and return a DateTime<Utc>
-fn parse_twitter_date(date: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
-    let dt = DateTime::parse_from_str(date, "%a %b %d %H:%M:%S %z %Y")?;
+///
+/// The Grailbird archive has dates like `2013-01-31 12:00:00 +0000` instead.
+fn parse_twitter_date(date: &str) -> std::result::Result<DateTime<Utc>, chrono::ParseError> {
+    let dt = DateTime::parse_from_str(date, "%a %b %d %H:%M:%S %z %Y")
+        .or_else(|e| DateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S %z").map_err(|_| e))?;
     Ok(dt.with_timezone(&Utc))
 }
 
@@ -74,4 +958,185 @@ mod tests {
         let expected = Utc.with_ymd_and_hms(2023, 3, 11, 4, 12, 48).unwrap();
         assert_eq!(parse_twitter_date(date), Ok(expected));
     }
+    #[test]
+    fn test_parse_tweets_missing_field() {
+        let tweets = r#"[{"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023"}}]"#;
+        assert!(parse_tweets(tweets).is_err());
+        assert!(parse_tweets("[{").is_err());
+    }
+    #[test]
+    fn test_parse_tweets() {
+        let tweets = r#"[
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "hello", "in_reply_to_user_id": null}},
+            {"tweet": {"created_at": "Sat Mar 11 05:12:48 +0000 2023", "full_text": "@hoge hi", "in_reply_to_user_id": "1"}}
+        ]"#;
+        let tweets = parse_tweets(tweets).unwrap();
+        assert_eq!(tweets.len(), 2);
+        assert_eq!(tweets[0].full_text(), "hello");
+        assert!(!tweets[0].is_reply());
+        assert!(tweets[1].is_reply());
+    }
+    #[test]
+    fn test_parse_tweets_of_every_generation() {
+        let tweets = r#"[
+            {"tweet": {"id_str": "3", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "2020-"}},
+            {"id_str": "2", "created_at": "Mon Mar 11 04:12:48 +0000 2019", "full_text": "2018-2019"},
+            {"id_str": "1", "created_at": "2013-01-31 12:00:00 +0000", "text": "grailbird",
+                "in_reply_to_user_id": 42, "geo": {}, "entities": {"user_mentions": [], "hashtags": [], "urls": []}}
+        ]"#;
+        let tweets = parse_tweets(tweets).unwrap();
+        let texts = tweets
+            .iter()
+            .map(|tweet| tweet.full_text())
+            .collect::<Vec<&str>>();
+        assert_eq!(texts, vec!["2020-", "2018-2019", "grailbird"]);
+        assert_eq!(
+            tweets[2].created_at(),
+            Utc.with_ymd_and_hms(2013, 1, 31, 12, 0, 0).unwrap()
+        );
+        assert!(tweets[2].is_reply());
+        assert!(tweets[2].geo().is_none());
+    }
+    #[test]
+    fn test_unknown_fields() {
+        let tweets = r#"[
+            {"tweet": {"id": "1", "created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "a", "favorited": false, "views": "10", "edit_controls": {}}}
+        ]"#;
+        let mut tweets = parse_tweets(tweets).unwrap();
+        let mut fields = tweets[0].take_unknown_fields();
+        fields.sort();
+        assert_eq!(fields, vec!["edit_controls", "views"]);
+        assert!(tweets[0].take_unknown_fields().is_empty());
+    }
+    #[test]
+    fn test_parse_tweets_with_geo() {
+        let tweets = r#"[
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "a", "geo": {"type": "Point", "coordinates": ["35.6595", "139.7005"]}, "place": {"name": "Shibuya-ku", "full_name": "Shibuya-ku, Tokyo"}}},
+            {"tweet": {"created_at": "Sat Mar 11 05:12:48 +0000 2023", "full_text": "b", "coordinates": {"type": "Point", "coordinates": [139.7005, 35.6595]}}},
+            {"tweet": {"created_at": "Sat Mar 11 06:12:48 +0000 2023", "full_text": "c", "geo": null}}
+        ]"#;
+        let tweets = parse_tweets(tweets).unwrap();
+        let geo = tweets[0].geo().unwrap();
+        assert_eq!(geo.name(), "Shibuya-ku, Tokyo");
+        assert_eq!(geo.coordinates, Some((35.6595, 139.7005)));
+        assert_eq!(
+            tweets[1].geo().unwrap().map_url().as_deref(),
+            Some(
+                "https://www.openstreetmap.org/?mlat=35.6595&mlon=139.7005#map=16/35.6595/139.7005"
+            )
+        );
+        assert_eq!(tweets[1].geo().unwrap().name(), "35.6595, 139.7005");
+        assert!(tweets[2].geo().is_none());
+    }
+    #[test]
+    fn test_parse_tweets_with_poll() {
+        let tweets = r#"[{"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "どっち？ https://t.co/card", "card": {"name": "poll2choice_text_only", "binding_values": {
+            "choice1_label": {"type": "STRING", "string_value": "犬"},
+            "choice1_count": {"type": "STRING", "string_value": "12"},
+            "choice2_label": {"type": "STRING", "string_value": "猫"},
+            "choice2_count": {"type": "STRING", "string_value": "30"},
+            "counts_are_final": {"type": "BOOLEAN", "boolean_value": true},
+            "card_url": {"type": "STRING", "string_value": "https://t.co/card"}
+        }}}}]"#;
+        let tweets = parse_tweets(tweets).unwrap();
+        assert_eq!(tweets[0].full_text(), "どっち？");
+        let poll = tweets[0].poll().unwrap();
+        assert!(poll.closed);
+        assert_eq!(
+            poll.options,
+            vec![
+                PollOption {
+                    label: "犬".to_string(),
+                    votes: Some(12)
+                },
+                PollOption {
+                    label: "猫".to_string(),
+                    votes: Some(30)
+                }
+            ]
+        );
+    }
+    #[test]
+    fn test_parse_tweets_with_audience() {
+        let tweets = r#"[
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "a", "limited_actions": "limit_trusted_friends_tweet"}},
+            {"tweet": {"created_at": "Sat Mar 11 05:12:48 +0000 2023", "full_text": "b", "community_id_str": "42"}},
+            {"tweet": {"created_at": "Sat Mar 11 06:12:48 +0000 2023", "full_text": "c", "limited_actions": "non_compliant"}}
+        ]"#;
+        let tweets = parse_tweets(tweets).unwrap();
+        assert_eq!(tweets[0].audience(), Audience::Circle);
+        assert_eq!(tweets[1].audience(), Audience::Community);
+        assert_eq!(tweets[2].audience(), Audience::Public);
+    }
+    #[test]
+    fn test_parse_tweets_possibly_sensitive() {
+        let tweets = r#"[
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "a", "possibly_sensitive": true}},
+            {"tweet": {"created_at": "Sat Mar 11 05:12:48 +0000 2023", "full_text": "b", "possibly_sensitive": null}}
+        ]"#;
+        let tweets = parse_tweets(tweets).unwrap();
+        assert!(tweets[0].is_possibly_sensitive());
+        assert!(!tweets[1].is_possibly_sensitive());
+    }
+    #[test]
+    fn test_has_hashtag() {
+        let tweets = r#"[{"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "I love #RustLang",
+            "entities": {"hashtags": [{"text": "RustLang", "indices": ["7", "16"]}]}}}]"#;
+        let tweets = parse_tweets(tweets).unwrap();
+        assert!(tweets[0].has_hashtag("rustlang"));
+        assert!(tweets[0].has_hashtag("#RustLang"));
+        assert!(!tweets[0].has_hashtag("rust"));
+    }
+    #[test]
+    fn test_involves_user() {
+        let tweets = r#"[
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "@Hoge hi", "in_reply_to_user_id": "1",
+                "in_reply_to_screen_name": "Hoge", "entities": {"user_mentions": [{"screen_name": "Hoge", "name": "ほげ"}]}}},
+            {"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "cc @fuga",
+                "entities": {"user_mentions": [{"screen_name": "fuga", "name": "ふが"}]}}}
+        ]"#;
+        let tweets = parse_tweets(tweets).unwrap();
+        assert!(tweets[0].involves_user("@hoge"));
+        assert!(!tweets[0].involves_user("fuga"));
+        assert!(tweets[1].involves_user("fuga"));
+    }
+    #[test]
+    fn test_plain_text() {
+        let tweet = Tweet::new_with_local_datetime(
+            Local::now(),
+            "Tom &amp; Jerry &lt;3 https://t.co/abc".to_string(),
+            false,
+        );
+        assert_eq!(tweet.plain_text(), "Tom & Jerry <3");
+    }
+    #[test]
+    fn test_html_to_text() {
+        assert_eq!(
+            html_to_text(
+                r##"<p>Hi <span class="h-card"><a href="https://example.com/@alice" class="u-url mention">@<span>alice</span></a></span> &amp; <a href="https://example.com/tags/rust" class="mention hashtag" rel="tag">#<span>rust</span></a></p><p>see <a href="https://example.org/very/long/path"><span class="invisible">https://</span><span class="ellipsis">example.org/very</span><span class="invisible">/long/path</span></a><br />bye</p>"##
+            ),
+            "Hi @alice & #rust\n\nsee https://example.org/very/long/path\nbye"
+        );
+    }
+    #[test]
+    fn test_quoted_status_ids() {
+        let tweets = parse_tweets(
+            r#"[{"tweet": {"created_at": "Sat Mar 11 04:12:48 +0000 2023", "full_text": "see https://t.co/a https://t.co/b", "entities": {"urls": [
+                {"url": "https://t.co/a", "expanded_url": "https://twitter.com/hoge/status/123"},
+                {"url": "https://t.co/b", "expanded_url": "https://example.com/status/456"}
+            ]}}}]"#,
+        )
+        .unwrap();
+        assert_eq!(tweets[0].quoted_status_ids(), vec!["123"]);
+    }
+    #[test]
+    fn test_parse_tweets_invalid_date() {
+        let tweets = r#"[{"tweet": {"created_at": "2023-03-11", "full_text": "hello"}}]"#;
+        assert!(matches!(parse_tweets(tweets), Err(Error::Date(_))));
+    }
+    #[test]
+    fn test_load_tweets_missing_file() {
+        let err = load_tweets(Path::new("no/such/tweets.js"), |_| {}).unwrap_err();
+        assert_eq!(err.exit_code(), crate::exit_code::INPUT_MISSING);
+    }
 }
@@ -0,0 +1,137 @@
+//! Conversion of the archives dropped into a folder
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// File of the tweets in the `data` folder of the Twitter archive
+const TWEETS_FILE_NAME: &str = "tweets.js";
+
+/// Extensions of the files taken from the folder
+const EXTENSIONS: &[&str] = &["zip", "js", "json", "car"];
+
+/// A folder polled for the archives placed in it
+///
+/// A file is reported once its size has stayed the same between two polls, so that the archives
+/// still being downloaded or copied are not read.
+#[derive(Debug)]
+pub struct DropFolder {
+    dir: PathBuf,
+    /// Size of each file at the last poll, and whether it has been reported
+    files: HashMap<PathBuf, (u64, bool)>,
+}
+impl DropFolder {
+    /// Start watching `dir`, ignoring the files already in it
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let mut folder = Self {
+            dir: dir.into(),
+            files: HashMap::new(),
+        };
+        for (path, size) in folder.list()? {
+            folder.files.insert(path, (size, true));
+        }
+        Ok(folder)
+    }
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+    /// The files placed in the folder since the last poll that are complete
+    pub fn poll(&mut self) -> Result<Vec<PathBuf>> {
+        let mut ready = Vec::new();
+        for (path, size) in self.list()? {
+            match self.files.get(&path) {
+                Some(&(last_size, reported)) if last_size == size => {
+                    if !reported {
+                        ready.push(path.clone());
+                    }
+                    self.files.insert(path, (size, true));
+                }
+                // A new file, or a file still growing
+                _ => {
+                    self.files.insert(path, (size, false));
+                }
+            }
+        }
+        ready.sort();
+        Ok(ready)
+    }
+    fn list(&self) -> Result<Vec<(PathBuf, u64)>> {
+        let input_error = |source| Error::Input {
+            path: self.dir.display().to_string(),
+            source,
+        };
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&self.dir).map_err(input_error)? {
+            let entry = entry.map_err(input_error)?;
+            let path = entry.path();
+            let extension = path.extension().and_then(|ext| ext.to_str());
+            if !extension.is_some_and(|ext| EXTENSIONS.contains(&ext)) {
+                continue;
+            }
+            let metadata = entry.metadata().map_err(input_error)?;
+            if metadata.is_file() {
+                files.push((path, metadata.len()));
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// The file of the posts to convert in a file dropped into the folder
+///
+/// A zip file of an archive is extracted with the `unzip` command to a folder named after it,
+/// next to it, and its `data/tweets.js` is returned.
+pub fn posts_file(path: &Path) -> Result<PathBuf> {
+    if path.extension().and_then(|ext| ext.to_str()) != Some("zip") {
+        return Ok(path.to_path_buf());
+    }
+    let input_error = |source| Error::Input {
+        path: path.display().to_string(),
+        source,
+    };
+    let dir = path.with_extension("");
+    let output = Command::new("unzip")
+        .args(["-o", "-q"])
+        .arg(path)
+        .args(["data/*", "-d"])
+        .arg(&dir)
+        .output()
+        .map_err(input_error)?;
+    if !output.status.success() {
+        return Err(input_error(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )));
+    }
+    let tweets_file_path = dir.join("data").join(TWEETS_FILE_NAME);
+    if !tweets_file_path.is_file() {
+        return Err(input_error(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("there is no data/{} in the archive", TWEETS_FILE_NAME),
+        )));
+    }
+    Ok(tweets_file_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DropFolder;
+
+    #[test]
+    fn test_poll() {
+        let dir =
+            std::env::temp_dir().join(format!("twitter2obsidian-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("old.json"), "[]").unwrap();
+        let mut folder = DropFolder::new(&dir).unwrap();
+        assert!(folder.poll().unwrap().is_empty());
+
+        std::fs::write(dir.join("tweets.js"), "[]").unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        // Reported once its size is known to be stable
+        assert!(folder.poll().unwrap().is_empty());
+        assert_eq!(folder.poll().unwrap(), vec![dir.join("tweets.js")]);
+        assert!(folder.poll().unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
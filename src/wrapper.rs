@@ -0,0 +1,117 @@
+//! The JavaScript wrapper of the files of the archive
+//!
+//! The files in the `data` folder are scripts assigning arrays, such as
+//! `window.YTD.tweets.part0 = [...]`, and a large archive may assign several parts in one file.
+//! The assignments are stripped so that the arrays can be read as JSON one after another.
+use crate::error::{Error, Result};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+fn peek<R: BufRead>(reader: &mut R) -> io::Result<Option<u8>> {
+    Ok(reader.fill_buf()?.first().copied())
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn is_name(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'_' | b'$' | b'.')
+}
+
+/// Skip to the start of the next array, past the assignment before it if any
+///
+/// Returns `false` at the end of the file. Plain JSON without an assignment is accepted too.
+pub(crate) fn next_array<R: BufRead>(reader: &mut R) -> io::Result<bool> {
+    let mut assigned = false;
+    loop {
+        match peek(reader)? {
+            None if assigned => {
+                return Err(invalid(
+                    "the file ends before the array of the assignment".to_string(),
+                ))
+            }
+            None => return Ok(false),
+            Some(b'[') => return Ok(true),
+            Some(b) if b.is_ascii_whitespace() || (b == b';' && !assigned) => reader.consume(1),
+            Some(b) if is_name(b) && !assigned => {
+                while peek(reader)?.is_some_and(is_name) {
+                    reader.consume(1);
+                }
+                while peek(reader)?.is_some_and(|b| b.is_ascii_whitespace()) {
+                    reader.consume(1);
+                }
+                if peek(reader)? != Some(b'=') {
+                    return Err(invalid("expected `=` after the name assigned".to_string()));
+                }
+                reader.consume(1);
+                assigned = true;
+            }
+            Some(b) => {
+                return Err(invalid(format!(
+                    "unexpected `{}` where an array or an assignment such as \
+                     `window.YTD.tweets.part0 = [` is expected",
+                    b.escape_ascii()
+                )))
+            }
+        }
+    }
+}
+
+/// Read every array of a reader into one vector
+pub(crate) fn read_arrays<T: DeserializeOwned, R: BufRead>(mut reader: R) -> io::Result<Vec<T>> {
+    let mut items = Vec::new();
+    while next_array(&mut reader)? {
+        let mut deserializer = serde_json::Deserializer::from_reader(&mut reader);
+        items.extend(Vec::<T>::deserialize(&mut deserializer)?);
+    }
+    Ok(items)
+}
+
+/// Load the arrays of a file of the archive, concatenated
+pub fn load_arrays<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>> {
+    let input_error = |source| Error::Input {
+        path: path.display().to_string(),
+        source,
+    };
+    let file = File::open(path).map_err(input_error)?;
+    read_arrays(BufReader::new(file)).map_err(|e| match e.downcast::<serde_json::Error>() {
+        Ok(e) => Error::Json(e),
+        Err(e) => input_error(e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{next_array, read_arrays};
+    use std::io::{BufReader, Read};
+
+    fn read(script: &str) -> Result<Vec<u32>, String> {
+        read_arrays(BufReader::with_capacity(4, script.as_bytes())).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_next_array() {
+        let mut reader = BufReader::with_capacity(4, "window.YTD.tweets.part0 = [1]".as_bytes());
+        assert!(next_array(&mut reader).unwrap());
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "[1]");
+    }
+    #[test]
+    fn test_read_arrays() {
+        assert_eq!(read("[1, 2]"), Ok(vec![1, 2]));
+        assert_eq!(read(""), Ok(vec![]));
+        assert_eq!(
+            read("window.YTD.tweets.part0 = [1, 2]\nwindow.YTD.tweets.part1 = [3];\n"),
+            Ok(vec![1, 2, 3])
+        );
+        assert_eq!(read("Grailbird.data.tweets_2013_01 = \n [4]"), Ok(vec![4]));
+        assert!(read("var tweets [1]").is_err());
+        assert!(read("window.YTD.tweets.part0 = ").is_err());
+    }
+}